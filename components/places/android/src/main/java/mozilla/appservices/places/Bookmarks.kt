@@ -34,6 +34,20 @@ enum class BookmarkType(val value: Int) {
     Separator(3),
 }
 
+/**
+ * How `sortFolderChildren` orders a folder's children.
+ *
+ * Must match ChildrenSortOrder in the Rust code.
+ */
+enum class ChildrenSortOrder(val value: Int) {
+    /** By title, ignoring case. Bookmarks without a title are sorted by URL. */
+    Title(0),
+    /** Oldest first. */
+    DateAdded(1),
+    /** Least recently modified first. */
+    LastModified(2),
+}
+
 /**
  * An interface defining the set of fields common to all nodes
  * in the bookmark tree.
@@ -99,7 +113,19 @@ data class BookmarkItem(
      * Note that the bookmark storage layer treats NULL and the
      * empty string as equivalent in titles.
      */
-    val title: String
+    val title: String,
+
+    /**
+     * The description of the bookmarked page, if one was recorded with
+     * `notePageMetadata`.
+     */
+    val description: String? = null,
+
+    /**
+     * The URL of a preview image for the bookmarked page, if one was
+     * recorded with `notePageMetadata`.
+     */
+    val previewImageUrl: String? = null
 ) : BookmarkTreeNode() {
     override val type get() = BookmarkType.Bookmark
 }
@@ -242,6 +268,14 @@ interface ReadableBookmarksConnection : InterruptibleConnection {
      * has its `interrupt()` method called on another thread.
      */
     fun getRecentBookmarks(limit: Int): List<BookmarkItem>
+
+    /**
+     * Export all bookmarks, including their tags and keywords, in the
+     * Netscape `bookmarks.html` format that other browsers can import.
+     *
+     * @return The contents of the `bookmarks.html` file.
+     */
+    fun exportBookmarksToHtml(): String
 }
 
 /**
@@ -262,6 +296,23 @@ interface WritableBookmarksConnection : ReadableBookmarksConnection {
      */
     fun deleteBookmarkNode(guid: String): Boolean
 
+    /**
+     * Restore a recently deleted bookmark, separator or folder, including all
+     * of a folder's descendants. This can be used to undo `deleteBookmarkNode`.
+     *
+     * The item is restored to its original folder and position if the folder
+     * still exists, or appended to [BookmarkRoot.Unfiled] if it doesn't. Only
+     * the most recent 20 deletions can be restored, and each only once.
+     *
+     * @param guid The GUID of the deleted item.
+     * @return The GUID of the restored item. This is usually `guid`, but may
+     * be a new GUID if another item has been added with the same GUID since.
+     *
+     * @throws UnknownBookmarkItem If `guid` does not refer to a recently
+     * deleted item.
+     */
+    fun restoreDeletedBookmark(guid: String): String
+
     /**
      * Create a bookmark folder, returning its guid.
      *
@@ -343,6 +394,77 @@ interface WritableBookmarksConnection : ReadableBookmarksConnection {
      * folder node.
      */
     fun updateBookmark(guid: String, info: BookmarkUpdateInfo)
+
+    /**
+     * Move several items into a folder at once, keeping them together in the
+     * order given. This is done in a single transaction, so either all the
+     * items are moved, or none are.
+     *
+     * @param guids The GUIDs of the items to move.
+     * @param parentGUID The GUID of the folder to move them to. It may be the
+     * folder some or all of them are already in.
+     * @param position The index of the child to move them in front of. If not
+     * provided, or if every child from this index on is being moved, the items
+     * are moved to the end of the folder.
+     *
+     * @throws CannotUpdateRoot If one of `guids` is a bookmark root, or
+     * `parentGUID` is [BookmarkRoot.Root] (e.g. "root________")
+     * @throws UnknownBookmarkItem If `parentGUID` or one of `guids` does not
+     * refer to a known bookmark.
+     * @throws InvalidParent If `parentGUID` does not refer to a folder node, or
+     * refers to one of the folders being moved or one of their descendants.
+     */
+    fun moveBookmarks(guids: List<String>, parentGUID: String, position: Int? = null)
+
+    /**
+     * Copy a bookmark, separator or folder, including all of a folder's
+     * descendants. The copies have new GUIDs.
+     *
+     * @param guid The GUID of the item to copy.
+     * @param parentGUID The GUID of the folder to copy it to.
+     * @param position The index where to insert the copy inside its parent.
+     * If not provided, the copy will be appended.
+     * @return The GUID of the copy.
+     *
+     * @throws CannotUpdateRoot If `guid` is a bookmark root, or `parentGUID`
+     * is [BookmarkRoot.Root] (e.g. "root________")
+     * @throws UnknownBookmarkItem If `guid` or `parentGUID` does not refer to
+     * a known bookmark.
+     * @throws InvalidParent If `parentGUID` does not refer to a folder node.
+     */
+    fun copyBookmark(guid: String, parentGUID: String, position: Int? = null): String
+
+    /**
+     * Sort the children of a folder. Separators stay where they are, and the
+     * items between them are sorted separately.
+     *
+     * @param guid The GUID of the folder.
+     * @param order How to sort the children.
+     *
+     * @throws UnknownBookmarkItem If `guid` does not refer to a known bookmark.
+     * @throws InvalidParent If `guid` does not refer to a folder node.
+     */
+    fun sortFolderChildren(guid: String, order: ChildrenSortOrder)
+
+    /**
+     * Reverse the order of a folder's children.
+     *
+     * @param guid The GUID of the folder.
+     *
+     * @throws UnknownBookmarkItem If `guid` does not refer to a known bookmark.
+     * @throws InvalidParent If `guid` does not refer to a folder node.
+     */
+    fun reverseFolderChildren(guid: String)
+
+    /**
+     * Import bookmarks from a Netscape `bookmarks.html` file, as exported by
+     * most browsers. The imported bookmarks are added to the existing ones,
+     * with the toolbar and "Other Bookmarks" folders merged into their roots.
+     * Bookmarks with invalid URLs are skipped.
+     *
+     * @param html The contents of the `bookmarks.html` file.
+     */
+    fun importBookmarksFromHtml(html: String)
 }
 
 /**
@@ -474,7 +596,9 @@ internal fun unpackProtobuf(msg: MsgTypes.BookmarkNode): BookmarkTreeNode {
                     dateAdded = dateAdded,
                     lastModified = lastModified,
                     title = title,
-                    url = msg.url
+                    url = msg.url,
+                    description = if (msg.hasDescription()) { msg.description } else { null },
+                    previewImageUrl = if (msg.hasPreviewImageUrl()) { msg.previewImageUrl } else { null }
             )
         }
 
@@ -0,0 +1,195 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.places
+
+import org.json.JSONObject
+import java.util.concurrent.atomic.AtomicLong
+
+/**
+ * A change to history or bookmarks. Must match `PlacesEvent` in the Rust code.
+ */
+sealed class PlacesEvent {
+    /** A visit was added to a page. `visitDate` is in milliseconds since the unix epoch. */
+    data class VisitAdded(
+        val url: String,
+        val visitDate: Long,
+        val visitType: VisitType,
+        val isRemote: Boolean
+    ) : PlacesEvent()
+
+    /** A page was removed from history, along with all its visits. */
+    data class PageDeleted(val url: String, val guid: String) : PlacesEvent()
+
+    /** All history was removed. Bookmarked pages are kept, but lose their visits. */
+    object HistoryCleared : PlacesEvent()
+
+    /** A page's title changed. */
+    data class TitleChanged(val url: String, val title: String) : PlacesEvent()
+
+    /** A bookmark, folder or separator was added. */
+    data class BookmarkAdded(
+        val guid: String,
+        val parentGUID: String,
+        val position: Int,
+        val type: BookmarkType,
+        /** The bookmarked URL, for bookmarks. */
+        val url: String?
+    ) : PlacesEvent()
+
+    /** An item was moved within its folder, or to a different folder. */
+    data class BookmarkMoved(
+        val guid: String,
+        val oldParentGUID: String,
+        val oldPosition: Int,
+        val parentGUID: String,
+        val position: Int
+    ) : PlacesEvent()
+
+    /** An item's title or URL changed. The fields are the new values. */
+    data class BookmarkUpdated(val guid: String, val title: String?, val url: String?) : PlacesEvent()
+
+    /**
+     * An item was removed. If it was a folder, its descendants were removed
+     * too, without events of their own.
+     */
+    data class BookmarkRemoved(
+        val guid: String,
+        val parentGUID: String,
+        val position: Int,
+        val type: BookmarkType
+    ) : PlacesEvent()
+
+    /**
+     * Sync applied incoming changes for a collection ("history" or
+     * "bookmarks"). Individual changes aren't reported, so anything showing
+     * that collection should be re-queried.
+     */
+    data class SyncApplied(val collection: String) : PlacesEvent()
+
+    companion object {
+        internal fun fromJSON(obj: JSONObject): PlacesEvent? {
+            return when (obj.getString("type")) {
+                "visitAdded" -> VisitAdded(
+                    url = obj.getString("url"),
+                    visitDate = obj.getLong("visitDate"),
+                    visitType = intToVisitType[obj.getInt("visitType")]!!,
+                    isRemote = obj.getBoolean("isRemote")
+                )
+                "pageDeleted" -> PageDeleted(obj.getString("url"), obj.getString("guid"))
+                "historyCleared" -> HistoryCleared
+                "titleChanged" -> TitleChanged(obj.getString("url"), obj.getString("title"))
+                "bookmarkAdded" -> BookmarkAdded(
+                    guid = obj.getString("guid"),
+                    parentGUID = obj.getString("parentGuid"),
+                    position = obj.getInt("position"),
+                    type = bookmarkTypeFromInt(obj.getInt("kind")),
+                    url = obj.optStringOrNull("url")
+                )
+                "bookmarkMoved" -> BookmarkMoved(
+                    guid = obj.getString("guid"),
+                    oldParentGUID = obj.getString("oldParentGuid"),
+                    oldPosition = obj.getInt("oldPosition"),
+                    parentGUID = obj.getString("parentGuid"),
+                    position = obj.getInt("position")
+                )
+                "bookmarkUpdated" -> BookmarkUpdated(
+                    guid = obj.getString("guid"),
+                    title = obj.optStringOrNull("title"),
+                    url = obj.optStringOrNull("url")
+                )
+                "bookmarkRemoved" -> BookmarkRemoved(
+                    guid = obj.getString("guid"),
+                    parentGUID = obj.getString("parentGuid"),
+                    position = obj.getInt("position"),
+                    type = bookmarkTypeFromInt(obj.getInt("kind"))
+                )
+                "syncApplied" -> SyncApplied(obj.getString("collection"))
+                // Newer versions of the library might add events.
+                else -> null
+            }
+        }
+
+        private fun bookmarkTypeFromInt(value: Int): BookmarkType {
+            return BookmarkType.values().first { it.value == value }
+        }
+
+        private fun JSONObject.optStringOrNull(key: String): String? {
+            return if (isNull(key)) null else getString(key)
+        }
+    }
+}
+
+/**
+ * The events from a single transaction.
+ */
+data class PlacesEventBatch(
+    /** Whether the changes were made by Sync. */
+    val fromSync: Boolean,
+    val events: List<PlacesEvent>
+)
+
+/**
+ * The result of [PlacesEventQueue.poll].
+ */
+data class QueuedPlacesEvents(
+    /** The queued batches, oldest first. */
+    val batches: List<PlacesEventBatch>,
+
+    /**
+     * The number of batches dropped because the queue was full. If this isn't
+     * zero, anything showing history or bookmarks should be re-queried.
+     */
+    val dropped: Int
+) {
+    companion object {
+        internal fun fromJSONString(jsonString: String): QueuedPlacesEvents {
+            val obj = JSONObject(jsonString)
+            val batchesArray = obj.getJSONArray("batches")
+            val batches = (0 until batchesArray.length()).map { index ->
+                val batchObj = batchesArray.getJSONObject(index)
+                val eventsArray = batchObj.getJSONArray("events")
+                PlacesEventBatch(
+                    fromSync = batchObj.getBoolean("fromSync"),
+                    events = (0 until eventsArray.length()).mapNotNull {
+                        PlacesEvent.fromJSON(eventsArray.getJSONObject(it))
+                    }
+                )
+            }
+            return QueuedPlacesEvents(batches, obj.getInt("dropped"))
+        }
+    }
+}
+
+/**
+ * Queues change events made by a [PlacesApi]'s writer connection and by
+ * Sync, until they're polled. Events are grouped into a batch per
+ * transaction, and only committed changes are reported.
+ *
+ * The queue holds a limited number of batches, so it should be polled
+ * regularly, and closed when it's no longer needed.
+ */
+class PlacesEventQueue internal constructor(queueHandle: Long) : AutoCloseable {
+    private val handle: AtomicLong = AtomicLong(queueHandle)
+
+    /**
+     * Returns and clears the queued events.
+     */
+    fun poll(): QueuedPlacesEvents {
+        val json = rustCallForString(this) { error ->
+            LibPlacesFFI.INSTANCE.places_events_queue_poll(this.handle.get(), error)
+        }
+        return QueuedPlacesEvents.fromJSONString(json)
+    }
+
+    @Synchronized
+    override fun close() {
+        val handle = this.handle.getAndSet(0L)
+        if (handle != 0L) {
+            rustCall(this) { error ->
+                LibPlacesFFI.INSTANCE.places_events_queue_destroy(handle, error)
+            }
+        }
+    }
+}
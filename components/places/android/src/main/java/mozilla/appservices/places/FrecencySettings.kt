@@ -0,0 +1,82 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.places
+
+import org.json.JSONObject
+
+/**
+ * The weights and bonuses used to calculate frecency, which ranks pages for
+ * autocomplete and top sites. These correspond to Desktop's
+ * `places.frecency.*` preferences. Settings that are null use their default
+ * values.
+ *
+ * Change the settings with [PlacesManager.setFrecencySettings], and then
+ * call [PlacesManager.recalculateFrecencies] to apply them to existing pages.
+ */
+data class FrecencySettings(
+    /** The number of recent visits to sample for each page. Must be positive. */
+    val numVisits: Int? = null,
+
+    // The cutoffs, in days, for the age buckets. These must be increasing.
+    val firstBucketCutoffDays: Int? = null,
+    val secondBucketCutoffDays: Int? = null,
+    val thirdBucketCutoffDays: Int? = null,
+    val fourthBucketCutoffDays: Int? = null,
+
+    // The weight for visits in each age bucket. Visits older than the fourth
+    // cutoff use the default weight.
+    val firstBucketWeight: Int? = null,
+    val secondBucketWeight: Int? = null,
+    val thirdBucketWeight: Int? = null,
+    val fourthBucketWeight: Int? = null,
+    val defaultBucketWeight: Int? = null,
+
+    // The bonus, as a percentage, for each kind of visit.
+    val embedVisitBonus: Int? = null,
+    val framedLinkVisitBonus: Int? = null,
+    val linkVisitBonus: Int? = null,
+    val typedVisitBonus: Int? = null,
+    val bookmarkVisitBonus: Int? = null,
+    val downloadVisitBonus: Int? = null,
+    val permanentRedirectVisitBonus: Int? = null,
+    val temporaryRedirectVisitBonus: Int? = null,
+    val redirectSourceVisitBonus: Int? = null,
+    val defaultVisitBonus: Int? = null,
+    val unvisitedBookmarkBonus: Int? = null,
+    val unvisitedTypedBonus: Int? = null,
+    val reloadVisitBonus: Int? = null
+) {
+    internal fun toJSON(): JSONObject {
+        val o = JSONObject()
+        listOf(
+            "numVisits" to numVisits,
+            "firstBucketCutoffDays" to firstBucketCutoffDays,
+            "secondBucketCutoffDays" to secondBucketCutoffDays,
+            "thirdBucketCutoffDays" to thirdBucketCutoffDays,
+            "fourthBucketCutoffDays" to fourthBucketCutoffDays,
+            "firstBucketWeight" to firstBucketWeight,
+            "secondBucketWeight" to secondBucketWeight,
+            "thirdBucketWeight" to thirdBucketWeight,
+            "fourthBucketWeight" to fourthBucketWeight,
+            "defaultBucketWeight" to defaultBucketWeight,
+            "embedVisitBonus" to embedVisitBonus,
+            "framedLinkVisitBonus" to framedLinkVisitBonus,
+            "linkVisitBonus" to linkVisitBonus,
+            "typedVisitBonus" to typedVisitBonus,
+            "bookmarkVisitBonus" to bookmarkVisitBonus,
+            "downloadVisitBonus" to downloadVisitBonus,
+            "permanentRedirectVisitBonus" to permanentRedirectVisitBonus,
+            "temporaryRedirectVisitBonus" to temporaryRedirectVisitBonus,
+            "redirectSourceVisitBonus" to redirectSourceVisitBonus,
+            "defaultVisitBonus" to defaultVisitBonus,
+            "unvisitedBookmarkBonus" to unvisitedBookmarkBonus,
+            "unvisitedTypedBonus" to unvisitedTypedBonus,
+            "reloadVisitBonus" to reloadVisitBonus
+        ).forEach { (key, value) ->
+            value?.let { o.put(key, it) }
+        }
+        return o
+    }
+}
@@ -0,0 +1,29 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.places
+
+import org.json.JSONArray
+import org.json.JSONObject
+
+/**
+ * Decides which URLs are recorded in history, in addition to the URLs that
+ * are never recorded. Set it with [PlacesManager.setHistoryPolicy].
+ */
+data class HistoryPolicy(
+    /** Schemes to block, without the trailing colon, like "ftp". */
+    val blockedSchemes: List<String> = listOf(),
+    /**
+     * Hosts to block, like "example.com". Blocking a host also blocks its
+     * subdomains, on any port.
+     */
+    val blockedHosts: List<String> = listOf()
+) {
+    internal fun toJSON(): JSONObject {
+        val o = JSONObject()
+        o.put("blockedSchemes", JSONArray(blockedSchemes))
+        o.put("blockedHosts", JSONArray(blockedHosts))
+        return o
+    }
+}
@@ -0,0 +1,110 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.places
+
+import org.json.JSONObject
+
+/**
+ * What the last history sync did with each incoming record, and why. Turn
+ * these on with [PlacesManager.setHistorySyncDiagnosticsEnabled], and fetch
+ * them with [PlacesManager.getLastHistorySyncDiagnostics].
+ *
+ * Records are identified by their GUID. URLs and titles aren't included, so
+ * these are safe to attach to bug reports.
+ */
+data class HistorySyncDiagnostics(
+    /** The incoming records, in the order they were received. */
+    val records: List<RecordDiagnostics>
+) {
+    companion object {
+        internal fun fromJSON(jsonText: String): HistorySyncDiagnostics {
+            val array = JSONObject(jsonText).getJSONArray("records")
+            val records = (0 until array.length()).map {
+                RecordDiagnostics.fromJSON(array.getJSONObject(it))
+            }
+            return HistorySyncDiagnostics(records)
+        }
+    }
+}
+
+/**
+ * What we did with an incoming history record.
+ */
+enum class PlanKind {
+    /** Ignored, because the history policy doesn't allow its URL. */
+    SKIP,
+    /** Malformed: it couldn't be parsed, or has an invalid GUID or URL. */
+    INVALID,
+    /** An error happened while planning it. */
+    FAILED,
+    /** A tombstone, so the page was deleted. */
+    DELETE,
+    /** Visits were added to the page, or its GUID changed. */
+    APPLY,
+    /** We already had everything in the record. */
+    RECONCILED;
+
+    companion object {
+        internal fun fromString(value: String): PlanKind {
+            return valueOf(value.toUpperCase())
+        }
+    }
+}
+
+data class RecordDiagnostics(
+    val guid: String,
+    val plan: PlanKind,
+    /** Why the record was skipped, or the error for invalid and failed records. */
+    val reason: String?,
+    val visits: VisitDiagnostics
+) {
+    companion object {
+        internal fun fromJSON(jsonObject: JSONObject): RecordDiagnostics {
+            return RecordDiagnostics(
+                guid = jsonObject.getString("guid"),
+                plan = PlanKind.fromString(jsonObject.getString("plan")),
+                reason = if (jsonObject.isNull("reason")) null else jsonObject.getString("reason"),
+                visits = VisitDiagnostics.fromJSON(jsonObject.getJSONObject("visits"))
+            )
+        }
+    }
+}
+
+/**
+ * What happened to the visits in an incoming history record.
+ */
+data class VisitDiagnostics(
+    /** Visits that were added. */
+    val applied: Int,
+    /** Visits we already had. */
+    val duplicate: Int,
+    /**
+     * Visits in the future, which were moved to the current time. These are
+     * also counted as applied or duplicate.
+     */
+    val clamped: Int,
+    /** Visits before 1993-01-23, which were dropped. */
+    val tooOld: Int,
+    /** Visits with an unknown transition type, which were dropped. */
+    val unknownTransition: Int,
+    /**
+     * Visits older than the 20 most recent visits we already had for the
+     * page, which were dropped.
+     */
+    val beyondMaxVisits: Int
+) {
+    companion object {
+        internal fun fromJSON(jsonObject: JSONObject): VisitDiagnostics {
+            return VisitDiagnostics(
+                applied = jsonObject.getInt("applied"),
+                duplicate = jsonObject.getInt("duplicate"),
+                clamped = jsonObject.getInt("clamped"),
+                tooOld = jsonObject.getInt("tooOld"),
+                unknownTransition = jsonObject.getInt("unknownTransition"),
+                beyondMaxVisits = jsonObject.getInt("beyondMaxVisits")
+            )
+        }
+    }
+}
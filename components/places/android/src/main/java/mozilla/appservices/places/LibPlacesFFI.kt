@@ -55,6 +55,18 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     ): PlacesApiHandle
 
+    /** Start queueing change events for the API's connections */
+    fun places_events_queue_new(
+        handle: PlacesApiHandle,
+        out_err: RustError.ByReference
+    ): PlacesEventQueueHandle
+
+    /** Returns JSON string, which you need to free with places_destroy_string */
+    fun places_events_queue_poll(
+        handle: PlacesEventQueueHandle,
+        out_err: RustError.ByReference
+    ): Pointer?
+
     /** Create a new places connection */
     fun places_connection_new(
         handle: PlacesApiHandle,
@@ -68,6 +80,15 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     )
 
+    fun places_note_page_metadata(
+        handle: PlacesConnectionHandle,
+        url: String,
+        title: String?,
+        description: String?,
+        previewImageUrl: String?,
+        out_err: RustError.ByReference
+    )
+
     /** Returns JSON string, which you need to free with places_destroy_string */
     fun places_query_autocomplete(
         handle: PlacesConnectionHandle,
@@ -118,6 +139,16 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     )
 
+    fun places_new_search_interrupt_handle(
+        conn: PlacesConnectionHandle,
+        out_err: RustError.ByReference
+    ): RawPlacesSearchInterruptHandle?
+
+    fun places_interrupt_search(
+        handle: RawPlacesSearchInterruptHandle,
+        out_err: RustError.ByReference
+    )
+
     fun places_delete_place(
         handle: PlacesConnectionHandle,
         url: String,
@@ -131,6 +162,12 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     )
 
+    fun places_delete_history_for_host(
+        handle: PlacesConnectionHandle,
+        host: String,
+        out_err: RustError.ByReference
+    )
+
     fun places_delete_visit(
         handle: PlacesConnectionHandle,
         visit_url: String,
@@ -153,6 +190,13 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     )
 
+    fun places_expire_history(
+        handle: PlacesConnectionHandle,
+        max_pages: Long,
+        max_visit_age_ms: Long,
+        out_err: RustError.ByReference
+    )
+
     fun places_delete_everything(
         handle: PlacesConnectionHandle,
         out_err: RustError.ByReference
@@ -174,7 +218,122 @@ internal interface LibPlacesFFI : Library {
         error: RustError.ByReference
     ): RustBuffer.ByValue
 
-    fun places_get_visit_count(
+    fun places_search_history(
+        handle: PlacesConnectionHandle,
+        query: String,
+        startDate: Long,
+        endDate: Long,
+        excludeTypes: Int,
+        groupByDate: Byte,
+        cursor: String?,
+        limit: Int,
+        error: RustError.ByReference
+    ): RustBuffer.ByValue
+
+    fun places_get_visit_chain(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    ): RustBuffer.ByValue
+
+    /** Returns a JSON string, which must be freed with `places_destroy_string`. */
+    fun places_get_top_frecent_sites(
+        handle: PlacesConnectionHandle,
+        limit: Int,
+        grouping: Byte,
+        excludeHidden: Byte,
+        excludeRedirectSources: Byte,
+        excludeErrorPages: Byte,
+        error: RustError.ByReference
+    ): Pointer?
+
+    /** Returns a JSON string, which must be freed with `places_destroy_string`. */
+    fun places_get_visited_domains(
+        handle: PlacesConnectionHandle,
+        limit: Int,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun places_pin_top_site(
+        handle: PlacesConnectionHandle,
+        url: String,
+        title: String?,
+        position: Int,
+        error: RustError.ByReference
+    )
+
+    fun places_unpin_top_site(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    ): Byte
+
+    fun places_block_top_site(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    )
+
+    fun places_unblock_top_site(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    ): Byte
+
+    fun places_tag_url(
+        handle: PlacesConnectionHandle,
+        url: String,
+        tag: String,
+        error: RustError.ByReference
+    )
+
+    fun places_untag_url(
+        handle: PlacesConnectionHandle,
+        url: String,
+        tag: String,
+        error: RustError.ByReference
+    )
+
+    fun places_remove_all_tags_from_url(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    )
+
+    fun places_remove_tag(
+        handle: PlacesConnectionHandle,
+        tag: String,
+        error: RustError.ByReference
+    )
+
+    fun places_rename_tag(
+        handle: PlacesConnectionHandle,
+        oldTag: String,
+        newTag: String,
+        error: RustError.ByReference
+    )
+
+    /** Returns a JSON string, which must be freed with `places_destroy_string`. */
+    fun places_get_tags_for_url(
+        handle: PlacesConnectionHandle,
+        url: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    /** Returns a JSON string, which must be freed with `places_destroy_string`. */
+    fun places_get_urls_with_tag(
+        handle: PlacesConnectionHandle,
+        tag: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    /** Returns a JSON string, which must be freed with `places_destroy_string`. */
+    fun places_get_all_tags(
+        handle: PlacesConnectionHandle,
+        error: RustError.ByReference
+    ): Pointer?
+
+
         handle: PlacesConnectionHandle,
         excludeTypes: Int,
         error: RustError.ByReference
@@ -205,6 +364,56 @@ internal interface LibPlacesFFI : Library {
         out_err: RustError.ByReference
     )
 
+    fun places_api_set_frecency_settings(
+        handle: PlacesApiHandle,
+        settings_json: String,
+        out_err: RustError.ByReference
+    )
+
+    fun places_api_set_history_policy(
+        handle: PlacesApiHandle,
+        policy_json: String,
+        out_err: RustError.ByReference
+    )
+
+    fun places_api_set_history_backfill_settings(
+        handle: PlacesApiHandle,
+        max_records_per_sync: Int,
+        horizon_days: Int,
+        out_err: RustError.ByReference
+    )
+
+    fun places_api_set_history_sync_diagnostics_enabled(
+        handle: PlacesApiHandle,
+        enabled: Byte,
+        out_err: RustError.ByReference
+    )
+
+    /** Returns a JSON string, or null if diagnostics weren't enabled */
+    fun places_api_get_last_history_sync_diagnostics(
+        handle: PlacesApiHandle,
+        out_err: RustError.ByReference
+    ): Pointer?
+
+    fun places_api_set_input_history_sync_enabled(
+        handle: PlacesApiHandle,
+        enabled: Byte,
+        out_err: RustError.ByReference
+    )
+
+    /** Returns the number of pages recalculated */
+    fun places_api_recalculate_frecencies(
+        handle: PlacesApiHandle,
+        out_err: RustError.ByReference
+    ): Long
+
+    /** Returns a JSON string containing the per-table import counts. */
+    fun places_import_from_desktop(
+        handle: PlacesApiHandle,
+        db_path: String,
+        out_err: RustError.ByReference
+    ): Pointer?
+
     fun bookmarks_get_all_with_url(
         handle: PlacesConnectionHandle,
         url: String,
@@ -252,6 +461,41 @@ internal interface LibPlacesFFI : Library {
         error: RustError.ByReference
     )
 
+    fun bookmarks_restore_deleted(
+        handle: PlacesConnectionHandle,
+        guid: String,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun bookmarks_move(
+        handle: PlacesConnectionHandle,
+        guids: String,
+        parentGUID: String,
+        position: Int,
+        error: RustError.ByReference
+    )
+
+    fun bookmarks_copy(
+        handle: PlacesConnectionHandle,
+        guid: String,
+        parentGUID: String,
+        position: Int,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun bookmarks_sort_children(
+        handle: PlacesConnectionHandle,
+        guid: String,
+        order: Byte,
+        error: RustError.ByReference
+    )
+
+    fun bookmarks_reverse_children(
+        handle: PlacesConnectionHandle,
+        guid: String,
+        error: RustError.ByReference
+    )
+
     // Returns 1 if the item existed and was deleted.
     fun bookmarks_delete(
         handle: PlacesConnectionHandle,
@@ -259,6 +503,18 @@ internal interface LibPlacesFFI : Library {
         error: RustError.ByReference
     ): Byte
 
+    fun places_bookmarks_import_html(
+        handle: PlacesConnectionHandle,
+        html: String,
+        error: RustError.ByReference
+    )
+
+    // Returns an HTML string, which must be freed with places_destroy_string
+    fun places_bookmarks_export_html(
+        handle: PlacesConnectionHandle,
+        error: RustError.ByReference
+    ): Pointer?
+
     /** Destroy strings returned from libplaces_ffi calls. */
     fun places_destroy_string(s: Pointer)
 
@@ -274,15 +530,23 @@ internal interface LibPlacesFFI : Library {
     /** Destroy api created using `places_api_new` */
     fun places_api_destroy(handle: PlacesApiHandle, out_err: RustError.ByReference)
 
+    /** Destroy queue created using `places_events_queue_new` */
+    fun places_events_queue_destroy(handle: PlacesEventQueueHandle, out_err: RustError.ByReference)
+
     /** Destroy handle created using `places_new_interrupt_handle` */
     fun places_interrupt_handle_destroy(obj: RawPlacesInterruptHandle)
 
+    /** Destroy handle created using `places_new_search_interrupt_handle` */
+    fun places_search_interrupt_handle_destroy(obj: RawPlacesSearchInterruptHandle)
+
     fun places_destroy_bytebuffer(bb: RustBuffer.ByValue)
 }
 
 internal typealias PlacesConnectionHandle = Long
 internal typealias PlacesApiHandle = Long
+internal typealias PlacesEventQueueHandle = Long
 
 // This doesn't use a handle to avoid unnecessary locking and
 // because the type is panic safe, sync, and send.
 class RawPlacesInterruptHandle : PointerType()
+class RawPlacesSearchInterruptHandle : PointerType()
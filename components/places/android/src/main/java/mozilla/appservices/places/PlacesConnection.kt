@@ -105,6 +105,77 @@ class PlacesApi(path: String) : PlacesManager, AutoCloseable {
         }
         return SyncTelemetryPing.fromJSONString(pingJSONString)
     }
+
+    override fun importFromDesktop(path: String): DesktopImportMetrics {
+        val json = rustCallForString(this) { error ->
+            LibPlacesFFI.INSTANCE.places_import_from_desktop(this.handle.get(), path, error)
+        }
+        return DesktopImportMetrics.fromJSONString(json)
+    }
+
+    override fun openEventQueue(): PlacesEventQueue {
+        val queueHandle = rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_events_queue_new(this.handle.get(), error)
+        }
+        return PlacesEventQueue(queueHandle)
+    }
+
+    override fun setFrecencySettings(settings: FrecencySettings) {
+        rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_set_frecency_settings(
+                    this.handle.get(), settings.toJSON().toString(), error)
+        }
+    }
+
+    override fun setHistoryPolicy(policy: HistoryPolicy) {
+        rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_set_history_policy(
+                    this.handle.get(), policy.toJSON().toString(), error)
+        }
+    }
+
+    override fun setHistoryBackfillSettings(maxRecordsPerSync: Int, horizonDays: Int) {
+        require(maxRecordsPerSync > 0) { "maxRecordsPerSync must be positive" }
+        require(horizonDays > 0) { "horizonDays must be positive" }
+        rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_set_history_backfill_settings(
+                    this.handle.get(), maxRecordsPerSync, horizonDays, error)
+        }
+    }
+
+    override fun setHistorySyncDiagnosticsEnabled(enabled: Boolean) {
+        rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_set_history_sync_diagnostics_enabled(
+                    this.handle.get(), if (enabled) 1 else 0, error)
+        }
+    }
+
+    override fun getLastHistorySyncDiagnostics(): HistorySyncDiagnostics? {
+        // Like `matchUrl`, this can return null on success, so we can't use
+        // rustCallForString.
+        val jsonPtr = rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_get_last_history_sync_diagnostics(
+                    this.handle.get(), error)
+        }
+        try {
+            return jsonPtr?.getString(0, "utf-8")?.let { HistorySyncDiagnostics.fromJSON(it) }
+        } finally {
+            jsonPtr?.let { LibPlacesFFI.INSTANCE.places_destroy_string(it) }
+        }
+    }
+
+    override fun setInputHistorySyncEnabled(enabled: Boolean) {
+        rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_set_input_history_sync_enabled(
+                    this.handle.get(), if (enabled) 1 else 0, error)
+        }
+    }
+
+    override fun recalculateFrecencies(): Long {
+        return rustCall(this) { error ->
+            LibPlacesFFI.INSTANCE.places_api_recalculate_frecencies(this.handle.get(), error)
+        }
+    }
 }
 
 internal inline fun <U> rustCall(syncOn: Any, callback: (RustError.ByReference) -> U): U {
@@ -135,6 +206,7 @@ internal inline fun rustCallForString(syncOn: Any, callback: (RustError.ByRefere
 open class PlacesConnection internal constructor(connHandle: Long) : InterruptibleConnection, AutoCloseable {
     protected var handle: AtomicLong = AtomicLong(0)
     protected var interruptHandle: InterruptHandle
+    protected var searchInterruptHandle: SearchInterruptHandle
 
     init {
         handle.set(connHandle)
@@ -148,6 +220,17 @@ open class PlacesConnection internal constructor(connHandle: Long) : Interruptib
             }
             throw e
         }
+        try {
+            searchInterruptHandle = SearchInterruptHandle(rustCall { err ->
+                LibPlacesFFI.INSTANCE.places_new_search_interrupt_handle(connHandle, err)
+            }!!)
+        } catch (e: Throwable) {
+            interruptHandle.close()
+            rustCall { error ->
+                LibPlacesFFI.INSTANCE.places_connection_destroy(this.handle.getAndSet(0), error)
+            }
+            throw e
+        }
     }
 
     @Synchronized
@@ -159,6 +242,7 @@ open class PlacesConnection internal constructor(connHandle: Long) : Interruptib
             }
         }
         interruptHandle.close()
+        searchInterruptHandle.close()
     }
 
     @Synchronized
@@ -188,7 +272,8 @@ open class PlacesConnection internal constructor(connHandle: Long) : Interruptib
 open class PlacesReaderConnection internal constructor(connHandle: Long) :
         PlacesConnection(connHandle),
         ReadableHistoryConnection,
-        ReadableBookmarksConnection {
+        ReadableBookmarksConnection,
+        ReadableTagsConnection {
     override fun queryAutocomplete(query: String, limit: Int): List<SearchResult> {
         val json = rustCallForString { error ->
             LibPlacesFFI.INSTANCE.places_query_autocomplete(this.handle.get(), query, limit, error)
@@ -196,6 +281,10 @@ open class PlacesReaderConnection internal constructor(connHandle: Long) :
         return SearchResult.fromJSONArray(json)
     }
 
+    override fun cancelAutocomplete() {
+        this.searchInterruptHandle.interrupt()
+    }
+
     override fun matchUrl(query: String): String? {
         // Can't use rustCallForString if we return null on success. Possibly worth splitting
         // into a rustCallForOptString or something, but I'll wait until we need it again.
@@ -277,6 +366,65 @@ open class PlacesReaderConnection internal constructor(connHandle: Long) :
         }
     }
 
+    override fun searchHistory(
+        query: String,
+        start: Long,
+        end: Long,
+        excludeTypes: List<VisitType>,
+        groupByDate: Boolean,
+        cursor: String?,
+        limit: Int
+    ): HistorySearchResults {
+        val resultBuffer = rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_search_history(
+                    this.handle.get(), query, start, end, visitTransitionSet(excludeTypes),
+                    if (groupByDate) 1 else 0, cursor, limit, error)
+        }
+        try {
+            val results = MsgTypes.HistorySearchResults.parseFrom(resultBuffer.asCodedInputStream()!!)
+            return HistorySearchResults.fromMessage(results)
+        } finally {
+            LibPlacesFFI.INSTANCE.places_destroy_bytebuffer(resultBuffer)
+        }
+    }
+
+    override fun getVisitChain(url: String): List<VisitInfo> {
+        val infoBuffer = rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_get_visit_chain(this.handle.get(), url, error)
+        }
+        try {
+            val infos = MsgTypes.HistoryVisitInfos.parseFrom(infoBuffer.asCodedInputStream()!!)
+            return VisitInfo.fromMessage(infos)
+        } finally {
+            LibPlacesFFI.INSTANCE.places_destroy_bytebuffer(infoBuffer)
+        }
+    }
+
+    override fun getTopFrecentSites(
+        limit: Int,
+        grouping: TopSitesGrouping,
+        excludeHidden: Boolean,
+        excludeRedirectSources: Boolean,
+        excludeErrorPages: Boolean
+    ): List<TopFrecentSiteInfo> {
+        val json = rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_get_top_frecent_sites(
+                    this.handle.get(), limit, grouping.value.toByte(),
+                    if (excludeHidden) 1 else 0,
+                    if (excludeRedirectSources) 1 else 0,
+                    if (excludeErrorPages) 1 else 0,
+                    error)
+        }
+        return TopFrecentSiteInfo.fromJSONArray(json)
+    }
+
+    override fun getVisitedDomains(limit: Int): List<VisitedDomain> {
+        val json = rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_get_visited_domains(this.handle.get(), limit, error)
+        }
+        return VisitedDomain.fromJSONArray(json)
+    }
+
     override fun getVisitCount(excludeTypes: List<VisitType>): Long {
         return rustCall { error ->
             LibPlacesFFI.INSTANCE.places_get_visit_count(
@@ -352,6 +500,42 @@ open class PlacesReaderConnection internal constructor(connHandle: Long) :
             LibPlacesFFI.INSTANCE.places_destroy_bytebuffer(rustBuf)
         }
     }
+
+    override fun exportBookmarksToHtml(): String {
+        return rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_bookmarks_export_html(this.handle.get(), error)
+        }
+    }
+
+    override fun getTagsForURL(url: String): List<String> {
+        val json = rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_get_tags_for_url(this.handle.get(), url, error)
+        }
+        return stringsFromJSONArray(json)
+    }
+
+    override fun getURLsWithTag(tag: String): List<String> {
+        val json = rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_get_urls_with_tag(this.handle.get(), tag, error)
+        }
+        return stringsFromJSONArray(json)
+    }
+
+    override fun getAllTags(): List<TagInfo> {
+        val json = rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.places_get_all_tags(this.handle.get(), error)
+        }
+        return TagInfo.fromJSONArray(json)
+    }
+
+    private fun stringsFromJSONArray(json: String): List<String> {
+        val arr = JSONArray(json)
+        val result = mutableListOf<String>()
+        for (idx in 0 until arr.length()) {
+            result.add(arr.getString(idx))
+        }
+        return result
+    }
 }
 
 fun visitTransitionSet(l: List<VisitType>): Int {
@@ -371,7 +555,8 @@ fun visitTransitionSet(l: List<VisitType>): Int {
 class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesApi) :
         PlacesReaderConnection(connHandle),
         WritableHistoryConnection,
-        WritableBookmarksConnection {
+        WritableBookmarksConnection,
+        WritableTagsConnection {
     // The reference to our PlacesAPI. Mostly used to know how to handle getting closed.
     val apiRef = WeakReference(api)
     override fun noteObservation(data: VisitObservation) {
@@ -381,6 +566,18 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         }
     }
 
+    override fun notePageMetadata(
+        url: String,
+        title: String?,
+        description: String?,
+        previewImageUrl: String?
+    ) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_note_page_metadata(
+                    this.handle.get(), url, title, description, previewImageUrl, error)
+        }
+    }
+
     override fun deletePlace(url: String) {
         rustCall { error ->
             LibPlacesFFI.INSTANCE.places_delete_place(
@@ -406,6 +603,13 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         }
     }
 
+    override fun deleteHistoryForHost(host: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_delete_history_for_host(
+                    this.handle.get(), host, error)
+        }
+    }
+
     override fun wipeLocal() {
         rustCall { error ->
             LibPlacesFFI.INSTANCE.places_wipe_local(this.handle.get(), error)
@@ -424,6 +628,39 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         }
     }
 
+    override fun expireHistory(maxPages: Long?, maxVisitAgeMs: Long?) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_expire_history(
+                    this.handle.get(), maxPages ?: -1, maxVisitAgeMs ?: -1, error)
+        }
+    }
+
+    override fun pinTopSite(url: String, title: String?, position: Int) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_pin_top_site(this.handle.get(), url, title, position, error)
+        }
+    }
+
+    override fun unpinTopSite(url: String): Boolean {
+        val wasPinned = rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_unpin_top_site(this.handle.get(), url, error)
+        }
+        return wasPinned.toInt() != 0
+    }
+
+    override fun blockTopSite(url: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_block_top_site(this.handle.get(), url, error)
+        }
+    }
+
+    override fun unblockTopSite(url: String): Boolean {
+        val wasBlocked = rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_unblock_top_site(this.handle.get(), url, error)
+        }
+        return wasBlocked.toInt() != 0
+    }
+
     override fun deleteEverything() {
         rustCall { error ->
             LibPlacesFFI.INSTANCE.places_delete_everything(this.handle.get(), error)
@@ -437,6 +674,48 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         return existedByte.toInt() != 0
     }
 
+    override fun restoreDeletedBookmark(guid: String): String {
+        return rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.bookmarks_restore_deleted(this.handle.get(), guid, error)
+        }
+    }
+
+    override fun importBookmarksFromHtml(html: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_bookmarks_import_html(this.handle.get(), html, error)
+        }
+    }
+
+    override fun tagURL(url: String, tag: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_tag_url(this.handle.get(), url, tag, error)
+        }
+    }
+
+    override fun untagURL(url: String, tag: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_untag_url(this.handle.get(), url, tag, error)
+        }
+    }
+
+    override fun removeAllTagsFromURL(url: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_remove_all_tags_from_url(this.handle.get(), url, error)
+        }
+    }
+
+    override fun removeTag(tag: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_remove_tag(this.handle.get(), tag, error)
+        }
+    }
+
+    override fun renameTag(oldTag: String, newTag: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.places_rename_tag(this.handle.get(), oldTag, newTag, error)
+        }
+    }
+
     // Does the shared insert work, takes the position just because
     // its a little tedious to type out setting it
     private fun doInsert(builder: MsgTypes.BookmarkNode.Builder, position: Int?): String {
@@ -482,6 +761,31 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
         }
     }
 
+    override fun moveBookmarks(guids: List<String>, parentGUID: String, position: Int?) {
+        val json = JSONArray(guids).toString()
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.bookmarks_move(this.handle.get(), json, parentGUID, position ?: -1, error)
+        }
+    }
+
+    override fun copyBookmark(guid: String, parentGUID: String, position: Int?): String {
+        return rustCallForString { error ->
+            LibPlacesFFI.INSTANCE.bookmarks_copy(this.handle.get(), guid, parentGUID, position ?: -1, error)
+        }
+    }
+
+    override fun sortFolderChildren(guid: String, order: ChildrenSortOrder) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.bookmarks_sort_children(this.handle.get(), guid, order.value.toByte(), error)
+        }
+    }
+
+    override fun reverseFolderChildren(guid: String) {
+        rustCall { error ->
+            LibPlacesFFI.INSTANCE.bookmarks_reverse_children(this.handle.get(), guid, error)
+        }
+    }
+
     @Synchronized
     override fun close() {
         // If our API is still around, do nothing.
@@ -496,6 +800,7 @@ class PlacesWriterConnection internal constructor(connHandle: Long, api: PlacesA
     internal fun takeHandle(): PlacesConnectionHandle {
         val handle = this.handle.getAndSet(0L)
         interruptHandle.close()
+        searchInterruptHandle.close()
         return handle
     }
 }
@@ -549,6 +854,87 @@ interface PlacesManager {
      * you have all connections you intend using open before calling this.
      */
     fun syncBookmarks(syncInfo: SyncAuthInfo): SyncTelemetryPing
+
+    /**
+     * Imports history and bookmarks from a desktop Firefox `places.sqlite`
+     * at [path], merging them with the existing data. Items that already
+     * exist are left alone, so importing the same file twice is harmless.
+     *
+     * @return The number of rows imported and skipped for each table.
+     */
+    fun importFromDesktop(path: String): DesktopImportMetrics
+
+    /**
+     * Starts queueing change events for history and bookmarks. See
+     * [PlacesEventQueue] for details. The queue must be closed when it's no
+     * longer needed.
+     */
+    fun openEventQueue(): PlacesEventQueue
+
+    /**
+     * Changes the settings used to calculate frecency. Settings that are
+     * null in [settings] are reset to their defaults. Existing frecencies
+     * aren't changed until [recalculateFrecencies] is called.
+     */
+    fun setFrecencySettings(settings: FrecencySettings)
+
+    /**
+     * Recalculates the frecency of every page with the current settings,
+     * returning the number of pages recalculated.
+     *
+     * This may take some time for large histories, so it should be called
+     * from a background thread. It runs on the sync connection, so it
+     * blocks syncing but not the writer connection. If the app is killed
+     * before it finishes, the next call continues where it left off.
+     */
+    fun recalculateFrecencies(): Long
+
+    /**
+     * Changes which URLs are recorded in history, for new visits and for
+     * history received from Sync. `about:`, `data:`, `javascript:`,
+     * `blob:` and `file:` URLs are never recorded.
+     *
+     * Changing the policy doesn't remove existing history for the blocked
+     * URLs; use [WritableHistoryConnection.deleteHistoryForHost] for that.
+     *
+     * @throws PlacesException if a scheme or host in the policy is invalid.
+     */
+    fun setHistoryPolicy(policy: HistoryPolicy)
+
+    /**
+     * Changes how much history is downloaded in each sync.
+     *
+     * The first history sync on a new device downloads only the most
+     * recently changed records, so that it finishes quickly. Later syncs
+     * download older records, alternating with syncs that download new ones,
+     * until they've all been downloaded or the horizon is reached.
+     *
+     * @param maxRecordsPerSync The most records to download in a first sync or
+     * a backfill. Defaults to 5000.
+     * @param horizonDays How far back, in days, to download history on a new
+     * device. Defaults to 60.
+     * @throws IllegalArgumentException if either value isn't positive.
+     */
+    fun setHistoryBackfillSettings(maxRecordsPerSync: Int, horizonDays: Int)
+
+    /**
+     * Turns on or off recording what each history sync does with every
+     * incoming record, to help debug missing history. Off by default.
+     * Turning it off also forgets the last sync's diagnostics.
+     */
+    fun setHistorySyncDiagnosticsEnabled(enabled: Boolean)
+
+    /**
+     * Returns what the last history sync did with each incoming record, or
+     * null if diagnostics weren't enabled for it.
+     */
+    fun getLastHistorySyncDiagnostics(): HistorySyncDiagnostics?
+
+    /**
+     * Turns on or off syncing the inputs typed into the URL bar for adaptive
+     * autocomplete. Off by default.
+     */
+    fun setInputHistorySyncEnabled(enabled: Boolean)
 }
 
 interface InterruptibleConnection : AutoCloseable {
@@ -568,6 +954,17 @@ interface ReadableHistoryConnection : InterruptibleConnection {
      */
     fun queryAutocomplete(query: String, limit: Int): List<SearchResult>
 
+    /**
+     * Cancel a [queryAutocomplete] call that's running on a separate thread,
+     * for example because the user typed another character. Unlike
+     * [interrupt], this doesn't affect any other operations on this
+     * connection.
+     *
+     * The canceled [queryAutocomplete] call throws [OperationInterrupted].
+     * This does nothing if a query isn't running.
+     */
+    fun cancelAutocomplete()
+
     /**
      * See if a url that's sufficiently close to `search` exists in
      * the database.
@@ -624,6 +1021,47 @@ interface ReadableHistoryConnection : InterruptibleConnection {
      */
     fun getVisitPage(offset: Long, count: Long, excludeTypes: List<VisitType> = listOf()): List<VisitInfo>
 
+    /**
+     * Search history, returning one result per page with the number of matching
+     * visits to it. Results are ordered by each page's most recent matching visit,
+     * newest first.
+     *
+     * Unlike [getVisitPage], this pages with a cursor, so new visits don't shift
+     * the results while you're paging through them.
+     *
+     * @param query Words to search for in page titles and URLs. Every word must
+     * match. An empty query matches every page.
+     * @param start The (inclusive) start of the date range, in milliseconds since
+     * the unix epoch.
+     * @param end The (inclusive) end of the date range, in milliseconds since the
+     * unix epoch.
+     * @param excludeTypes List of visit types to exclude.
+     * @param groupByDate Whether to set the [HistorySearchResult.bucket] of each
+     * result.
+     * @param cursor The [HistorySearchResults.nextCursor] from the previous page,
+     * or null for the first page.
+     * @param limit The maximum number of results to return.
+     */
+    fun searchHistory(
+        query: String = "",
+        start: Long = 0,
+        end: Long = Long.MAX_VALUE,
+        excludeTypes: List<VisitType> = listOf(),
+        groupByDate: Boolean = false,
+        cursor: String? = null,
+        limit: Int = 100
+    ): HistorySearchResults
+
+    /**
+     * Get the chain of visits which led to the most recent visit to [url], found
+     * by following each visit's referrer back through any links and redirects.
+     * The chain is ordered from its start, and ends with the most recent visit to
+     * [url]. Returns an empty list if [url] has never been visited.
+     *
+     * @param url The URL of the page at the end of the chain.
+     */
+    fun getVisitChain(url: String): List<VisitInfo>
+
     /**
      * Get the number of history visits.
      *
@@ -637,6 +1075,38 @@ interface ReadableHistoryConnection : InterruptibleConnection {
      * @param excludeTypes List of visit types to exclude.
      */
     fun getVisitCount(excludeTypes: List<VisitType> = listOf()): Long
+
+    /**
+     * Returns the most frecent sites, for a "top sites" or "most visited"
+     * view. Sites pinned with [WritableHistoryConnection.pinTopSite] are
+     * returned at their positions, and blocked sites are left out.
+     *
+     * @param limit The maximum number of sites to return, including pinned
+     * sites.
+     * @param grouping Whether to return pages, the most frecent page for each
+     * origin, or origins.
+     * @param excludeHidden Whether to leave out hidden pages, such as the
+     * pages loaded in frames.
+     * @param excludeRedirectSources Whether to leave out pages whose latest
+     * visit redirected to another page.
+     * @param excludeErrorPages Whether to leave out pages whose latest visit
+     * failed to load.
+     */
+    fun getTopFrecentSites(
+        limit: Int = 10,
+        grouping: TopSitesGrouping = TopSitesGrouping.PAGES,
+        excludeHidden: Boolean = true,
+        excludeRedirectSources: Boolean = true,
+        excludeErrorPages: Boolean = true
+    ): List<TopFrecentSiteInfo>
+
+    /**
+     * Returns the hosts that have been visited, most recently visited first,
+     * with the number of visits and the time of the latest visit to each.
+     *
+     * @param limit The maximum number of hosts to return.
+     */
+    fun getVisitedDomains(limit: Int = 100): List<VisitedDomain>
 }
 
 interface WritableHistoryConnection : ReadableHistoryConnection {
@@ -645,6 +1115,20 @@ interface WritableHistoryConnection : ReadableHistoryConnection {
      */
     fun noteObservation(data: VisitObservation)
 
+    /**
+     * Record the title, description and preview image for a page, without
+     * recording a visit. These are returned by [getVisitInfos],
+     * [queryAutocomplete] and the bookmark fetching methods.
+     *
+     * Any of these may be null, to leave the existing value unchanged.
+     */
+    fun notePageMetadata(
+        url: String,
+        title: String? = null,
+        description: String? = null,
+        previewImageUrl: String? = null
+    )
+
     /**
      * Deletes all history visits, without recording tombstones.
      *
@@ -680,6 +1164,47 @@ interface WritableHistoryConnection : ReadableHistoryConnection {
      */
     fun pruneDestructively()
 
+    /**
+     * Expire old and infrequently visited history, so that the database
+     * doesn't grow without bound. Unlike [pruneDestructively], this only
+     * removes pages that aren't bookmarked, and the removals aren't synced.
+     *
+     * This runs in small chunks, and may be interrupted with [interrupt]
+     * without losing the work that's already done.
+     *
+     * @param maxPages The maximum number of pages to keep, or null for no
+     * limit. Pages with the lowest frecency are removed first.
+     * @param maxVisitAgeMs The maximum age of visits to keep, in
+     * milliseconds, or null for no limit.
+     */
+    fun expireHistory(maxPages: Long? = null, maxVisitAgeMs: Long? = null)
+
+    /**
+     * Pin a site at a position in the top sites, replacing any site that's
+     * already pinned there. Pinning a blocked site unblocks it.
+     *
+     * Pins and blocks are kept when the site is removed from history.
+     *
+     * @param title The title to show for the site, or null to use the title
+     * from history.
+     */
+    fun pinTopSite(url: String, title: String? = null, position: Int)
+
+    /**
+     * Unpin a site. Returns true if it was pinned.
+     */
+    fun unpinTopSite(url: String): Boolean
+
+    /**
+     * Keep a site out of the top sites. Blocking a pinned site unpins it.
+     */
+    fun blockTopSite(url: String)
+
+    /**
+     * Unblock a site. Returns true if it was blocked.
+     */
+    fun unblockTopSite(url: String): Boolean
+
     /**
      * Delete everything locally.
      *
@@ -745,6 +1270,18 @@ interface WritableHistoryConnection : ReadableHistoryConnection {
      * @param visitTimestamp The timestamp of the visit to delete, in MS since the unix epoch
      */
     fun deleteVisit(url: String, visitTimestamp: Long)
+
+    /**
+     * Deletes all history for a host and its subdomains, for "forget about
+     * this site". Deleting "example.com" also deletes the history for
+     * "www.example.com", on any port.
+     *
+     * Pages that have been synced are deleted on other devices, too.
+     * Bookmarked pages are kept, but their visits are deleted.
+     *
+     * @param host The host to delete, like "example.com".
+     */
+    fun deleteHistoryForHost(host: String)
 }
 
 class InterruptHandle internal constructor(raw: RawPlacesInterruptHandle) : AutoCloseable {
@@ -771,6 +1308,29 @@ class InterruptHandle internal constructor(raw: RawPlacesInterruptHandle) : Auto
     }
 }
 
+class SearchInterruptHandle internal constructor(raw: RawPlacesSearchInterruptHandle) : AutoCloseable {
+    private val handle: AtomicReference<RawPlacesSearchInterruptHandle?> = AtomicReference(raw)
+
+    @Synchronized
+    override fun close() {
+        val toFree = handle.getAndSet(null)
+        if (toFree != null) {
+            LibPlacesFFI.INSTANCE.places_search_interrupt_handle_destroy(toFree)
+        }
+    }
+
+    @Synchronized
+    fun interrupt() {
+        handle.get()?.let {
+            val e = RustError.ByReference()
+            LibPlacesFFI.INSTANCE.places_interrupt_search(it, e)
+            if (e.isFailure()) {
+                throw e.intoException()
+            }
+        }
+    }
+}
+
 open class PlacesException(msg: String) : Exception(msg)
 open class InternalPanic(msg: String) : PlacesException(msg)
 open class UrlParseFailed(msg: String) : PlacesException(msg)
@@ -796,7 +1356,7 @@ enum class VisitType(val type: Int) {
     RELOAD(9)
 }
 
-private val intToVisitType: Map<Int, VisitType> = VisitType.values().associateBy(VisitType::type)
+internal val intToVisitType: Map<Int, VisitType> = VisitType.values().associateBy(VisitType::type)
 
 /**
  * Encapsulates either information about a visit to a page, or meta information about the page,
@@ -837,7 +1397,9 @@ data class SearchResult(
     val url: String,
     val title: String,
     val frecency: Long,
-    val iconUrl: String? = null
+    val iconUrl: String? = null,
+    val description: String? = null,
+    val previewImageUrl: String? = null
     // Skipping `reasons` for now...
 ) {
     companion object {
@@ -847,7 +1409,9 @@ data class SearchResult(
                 url = jsonObject.getString("url"),
                 title = jsonObject.getString("title"),
                 frecency = jsonObject.getLong("frecency"),
-                iconUrl = stringOrNull(jsonObject, "icon_url")
+                iconUrl = stringOrNull(jsonObject, "icon_url"),
+                description = stringOrNull(jsonObject, "description"),
+                previewImageUrl = stringOrNull(jsonObject, "preview_image_url")
             )
         }
 
@@ -862,6 +1426,215 @@ data class SearchResult(
     }
 }
 
+/**
+ * What happened to the rows of one of the tables in a desktop import. Rows
+ * that were neither imported nor skipped already existed.
+ */
+data class TableImportCounts(
+    /**
+     * The number of rows in the desktop table.
+     */
+    val total: Int,
+
+    /**
+     * The number of rows that were added.
+     */
+    val imported: Int,
+
+    /**
+     * The number of rows that were invalid, or referenced something that was
+     * skipped.
+     */
+    val skipped: Int
+) {
+    companion object {
+        internal fun fromJSON(jsonObject: JSONObject): TableImportCounts {
+            return TableImportCounts(
+                total = jsonObject.getInt("total"),
+                imported = jsonObject.getInt("imported"),
+                skipped = jsonObject.getInt("skipped")
+            )
+        }
+    }
+}
+
+/**
+ * The results of `PlacesManager.importFromDesktop`.
+ */
+data class DesktopImportMetrics(
+    val places: TableImportCounts,
+    val visits: TableImportCounts,
+    val bookmarks: TableImportCounts,
+    val keywords: TableImportCounts,
+    val inputHistory: TableImportCounts
+) {
+    companion object {
+        internal fun fromJSONString(jsonString: String): DesktopImportMetrics {
+            val o = JSONObject(jsonString)
+            return DesktopImportMetrics(
+                places = TableImportCounts.fromJSON(o.getJSONObject("places")),
+                visits = TableImportCounts.fromJSON(o.getJSONObject("visits")),
+                bookmarks = TableImportCounts.fromJSON(o.getJSONObject("bookmarks")),
+                keywords = TableImportCounts.fromJSON(o.getJSONObject("keywords")),
+                inputHistory = TableImportCounts.fromJSON(o.getJSONObject("input_history"))
+            )
+        }
+    }
+}
+
+/**
+ * The date bucket of a [HistorySearchResult], based on its most recent
+ * matching visit.
+ */
+enum class HistorySearchBucket(val value: Int) {
+    /** Visited in the last 24 hours. */
+    DAY(0),
+    /** Visited in the last 7 days. */
+    WEEK(1),
+    /** Visited more than 7 days ago. */
+    OLDER(2)
+}
+
+private val intToHistorySearchBucket: Map<Int, HistorySearchBucket> =
+    HistorySearchBucket.values().associateBy(HistorySearchBucket::value)
+
+/**
+ * A page matching a history search. Returned by `searchHistory`.
+ */
+data class HistorySearchResult(
+    val url: String,
+    val title: String?,
+    /**
+     * The time of the most recent matching visit, in milliseconds since the
+     * unix epoch.
+     */
+    val lastVisitTime: Long,
+    /**
+     * The number of matching visits.
+     */
+    val visitCount: Long,
+    /**
+     * The date bucket, if the search was grouped by date.
+     */
+    val bucket: HistorySearchBucket?
+)
+
+/**
+ * A page of history search results. Returned by `searchHistory`.
+ */
+data class HistorySearchResults(
+    val results: List<HistorySearchResult>,
+    /**
+     * Pass this to `searchHistory` to get the next page of results, or null
+     * if there are no more.
+     */
+    val nextCursor: String?
+) {
+    companion object {
+        internal fun fromMessage(msg: MsgTypes.HistorySearchResults): HistorySearchResults {
+            return HistorySearchResults(
+                results = msg.resultsList.map {
+                    HistorySearchResult(
+                        url = it.url,
+                        title = if (it.hasTitle()) it.title else null,
+                        lastVisitTime = it.lastVisitDate,
+                        visitCount = it.visitCount,
+                        bucket = if (it.hasBucket()) intToHistorySearchBucket[it.bucket] else null
+                    )
+                },
+                nextCursor = if (msg.hasNextCursor()) msg.nextCursor else null
+            )
+        }
+    }
+}
+
+/**
+ * How `getTopFrecentSites` groups pages into sites.
+ */
+enum class TopSitesGrouping(val value: Int) {
+    /** Each page is a separate site. */
+    PAGES(0),
+    /** Only the most frecent page for each origin is returned. */
+    ONE_PAGE_PER_ORIGIN(1),
+    /** Origins are returned instead of pages. */
+    ORIGINS(2)
+}
+
+/**
+ * A site returned by `getTopFrecentSites`.
+ */
+data class TopFrecentSiteInfo(
+    /**
+     * The URL of the page, or the root URL of the origin when grouping by
+     * origin.
+     */
+    val url: String,
+    val title: String?,
+    val frecency: Long,
+    /**
+     * Whether the site was pinned with `pinTopSite`.
+     */
+    val pinned: Boolean
+) {
+    companion object {
+        internal fun fromJSON(jsonObject: JSONObject): TopFrecentSiteInfo {
+            return TopFrecentSiteInfo(
+                url = jsonObject.getString("url"),
+                title = stringOrNull(jsonObject, "title"),
+                frecency = jsonObject.getLong("frecency"),
+                pinned = jsonObject.getBoolean("pinned")
+            )
+        }
+
+        internal fun fromJSONArray(jsonArrayText: String): List<TopFrecentSiteInfo> {
+            val result: MutableList<TopFrecentSiteInfo> = mutableListOf()
+            val array = JSONArray(jsonArrayText)
+            for (index in 0 until array.length()) {
+                result.add(fromJSON(array.getJSONObject(index)))
+            }
+            return result
+        }
+    }
+}
+
+/**
+ * A host returned by `getVisitedDomains`.
+ */
+data class VisitedDomain(
+    /**
+     * The host, including the port if it isn't the default.
+     */
+    val host: String,
+    /**
+     * The number of visits to pages on the host.
+     */
+    val visitCount: Long,
+    /**
+     * The time of the latest visit to the host, in milliseconds since the
+     * unix epoch.
+     */
+    val lastVisitDate: Long
+) {
+    companion object {
+        internal fun fromJSON(jsonObject: JSONObject): VisitedDomain {
+            return VisitedDomain(
+                host = jsonObject.getString("host"),
+                visitCount = jsonObject.getLong("visitCount"),
+                lastVisitDate = jsonObject.getLong("lastVisitDate")
+            )
+        }
+
+        internal fun fromJSONArray(jsonArrayText: String): List<VisitedDomain> {
+            val result: MutableList<VisitedDomain> = mutableListOf()
+            val array = JSONArray(jsonArrayText)
+            for (index in 0 until array.length()) {
+                result.add(fromJSON(array.getJSONObject(index)))
+            }
+            return result
+        }
+    }
+}
+
 /**
  * Information about a history visit. Returned by `PlacesAPI.getVisitInfos`.
  */
@@ -884,7 +1657,18 @@ data class VisitInfo(
     /**
      * What the transition type of the visit is.
      */
-    val visitType: VisitType
+    val visitType: VisitType,
+
+    /**
+     * The description of the page, if one was recorded with `notePageMetadata`.
+     */
+    val description: String? = null,
+
+    /**
+     * The URL of a preview image for the page, if one was recorded with
+     * `notePageMetadata`.
+     */
+    val previewImageUrl: String? = null
 ) {
     companion object {
         internal fun fromMessage(msg: MsgTypes.HistoryVisitInfos): List<VisitInfo> {
@@ -892,7 +1676,9 @@ data class VisitInfo(
                 VisitInfo(url = it.url,
                     title = it.title,
                     visitTime = it.timestamp,
-                    visitType = intToVisitType[it.visitType]!!)
+                    visitType = intToVisitType[it.visitType]!!,
+                    description = if (it.hasDescription()) it.description else null,
+                    previewImageUrl = if (it.hasPreviewImageUrl()) it.previewImageUrl else null)
             }
         }
     }
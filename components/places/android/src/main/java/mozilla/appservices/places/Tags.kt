@@ -0,0 +1,121 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package mozilla.appservices.places
+
+import org.json.JSONArray
+import org.json.JSONObject
+
+/**
+ * A tag, and the number of URLs that have it. Returned by `getAllTags`.
+ */
+data class TagInfo(
+    val tag: String,
+
+    /**
+     * The number of URLs with this tag.
+     */
+    val urlCount: Int,
+
+    /**
+     * When the tag was last added to a URL or renamed, in milliseconds since
+     * the unix epoch.
+     */
+    val lastModified: Long
+) {
+    companion object {
+        internal fun fromJSONArray(jsonArrayText: String): List<TagInfo> {
+            val result: MutableList<TagInfo> = mutableListOf()
+            val array = JSONArray(jsonArrayText)
+            for (index in 0 until array.length()) {
+                val obj: JSONObject = array.getJSONObject(index)
+                result.add(TagInfo(
+                    tag = obj.getString("tag"),
+                    urlCount = obj.getInt("url_count"),
+                    lastModified = obj.getLong("last_modified")
+                ))
+            }
+            return result
+        }
+    }
+}
+
+/**
+ * An interface defining the set of read-only tag operations.
+ */
+interface ReadableTagsConnection : InterruptibleConnection {
+    /**
+     * Returns the tags for a URL, most recently added first.
+     *
+     * @param url The URL to get the tags for.
+     */
+    fun getTagsForURL(url: String): List<String>
+
+    /**
+     * Returns the URLs with a tag.
+     *
+     * @param tag The tag to look up.
+     *
+     * @throws InvalidTag If `tag` is empty, longer than 100 bytes, or
+     * contains whitespace.
+     */
+    fun getURLsWithTag(tag: String): List<String>
+
+    /**
+     * Returns every tag that's used by at least one URL, with the number of
+     * URLs that have it. The most used tags are returned first.
+     */
+    fun getAllTags(): List<TagInfo>
+}
+
+/**
+ * An interface defining the set of tag operations that modify the database.
+ */
+interface WritableTagsConnection : ReadableTagsConnection {
+    /**
+     * Tag a URL. Leading and trailing whitespace is removed from the tag.
+     *
+     * @throws InvalidTag If `tag` is empty, longer than 100 bytes, or
+     * contains whitespace.
+     * @throws NoSuchUrl If `url` isn't in the database, because it hasn't
+     * been visited or bookmarked.
+     */
+    fun tagURL(url: String, tag: String)
+
+    /**
+     * Remove a tag from a URL. Does nothing if the URL doesn't have the tag.
+     *
+     * @throws InvalidTag If `tag` is invalid.
+     */
+    fun untagURL(url: String, tag: String)
+
+    /**
+     * Remove all tags from a URL.
+     */
+    fun removeAllTagsFromURL(url: String)
+
+    /**
+     * Remove a tag from all URLs.
+     */
+    fun removeTag(tag: String)
+
+    /**
+     * Rename a tag on all URLs that have it. If a tag named `newTag` already
+     * exists, the two tags are merged. Does nothing if `oldTag` doesn't
+     * exist.
+     *
+     * @throws InvalidTag If either tag is invalid.
+     */
+    fun renameTag(oldTag: String, newTag: String)
+}
+
+/**
+ * Thrown when a tag is empty, longer than 100 bytes, or contains whitespace.
+ */
+open class InvalidTag(msg: String) : PlacesException(msg)
+
+/**
+ * Thrown when tagging a URL that isn't in the database.
+ */
+open class NoSuchUrl(msg: String) : PlacesException(msg)
@@ -225,6 +225,22 @@ class PlacesConnectionTest {
         assertEquals(0, empty.size)
     }
 
+    @Test
+    fun testGetVisitChain() {
+        val search = "https://search.example.com/?q=rust"
+        val redirect = "https://example.com/r"
+        val target = "https://www.example.com/"
+        db.noteObservation(VisitObservation(url = search, visitType = VisitType.TYPED, at = 100000))
+        db.noteObservation(VisitObservation(url = redirect, visitType = VisitType.LINK, isRedirectSource = true,
+                referrer = search, at = 110000))
+        db.noteObservation(VisitObservation(url = target, visitType = VisitType.REDIRECT_TEMPORARY,
+                referrer = redirect, at = 120000))
+
+        assertEquals(listOf(search, redirect, target), db.getVisitChain(target).map { it.url })
+        assertEquals(listOf(search, redirect), db.getVisitChain(redirect).map { it.url })
+        assertEquals(0, db.getVisitChain("https://www.example.com/never-visited").size)
+    }
+
     @Test
     fun testCreateBookmark() {
         val itemGUID = db.createBookmarkItem(
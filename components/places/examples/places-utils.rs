@@ -8,6 +8,8 @@
 use cli_support::fxa_creds::{get_cli_fxa, get_default_fxa_config};
 use places::bookmark_sync::store::BookmarksStore;
 use places::history_sync::store::HistoryStore;
+use places::storage::bookmarks::duplicates::{find_duplicate_bookmarks, merge_duplicates};
+use places::storage::bookmarks::integrity::check_and_repair_bookmarks;
 use places::storage::bookmarks::{
     fetch_tree, insert_tree, BookmarkNode, BookmarkRootGuid, BookmarkTreeNode, FolderNode,
     SeparatorNode,
@@ -166,6 +168,30 @@ fn run_native_export(db: &PlacesDb, filename: String) -> Result<()> {
     Ok(())
 }
 
+fn run_check_bookmarks(db: &PlacesDb, repair: bool) -> Result<()> {
+    let problems = check_and_repair_bookmarks(db, repair)?;
+    println!("{}", serde_json::to_string_pretty(&problems)?);
+    if repair && !problems.is_empty() {
+        println!("Repaired {} problems", problems.len());
+    }
+    Ok(())
+}
+
+fn run_dedupe_bookmarks(db: &PlacesDb, merge: bool) -> Result<()> {
+    let duplicates = find_duplicate_bookmarks(db)?;
+    if !merge {
+        println!("{}", serde_json::to_string_pretty(&duplicates)?);
+        return Ok(());
+    }
+    let mut merged = Vec::new();
+    for duplicate in &duplicates {
+        merged.extend(merge_duplicates(db, &duplicate.url)?);
+    }
+    println!("{}", serde_json::to_string_pretty(&merged)?);
+    println!("Merged duplicates for {} URLs", merged.len());
+    Ok(())
+}
+
 fn sync(
     api: &PlacesApi,
     mut engine_names: Vec<String>,
@@ -356,6 +382,22 @@ enum Command {
         /// Imports bookmarks from a desktop export
         input_file: String,
     },
+
+    #[structopt(name = "check-bookmarks")]
+    /// Checks the bookmark tree for problems, and prints them as JSON
+    CheckBookmarks {
+        #[structopt(name = "repair", long)]
+        /// Repair the problems found.
+        repair: bool,
+    },
+
+    #[structopt(name = "dedupe-bookmarks")]
+    /// Finds bookmarks with the same URL, and prints them as JSON
+    DedupeBookmarks {
+        #[structopt(name = "merge", long)]
+        /// Merge each URL's bookmarks into the oldest one.
+        merge: bool,
+    },
 }
 
 fn main() -> Result<()> {
@@ -380,5 +422,7 @@ fn main() -> Result<()> {
         Command::ImportBookmarks { input_file } => run_native_import(&db, input_file),
         Command::ImportIosBookmarks { input_file } => run_ios_import(&api, input_file),
         Command::ImportDesktopBookmarks { input_file } => run_desktop_import(&db, input_file),
+        Command::CheckBookmarks { repair } => run_check_bookmarks(&db, repair),
+        Command::DedupeBookmarks { merge } => run_dedupe_bookmarks(&db, merge),
     }
 }
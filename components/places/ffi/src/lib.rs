@@ -12,14 +12,21 @@ use ffi_support::{
     define_box_destructor, define_bytebuffer_destructor, define_handle_map_deleter,
     define_string_destructor, ByteBuffer, ConcurrentHandleMap, ExternError, FfiStr,
 };
+use places::api::history::HistoryPolicy;
+use places::db::SearchInterruptHandle;
 use places::error::*;
+use places::events::{ObserverId, PlacesEventQueue};
+use places::frecency::FrecencySettings;
+use places::history_sync::HistoryBackfillSettings;
 use places::msg_types::BookmarkNodeList;
 use places::storage::bookmarks;
 use places::types::{SyncGuid, VisitTransitionSet};
 use places::{storage, ConnectionType, PlacesApi, PlacesDb};
 use sql_support::SqlInterruptHandle;
+use std::convert::TryFrom;
 use std::os::raw::c_char;
 use std::sync::Arc;
+use std::time::Duration;
 
 use places::api::matcher::{match_url, search_frecent, SearchParams};
 
@@ -45,6 +52,21 @@ pub extern "C" fn places_enable_logcat_logging() {
 lazy_static::lazy_static! {
     static ref APIS: ConcurrentHandleMap<Arc<PlacesApi>> = ConcurrentHandleMap::new();
     static ref CONNECTIONS: ConcurrentHandleMap<PlacesDb> = ConcurrentHandleMap::new();
+    static ref EVENT_QUEUES: ConcurrentHandleMap<EventQueue> = ConcurrentHandleMap::new();
+}
+
+/// A queue of change events for a `PlacesApi`. The queue stops receiving
+/// events when it's destroyed.
+struct EventQueue {
+    api: Arc<PlacesApi>,
+    queue: Arc<PlacesEventQueue>,
+    observer_id: ObserverId,
+}
+
+impl Drop for EventQueue {
+    fn drop(&mut self) {
+        self.api.remove_observer(self.observer_id);
+    }
 }
 
 /// Instantiate a places API. Returned api must be freed with
@@ -101,6 +123,48 @@ pub extern "C" fn places_bookmarks_import_from_ios(
     })
 }
 
+/// Import history and bookmarks from a desktop Firefox `places.sqlite`,
+/// returning the per-table counts as a JSON string.
+#[no_mangle]
+pub extern "C" fn places_import_from_desktop(
+    api_handle: u64,
+    db_path: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_import_from_desktop");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        places::import::import_desktop_places(api, db_path.as_str())
+    })
+}
+
+/// Import bookmarks from the contents of a Netscape `bookmarks.html` file,
+/// appending them to the existing bookmarks.
+#[no_mangle]
+pub extern "C" fn places_bookmarks_import_html(
+    handle: u64,
+    html: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_bookmarks_import_html");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        places::import::import_bookmarks_html(conn, html.as_str())?;
+        Ok(())
+    })
+}
+
+/// Export all bookmarks as a Netscape `bookmarks.html` file. Returned string
+/// must be freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_bookmarks_export_html(
+    handle: u64,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_bookmarks_export_html");
+    CONNECTIONS.call_with_result(error, handle, |conn| {
+        places::import::export_bookmarks_html(conn)
+    })
+}
+
 // Best effort, ignores failure.
 #[no_mangle]
 pub extern "C" fn places_api_return_write_conn(
@@ -132,6 +196,137 @@ pub extern "C" fn places_api_reset_bookmarks(api_handle: u64, error: &mut Extern
     })
 }
 
+/// Changes the frecency settings for the API's connections. `settings_json`
+/// is a JSON object with the settings to change; any settings it doesn't
+/// include are reset to their defaults.
+#[no_mangle]
+pub extern "C" fn places_api_set_frecency_settings(
+    api_handle: u64,
+    settings_json: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_api_set_frecency_settings");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        let settings: FrecencySettings = serde_json::from_str(settings_json.as_str())?;
+        api.set_frecency_settings(settings)
+    })
+}
+
+/// Changes the policy for which URLs are recorded in history. `policy_json`
+/// is a JSON object with `blockedSchemes` and `blockedHosts` arrays.
+#[no_mangle]
+pub extern "C" fn places_api_set_history_policy(
+    api_handle: u64,
+    policy_json: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_api_set_history_policy");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        let policy: HistoryPolicy = serde_json::from_str(policy_json.as_str())?;
+        api.set_history_policy(policy)
+    })
+}
+
+/// Changes how many history records are downloaded in each sync, and how far
+/// back a new device backfills history.
+#[no_mangle]
+pub extern "C" fn places_api_set_history_backfill_settings(
+    api_handle: u64,
+    max_records_per_sync: u32,
+    horizon_days: u32,
+    error: &mut ExternError,
+) {
+    log::debug!("places_api_set_history_backfill_settings");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        api.set_history_backfill_settings(HistoryBackfillSettings {
+            max_records_per_sync: max_records_per_sync as usize,
+            horizon_days,
+        })
+    })
+}
+
+/// Turns per-record diagnostics for history syncs on or off.
+#[no_mangle]
+pub extern "C" fn places_api_set_history_sync_diagnostics_enabled(
+    api_handle: u64,
+    enabled: u8, // JNA has issues with bools...
+    error: &mut ExternError,
+) {
+    log::debug!("places_api_set_history_sync_diagnostics_enabled");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        api.set_history_sync_diagnostics_enabled(enabled != 0);
+        Ok(())
+    })
+}
+
+/// Returns the last history sync's diagnostics as JSON, or null if they
+/// weren't enabled for it.
+#[no_mangle]
+pub extern "C" fn places_api_get_last_history_sync_diagnostics(
+    api_handle: u64,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_api_get_last_history_sync_diagnostics");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        Ok(match api.last_history_sync_diagnostics() {
+            Some(diagnostics) => Some(serde_json::to_string(&diagnostics)?),
+            None => None,
+        })
+    })
+}
+
+/// Turns syncing adaptive autocomplete input history on or off.
+#[no_mangle]
+pub extern "C" fn places_api_set_input_history_sync_enabled(
+    api_handle: u64,
+    enabled: u8, // JNA has issues with bools...
+    error: &mut ExternError,
+) {
+    log::debug!("places_api_set_input_history_sync_enabled");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        api.set_input_history_sync_enabled(enabled != 0);
+        Ok(())
+    })
+}
+
+/// Recalculates all frecencies on the sync connection, returning the number
+/// of pages recalculated.
+#[no_mangle]
+pub extern "C" fn places_api_recalculate_frecencies(
+    api_handle: u64,
+    error: &mut ExternError,
+) -> i64 {
+    log::debug!("places_api_recalculate_frecencies");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        Ok(api.recalculate_frecencies()? as i64)
+    })
+}
+
+/// Starts queueing change events for the API's connections. The returned
+/// queue must be destroyed with `places_events_queue_destroy`.
+#[no_mangle]
+pub extern "C" fn places_events_queue_new(api_handle: u64, error: &mut ExternError) -> u64 {
+    log::debug!("places_events_queue_new");
+    APIS.call_with_result(error, api_handle, |api| -> places::Result<_> {
+        let queue = Arc::new(PlacesEventQueue::default());
+        let observer_id = api.add_observer(queue.clone());
+        Ok(EVENT_QUEUES.insert(EventQueue {
+            api: api.clone(),
+            queue,
+            observer_id,
+        }))
+    })
+}
+
+/// Returns and clears the queued events, as JSON.
+#[no_mangle]
+pub extern "C" fn places_events_queue_poll(handle: u64, error: &mut ExternError) -> *mut c_char {
+    log::debug!("places_events_queue_poll");
+    EVENT_QUEUES.call_with_result(error, handle, |queue| -> places::Result<_> {
+        Ok(serde_json::to_string(&queue.queue.take())?)
+    })
+}
+
 /// Get the interrupt handle for a connection. Must be destroyed with
 /// `places_interrupt_handle_destroy`.
 #[no_mangle]
@@ -147,6 +342,25 @@ pub extern "C" fn places_interrupt(handle: &SqlInterruptHandle, error: &mut Exte
     ffi_support::call_with_output(error, || handle.interrupt())
 }
 
+/// Get a handle for canceling autocomplete searches on a connection, without
+/// interrupting anything else it's doing. Must be destroyed with
+/// `places_search_interrupt_handle_destroy`.
+#[no_mangle]
+pub extern "C" fn places_new_search_interrupt_handle(
+    handle: u64,
+    error: &mut ExternError,
+) -> *mut SearchInterruptHandle {
+    CONNECTIONS.call_with_output(error, handle, |conn| conn.new_search_interrupt_handle())
+}
+
+/// Cancel the autocomplete search (see `places_query_autocomplete`) that's
+/// running on the handle's connection, if any. The search fails with an
+/// interrupted error.
+#[no_mangle]
+pub extern "C" fn places_interrupt_search(handle: &SearchInterruptHandle, error: &mut ExternError) {
+    ffi_support::call_with_output(error, || handle.interrupt())
+}
+
 /// Add an observation to the database. The observation is a VisitObservation represented as JSON.
 /// Errors are logged.
 #[no_mangle]
@@ -163,6 +377,29 @@ pub extern "C" fn places_note_observation(
     })
 }
 
+/// Record the title, description and preview image for a page, without adding
+/// a visit. Any of them may be null, to leave them unchanged.
+#[no_mangle]
+pub extern "C" fn places_note_page_metadata(
+    handle: u64,
+    url: FfiStr<'_>,
+    title: FfiStr<'_>,
+    description: FfiStr<'_>,
+    preview_image_url: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_note_page_metadata");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        let metadata = storage::history::PageMetadata {
+            title: title.into_opt_string(),
+            description: description.into_opt_string(),
+            preview_image_url: preview_image_url.as_opt_str().map(parse_url).transpose()?,
+        };
+        storage::history::note_page_metadata(conn, &url, &metadata)
+    })
+}
+
 /// Execute a query, returning a `Vec<SearchResult>` as a JSON string. Returned string must be freed
 /// using `places_destroy_string`. Returns null and logs on errors (for now).
 #[no_mangle]
@@ -270,6 +507,33 @@ pub extern "C" fn places_delete_place(handle: u64, url: FfiStr<'_>, error: &mut
     })
 }
 
+#[no_mangle]
+pub extern "C" fn places_delete_history_for_host(
+    handle: u64,
+    host: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_delete_history_for_host");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        storage::history::delete_history_for_host(conn, host.as_str())
+    })
+}
+
+/// Returns the visited hosts as a JSON array of `VisitedDomain`.
+/// Returned string must be freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_get_visited_domains(
+    handle: u64,
+    limit: u32,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_get_visited_domains");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let domains = storage::history::get_visited_domains(conn, limit)?;
+        Ok(serde_json::to_string(&domains)?)
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn places_delete_visits_between(
     handle: u64,
@@ -338,6 +602,28 @@ pub extern "C" fn places_prune_destructively(handle: u64, error: &mut ExternErro
     })
 }
 
+/// Expire old and infrequently visited history. Pass a negative value for
+/// either limit to leave it unset.
+#[no_mangle]
+pub extern "C" fn places_expire_history(
+    handle: u64,
+    max_pages: i64,
+    max_visit_age_ms: i64,
+    error: &mut ExternError,
+) {
+    log::debug!("places_expire_history");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let policy = storage::expiration::HistoryExpirationPolicy {
+            max_pages: u32::try_from(max_pages).ok(),
+            max_visit_age: u64::try_from(max_visit_age_ms)
+                .ok()
+                .map(Duration::from_millis),
+        };
+        storage::expiration::expire_history(conn, &policy)?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn places_delete_everything(handle: u64, error: &mut ExternError) {
     log::debug!("places_delete_everything");
@@ -406,6 +692,241 @@ pub extern "C" fn places_get_visit_page(
     })
 }
 
+/// Search history, returning one result per page. `cursor` is the
+/// `next_cursor` from the previous page of results, or null for the first
+/// page.
+#[no_mangle]
+pub extern "C" fn places_search_history(
+    handle: u64,
+    query: FfiStr<'_>,
+    start_date: i64,
+    end_date: i64,
+    exclude_types: i32,
+    group_by_date: u8,
+    cursor: FfiStr<'_>,
+    limit: u32,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("places_search_history");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        storage::history::search_history(
+            conn,
+            &storage::history::HistorySearchParams {
+                query: query.into_string(),
+                start: places::Timestamp(start_date.max(0) as u64),
+                end: places::Timestamp(end_date.max(0) as u64),
+                exclude_types: VisitTransitionSet::from_u16(exclude_types as u16)
+                    .expect("Bug: Invalid VisitTransitionSet"),
+                group_by_date: group_by_date != 0,
+                cursor: cursor.into_opt_string(),
+                limit,
+            },
+        )
+    })
+}
+
+/// Returns the chain of visits which led to the most recent visit to `url`,
+/// as a `HistoryVisitInfos` message.
+#[no_mangle]
+pub extern "C" fn places_get_visit_chain(
+    handle: u64,
+    url: FfiStr<'_>,
+    error: &mut ExternError,
+) -> ByteBuffer {
+    log::debug!("places_get_visit_chain");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::history::get_visit_chain_for_url(conn, &url)
+    })
+}
+
+/// Returns the top frecent sites as a JSON array of `TopFrecentSiteInfo`.
+/// Returned string must be freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_get_top_frecent_sites(
+    handle: u64,
+    limit: u32,
+    grouping: u8,
+    exclude_hidden: u8,
+    exclude_redirect_sources: u8,
+    exclude_error_pages: u8,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_get_top_frecent_sites");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let sites = storage::top_sites::top_frecent_sites(
+            conn,
+            &storage::top_sites::TopFrecentSitesOptions {
+                limit,
+                grouping: storage::top_sites::TopSitesGrouping::from_u8(grouping)
+                    .expect("Bug: Invalid TopSitesGrouping"),
+                exclude_hidden: exclude_hidden != 0,
+                exclude_redirect_sources: exclude_redirect_sources != 0,
+                exclude_error_pages: exclude_error_pages != 0,
+            },
+        )?;
+        Ok(serde_json::to_string(&sites)?)
+    })
+}
+
+/// Pin a site at a position in the top sites. `title` may be null.
+#[no_mangle]
+pub extern "C" fn places_pin_top_site(
+    handle: u64,
+    url: FfiStr<'_>,
+    title: FfiStr<'_>,
+    position: u32,
+    error: &mut ExternError,
+) {
+    log::debug!("places_pin_top_site");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::top_sites::pin_top_site(conn, &url, title.as_opt_str(), position)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_unpin_top_site(
+    handle: u64,
+    url: FfiStr<'_>,
+    error: &mut ExternError,
+) -> u8 {
+    log::debug!("places_unpin_top_site");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::top_sites::unpin_top_site(conn, &url)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_block_top_site(handle: u64, url: FfiStr<'_>, error: &mut ExternError) {
+    log::debug!("places_block_top_site");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::top_sites::block_top_site(conn, &url)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_unblock_top_site(
+    handle: u64,
+    url: FfiStr<'_>,
+    error: &mut ExternError,
+) -> u8 {
+    log::debug!("places_unblock_top_site");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::top_sites::unblock_top_site(conn, &url)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_tag_url(
+    handle: u64,
+    url: FfiStr<'_>,
+    tag: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_tag_url");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::tags::tag_url(conn, &url, tag.as_str())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_untag_url(
+    handle: u64,
+    url: FfiStr<'_>,
+    tag: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_untag_url");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::tags::untag_url(conn, &url, tag.as_str())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_remove_all_tags_from_url(
+    handle: u64,
+    url: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_remove_all_tags_from_url");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        storage::tags::remove_all_tags_from_url(conn, &url)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_remove_tag(handle: u64, tag: FfiStr<'_>, error: &mut ExternError) {
+    log::debug!("places_remove_tag");
+    CONNECTIONS.call_with_result(error, handle, |conn| {
+        storage::tags::remove_tag(conn, tag.as_str())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn places_rename_tag(
+    handle: u64,
+    old_tag: FfiStr<'_>,
+    new_tag: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("places_rename_tag");
+    CONNECTIONS.call_with_result(error, handle, |conn| {
+        storage::tags::rename_tag(conn, old_tag.as_str(), new_tag.as_str())
+    })
+}
+
+/// Returns the tags for a URL as a JSON array of strings. Returned string must
+/// be freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_get_tags_for_url(
+    handle: u64,
+    url: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_get_tags_for_url");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let url = parse_url(url.as_str())?;
+        let tags = storage::tags::get_tags_for_url(conn, &url)?;
+        Ok(serde_json::to_string(&tags)?)
+    })
+}
+
+/// Returns the URLs with a tag as a JSON array of strings. Returned string
+/// must be freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_get_urls_with_tag(
+    handle: u64,
+    tag: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("places_get_urls_with_tag");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let urls = storage::tags::get_urls_with_tag(conn, tag.as_str())?
+            .into_iter()
+            .map(url::Url::into_string)
+            .collect::<Vec<_>>();
+        Ok(serde_json::to_string(&urls)?)
+    })
+}
+
+/// Returns all tags as a JSON array of `TagInfo`. Returned string must be
+/// freed using `places_destroy_string`.
+#[no_mangle]
+pub extern "C" fn places_get_all_tags(handle: u64, error: &mut ExternError) -> *mut c_char {
+    log::debug!("places_get_all_tags");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let tags = storage::tags::get_all_tags(conn)?;
+        Ok(serde_json::to_string(&tags)?)
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn sync15_history_sync(
     handle: u64,
@@ -529,6 +1050,90 @@ pub unsafe extern "C" fn bookmarks_update(
     })
 }
 
+/// Converts a position passed over the FFI, where a negative value means
+/// "append", to a `BookmarkPosition`.
+fn bookmark_position(position: i32) -> bookmarks::BookmarkPosition {
+    match u32::try_from(position) {
+        Ok(pos) => bookmarks::BookmarkPosition::Specific(pos),
+        Err(_) => bookmarks::BookmarkPosition::Append,
+    }
+}
+
+/// Move the items whose GUIDs are in the JSON array `guids` into a folder,
+/// keeping them together in the order given. Pass a negative `position` to
+/// append them.
+#[no_mangle]
+pub extern "C" fn bookmarks_move(
+    handle: u64,
+    guids: FfiStr<'_>,
+    parent_guid: FfiStr<'_>,
+    position: i32,
+    error: &mut ExternError,
+) {
+    log::debug!("bookmarks_move");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let guids: Vec<SyncGuid> = serde_json::from_str(guids.as_str())?;
+        bookmarks::move_bookmarks(
+            conn,
+            &guids,
+            &SyncGuid(parent_guid.into_string()),
+            bookmark_position(position),
+        )?;
+        Ok(())
+    })
+}
+
+/// Copy an item, including all descendants of a folder, returning the GUID
+/// of the copy. Pass a negative `position` to append it.
+#[no_mangle]
+pub extern "C" fn bookmarks_copy(
+    handle: u64,
+    guid: FfiStr<'_>,
+    parent_guid: FfiStr<'_>,
+    position: i32,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("bookmarks_copy");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let guid = bookmarks::copy_bookmark(
+            conn,
+            &SyncGuid(guid.into_string()),
+            &SyncGuid(parent_guid.into_string()),
+            bookmark_position(position),
+        )?;
+        Ok(guid.0)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn bookmarks_sort_children(
+    handle: u64,
+    guid: FfiStr<'_>,
+    order: u8,
+    error: &mut ExternError,
+) {
+    log::debug!("bookmarks_sort_children");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let order =
+            bookmarks::ChildrenSortOrder::from_u8(order).expect("Bug: Invalid ChildrenSortOrder");
+        bookmarks::sort_folder_children(conn, &SyncGuid(guid.into_string()), order)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn bookmarks_reverse_children(
+    handle: u64,
+    guid: FfiStr<'_>,
+    error: &mut ExternError,
+) {
+    log::debug!("bookmarks_reverse_children");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        bookmarks::reverse_folder_children(conn, &SyncGuid(guid.into_string()))?;
+        Ok(())
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn bookmarks_delete(handle: u64, id: FfiStr<'_>, error: &mut ExternError) -> u8 {
     log::debug!("bookmarks_delete");
@@ -539,6 +1144,22 @@ pub extern "C" fn bookmarks_delete(handle: u64, id: FfiStr<'_>, error: &mut Exte
     })
 }
 
+/// Restore a recently deleted item, returning its GUID, which may differ from
+/// `guid` if another item has been added with the same GUID since.
+#[no_mangle]
+pub extern "C" fn bookmarks_restore_deleted(
+    handle: u64,
+    guid: FfiStr<'_>,
+    error: &mut ExternError,
+) -> *mut c_char {
+    log::debug!("bookmarks_restore_deleted");
+    CONNECTIONS.call_with_result(error, handle, |conn| -> places::Result<_> {
+        let guid = SyncGuid(guid.into_string());
+        let restored = bookmarks::undo::restore_deleted_bookmark(conn, &guid)?;
+        Ok(restored.0)
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn bookmarks_get_all_with_url(
     handle: u64,
@@ -594,4 +1215,9 @@ define_bytebuffer_destructor!(places_destroy_bytebuffer);
 define_handle_map_deleter!(APIS, places_api_destroy);
 
 define_handle_map_deleter!(CONNECTIONS, places_connection_destroy);
+define_handle_map_deleter!(EVENT_QUEUES, places_events_queue_destroy);
 define_box_destructor!(SqlInterruptHandle, places_interrupt_handle_destroy);
+define_box_destructor!(
+    SearchInterruptHandle,
+    places_search_interrupt_handle_destroy
+);
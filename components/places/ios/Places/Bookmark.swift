@@ -36,6 +36,21 @@ public enum BookmarkNodeType: Int32 {
     // are not supported
 }
 
+/**
+ * How `sortFolderChildren` orders a folder's children.
+ */
+public enum ChildrenSortOrder: UInt8 {
+    // Note: these values need to match the Rust ChildrenSortOrder
+    // enum in bookmarks.rs
+
+    /// By title, ignoring case. Bookmarks without a title are sorted by URL.
+    case title = 0
+    /// Oldest first.
+    case dateAdded = 1
+    /// Least recently modified first.
+    case lastModified = 2
+}
+
 /**
  * A base class containing the set of fields common to all nodes
  * in the bookmark tree.
@@ -137,15 +152,31 @@ public class BookmarkItem: BookmarkNode {
      */
     public let title: String
 
+    /**
+     * The description of the bookmarked page, if one was recorded with
+     * `notePageMetadata`.
+     */
+    public let description: String?
+
+    /**
+     * The URL of a preview image for the bookmarked page, if one was
+     * recorded with `notePageMetadata`.
+     */
+    public let previewImageUrl: String?
+
     public init(guid: String,
                 dateAdded: Int64,
                 lastModified: Int64,
                 parentGUID: String?,
                 position: UInt32,
                 url: String,
-                title: String) {
+                title: String,
+                description: String? = nil,
+                previewImageUrl: String? = nil) {
         self.url = url
         self.title = title
+        self.description = description
+        self.previewImageUrl = previewImageUrl
         super.init(
             type: .bookmark,
             guid: guid,
@@ -232,7 +263,9 @@ internal func unpackProtobuf(msg: MsgTypes_BookmarkNode) -> BookmarkNode {
             parentGUID: parentGUID,
             position: position,
             url: msg.url,
-            title: title
+            title: title,
+            description: msg.hasDescription ? msg.description : nil,
+            previewImageUrl: msg.hasPreviewImageURL ? msg.previewImageURL : nil
         )
     case .separator:
         return BookmarkSeparator(
@@ -275,7 +308,9 @@ internal func unpackProtobufItemList(msg: MsgTypes_BookmarkNodeList) -> [Bookmar
             parentGUID: node.parentGuid,
             position: node.position,
             url: node.url,
-            title: node.hasTitle ? node.title : ""
+            title: node.hasTitle ? node.title : "",
+            description: node.hasDescription ? node.description : nil,
+            previewImageUrl: node.hasPreviewImageURL ? node.previewImageURL : nil
         )
     }
 }
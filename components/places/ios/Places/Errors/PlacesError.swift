@@ -56,6 +56,13 @@ public enum PlacesError: Error {
     /// insert a new item as a child of root________.
     case cannotUpdateRoot(message: String)
 
+    /// Thrown when a tag is empty, longer than 100 bytes, or contains
+    /// whitespace.
+    case invalidTag(message: String)
+
+    /// Thrown when tagging a URL that isn't in the database.
+    case noSuchUrl(message: String)
+
     // The name is attempting to indicate that we free rustError.message if it
     // existed, and that it's a very bad idea to touch it after you call this
     // function
@@ -96,6 +103,12 @@ public enum PlacesError: Error {
         case Places_InvalidPlace_CannotUpdateRoot:
             return .cannotUpdateRoot(message: String(freeingPlacesString: message!))
 
+        case Places_InvalidPlace_InvalidTag:
+            return .invalidTag(message: String(freeingPlacesString: message!))
+
+        case Places_InvalidPlace_NoSuchUrl:
+            return .noSuchUrl(message: String(freeingPlacesString: message!))
+
         case Places_Corrupt:
             return .databaseCorrupt(message: String(freeingPlacesString: message!))
 
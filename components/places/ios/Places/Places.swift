@@ -7,6 +7,7 @@ import os.log
 
 internal typealias APIHandle = UInt64
 internal typealias ConnectionHandle = UInt64
+internal typealias EventQueueHandle = UInt64
 
 /**
  * This is something like a places connection manager. It primarialy exists to
@@ -194,6 +195,141 @@ public class PlacesAPI {
         }
     }
 
+    /**
+     * Start queueing change events for history and bookmarks, made by the
+     * writer connection and by syncs. See `PlacesEventQueue` for details.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func openEventQueue() throws -> PlacesEventQueue {
+        return try queue.sync {
+            let queueHandle = try PlacesError.unwrap { error in
+                places_events_queue_new(handle, error)
+            }
+            return PlacesEventQueue(handle: queueHandle)
+        }
+    }
+
+    /**
+     * Change the settings used to calculate frecency. Settings that are nil
+     * in `settings` are reset to their defaults. Existing frecencies aren't
+     * changed until `recalculateFrecencies` is called.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: If the settings are invalid, or when an error
+     *                                 that has not specifically been exposed to
+     *                                 Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setFrecencySettings(_ settings: FrecencySettings) throws {
+        let data = try JSONEncoder().encode(settings)
+        let json = String(data: data, encoding: .utf8)!
+        try queue.sync {
+            try PlacesError.unwrap { error in
+                places_api_set_frecency_settings(handle, json, error)
+            }
+        }
+    }
+
+    /**
+     * Change which URLs are recorded in history, for new visits and for
+     * history received from Sync. `about:`, `data:`, `javascript:`, `blob:`
+     * and `file:` URLs are never recorded. Changing the policy doesn't remove
+     * existing history for the blocked URLs.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: If a scheme or host in the policy is invalid, or
+     *                                 when an error that has not specifically been
+     *                                 exposed to Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setHistoryPolicy(_ policy: HistoryPolicy) throws {
+        let data = try JSONEncoder().encode(policy)
+        let json = String(data: data, encoding: .utf8)!
+        try queue.sync {
+            try PlacesError.unwrap { error in
+                places_api_set_history_policy(handle, json, error)
+            }
+        }
+    }
+
+    /**
+     * Change how much history is downloaded in each sync.
+     *
+     * The first history sync on a new device downloads only the most recently
+     * changed records, so that it finishes quickly. Later syncs download older
+     * records, alternating with syncs that download new ones, until they've all
+     * been downloaded or the horizon is reached.
+     *
+     * - Parameter maxRecordsPerSync: The most records to download in a first
+     *                                sync or a backfill. Defaults to 5000.
+     * - Parameter horizonDays: How far back, in days, to download history on a
+     *                          new device. Defaults to 60.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: If either value is zero, or when an error that
+     *                                 has not specifically been exposed to Swift is
+     *                                 encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setHistoryBackfillSettings(maxRecordsPerSync: UInt32, horizonDays: UInt32) throws {
+        try queue.sync {
+            try PlacesError.unwrap { error in
+                places_api_set_history_backfill_settings(handle, maxRecordsPerSync, horizonDays, error)
+            }
+        }
+    }
+
+    /**
+     * Turn syncing the inputs typed into the URL bar for adaptive
+     * autocomplete on or off. It's off by default.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func setInputHistorySyncEnabled(_ enabled: Bool) throws {
+        try queue.sync {
+            try PlacesError.unwrap { error in
+                places_api_set_input_history_sync_enabled(handle, enabled ? 1 : 0, error)
+            }
+        }
+    }
+
+    /**
+     * Recalculate the frecency of every page with the current settings. This
+     * may take some time for large histories, so it shouldn't be called on
+     * the main thread. Like `sync`, it can be cancelled with `interrupt`, and
+     * the next call continues where it left off.
+     *
+     * - Returns: The number of pages recalculated.
+     *
+     * - Throws:
+     *     - `PlacesError.databaseInterrupted`: If a call is made to `interrupt()` on this
+     *                                          object from another thread.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func recalculateFrecencies() throws -> Int64 {
+        return try queue.sync {
+            try PlacesError.unwrap { error in
+                places_api_recalculate_frecencies(handle, error)
+            }
+        }
+    }
+
     /**
      * Attempt to interrupt a long-running operation which may be happening
      * concurrently (specifically, for `interrupt` on `PlacesAPI`, this refers
@@ -215,6 +351,7 @@ public class PlacesReadConnection {
     fileprivate var handle: ConnectionHandle
     fileprivate weak var api: PlacesAPI?
     fileprivate let interruptHandle: InterruptHandle
+    fileprivate let searchInterruptHandle: SearchInterruptHandle
 
     fileprivate init(handle: ConnectionHandle, api: PlacesAPI? = nil) throws {
         self.handle = handle
@@ -222,6 +359,9 @@ public class PlacesReadConnection {
         interruptHandle = InterruptHandle(ptr: try PlacesError.unwrap { error in
             places_new_interrupt_handle(handle, error)
         })
+        searchInterruptHandle = SearchInterruptHandle(ptr: try PlacesError.unwrap { error in
+            places_new_search_interrupt_handle(handle, error)
+        })
     }
 
     // Note: caller synchronizes!
@@ -249,6 +389,50 @@ public class PlacesReadConnection {
         }
     }
 
+    /**
+     * Returns autocomplete results for the text the user has typed, ordered
+     * by frecency.
+     *
+     * - Parameter search: The text the user has typed.
+     *
+     * - Parameter limit: The maximum number of results to return.
+     *
+     * - Throws:
+     *     - `PlacesError.databaseInterrupted`: If a call is made to `cancelAutocomplete()` or
+     *                                          `interrupt()` on this object from another thread.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func queryAutocomplete(search: String, limit: Int32) throws -> [SearchResult] {
+        return try queue.sync {
+            try self.checkApi()
+            let json = try PlacesError.unwrap { error in
+                places_query_autocomplete(self.handle, search, limit, error)
+            }
+            return try JSONDecoder().decode([SearchResult].self,
+                                            from: String(freeingPlacesString: json).data(using: .utf8)!)
+        }
+    }
+
+    /**
+     * Cancel a `queryAutocomplete` call that's running on another thread, for
+     * example because the user typed another character. Unlike `interrupt`,
+     * this doesn't affect any other operations on this connection.
+     *
+     * The canceled `queryAutocomplete` call fails with a
+     * `PlacesError.databaseInterrupted` error. This does nothing if a query
+     * isn't running.
+     */
+    open func cancelAutocomplete() {
+        searchInterruptHandle.interrupt()
+    }
+
     /**
      * Returns the bookmark subtree rooted at `rootGUID`.
      *
@@ -456,6 +640,144 @@ public class PlacesReadConnection {
         }
     }
 
+    /**
+     * Returns the tags for a URL, most recently added first.
+     *
+     * - Parameter url: The URL to get the tags for.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If `url` is not a valid URL.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getTags(forURL url: String) throws -> [String] {
+        return try queue.sync {
+            try self.checkApi()
+            let json = try PlacesError.unwrap { error in
+                places_get_tags_for_url(self.handle, url, error)
+            }
+            return try decodeStringArray(String(freeingPlacesString: json))
+        }
+    }
+
+    /**
+     * Returns the URLs with a tag.
+     *
+     * - Parameter tag: The tag to look up.
+     *
+     * - Throws:
+     *     - `PlacesError.invalidTag`: If the tag is empty, longer than 100 bytes,
+     *                                 or contains whitespace.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getURLs(withTag tag: String) throws -> [String] {
+        return try queue.sync {
+            try self.checkApi()
+            let json = try PlacesError.unwrap { error in
+                places_get_urls_with_tag(self.handle, tag, error)
+            }
+            return try decodeStringArray(String(freeingPlacesString: json))
+        }
+    }
+
+    /**
+     * Returns every tag that's used by at least one URL, with the number of
+     * URLs that have it. The most used tags are returned first.
+     *
+     * - Throws:
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getAllTags() throws -> [TagInfo] {
+        return try queue.sync {
+            try self.checkApi()
+            let json = try PlacesError.unwrap { error in
+                places_get_all_tags(self.handle, error)
+            }
+            return try JSONDecoder().decode([TagInfo].self,
+                                            from: String(freeingPlacesString: json).data(using: .utf8)!)
+        }
+    }
+
+    /**
+     * Returns the chain of visits which led to the most recent visit to a
+     * URL, found by following each visit's referrer back through any links
+     * and redirects. The chain starts with the first visit, and ends with the
+     * most recent visit to `url`.
+     *
+     * - Parameter url: The URL of the page at the end of the chain.
+     *
+     * - Returns: The visits in the chain, or an empty array if `url` has
+     *            never been visited.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If `url` is not a valid URL.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func getVisitChain(url: String) throws -> [VisitInfo] {
+        return try queue.sync {
+            try self.checkApi()
+            let buffer = try PlacesError.unwrap { error in
+                places_get_visit_chain(self.handle, url, error)
+            }
+            defer { places_destroy_bytebuffer(buffer) }
+            let msg = try MsgTypes_HistoryVisitInfos(serializedData: Data(placesRustBuffer: buffer))
+            return msg.infos.map(VisitInfo.init(msg:))
+        }
+    }
+
+    /**
+     * Export all bookmarks, including their tags and keywords, in the
+     * Netscape `bookmarks.html` format that other browsers can import.
+     *
+     * - Returns: The contents of the `bookmarks.html` file.
+     *
+     * - Throws:
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func exportBookmarksToHtml() throws -> String {
+        return try queue.sync {
+            try self.checkApi()
+            let html = try PlacesError.unwrap { error in
+                places_bookmarks_export_html(self.handle, error)
+            }
+            return String(freeingPlacesString: html)
+        }
+    }
+
     /**
      * Attempt to interrupt a long-running operation which may be
      * happening concurrently. If the operation is interrupted,
@@ -508,17 +830,17 @@ public class PlacesWriteConnection: PlacesReadConnection {
     }
 
     /**
-     * Delete the bookmark with the provided GUID.
-     *
-     * If the requested bookmark is a folder, all children of
-     * bookmark are deleted as well, recursively.
-     *
-     * - Parameter guid: The GUID of the bookmark to delete
+     * Expire old and infrequently visited history, so that the database
+     * doesn't grow without bound. Only pages that aren't bookmarked are
+     * removed, and the removals aren't synced.
      *
-     * - Returns: Whether or not the bookmark existed.
+     * - Parameter maxPages: The maximum number of pages to keep, or nil for
+     *                       no limit. Pages with the lowest frecency are
+     *                       removed first.
+     * - Parameter maxVisitAgeMs: The maximum age of visits to keep, in
+     *                            milliseconds, or nil for no limit.
      *
      * - Throws:
-     *     - `PlacesError.cannotUpdateRoot`: if `guid` is one of the bookmark roots.
      *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
      *                                            object has been closed. This indicates API
      *                                            misuse.
@@ -528,35 +850,30 @@ public class PlacesWriteConnection: PlacesReadConnection {
      *     - `PlacesError.panic`: If the rust code panics while completing this
      *                            operation. (If this occurs, please let us know).
      */
-    @discardableResult
-    open func deleteBookmarkNode(guid: String) throws -> Bool {
+    open func expireHistory(maxPages: Int64? = nil, maxVisitAgeMs: Int64? = nil) throws {
         return try queue.sync {
             try self.checkApi()
-            let resByte = try PlacesError.unwrap { error in
-                bookmarks_delete(self.handle, guid, error)
+            try PlacesError.unwrap { error in
+                places_expire_history(self.handle, maxPages ?? -1, maxVisitAgeMs ?? -1, error)
             }
-            return resByte != 0
         }
     }
 
     /**
-     * Create a bookmark folder, returning its guid.
-     *
-     * - Parameter parentGUID: The GUID of the (soon to be) parent of this bookmark.
-     *
-     * - Parameter title: The title of the folder.
+     * Record the title, description and preview image for a page, without
+     * recording a visit. The description and preview image are returned
+     * with bookmarks of the page.
      *
-     * - Parameter position: The index where to insert the record inside
-     *                       its parent. If not provided, this item will
-     *                       be appended.
-     *
-     * - Returns: The GUID of the newly inserted bookmark folder.
+     * - Parameter url: The URL of the page.
+     * - Parameter title: The title of the page, or nil to leave it unchanged.
+     * - Parameter description: The description of the page, or nil to leave
+     *                          it unchanged.
+     * - Parameter previewImageUrl: The URL of a preview image for the page,
+     *                              or nil to leave it unchanged.
      *
      * - Throws:
-     *     - `PlacesError.cannotUpdateRoot`: If `parentGUID` is `BookmarkRoots.RootGUID`.
-     *     - `PlacesError.noSuchItem`: If `parentGUID` does not refer to a known bookmark.
-     *     - `PlacesError.invalidParent`: If `parentGUID` refers to a bookmark which is
-     *                                    not a folder.
+     *     - `PlacesError.urlParseError`: If `url` or `previewImageUrl` is not a
+     *                                    valid URL.
      *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
      *                                            object has been closed. This indicates API
      *                                            misuse.
@@ -566,33 +883,30 @@ public class PlacesWriteConnection: PlacesReadConnection {
      *     - `PlacesError.panic`: If the rust code panics while completing this
      *                            operation. (If this occurs, please let us know).
      */
-    @discardableResult
-    open func createFolder(parentGUID: String,
-                           title: String,
-                           position: UInt32? = nil) throws -> String {
+    open func notePageMetadata(url: String,
+                               title: String? = nil,
+                               description: String? = nil,
+                               previewImageUrl: String? = nil) throws {
         return try queue.sync {
             try self.checkApi()
-            var msg = insertionMsg(type: .folder, parentGUID: parentGUID, position: position)
-            msg.title = title
-            return try doInsert(msg: msg)
+            try PlacesError.unwrap { error in
+                places_note_page_metadata(self.handle, url, title, description, previewImageUrl, error)
+            }
         }
     }
 
     /**
-     * Create a bookmark separator, returning its guid.
-     *
-     * - Parameter parentGUID: The GUID of the (soon to be) parent of this bookmark.
+     * Tag a URL. Leading and trailing whitespace is removed from the tag.
      *
-     * - Parameter position: The index where to insert the record inside
-     *                       its parent. If not provided, this item will
-     *                       be appended.
+     * - Parameter url: The URL to tag. It must already be in the database,
+     *                  because it's been visited or bookmarked.
+     * - Parameter tag: The tag to add.
      *
-     * - Returns: The GUID of the newly inserted bookmark separator.
      * - Throws:
-     *     - `PlacesError.cannotUpdateRoot`: If `parentGUID` is `BookmarkRoots.RootGUID`.
-     *     - `PlacesError.noSuchItem`: If `parentGUID` does not refer to a known bookmark.
-     *     - `PlacesError.invalidParent`: If `parentGUID` refers to a bookmark which is
-     *                                    not a folder.
+     *     - `PlacesError.urlParseError`: If `url` is not a valid URL.
+     *     - `PlacesError.invalidTag`: If the tag is empty, longer than 100 bytes,
+     *                                 or contains whitespace.
+     *     - `PlacesError.noSuchUrl`: If `url` isn't in the database.
      *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
      *                                            object has been closed. This indicates API
      *                                            misuse.
@@ -602,8 +916,219 @@ public class PlacesWriteConnection: PlacesReadConnection {
      *     - `PlacesError.panic`: If the rust code panics while completing this
      *                            operation. (If this occurs, please let us know).
      */
-    @discardableResult
-    open func createSeparator(parentGUID: String, position: UInt32? = nil) throws -> String {
+    open func tagURL(_ url: String, tag: String) throws {
+        return try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_tag_url(self.handle, url, tag, error)
+            }
+        }
+    }
+
+    /**
+     * Remove a tag from a URL. Does nothing if the URL doesn't have the tag.
+     *
+     * - Parameter url: The URL to untag.
+     * - Parameter tag: The tag to remove.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If `url` is not a valid URL.
+     *     - `PlacesError.invalidTag`: If the tag is empty, longer than 100 bytes,
+     *                                 or contains whitespace.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func untagURL(_ url: String, tag: String) throws {
+        return try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_untag_url(self.handle, url, tag, error)
+            }
+        }
+    }
+
+    /**
+     * Remove all tags from a URL.
+     *
+     * - Parameter url: The URL to untag.
+     *
+     * - Throws:
+     *     - `PlacesError.urlParseError`: If `url` is not a valid URL.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func removeAllTags(fromURL url: String) throws {
+        return try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_remove_all_tags_from_url(self.handle, url, error)
+            }
+        }
+    }
+
+    /**
+     * Remove a tag from all URLs.
+     *
+     * - Parameter tag: The tag to remove.
+     *
+     * - Throws:
+     *     - `PlacesError.invalidTag`: If the tag is empty, longer than 100 bytes,
+     *                                 or contains whitespace.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func removeTag(_ tag: String) throws {
+        return try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_remove_tag(self.handle, tag, error)
+            }
+        }
+    }
+
+    /**
+     * Rename a tag on all URLs that have it. If a tag named `newTag` already
+     * exists, the two tags are merged. Does nothing if `oldTag` doesn't exist.
+     *
+     * - Parameter oldTag: The tag to rename.
+     * - Parameter newTag: The new name for the tag.
+     *
+     * - Throws:
+     *     - `PlacesError.invalidTag`: If the tag is empty, longer than 100 bytes,
+     *                                 or contains whitespace.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func renameTag(_ oldTag: String, to newTag: String) throws {
+        return try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_rename_tag(self.handle, oldTag, newTag, error)
+            }
+        }
+    }
+
+    /**
+     * Delete the bookmark with the provided GUID.
+     *
+     * If the requested bookmark is a folder, all children of
+     * bookmark are deleted as well, recursively.
+     *
+     * - Parameter guid: The GUID of the bookmark to delete
+     *
+     * - Returns: Whether or not the bookmark existed.
+     *
+     * - Throws:
+     *     - `PlacesError.cannotUpdateRoot`: if `guid` is one of the bookmark roots.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func deleteBookmarkNode(guid: String) throws -> Bool {
+        return try queue.sync {
+            try self.checkApi()
+            let resByte = try PlacesError.unwrap { error in
+                bookmarks_delete(self.handle, guid, error)
+            }
+            return resByte != 0
+        }
+    }
+
+    /**
+     * Create a bookmark folder, returning its guid.
+     *
+     * - Parameter parentGUID: The GUID of the (soon to be) parent of this bookmark.
+     *
+     * - Parameter title: The title of the folder.
+     *
+     * - Parameter position: The index where to insert the record inside
+     *                       its parent. If not provided, this item will
+     *                       be appended.
+     *
+     * - Returns: The GUID of the newly inserted bookmark folder.
+     *
+     * - Throws:
+     *     - `PlacesError.cannotUpdateRoot`: If `parentGUID` is `BookmarkRoots.RootGUID`.
+     *     - `PlacesError.noSuchItem`: If `parentGUID` does not refer to a known bookmark.
+     *     - `PlacesError.invalidParent`: If `parentGUID` refers to a bookmark which is
+     *                                    not a folder.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func createFolder(parentGUID: String,
+                           title: String,
+                           position: UInt32? = nil) throws -> String {
+        return try queue.sync {
+            try self.checkApi()
+            var msg = insertionMsg(type: .folder, parentGUID: parentGUID, position: position)
+            msg.title = title
+            return try doInsert(msg: msg)
+        }
+    }
+
+    /**
+     * Create a bookmark separator, returning its guid.
+     *
+     * - Parameter parentGUID: The GUID of the (soon to be) parent of this bookmark.
+     *
+     * - Parameter position: The index where to insert the record inside
+     *                       its parent. If not provided, this item will
+     *                       be appended.
+     *
+     * - Returns: The GUID of the newly inserted bookmark separator.
+     * - Throws:
+     *     - `PlacesError.cannotUpdateRoot`: If `parentGUID` is `BookmarkRoots.RootGUID`.
+     *     - `PlacesError.noSuchItem`: If `parentGUID` does not refer to a known bookmark.
+     *     - `PlacesError.invalidParent`: If `parentGUID` refers to a bookmark which is
+     *                                    not a folder.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func createSeparator(parentGUID: String, position: UInt32? = nil) throws -> String {
         return try queue.sync {
             try self.checkApi()
             let msg = insertionMsg(type: .separator, parentGUID: parentGUID, position: position)
@@ -741,6 +1266,202 @@ public class PlacesWriteConnection: PlacesReadConnection {
         }
     }
 
+    /**
+     * Restore a recently deleted bookmark, separator or folder, including all
+     * of a folder's descendants. This can be used to undo `deleteBookmarkNode`.
+     *
+     * The item is restored to its original folder and position if the folder
+     * still exists, or appended to `BookmarkRoots.UnfiledFolderGUID` if it
+     * doesn't. Only the most recent 20 deletions can be restored, and each
+     * only once.
+     *
+     * - Parameter guid: The GUID of the deleted item.
+     *
+     * - Returns: The GUID of the restored item. This is usually `guid`, but may be
+     *            a new GUID if another item has been added with the same GUID since.
+     *
+     * - Throws:
+     *     - `PlacesError.noSuchItem`: If `guid` does not refer to a recently deleted item.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func restoreDeletedBookmark(guid: String) throws -> String {
+        return try queue.sync {
+            try self.checkApi()
+            let ptr = try PlacesError.unwrap { error in
+                bookmarks_restore_deleted(self.handle, guid, error)
+            }
+            return String(freeingPlacesString: ptr)
+        }
+    }
+
+    /**
+     * Move several items into a folder at once, keeping them together in the
+     * order given. This is done in a single transaction, so either all the
+     * items are moved, or none are.
+     *
+     * - Parameter guids: The GUIDs of the items to move.
+     * - Parameter parentGUID: The GUID of the folder to move them to. It may be
+     *                         the folder some or all of them are already in.
+     * - Parameter position: The index of the child to move them in front of. If
+     *                       nil, or if every child from this index on is being
+     *                       moved, the items are moved to the end of the folder.
+     *
+     * - Throws:
+     *     - `PlacesError.cannotUpdateRoot`: If one of `guids` is a member of
+     *                                       `BookmarkRoots.All`, or `parentGUID` is
+     *                                       `BookmarkRoots.RootGUID`.
+     *     - `PlacesError.noSuchItem`: If `parentGUID` or one of `guids` does not refer
+     *                                 to a known bookmark.
+     *     - `PlacesError.invalidParent`: If `parentGUID` does not refer to a folder, or
+     *                                    refers to one of the folders being moved or
+     *                                    one of their descendants.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func moveBookmarks(guids: [String], parentGUID: String, position: UInt32? = nil) throws {
+        let data = try JSONSerialization.data(withJSONObject: guids)
+        let json = String(data: data, encoding: .utf8)!
+        try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                bookmarks_move(self.handle, json, parentGUID, position.map { Int32($0) } ?? -1, error)
+            }
+        }
+    }
+
+    /**
+     * Copy a bookmark, separator or folder, including all of a folder's
+     * descendants. The copies have new GUIDs.
+     *
+     * - Parameter guid: The GUID of the item to copy.
+     * - Parameter parentGUID: The GUID of the folder to copy it to.
+     * - Parameter position: The index where to insert the copy inside its
+     *                       parent. If nil, the copy is appended.
+     *
+     * - Returns: The GUID of the copy.
+     *
+     * - Throws:
+     *     - `PlacesError.cannotUpdateRoot`: If `guid` is a member of `BookmarkRoots.All`,
+     *                                       or `parentGUID` is `BookmarkRoots.RootGUID`.
+     *     - `PlacesError.noSuchItem`: If `guid` or `parentGUID` does not refer to a
+     *                                 known bookmark.
+     *     - `PlacesError.invalidParent`: If `parentGUID` does not refer to a folder.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    @discardableResult
+    open func copyBookmark(guid: String, parentGUID: String, position: UInt32? = nil) throws -> String {
+        return try queue.sync {
+            try self.checkApi()
+            let ptr = try PlacesError.unwrap { error in
+                bookmarks_copy(self.handle, guid, parentGUID, position.map { Int32($0) } ?? -1, error)
+            }
+            return String(freeingPlacesString: ptr)
+        }
+    }
+
+    /**
+     * Sort the children of a folder. Separators stay where they are, and the
+     * items between them are sorted separately.
+     *
+     * - Parameter guid: The GUID of the folder.
+     * - Parameter order: How to sort the children.
+     *
+     * - Throws:
+     *     - `PlacesError.noSuchItem`: If `guid` does not refer to a known bookmark.
+     *     - `PlacesError.invalidParent`: If `guid` does not refer to a folder.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func sortFolderChildren(guid: String, order: ChildrenSortOrder) throws {
+        try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                bookmarks_sort_children(self.handle, guid, order.rawValue, error)
+            }
+        }
+    }
+
+    /**
+     * Reverse the order of a folder's children.
+     *
+     * - Parameter guid: The GUID of the folder.
+     *
+     * - Throws:
+     *     - `PlacesError.noSuchItem`: If `guid` does not refer to a known bookmark.
+     *     - `PlacesError.invalidParent`: If `guid` does not refer to a folder.
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func reverseFolderChildren(guid: String) throws {
+        try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                bookmarks_reverse_children(self.handle, guid, error)
+            }
+        }
+    }
+
+    /**
+     * Import bookmarks from a Netscape `bookmarks.html` file, as exported by
+     * most browsers. The imported bookmarks are added to the existing ones,
+     * with the toolbar and "Other Bookmarks" folders merged into their roots.
+     * Bookmarks with invalid URLs are skipped. If the import fails, none of
+     * the bookmarks are imported.
+     *
+     * - Parameter html: The contents of the `bookmarks.html` file.
+     *
+     * - Throws:
+     *     - `PlacesError.connUseAfterAPIClosed`: if the PlacesAPI that returned this connection
+     *                                            object has been closed. This indicates API
+     *                                            misuse.
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered (for example IO errors from
+     *                                 the database code, etc).
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    open func importBookmarksFromHtml(_ html: String) throws {
+        try queue.sync {
+            try self.checkApi()
+            try PlacesError.unwrap { error in
+                places_bookmarks_import_html(self.handle, html, error)
+            }
+        }
+    }
+
     // Helper for the various creation functions.
     // Note: Caller synchronizes
     private func doInsert(msg: MsgTypes_BookmarkNode) throws -> String {
@@ -769,6 +1490,232 @@ public class PlacesWriteConnection: PlacesReadConnection {
     }
 }
 
+/**
+ * A tag, and the number of URLs that have it. Returned by `getAllTags`.
+ */
+public struct TagInfo: Decodable {
+    public let tag: String
+    /// The number of URLs with this tag.
+    public let urlCount: UInt32
+    /// When the tag was last added to a URL or renamed, in milliseconds since
+    /// the unix epoch.
+    public let lastModified: Int64
+
+    enum CodingKeys: String, CodingKey {
+        case tag
+        case urlCount = "url_count"
+        case lastModified = "last_modified"
+    }
+}
+
+/**
+ * A visit to a page. Returned by `getVisitChain`.
+ */
+public struct VisitInfo {
+    public let url: String
+    public let title: String?
+    /// When the page was visited, in milliseconds since the unix epoch.
+    public let visitTime: Int64
+    /// The transition type of the visit, as in `PlacesEvent.visitAdded`.
+    public let visitType: Int32
+    /// The description of the page, if one was recorded with `notePageMetadata`.
+    public let description: String?
+    /// The URL of a preview image for the page, if one was recorded with
+    /// `notePageMetadata`.
+    public let previewImageUrl: String?
+
+    internal init(msg: MsgTypes_HistoryVisitInfo) {
+        url = msg.url
+        title = msg.hasTitle ? msg.title : nil
+        visitTime = msg.timestamp
+        visitType = msg.visitType
+        description = msg.hasDescription ? msg.description : nil
+        previewImageUrl = msg.hasPreviewImageURL ? msg.previewImageURL : nil
+    }
+}
+
+/**
+ * An autocomplete result. Returned by `queryAutocomplete`.
+ */
+public struct SearchResult: Decodable {
+    /// The search string for this match.
+    public let searchString: String
+    /// The URL to open when the user confirms the match.
+    public let url: String
+    /// The title to show in the UI.
+    public let title: String
+    /// The favicon URL, if known.
+    public let iconUrl: String?
+    /// A frecency score for this match.
+    public let frecency: Int64
+    /// The description of the page, if one was recorded with `notePageMetadata`.
+    public let description: String?
+    /// The URL of a preview image for the page, if one was recorded with
+    /// `notePageMetadata`.
+    public let previewImageUrl: String?
+
+    enum CodingKeys: String, CodingKey {
+        case searchString = "search_string"
+        case url
+        case title
+        case iconUrl = "icon_url"
+        case frecency
+        case description
+        case previewImageUrl = "preview_image_url"
+    }
+}
+
+private func decodeStringArray(_ json: String) throws -> [String] {
+    return try JSONDecoder().decode([String].self, from: json.data(using: .utf8)!)
+}
+
+/**
+ * Queues change events for history and bookmarks until they're polled.
+ * Events are grouped into a batch per transaction, and only committed
+ * changes are reported. Events stop being queued once this is released.
+ *
+ * The queue holds a limited number of batches, so it should be polled
+ * regularly.
+ */
+public class PlacesEventQueue {
+    private let queue = DispatchQueue(label: "com.mozilla.places.events")
+    private let handle: EventQueueHandle
+
+    fileprivate init(handle: EventQueueHandle) {
+        self.handle = handle
+    }
+
+    deinit {
+        PlacesError.unwrapOrLog { error in
+            places_events_queue_destroy(self.handle, error)
+        }
+    }
+
+    /**
+     * Returns and clears the queued events.
+     *
+     * - Throws:
+     *     - `PlacesError.unexpected`: When an error that has not specifically been exposed
+     *                                 to Swift is encountered.
+     *     - `PlacesError.panic`: If the rust code panics while completing this
+     *                            operation. (If this occurs, please let us know).
+     */
+    public func poll() throws -> QueuedPlacesEvents {
+        return try queue.sync {
+            let json = try PlacesError.unwrap { error in
+                places_events_queue_poll(self.handle, error)
+            }
+            return try JSONDecoder().decode(QueuedPlacesEvents.self,
+                                            from: String(freeingPlacesString: json).data(using: .utf8)!)
+        }
+    }
+}
+
+/**
+ * The result of `PlacesEventQueue.poll`.
+ */
+public struct QueuedPlacesEvents: Decodable {
+    /// The queued batches, oldest first.
+    public let batches: [PlacesEventBatch]
+    /// The number of batches dropped because the queue was full. If this isn't
+    /// zero, anything showing history or bookmarks should be re-queried.
+    public let dropped: Int
+}
+
+/**
+ * The events from a single transaction.
+ */
+public struct PlacesEventBatch: Decodable {
+    /// Whether the changes were made by Sync.
+    public let fromSync: Bool
+    public let events: [PlacesEvent]
+}
+
+/**
+ * A change to history or bookmarks. Must match `PlacesEvent` in the Rust code.
+ * Dates are in milliseconds since the unix epoch.
+ */
+public enum PlacesEvent: Decodable {
+    case visitAdded(url: String, visitDate: Int64, visitType: Int32, isRemote: Bool)
+    case pageDeleted(url: String, guid: String)
+    /// All history was removed. Bookmarked pages are kept, but lose their visits.
+    case historyCleared
+    case titleChanged(url: String, title: String)
+    case bookmarkAdded(guid: String, parentGUID: String, position: UInt32,
+                       type: BookmarkNodeType, url: String?)
+    case bookmarkMoved(guid: String, oldParentGUID: String, oldPosition: UInt32,
+                       parentGUID: String, position: UInt32)
+    /// The title and URL are the new values.
+    case bookmarkUpdated(guid: String, title: String?, url: String?)
+    /// If the item was a folder, its descendants were removed too, without
+    /// events of their own.
+    case bookmarkRemoved(guid: String, parentGUID: String, position: UInt32, type: BookmarkNodeType)
+    /// Sync applied incoming changes for a collection ("history" or
+    /// "bookmarks"). Individual changes aren't reported, so anything showing
+    /// that collection should be re-queried.
+    case syncApplied(collection: String)
+    /// An event added in a newer version of the library.
+    case unknown
+
+    enum CodingKeys: String, CodingKey {
+        case type, url, visitDate, visitType, isRemote, guid, title, parentGuid,
+            position, kind, oldParentGuid, oldPosition, collection
+    }
+
+    public init(from decoder: Decoder) throws {
+        let c = try decoder.container(keyedBy: CodingKeys.self)
+        switch try c.decode(String.self, forKey: .type) {
+        case "visitAdded":
+            self = .visitAdded(url: try c.decode(String.self, forKey: .url),
+                               visitDate: try c.decode(Int64.self, forKey: .visitDate),
+                               visitType: try c.decode(Int32.self, forKey: .visitType),
+                               isRemote: try c.decode(Bool.self, forKey: .isRemote))
+        case "pageDeleted":
+            self = .pageDeleted(url: try c.decode(String.self, forKey: .url),
+                                guid: try c.decode(String.self, forKey: .guid))
+        case "historyCleared":
+            self = .historyCleared
+        case "titleChanged":
+            self = .titleChanged(url: try c.decode(String.self, forKey: .url),
+                                 title: try c.decode(String.self, forKey: .title))
+        case "bookmarkAdded":
+            self = .bookmarkAdded(guid: try c.decode(String.self, forKey: .guid),
+                                  parentGUID: try c.decode(String.self, forKey: .parentGuid),
+                                  position: try c.decode(UInt32.self, forKey: .position),
+                                  type: try PlacesEvent.decodeType(c),
+                                  url: try c.decodeIfPresent(String.self, forKey: .url))
+        case "bookmarkMoved":
+            self = .bookmarkMoved(guid: try c.decode(String.self, forKey: .guid),
+                                  oldParentGUID: try c.decode(String.self, forKey: .oldParentGuid),
+                                  oldPosition: try c.decode(UInt32.self, forKey: .oldPosition),
+                                  parentGUID: try c.decode(String.self, forKey: .parentGuid),
+                                  position: try c.decode(UInt32.self, forKey: .position))
+        case "bookmarkUpdated":
+            self = .bookmarkUpdated(guid: try c.decode(String.self, forKey: .guid),
+                                    title: try c.decodeIfPresent(String.self, forKey: .title),
+                                    url: try c.decodeIfPresent(String.self, forKey: .url))
+        case "bookmarkRemoved":
+            self = .bookmarkRemoved(guid: try c.decode(String.self, forKey: .guid),
+                                    parentGUID: try c.decode(String.self, forKey: .parentGuid),
+                                    position: try c.decode(UInt32.self, forKey: .position),
+                                    type: try PlacesEvent.decodeType(c))
+        case "syncApplied":
+            self = .syncApplied(collection: try c.decode(String.self, forKey: .collection))
+        default:
+            self = .unknown
+        }
+    }
+
+    private static func decodeType(_ c: KeyedDecodingContainer<CodingKeys>) throws -> BookmarkNodeType {
+        let raw = try c.decode(Int32.self, forKey: .kind)
+        guard let type = BookmarkNodeType(rawValue: raw) else {
+            throw DecodingError.dataCorruptedError(forKey: .kind, in: c,
+                                                   debugDescription: "Unknown bookmark type \(raw)")
+        }
+        return type
+    }
+}
+
 // Wrapper around rust interrupt handle.
 private class InterruptHandle {
     let ptr: OpaquePointer
@@ -786,3 +1733,81 @@ private class InterruptHandle {
         }
     }
 }
+
+// Wrapper around rust search interrupt handle, which only cancels
+// `queryAutocomplete`.
+private class SearchInterruptHandle {
+    let ptr: OpaquePointer
+    init(ptr: OpaquePointer) {
+        self.ptr = ptr
+    }
+
+    deinit {
+        places_search_interrupt_handle_destroy(self.ptr)
+    }
+
+    func interrupt() {
+        PlacesError.unwrapOrLog { error in
+            places_interrupt_search(self.ptr, error)
+        }
+    }
+}
+
+/**
+ * The weights and bonuses used to calculate frecency, which ranks pages for
+ * autocomplete and top sites. These correspond to Desktop's
+ * `places.frecency.*` preferences. Settings that are nil use their default
+ * values.
+ */
+public struct FrecencySettings: Encodable {
+    /// The number of recent visits to sample for each page. Must be positive.
+    public var numVisits: Int?
+
+    // The cutoffs, in days, for the age buckets. These must be increasing.
+    public var firstBucketCutoffDays: Int?
+    public var secondBucketCutoffDays: Int?
+    public var thirdBucketCutoffDays: Int?
+    public var fourthBucketCutoffDays: Int?
+
+    // The weight for visits in each age bucket. Visits older than the fourth
+    // cutoff use the default weight.
+    public var firstBucketWeight: Int?
+    public var secondBucketWeight: Int?
+    public var thirdBucketWeight: Int?
+    public var fourthBucketWeight: Int?
+    public var defaultBucketWeight: Int?
+
+    // The bonus, as a percentage, for each kind of visit.
+    public var embedVisitBonus: Int?
+    public var framedLinkVisitBonus: Int?
+    public var linkVisitBonus: Int?
+    public var typedVisitBonus: Int?
+    public var bookmarkVisitBonus: Int?
+    public var downloadVisitBonus: Int?
+    public var permanentRedirectVisitBonus: Int?
+    public var temporaryRedirectVisitBonus: Int?
+    public var redirectSourceVisitBonus: Int?
+    public var defaultVisitBonus: Int?
+    public var unvisitedBookmarkBonus: Int?
+    public var unvisitedTypedBonus: Int?
+    public var reloadVisitBonus: Int?
+
+    public init() {}
+}
+
+/**
+ * Decides which URLs are recorded in history, in addition to the URLs that
+ * are never recorded. Set it with `PlacesAPI.setHistoryPolicy`.
+ */
+public struct HistoryPolicy: Encodable {
+    /// Schemes to block, without the trailing colon, like "ftp".
+    public var blockedSchemes: [String]
+    /// Hosts to block, like "example.com". Blocking a host also blocks its
+    /// subdomains, on any port.
+    public var blockedHosts: [String]
+
+    public init(blockedSchemes: [String] = [], blockedHosts: [String] = []) {
+        self.blockedSchemes = blockedSchemes
+        self.blockedHosts = blockedHosts
+    }
+}
@@ -7,6 +7,7 @@
 
 typedef uint64_t PlacesAPIHandle;
 typedef uint64_t PlacesConnectionHandle;
+typedef uint64_t PlacesEventQueueHandle;
 
 typedef enum PlacesErrorCode {
     Places_Panic = -1,
@@ -22,6 +23,8 @@ typedef enum PlacesErrorCode {
     Places_InvalidPlace_UrlTooLong = 64 + 2,
     Places_InvalidPlace_IllegalChange = 64 + 3,
     Places_InvalidPlace_CannotUpdateRoot = 64 + 4,
+    Places_InvalidPlace_InvalidTag = 64 + 5,
+    Places_InvalidPlace_NoSuchUrl = 64 + 6,
 } PlacesErrorCode;
 
 typedef struct PlacesRustError {
@@ -35,6 +38,7 @@ typedef struct PlacesRustBuffer {
 } PlacesRustBuffer;
 
 typedef struct RawPlacesInterruptHandle RawPlacesInterruptHandle;
+typedef struct RawPlacesSearchInterruptHandle RawPlacesSearchInterruptHandle;
 
 // Not a named enum because we need int32_t ABI in `places_connection_new`,
 // and using a named enum would be `int` (which usually is 32 bits these
@@ -93,6 +97,13 @@ RawPlacesInterruptHandle *_Nullable places_new_interrupt_handle(PlacesConnection
 void places_interrupt(RawPlacesInterruptHandle *_Nonnull interrupt,
                       PlacesRustError *_Nonnull out_err);
 
+RawPlacesSearchInterruptHandle *_Nullable
+places_new_search_interrupt_handle(PlacesConnectionHandle handle,
+                                   PlacesRustError *_Nonnull out_err);
+
+void places_interrupt_search(RawPlacesSearchInterruptHandle *_Nonnull interrupt,
+                             PlacesRustError *_Nonnull out_err);
+
 void places_delete_place(PlacesConnectionHandle handle,
                          const char *_Nonnull place_url,
                          PlacesRustError *_Nonnull out_err);
@@ -107,6 +118,14 @@ void places_delete_visits_between(PlacesConnectionHandle handle,
                                   int64_t end,
                                   PlacesRustError *_Nonnull out_err);
 
+void places_delete_history_for_host(PlacesConnectionHandle handle,
+                                    const char *_Nonnull host,
+                                    PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_get_visited_domains(PlacesConnectionHandle handle,
+                                           uint32_t limit,
+                                           PlacesRustError *_Nonnull out_err);
+
 void places_wipe_local(PlacesConnectionHandle handle,
                        PlacesRustError *_Nonnull out_err);
 
@@ -116,6 +135,52 @@ void places_run_maintenance(PlacesConnectionHandle handle,
 void places_prune_destructively(PlacesConnectionHandle handle,
                                 PlacesRustError *_Nonnull out_err);
 
+void places_expire_history(PlacesConnectionHandle handle,
+                           int64_t max_pages,
+                           int64_t max_visit_age_ms,
+                           PlacesRustError *_Nonnull out_err);
+
+void places_note_page_metadata(PlacesConnectionHandle handle,
+                               char const *_Nonnull url,
+                               char const *_Nullable title,
+                               char const *_Nullable description,
+                               char const *_Nullable preview_image_url,
+                               PlacesRustError *_Nonnull out_err);
+
+void places_tag_url(PlacesConnectionHandle handle,
+                    char const *_Nonnull url,
+                    char const *_Nonnull tag,
+                    PlacesRustError *_Nonnull out_err);
+
+void places_untag_url(PlacesConnectionHandle handle,
+                      char const *_Nonnull url,
+                      char const *_Nonnull tag,
+                      PlacesRustError *_Nonnull out_err);
+
+void places_remove_all_tags_from_url(PlacesConnectionHandle handle,
+                                     char const *_Nonnull url,
+                                     PlacesRustError *_Nonnull out_err);
+
+void places_remove_tag(PlacesConnectionHandle handle,
+                       char const *_Nonnull tag,
+                       PlacesRustError *_Nonnull out_err);
+
+void places_rename_tag(PlacesConnectionHandle handle,
+                       char const *_Nonnull old_tag,
+                       char const *_Nonnull new_tag,
+                       PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_get_tags_for_url(PlacesConnectionHandle handle,
+                                        char const *_Nonnull url,
+                                        PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_get_urls_with_tag(PlacesConnectionHandle handle,
+                                         char const *_Nonnull tag,
+                                         PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_get_all_tags(PlacesConnectionHandle handle,
+                                    PlacesRustError *_Nonnull out_err);
+
 void places_delete_everything(PlacesConnectionHandle handle,
                               PlacesRustError *_Nonnull out_err);
 
@@ -125,6 +190,10 @@ PlacesRustBuffer places_get_visit_infos(PlacesConnectionHandle handle,
                                         int32_t exclude_types,
                                         PlacesRustError *_Nonnull out_err);
 
+PlacesRustBuffer places_get_visit_chain(PlacesConnectionHandle handle,
+                                        char const *_Nonnull url,
+                                        PlacesRustError *_Nonnull out_err);
+
 char *_Nonnull sync15_history_sync(PlacesAPIHandle handle,
                                    char const *_Nonnull key_id,
                                    char const *_Nonnull access_token,
@@ -142,6 +211,39 @@ char *_Nonnull sync15_bookmarks_sync(PlacesAPIHandle handle,
 void places_api_reset_bookmarks(PlacesAPIHandle handle,
                                 PlacesRustError *_Nonnull out_err);
 
+void places_api_set_frecency_settings(PlacesAPIHandle handle,
+                                      const char *_Nonnull settings_json,
+                                      PlacesRustError *_Nonnull out_err);
+
+void places_api_set_history_policy(PlacesAPIHandle handle,
+                                   const char *_Nonnull policy_json,
+                                   PlacesRustError *_Nonnull out_err);
+
+void places_api_set_history_backfill_settings(PlacesAPIHandle handle,
+                                              uint32_t max_records_per_sync,
+                                              uint32_t horizon_days,
+                                              PlacesRustError *_Nonnull out_err);
+
+void places_api_set_history_sync_diagnostics_enabled(PlacesAPIHandle handle,
+                                                      uint8_t enabled,
+                                                      PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_api_get_last_history_sync_diagnostics(PlacesAPIHandle handle,
+                                                             PlacesRustError *_Nonnull out_err);
+
+void places_api_set_input_history_sync_enabled(PlacesAPIHandle handle,
+                                               uint8_t enabled,
+                                               PlacesRustError *_Nonnull out_err);
+
+int64_t places_api_recalculate_frecencies(PlacesAPIHandle handle,
+                                          PlacesRustError *_Nonnull out_err);
+
+PlacesEventQueueHandle places_events_queue_new(PlacesAPIHandle handle,
+                                               PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_events_queue_poll(PlacesEventQueueHandle handle,
+                                         PlacesRustError *_Nonnull out_err);
+
 RawPlacesInterruptHandle *_Nullable
 places_new_sync_conn_interrupt_handle(PlacesAPIHandle handle,
                                       PlacesRustError *_Nonnull out_err);
@@ -180,10 +282,42 @@ void bookmarks_update(PlacesConnectionHandle handle,
                       int32_t len,
                       PlacesRustError *_Nonnull out_err);
 
+char *_Nullable bookmarks_restore_deleted(PlacesConnectionHandle handle,
+                                          char const *_Nonnull guid,
+                                          PlacesRustError *_Nonnull out_err);
+
+void bookmarks_move(PlacesConnectionHandle handle,
+                    char const *_Nonnull guids_json,
+                    char const *_Nonnull parent_guid,
+                    int32_t position,
+                    PlacesRustError *_Nonnull out_err);
+
+char *_Nullable bookmarks_copy(PlacesConnectionHandle handle,
+                               char const *_Nonnull guid,
+                               char const *_Nonnull parent_guid,
+                               int32_t position,
+                               PlacesRustError *_Nonnull out_err);
+
+void bookmarks_sort_children(PlacesConnectionHandle handle,
+                             char const *_Nonnull guid,
+                             uint8_t order,
+                             PlacesRustError *_Nonnull out_err);
+
+void bookmarks_reverse_children(PlacesConnectionHandle handle,
+                                char const *_Nonnull guid,
+                                PlacesRustError *_Nonnull out_err);
+
 uint8_t bookmarks_delete(PlacesConnectionHandle handle,
                          char const *_Nonnull guid_to_delete,
                          PlacesRustError *_Nonnull out_err);
 
+void places_bookmarks_import_html(PlacesConnectionHandle handle,
+                                  char const *_Nonnull html,
+                                  PlacesRustError *_Nonnull out_err);
+
+char *_Nullable places_bookmarks_export_html(PlacesConnectionHandle handle,
+                                             PlacesRustError *_Nonnull out_err);
+
 // MARK: memory/lifecycle management
 
 void places_api_return_write_conn(PlacesAPIHandle api,
@@ -196,8 +330,13 @@ void places_destroy_string(char const *_Nonnull s);
 
 void places_interrupt_handle_destroy(RawPlacesInterruptHandle *_Nonnull handle);
 
+void places_search_interrupt_handle_destroy(RawPlacesSearchInterruptHandle *_Nonnull handle);
+
 void places_connection_destroy(PlacesConnectionHandle conn,
                                PlacesRustError *_Nonnull out_err);
 
 void places_api_destroy(PlacesAPIHandle api,
                         PlacesRustError *_Nonnull out_err);
+
+void places_events_queue_destroy(PlacesEventQueueHandle handle,
+                                 PlacesRustError *_Nonnull out_err);
@@ -48,3 +48,30 @@ BEGIN
         syncChangeCounter = syncChangeCounter + 1
     WHERE fk = OLD.place_id;
 END;
+
+-- These triggers bump the Sync change counter for all bookmarks of a URL when
+-- its keyword changes, since keywords are uploaded as part of the bookmark
+-- record.
+CREATE TEMP TRIGGER moz_keywords_afterinsert_sync_trigger
+AFTER INSERT ON moz_keywords
+BEGIN
+    UPDATE moz_bookmarks SET
+        syncChangeCounter = syncChangeCounter + 1
+    WHERE fk = NEW.place_id;
+END;
+
+CREATE TEMP TRIGGER moz_keywords_afterupdate_sync_trigger
+AFTER UPDATE ON moz_keywords
+BEGIN
+    UPDATE moz_bookmarks SET
+        syncChangeCounter = syncChangeCounter + 1
+    WHERE fk IN (OLD.place_id, NEW.place_id);
+END;
+
+CREATE TEMP TRIGGER moz_keywords_afterdelete_sync_trigger
+AFTER DELETE ON moz_keywords
+BEGIN
+    UPDATE moz_bookmarks SET
+        syncChangeCounter = syncChangeCounter + 1
+    WHERE fk = OLD.place_id;
+END;
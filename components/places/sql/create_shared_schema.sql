@@ -2,10 +2,6 @@
 -- License, v. 2.0. If a copy of the MPL was not distributed with this
 -- file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
--- XXX - TODO - moz_annos
--- XXX - TODO - moz_anno_attributes
--- XXX - TODO - moz_items_annos
-
 CREATE TABLE IF NOT EXISTS moz_places (
     id INTEGER PRIMARY KEY,
     url LONGVARCHAR NOT NULL,
@@ -30,6 +26,10 @@ CREATE TABLE IF NOT EXISTS moz_places (
     -- a couple of sync-related fields.
     sync_status TINYINT NOT NULL DEFAULT 1, -- 1 is SyncStatus::New
     sync_change_counter INTEGER NOT NULL DEFAULT 0, -- adding visits will increment this
+    -- Whether the most recent visit to the page was an error page, or
+    -- redirected to another page. These pages are left out of top sites.
+    is_error_page INTEGER NOT NULL DEFAULT 0,
+    is_redirect_source INTEGER NOT NULL DEFAULT 0,
 
     FOREIGN KEY(origin_id) REFERENCES moz_origins(id) ON DELETE CASCADE
 );
@@ -90,6 +90,10 @@ CREATE TABLE IF NOT EXISTS moz_inputhistory (
     place_id INTEGER NOT NULL,
     input LONGVARCHAR NOT NULL,
     use_count INTEGER,
+    -- When the input was last used to pick the page, in milliseconds. Older
+    -- rows have 0, meaning "unknown".
+    last_used INTEGER NOT NULL DEFAULT 0,
+    sync_change_counter INTEGER NOT NULL DEFAULT 1,
 
     PRIMARY KEY (place_id, input),
     FOREIGN KEY(place_id) REFERENCES moz_places(id) ON DELETE CASCADE
@@ -131,8 +135,98 @@ CREATE TABLE IF NOT EXISTS moz_bookmarks_deleted (
     dateRemoved INTEGER NOT NULL
 ) WITHOUT ROWID;
 
--- Note: desktop has/had a 'keywords' table, but we intentionally do not.
+-- Recently deleted bookmarks, so that deletions can be undone. Unlike
+-- `moz_bookmarks_deleted`, this is local only, and holds the whole deleted
+-- subtree, as a JSON `BookmarkTreeNode`, and where it was in the tree.
+CREATE TABLE IF NOT EXISTS moz_bookmarks_deleted_journal (
+    id INTEGER PRIMARY KEY,
+    guid TEXT NOT NULL UNIQUE,
+    parentGuid TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    dateRemoved INTEGER NOT NULL,
+    tree TEXT NOT NULL
+);
 
+-- Bookmark keywords. As on desktop, keywords belong to a URL rather than to
+-- a specific bookmark. Unlike desktop, each URL may only have a single
+-- keyword, as that's all a Sync record can represent, and we don't support
+-- POST data.
+CREATE TABLE IF NOT EXISTS moz_keywords(
+    id INTEGER PRIMARY KEY,
+    keyword TEXT UNIQUE NOT NULL,
+    place_id INTEGER UNIQUE NOT NULL REFERENCES moz_places(id)
+                                     ON DELETE CASCADE
+);
+
+
+-- Annotations are key/value metadata for pages (moz_annos) and bookmark
+-- items (moz_items_annos). The names are shared between both, and each
+-- annotation has an expiration policy; see `storage::annotations`.
+CREATE TABLE IF NOT EXISTS moz_anno_attributes (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS moz_annos (
+    id INTEGER PRIMARY KEY,
+    place_id INTEGER NOT NULL REFERENCES moz_places(id) ON DELETE CASCADE,
+    anno_attribute_id INTEGER NOT NULL REFERENCES moz_anno_attributes(id),
+    -- No declared type, so integers, doubles and strings are all stored as-is.
+    content NOT NULL,
+    expiration INTEGER NOT NULL DEFAULT 0,
+    dateAdded INTEGER NOT NULL DEFAULT 0,
+    lastModified INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(place_id, anno_attribute_id)
+);
+
+CREATE INDEX IF NOT EXISTS moz_annos_attributeindex ON moz_annos(anno_attribute_id);
+
+CREATE TABLE IF NOT EXISTS moz_items_annos (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES moz_bookmarks(id) ON DELETE CASCADE,
+    anno_attribute_id INTEGER NOT NULL REFERENCES moz_anno_attributes(id),
+    content NOT NULL,
+    expiration INTEGER NOT NULL DEFAULT 0,
+    dateAdded INTEGER NOT NULL DEFAULT 0,
+    lastModified INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(item_id, anno_attribute_id)
+);
+
+CREATE INDEX IF NOT EXISTS moz_items_annos_attributeindex ON moz_items_annos(anno_attribute_id);
+
+-- Favicons, in all the sizes we know about. Pages are linked to their icons
+-- by URL, via moz_pages_w_icons, so that icons can be looked up without
+-- touching moz_places. Root icons (`/favicon.ico`) also serve as a fallback
+-- for other pages on the same host; see `storage::icons`.
+CREATE TABLE IF NOT EXISTS moz_icons (
+    id INTEGER PRIMARY KEY,
+    icon_url TEXT NOT NULL,
+    -- The hash of the icon URL without its scheme and a leading "www.".
+    fixed_icon_url_hash INTEGER NOT NULL,
+    width INTEGER NOT NULL DEFAULT 0,
+    root INTEGER NOT NULL DEFAULT 0,
+    expire_ms INTEGER NOT NULL DEFAULT 0,
+    data BLOB NOT NULL,
+    UNIQUE(icon_url, width)
+);
+
+CREATE INDEX IF NOT EXISTS moz_icons_fixedurlhashindex ON moz_icons(fixed_icon_url_hash);
+
+CREATE TABLE IF NOT EXISTS moz_pages_w_icons (
+    id INTEGER PRIMARY KEY,
+    page_url TEXT NOT NULL,
+    page_url_hash INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS moz_pages_w_icons_urlhashindex ON moz_pages_w_icons(page_url_hash);
+
+CREATE TABLE IF NOT EXISTS moz_icons_to_pages (
+    page_id INTEGER NOT NULL REFERENCES moz_pages_w_icons(id) ON DELETE CASCADE,
+    icon_id INTEGER NOT NULL REFERENCES moz_icons(id) ON DELETE CASCADE,
+    PRIMARY KEY(page_id, icon_id)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS moz_icons_to_pages_iconindex ON moz_icons_to_pages(icon_id);
 
 CREATE TABLE IF NOT EXISTS moz_origins (
     id INTEGER PRIMARY KEY,
@@ -146,6 +240,20 @@ CREATE TABLE IF NOT EXISTS moz_origins (
 CREATE INDEX IF NOT EXISTS hostindex ON moz_origins(rev_host);
 
 
+-- Sites pinned to, and blocked from, the results of `top_frecent_sites`.
+-- These are keyed by URL rather than by place, so that pins survive history
+-- being deleted.
+CREATE TABLE IF NOT EXISTS moz_topsites_pinned (
+    url TEXT PRIMARY KEY,
+    title TEXT,
+    position INTEGER NOT NULL UNIQUE
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS moz_topsites_blocked (
+    url TEXT PRIMARY KEY
+) WITHOUT ROWID;
+
+
 -- This table holds key-value metadata for Places and its consumers. Sync stores
 -- the sync IDs for the bookmarks and history collections in this table, and the
 -- last sync time for history.
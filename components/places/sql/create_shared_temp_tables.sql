@@ -44,3 +44,14 @@ CREATE TEMP TABLE moz_updateoriginsupdate_temp (
     frecency_delta INTEGER NOT NULL,
     PRIMARY KEY (prefix, host)
 ) WITHOUT ROWID;
+
+-- This table is used, along with moz_places_afterdelete_trigger_icons, to
+-- remove icons for pages that have been removed from moz_places. A DELETE
+-- FROM moz_updateiconsdelete_temp removes each page's icon links, and any
+-- icons that no other page uses, so that we don't need to scan all icons
+-- after every removal.
+CREATE TEMP TABLE moz_updateiconsdelete_temp (
+    url_hash INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    PRIMARY KEY (url_hash, url)
+) WITHOUT ROWID;
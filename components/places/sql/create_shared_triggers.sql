@@ -47,9 +47,14 @@ END;
 
 -- NOTE: the values "0, 4, 7, 8, 9" below are EXCLUDED_VISIT_TYPES, stolen
 -- from desktop.
+-- We also unlink any visits which were referred by the deleted visit, as
+-- otherwise the `from_visit` foreign key would prevent the deletion.
 CREATE TEMP TRIGGER moz_historyvisits_afterdelete_trigger
 AFTER DELETE ON moz_historyvisits FOR EACH ROW
 BEGIN
+    UPDATE moz_historyvisits SET
+        from_visit = NULL
+    WHERE from_visit = OLD.id;
     UPDATE moz_places SET
         visit_count_local = visit_count_local - (OLD.visit_type NOT IN (0, 4, 7, 8, 9) AND OLD.is_local),
         visit_count_remote = visit_count_remote - (OLD.visit_type NOT IN (0, 4, 7, 8, 9) AND NOT(OLD.is_local)),
@@ -200,6 +205,37 @@ BEGIN
     {increase_frecency_stats};
 END;
 
+-- When a row is deleted from places, we note its URL in
+-- moz_updateiconsdelete_temp, so that we can remove its icons.
+CREATE TEMP TRIGGER moz_places_afterdelete_trigger_icons
+AFTER DELETE ON moz_places
+FOR EACH ROW
+BEGIN
+    INSERT OR IGNORE INTO moz_updateiconsdelete_temp(url_hash, url)
+    VALUES(OLD.url_hash, OLD.url);
+END;
+
+-- This trigger corresponds to the previous trigger
+-- (moz_places_afterdelete_trigger_icons). It removes icons that are only
+-- used by the removed page, and then the page's icon links.
+CREATE TEMP TRIGGER moz_updateiconsdelete_afterdelete_trigger
+AFTER DELETE ON moz_updateiconsdelete_temp FOR EACH ROW
+BEGIN
+    DELETE FROM moz_icons
+    WHERE id IN (SELECT ip.icon_id FROM moz_icons_to_pages ip
+                 JOIN moz_pages_w_icons p ON p.id = ip.page_id
+                 WHERE p.page_url_hash = OLD.url_hash AND
+                       p.page_url = OLD.url) AND
+          NOT EXISTS(SELECT 1 FROM moz_icons_to_pages ip
+                     JOIN moz_pages_w_icons p ON p.id = ip.page_id
+                     WHERE ip.icon_id = moz_icons.id AND
+                           (p.page_url_hash <> OLD.url_hash OR
+                            p.page_url <> OLD.url));
+
+    DELETE FROM moz_pages_w_icons
+    WHERE page_url_hash = OLD.url_hash AND page_url = OLD.url;
+END;
+
 -- Note: desktop places also has a notion of "frecency decay", and it only runs this
 -- `WHEN NOT is_frecency_decaying()`.
 CREATE TEMP TRIGGER moz_places_afterupdate_frecency_trigger
@@ -306,3 +342,44 @@ BEGIN
         foreign_count = foreign_count - 1
     WHERE id = OLD.place_id;
 END;
+
+-- These triggers adjust the foreign count for URLs with keywords, so that
+-- they're not expired or otherwise automatically removed.
+CREATE TEMP TRIGGER moz_keywords_afterinsert_trigger
+AFTER INSERT ON moz_keywords
+BEGIN
+    UPDATE moz_places SET
+        foreign_count = foreign_count + 1
+    WHERE id = NEW.place_id;
+END;
+
+CREATE TEMP TRIGGER moz_keywords_afterupdate_trigger
+AFTER UPDATE OF place_id ON moz_keywords
+BEGIN
+    UPDATE moz_places SET
+        foreign_count = foreign_count + 1
+    WHERE id = NEW.place_id;
+
+    UPDATE moz_places SET
+        foreign_count = foreign_count - 1
+    WHERE id = OLD.place_id;
+END;
+
+CREATE TEMP TRIGGER moz_keywords_afterdelete_trigger
+AFTER DELETE ON moz_keywords
+BEGIN
+    UPDATE moz_places SET
+        foreign_count = foreign_count - 1
+    WHERE id = OLD.place_id;
+END;
+
+-- Keywords are bookmark properties, so we remove a URL's keyword along with
+-- the last bookmark for that URL.
+CREATE TEMP TRIGGER moz_bookmarks_keywords_afterdelete_trigger
+AFTER DELETE ON moz_bookmarks
+FOR EACH ROW WHEN OLD.fk NOT NULL
+BEGIN
+    DELETE FROM moz_keywords
+    WHERE place_id = OLD.fk AND
+          NOT EXISTS(SELECT 1 FROM moz_bookmarks WHERE fk = OLD.fk);
+END;
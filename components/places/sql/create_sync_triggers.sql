@@ -157,6 +157,18 @@ BEGIN
     SELECT tagId, OLD.newPlaceId
     FROM moz_bookmarks_synced_tag_relation
     WHERE itemId = OLD.remoteId;
+
+    -- Remove existing keywords from the old and new URLs, and remove the new
+    -- keyword from any other URL that has it.
+    DELETE FROM moz_keywords
+    WHERE place_id IN (OLD.oldPlaceId, OLD.newPlaceId) OR
+          keyword = lower(trim(OLD.newKeyword));
+
+    -- Insert the new keyword for the new URL.
+    INSERT INTO moz_keywords(keyword, place_id)
+    SELECT lower(trim(OLD.newKeyword)), OLD.newPlaceId
+    WHERE OLD.newPlaceId NOT NULL AND
+          trim(IFNULL(OLD.newKeyword, '')) <> '';
 END;
 
 -- Updates all parents and positions to reflect the merged tree.
@@ -6,12 +6,92 @@ use super::apply_observation;
 use crate::db::PlacesDb;
 use crate::error::*;
 use crate::observation::VisitObservation;
+use crate::storage::URL_LENGTH_MAX;
 use crate::types::*;
-use url::Url;
+use serde_derive::*;
+use url::{Host, Url};
 // This module can become, roughly: PlacesUtils.history()
 
-pub fn can_add_url(_url: &Url) -> Result<bool> {
-    Ok(true)
+/// URLs with these schemes are never recorded in history, like Desktop's
+/// `nsNavHistory::CanAddURI`.
+const BLOCKED_SCHEMES: &[&str] = &["about", "data", "javascript", "blob", "file"];
+
+/// Decides which URLs are recorded in history, both for local visits and
+/// incoming synced records. Besides the URLs we never record, apps can
+/// block more schemes and hosts, for example for private domains.
+///
+/// Changing the policy doesn't remove existing history for the blocked URLs;
+/// use `storage::history::delete_history_for_host` for that.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HistoryPolicy {
+    /// Schemes to block, without the trailing colon, like "ftp".
+    pub blocked_schemes: Vec<String>,
+    /// Hosts to block. Blocking a host also blocks its subdomains, on any
+    /// port.
+    pub blocked_hosts: Vec<String>,
+}
+
+impl HistoryPolicy {
+    /// Returns the policy with its schemes and hosts normalized the same way
+    /// as URLs, so that they can be compared with them, or an error if any
+    /// are invalid.
+    pub fn normalize(&self) -> Result<HistoryPolicy> {
+        let blocked_schemes = self
+            .blocked_schemes
+            .iter()
+            .map(|scheme| {
+                let scheme = scheme.to_ascii_lowercase();
+                let is_valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+                if is_valid {
+                    Ok(scheme)
+                } else {
+                    Err(ErrorKind::InvalidHistoryPolicy("invalid blocked scheme").into())
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let blocked_hosts = self
+            .blocked_hosts
+            .iter()
+            .map(|host| match Host::parse(host) {
+                Ok(host) => Ok(host.to_string()),
+                Err(_) => Err(ErrorKind::InvalidHistoryPolicy("invalid blocked host").into()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(HistoryPolicy {
+            blocked_schemes,
+            blocked_hosts,
+        })
+    }
+
+    /// Returns true if visits to `url` should be recorded. The policy must
+    /// already be normalized.
+    pub fn can_add_url(&self, url: &Url) -> bool {
+        if url.as_str().len() > URL_LENGTH_MAX {
+            return false;
+        }
+        let scheme = url.scheme();
+        if BLOCKED_SCHEMES.contains(&scheme) || self.blocked_schemes.iter().any(|s| s == scheme) {
+            return false;
+        }
+        match url.host_str() {
+            Some(host) => !self.blocked_hosts.iter().any(|blocked| {
+                host == blocked
+                    || (host.ends_with(blocked.as_str())
+                        && host[..host.len() - blocked.len()].ends_with('.'))
+            }),
+            None => true,
+        }
+    }
+}
+
+/// Returns true if the connection's history policy allows recording visits
+/// to `url`.
+pub fn can_add_url(db: &PlacesDb, url: &Url) -> Result<bool> {
+    Ok(db.can_add_url(url))
 }
 
 // eg: PlacesUtils.history.insert({url: "http", title: ..., visits: [{date: ...}]})
@@ -45,10 +125,8 @@ pub fn insert(conn: &mut PlacesDb, place: AddablePlaceInfo) -> Result<()> {
             .with_visit_type(v.transition)
             .with_at(v.date)
             .with_title(place.title.clone())
-            .with_is_remote(!v.is_local);
-        // .with_referrer(...) ????
-
-        //if place.referrer
+            .with_is_remote(!v.is_local)
+            .with_referrer(v.referrer);
         apply_observation(conn, obs)?;
     }
     Ok(())
@@ -107,6 +185,51 @@ mod tests {
         assert_ne!(row.get::<_, i32>("frecency").expect("should work"), 0);
         // XXX - check more.
     }
+
+    #[test]
+    fn test_history_policy() {
+        let can_add = |policy: &HistoryPolicy, url: &str| {
+            policy.can_add_url(&Url::parse(url).expect("should be valid"))
+        };
+        let policy = HistoryPolicy::default();
+        assert!(can_add(&policy, "https://example.com/"));
+        assert!(can_add(&policy, "ftp://example.com/"));
+        assert!(!can_add(&policy, "about:blank"));
+        assert!(!can_add(&policy, "data:text/plain,hi"));
+        assert!(!can_add(&policy, "javascript:void(0)"));
+        assert!(!can_add(&policy, "blob:https://example.com/1234"));
+        assert!(!can_add(&policy, "file:///etc/hosts"));
+        let long_url = format!("https://example.com/{}", "x".repeat(URL_LENGTH_MAX));
+        assert!(!can_add(&policy, &long_url));
+
+        let policy = HistoryPolicy {
+            blocked_schemes: vec!["FTP".into()],
+            blocked_hosts: vec!["Example.COM".into(), "bücher.de".into()],
+        }
+        .normalize()
+        .expect("should be valid");
+        assert_eq!(policy.blocked_schemes, &["ftp"]);
+        assert_eq!(policy.blocked_hosts, &["example.com", "xn--bcher-kva.de"]);
+        assert!(!can_add(&policy, "ftp://mozilla.org/"));
+        assert!(!can_add(&policy, "https://example.com/"));
+        assert!(!can_add(&policy, "http://www.example.com:8080/"));
+        assert!(!can_add(&policy, "https://bücher.de/"));
+        assert!(can_add(&policy, "https://notexample.com/"));
+        assert!(can_add(&policy, "https://example.com.au/"));
+
+        assert!(HistoryPolicy {
+            blocked_schemes: vec!["ftp:".into()],
+            ..HistoryPolicy::default()
+        }
+        .normalize()
+        .is_err());
+        assert!(HistoryPolicy {
+            blocked_hosts: vec!["".into()],
+            ..HistoryPolicy::default()
+        }
+        .normalize()
+        .is_err());
+    }
 }
 
 /////////////////////////////////////////////
@@ -160,7 +283,7 @@ pub fn visit_uri(
     is_error_page: bool,
 ) -> Result<()> {
     // Silently return if URI is something we shouldn't add to DB.
-    if !can_add_url(&url)? {
+    if !can_add_url(conn, &url)? {
         return Ok(());
     };
     // Do not save a reloaded uri if we have visited the same URI recently.
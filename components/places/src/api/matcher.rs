@@ -3,14 +3,19 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::db::PlacesDb;
-use crate::error::Result;
+use crate::error::{Error, ErrorKind, Result};
+use crate::match_impl::split_search_tokens;
 pub use crate::match_impl::{MatchBehavior, SearchBehavior};
+use crate::types::Timestamp;
+use interrupt::{Interrupted, Interruptee};
 use rusqlite::{types::ToSql, Row};
 use serde_derive::*;
 use sql_support::{maybe_log_plan, ConnExt};
 use url::Url;
 
 // A helper to log, cache and execute a query, returning a vector of flattened rows.
+// Rows that fail to map are logged and skipped, but interrupting the query
+// aborts the search.
 fn query_flat_rows_and_then_named<T, F>(
     conn: &PlacesDb,
     sql: &str,
@@ -23,17 +28,33 @@ where
     maybe_log_plan(conn, sql, params);
     let mut stmt = conn.prepare_maybe_cached(sql, true)?;
     let iter = stmt.query_and_then_named(params, mapper)?;
-    Ok(iter
-        .inspect(|r| {
-            if let Err(ref e) = *r {
+    let mut results = Vec::new();
+    for result in iter {
+        match result {
+            Ok(row) => results.push(row),
+            Err(e) => {
+                if is_interrupted_error(&e) {
+                    return Err(ErrorKind::InterruptedError(Interrupted).into());
+                }
                 log::warn!("Failed to perform a search: {}", e);
                 if cfg!(debug_assertions) {
                     panic!("Failed to perform a search: {}", e);
                 }
             }
-        })
-        .flatten()
-        .collect::<Vec<_>>())
+        }
+    }
+    Ok(results)
+}
+
+// Returns true if SQLite aborted a statement because of a call to
+// `sqlite3_interrupt()`.
+fn is_interrupted_error(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::SqlError(rusqlite::Error::SqliteFailure(e, _)) => {
+            e.code == rusqlite::ErrorCode::OperationInterrupted
+        }
+        _ => false,
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,44 +64,130 @@ pub struct SearchParams {
 }
 
 /// Synchronously queries all providers for autocomplete matches, then filters
-/// the matches. This isn't cancelable yet; once a search is started, it can't
-/// be interrupted, even if the user moves on (see
-/// https://github.com/mozilla/application-services/issues/265).
+/// the matches. A running search can be canceled from another thread, for
+/// example when the user types another character, using the handle returned
+/// by `PlacesDb::new_search_interrupt_handle`. This aborts the current SQL
+/// statement, and fails the search with an `InterruptedError`.
 ///
 /// A provider can be anything that returns URL suggestions: Places history
 /// and bookmarks, synced tabs, search engine suggestions, and search keywords.
+///
+/// The search string is split into whitespace-separated tokens, all of which
+/// must match a result. Restriction tokens, like `*` for bookmarks, limit the
+/// results to pages of that kind instead; see `restriction_for_token`.
 pub fn search_frecent(conn: &PlacesDb, params: SearchParams) -> Result<Vec<SearchResult>> {
-    // TODO: Tokenize the query.
+    let scope = conn.begin_search_interrupt_scope();
+    let query = TokenizedQuery::new(&params.search_string);
+    if query.is_restricted_to(SearchBehavior::SEARCHES) {
+        // We don't have a search engine suggestions provider, so there's
+        // nothing to return for a search-only query.
+        return Ok(vec![]);
+    }
+    let search_string = query.search_string();
+    let search_behavior = query.search_behavior();
 
     // Try to find the first heuristic result. Desktop tries extensions,
     // search engine aliases, origins, URLs, search engine domains, and
     // preloaded sites, before trying to fall back to fixing up the URL,
-    // and a search if all else fails. We only try origins and URLs for
-    // heuristic matches, since that's all we support.
-
-    let matches = match_with_limit(
-        conn,
-        &[
+    // and a search if all else fails. We only try keywords, origins and URLs
+    // for heuristic matches, since that's all we support. As on Desktop,
+    // restricted searches skip the heuristic matches, and we only try to
+    // match an origin or URL if the query is a single token.
+    let keyword = Keyword::new(&params.search_string);
+    let origin_or_url = OriginOrUrl::new(&search_string);
+
+    // Query adaptive matches and suggestions, matching Anywhere.
+    let adaptive =
+        Adaptive::with_behavior(&search_string, MatchBehavior::Anywhere, search_behavior);
+    let suggestions =
+        Suggestions::with_behavior(&search_string, MatchBehavior::Anywhere, search_behavior);
+
+    let mut matchers: Vec<&dyn Matcher> = Vec::with_capacity(4);
+    if query.restriction.is_none() {
+        // A bookmark keyword, optionally followed by a search term.
+        matchers.push(&keyword);
+        if query.tokens.len() == 1 {
             // Try to match on the origin, or the full URL.
-            &OriginOrUrl::new(&params.search_string),
-            // query adaptive matches and suggestions, matching Anywhere.
-            &Adaptive::with_behavior(
-                &params.search_string,
-                MatchBehavior::Anywhere,
-                SearchBehavior::default(),
-            ),
-            &Suggestions::with_behavior(
-                &params.search_string,
-                MatchBehavior::Anywhere,
-                SearchBehavior::default(),
-            ),
-        ],
-        params.limit,
-    )?;
+            matchers.push(&origin_or_url);
+        }
+    }
+    matchers.push(&adaptive);
+    matchers.push(&suggestions);
+
+    let matches = match_with_limit(conn, &matchers, params.limit, &scope)?;
 
     Ok(matches)
 }
 
+/// Returns the search behavior that a restriction token limits the results
+/// of a search to, or `None` if the token isn't a restriction token. These
+/// are the same as Desktop's defaults for the `browser.urlbar.restrict.*`
+/// preferences.
+fn restriction_for_token(token: &str) -> Option<SearchBehavior> {
+    Some(match token {
+        "^" => SearchBehavior::HISTORY,
+        "*" => SearchBehavior::BOOKMARK,
+        "+" => SearchBehavior::TAG,
+        // We don't know which pages are open in tabs, so restricting to them
+        // would never match anything. Instead, we drop the token, and don't
+        // restrict the search at all.
+        "%" => SearchBehavior::empty(),
+        "?" => SearchBehavior::SEARCHES,
+        _ => return None,
+    })
+}
+
+/// An autocomplete query, split into search tokens and restrictions.
+#[derive(Debug, PartialEq)]
+struct TokenizedQuery<'query> {
+    /// The tokens to search for, excluding any restriction tokens.
+    tokens: Vec<&'query str>,
+
+    /// The behaviors to restrict matches to, or `None` if the query doesn't
+    /// contain any restriction tokens.
+    restriction: Option<SearchBehavior>,
+}
+
+impl<'query> TokenizedQuery<'query> {
+    fn new(search_string: &'query str) -> TokenizedQuery<'query> {
+        let mut tokens = Vec::new();
+        let mut restriction: Option<SearchBehavior> = None;
+        for token in split_search_tokens(search_string) {
+            match restriction_for_token(token) {
+                Some(behavior) if behavior.is_empty() => {}
+                Some(behavior) => {
+                    *restriction.get_or_insert_with(SearchBehavior::empty) |= behavior;
+                }
+                None => tokens.push(token),
+            }
+        }
+        TokenizedQuery {
+            tokens,
+            restriction,
+        }
+    }
+
+    /// Returns the tokens to search for, joined into a single string for
+    /// `AUTOCOMPLETE_MATCH`, which requires all of them to match.
+    fn search_string(&self) -> String {
+        self.tokens.join(" ")
+    }
+
+    /// Returns the search behavior for matching this query. Restricted
+    /// queries only match pages which satisfy all their restrictions.
+    fn search_behavior(&self) -> SearchBehavior {
+        match self.restriction {
+            Some(behavior) => behavior | SearchBehavior::RESTRICT,
+            None => SearchBehavior::default(),
+        }
+    }
+
+    fn is_restricted_to(&self, behavior: SearchBehavior) -> bool {
+        self.restriction
+            .map_or(false, |restriction| restriction.contains(behavior))
+    }
+}
+
 pub fn match_url(conn: &PlacesDb, query: impl AsRef<str>) -> Result<Option<String>> {
     let scope = conn.begin_interrupt_scope();
     let matcher = OriginOrUrl::new(query.as_ref());
@@ -100,6 +207,7 @@ fn match_with_limit(
     conn: &PlacesDb,
     matchers: &[&dyn Matcher],
     max_results: u32,
+    search_scope: &impl Interruptee,
 ) -> Result<(Vec<SearchResult>)> {
     let mut results = Vec::new();
     let mut rem_results = max_results;
@@ -109,10 +217,13 @@ fn match_with_limit(
             break;
         }
         scope.err_if_interrupted()?;
+        search_scope.err_if_interrupted()?;
         let matches = m.search(conn, rem_results)?;
         results.extend(matches);
         rem_results = rem_results.saturating_sub(results.len() as u32);
     }
+    // The last matcher might have finished before seeing the interrupt.
+    search_scope.err_if_interrupted()?;
     Ok(results)
 }
 
@@ -121,14 +232,17 @@ fn match_with_limit(
 pub fn accept_result(conn: &PlacesDb, result: &SearchResult) -> Result<()> {
     // See `nsNavHistory::AutoCompleteFeedback`.
     conn.execute_named(
-        "INSERT OR REPLACE INTO moz_inputhistory(place_id, input, use_count)
-         SELECT h.id, IFNULL(i.input, :input_text), IFNULL(i.use_count, 0) * .9 + 1
+        "INSERT OR REPLACE INTO moz_inputhistory(place_id, input, use_count, last_used,
+                                                 sync_change_counter)
+         SELECT h.id, IFNULL(i.input, :input_text), IFNULL(i.use_count, 0) * .9 + 1, :now,
+                IFNULL(i.sync_change_counter, 0) + 1
          FROM moz_places h
          LEFT JOIN moz_inputhistory i ON i.place_id = h.id AND i.input = :input_text
          WHERE url_hash = hash(:page_url) AND url = :page_url",
         &[
             (":input_text", &result.search_string),
             (":page_url", &result.url.as_str()),
+            (":now", &Timestamp::now()),
         ],
     )?;
 
@@ -160,6 +274,14 @@ pub fn split_after_host_and_port(href: &str) -> (&str, &str) {
     remainder.split_at(end)
 }
 
+/// Substitutes the search terms that followed a bookmark keyword into the
+/// keyword's URL. As on Desktop, `%s` is replaced with the escaped terms, and
+/// `%S` with the terms as typed.
+fn expand_keyword_url(href: &str, params: &str) -> String {
+    let escaped_params: String = url::form_urlencoded::byte_serialize(params.as_bytes()).collect();
+    href.replace("%s", &escaped_params).replace("%S", params)
+}
+
 fn looks_like_origin(string: &str) -> bool {
     // Skip nonascii characters, we'll either handle them in autocomplete_match or,
     // a later part of the origins query.
@@ -204,10 +326,30 @@ pub struct SearchResult {
     /// A frecency score for this match.
     pub frecency: i64,
 
+    /// The description of the page, if one was recorded with
+    /// `note_page_metadata`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The URL of a preview image for the page.
+    #[serde(with = "url_serde")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_image_url: Option<Url>,
+
     /// A list of reasons why this matched.
     pub reasons: Vec<MatchReason>,
 }
 
+/// Reads the `description` and `preview_image_url` columns of a result row.
+fn page_metadata_from_row(row: &rusqlite::Row<'_>) -> Result<(Option<String>, Option<Url>)> {
+    let description = row.get::<_, Option<String>>("description")?;
+    let preview_image_url = match row.get::<_, Option<String>>("preview_image_url")? {
+        Some(href) => Some(Url::parse(&href)?),
+        None => None,
+    };
+    Ok((description, preview_image_url))
+}
+
 impl SearchResult {
     /// Default search behaviors from Desktop: HISTORY, BOOKMARK, OPENPAGE, SEARCHES.
     /// Default match behavior: MATCH_BOUNDARY_ANYWHERE.
@@ -232,6 +374,7 @@ impl SearchResult {
             reasons.push(MatchReason::Bookmark);
         }
         let url = Url::parse(&url)?;
+        let (description, preview_image_url) = page_metadata_from_row(row)?;
 
         Ok(Self {
             search_string,
@@ -239,6 +382,8 @@ impl SearchResult {
             title,
             icon_url: None,
             frecency,
+            description,
+            preview_image_url,
             reasons,
         })
     }
@@ -260,6 +405,7 @@ impl SearchResult {
         let url = Url::parse(&url)?;
 
         let frecency = row.get::<_, i64>("frecency")?;
+        let (description, preview_image_url) = page_metadata_from_row(row)?;
 
         Ok(Self {
             search_string,
@@ -267,10 +413,35 @@ impl SearchResult {
             title,
             icon_url: None,
             frecency,
+            description,
+            preview_image_url,
             reasons,
         })
     }
 
+    pub fn from_keyword_row(row: &rusqlite::Row<'_>, keyword_params: &str) -> Result<Self> {
+        let search_string = row.get::<_, String>("searchString")?;
+        let href = row.get::<_, String>("url")?;
+        let history_title = row.get::<_, Option<String>>("title")?;
+        let bookmark_title = row.get::<_, Option<String>>("btitle")?;
+        let title = bookmark_title.or_else(|| history_title).unwrap_or_default();
+        let frecency = row.get::<_, i64>("frecency")?;
+
+        let url = Url::parse(&expand_keyword_url(&href, keyword_params))?;
+        let (description, preview_image_url) = page_metadata_from_row(row)?;
+
+        Ok(Self {
+            search_string,
+            url,
+            title,
+            icon_url: None,
+            frecency,
+            description,
+            preview_image_url,
+            reasons: vec![MatchReason::Keyword],
+        })
+    }
+
     pub fn from_origin_row(row: &rusqlite::Row<'_>) -> Result<Self> {
         let search_string = row.get::<_, String>("searchString")?;
         let url = row.get::<_, String>("url")?;
@@ -285,6 +456,8 @@ impl SearchResult {
             title: display_url,
             icon_url: None,
             frecency,
+            description: None,
+            preview_image_url: None,
             reasons: vec![MatchReason::Origin],
         })
     }
@@ -326,6 +499,8 @@ impl SearchResult {
             title: display_url,
             icon_url: None,
             frecency,
+            description: None,
+            preview_image_url: None,
             reasons,
         })
     }
@@ -448,6 +623,46 @@ impl<'query> Matcher for OriginOrUrl<'query> {
     }
 }
 
+struct Keyword<'query> {
+    query: &'query str,
+}
+
+impl<'query> Keyword<'query> {
+    pub fn new(query: &'query str) -> Keyword<'query> {
+        Keyword { query }
+    }
+}
+
+impl<'query> Matcher for Keyword<'query> {
+    fn search(&self, conn: &PlacesDb, _: u32) -> Result<Vec<SearchResult>> {
+        let query = self.query.trim();
+        let (keyword, params) = match query.find(char::is_whitespace) {
+            Some(index) => (&query[..index], query[index..].trim_start()),
+            None => (query, ""),
+        };
+        if keyword.is_empty() {
+            return Ok(vec![]);
+        }
+        let keyword = keyword.to_lowercase();
+        query_flat_rows_and_then_named(
+            conn,
+            "
+            SELECT h.url, h.title, h.description, h.preview_image_url,
+                   (SELECT title FROM moz_bookmarks
+                    WHERE fk = h.id AND
+                          title NOT NULL
+                    ORDER BY lastModified DESC
+                    LIMIT 1) AS btitle,
+                   h.frecency, :searchString AS searchString
+            FROM moz_keywords k
+            JOIN moz_places h ON h.id = k.place_id
+            WHERE k.keyword = :keyword",
+            &[(":searchString", &self.query), (":keyword", &keyword)],
+            |row| SearchResult::from_keyword_row(row, params),
+        )
+    }
+}
+
 struct Adaptive<'query> {
     query: &'query str,
     match_behavior: MatchBehavior,
@@ -475,6 +690,8 @@ impl<'query> Matcher for Adaptive<'query> {
             "
             SELECT h.url as url,
                    h.title as title,
+                   h.description as description,
+                   h.preview_image_url as preview_image_url,
                    EXISTS(SELECT 1 FROM moz_bookmarks
                           WHERE fk = h.id) AS bookmarked,
                    (SELECT title FROM moz_bookmarks
@@ -482,7 +699,10 @@ impl<'query> Matcher for Adaptive<'query> {
                           title NOT NULL
                     ORDER BY lastModified DESC
                     LIMIT 1) AS btitle,
-                   NULL AS tags,
+                   (SELECT GROUP_CONCAT(t.tag, ', ')
+                    FROM moz_tags t
+                    JOIN moz_tags_relation r ON r.tag_id = t.id
+                    WHERE r.place_id = h.id) AS tags,
                    h.visit_count_local + h.visit_count_remote AS visit_count,
                    h.typed as typed,
                    h.id as id,
@@ -539,7 +759,7 @@ impl<'query> Matcher for Suggestions<'query> {
         Ok(query_flat_rows_and_then_named(
             conn,
             "
-            SELECT h.url, h.title,
+            SELECT h.url, h.title, h.description, h.preview_image_url,
                    EXISTS(SELECT 1 FROM moz_bookmarks
                           WHERE fk = h.id) AS bookmarked,
                    (SELECT title FROM moz_bookmarks
@@ -547,7 +767,10 @@ impl<'query> Matcher for Suggestions<'query> {
                           title NOT NULL
                     ORDER BY lastModified DESC
                     LIMIT 1) AS btitle,
-                   NULL AS tags,
+                   (SELECT GROUP_CONCAT(t.tag, ', ')
+                    FROM moz_tags t
+                    JOIN moz_tags_relation r ON r.tag_id = t.id
+                    WHERE r.place_id = h.id) AS tags,
                    h.visit_count_local + h.visit_count_remote AS visit_count,
                    h.typed as typed,
                    h.id as id,
@@ -671,6 +894,8 @@ mod tests {
                 title: "Example page 123".into(),
                 icon_url: None,
                 frecency: -1,
+                description: None,
+                preview_image_url: None,
                 reasons: vec![],
             },
         )
@@ -706,10 +931,284 @@ mod tests {
                 title: "example.com/".into(),
                 icon_url: None,
                 frecency: -1,
+                description: None,
+                preview_image_url: None,
                 reasons: vec![MatchReason::Origin],
             }]
         );
     }
+    #[test]
+    fn tokenize() {
+        let query = TokenizedQuery::new("  rust  docs ");
+        assert_eq!(query.tokens, vec!["rust", "docs"]);
+        assert_eq!(query.restriction, None);
+        assert_eq!(query.search_string(), "rust docs");
+        assert_eq!(query.search_behavior(), SearchBehavior::default());
+
+        let query = TokenizedQuery::new("* rust + docs");
+        assert_eq!(query.tokens, vec!["rust", "docs"]);
+        assert_eq!(
+            query.restriction,
+            Some(SearchBehavior::BOOKMARK | SearchBehavior::TAG)
+        );
+        assert_eq!(
+            query.search_behavior(),
+            SearchBehavior::BOOKMARK | SearchBehavior::TAG | SearchBehavior::RESTRICT
+        );
+        assert!(query.is_restricted_to(SearchBehavior::BOOKMARK));
+        assert!(!query.is_restricted_to(SearchBehavior::SEARCHES));
+
+        // Restriction characters are only tokens on their own.
+        let query = TokenizedQuery::new("^rust 100% ?");
+        assert_eq!(query.tokens, vec!["^rust", "100%"]);
+        assert_eq!(query.restriction, Some(SearchBehavior::SEARCHES));
+
+        // The open tabs restriction is ignored.
+        let query = TokenizedQuery::new("% rust");
+        assert_eq!(query.tokens, vec!["rust"]);
+        assert_eq!(query.restriction, None);
+
+        // We split on the same whitespace as `AUTOCOMPLETE_MATCH`, which
+        // doesn't include non-breaking spaces.
+        let query = TokenizedQuery::new("rust\u{a0}docs\tgame");
+        assert_eq!(query.tokens, vec!["rust\u{a0}docs", "game"]);
+    }
+
+    #[test]
+    fn search_tokens() -> Result<()> {
+        use crate::storage::bookmarks::{
+            insert_bookmark, BookmarkPosition, BookmarkRootGuid, InsertableBookmark, InsertableItem,
+        };
+
+        let conn = new_mem_connection();
+        let docs_url = Url::parse("http://example.com/rust/docs")?;
+        let game_url = Url::parse("http://example.com/rust-game")?;
+        for (url, title) in &[(&docs_url, "Rust"), (&game_url, "Rust, the game")] {
+            apply_observation(
+                &conn,
+                VisitObservation::new((*url).clone())
+                    .with_title(title.to_string())
+                    .with_visit_type(VisitTransition::Link)
+                    .with_at(Timestamp::now()),
+            )?;
+        }
+        insert_bookmark(
+            &conn,
+            &InsertableItem::Bookmark(InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: game_url.clone(),
+                title: None,
+            }),
+        )?;
+
+        let search = |search_string: &str| -> Vec<Url> {
+            let mut urls = search_frecent(
+                &conn,
+                SearchParams {
+                    search_string: search_string.into(),
+                    limit: 10,
+                },
+            )
+            .expect("Should search")
+            .into_iter()
+            .map(|result| result.url)
+            .collect::<Vec<_>>();
+            urls.sort();
+            urls
+        };
+
+        // Every token must match.
+        assert_eq!(search("rust docs"), vec![docs_url.clone()]);
+        assert_eq!(search("game RUST"), vec![game_url.clone()]);
+        assert_eq!(search("rust"), vec![docs_url.clone(), game_url.clone()]);
+        assert!(search("rust docs game").is_empty());
+
+        // Restriction tokens limit the kinds of results.
+        assert_eq!(search("rust *"), vec![game_url.clone()]);
+        assert_eq!(search("^ rust"), vec![docs_url.clone(), game_url.clone()]);
+        assert!(search("* rust docs").is_empty());
+        assert!(search("+ rust").is_empty());
+        assert!(search("? rust").is_empty());
+        assert_eq!(search("% rust"), vec![docs_url.clone(), game_url.clone()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_tags() -> Result<()> {
+        use crate::storage::tags::tag_url;
+
+        let conn = new_mem_connection();
+        let soup_url = Url::parse("http://example.com/soup")?;
+        let stew_url = Url::parse("http://example.com/stew")?;
+        for (url, title) in &[(&soup_url, "Tomato soup"), (&stew_url, "Bean stew")] {
+            apply_observation(
+                &conn,
+                VisitObservation::new((*url).clone())
+                    .with_title(title.to_string())
+                    .with_visit_type(VisitTransition::Link)
+                    .with_at(Timestamp::now()),
+            )?;
+        }
+        tag_url(&conn, &soup_url, "recipes")?;
+        tag_url(&conn, &soup_url, "lunch")?;
+
+        let search = |search_string: &str| -> Vec<SearchResult> {
+            search_frecent(
+                &conn,
+                SearchParams {
+                    search_string: search_string.into(),
+                    limit: 10,
+                },
+            )
+            .expect("Should search")
+        };
+
+        // Tags are matched like titles.
+        let results = search("recipes");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, soup_url);
+        assert!(results[0].reasons.iter().any(|reason| match reason {
+            MatchReason::Tags(tags) => tags.contains("recipes") && tags.contains("lunch"),
+            _ => false,
+        }));
+
+        // The tag restriction token only matches tagged pages.
+        let urls = |results: Vec<SearchResult>| -> Vec<Url> {
+            results.into_iter().map(|result| result.url).collect()
+        };
+        assert_eq!(urls(search("+ example")), vec![soup_url.clone()]);
+        assert_eq!(urls(search("+")), vec![soup_url.clone()]);
+        assert!(search("+ stew").is_empty());
+        assert_eq!(urls(search("stew")), vec![stew_url.clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn search_page_metadata() -> Result<()> {
+        use crate::storage::history::{note_page_metadata, PageMetadata};
+
+        let conn = new_mem_connection();
+        let url = Url::parse("http://example.com/rust/docs")?;
+        apply_observation(
+            &conn,
+            VisitObservation::new(url.clone())
+                .with_title("Rust docs".to_string())
+                .with_visit_type(VisitTransition::Link)
+                .with_at(Timestamp::now()),
+        )?;
+        note_page_metadata(
+            &conn,
+            &url,
+            &PageMetadata {
+                description: Some("Documentation for Rust".into()),
+                preview_image_url: Some(Url::parse("http://example.com/rust.png")?),
+                ..PageMetadata::default()
+            },
+        )?;
+
+        let results = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "rust docs".into(),
+                limit: 10,
+            },
+        )?;
+        let result = results
+            .iter()
+            .find(|result| result.url == url)
+            .expect("Should find page");
+        assert_eq!(
+            result.description,
+            Some("Documentation for Rust".to_string())
+        );
+        assert_eq!(
+            result.preview_image_url,
+            Some(Url::parse("http://example.com/rust.png")?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn search_keyword() -> Result<()> {
+        use crate::storage::bookmarks::{
+            insert_bookmark, BookmarkPosition, BookmarkRootGuid, InsertableBookmark, InsertableItem,
+        };
+        use crate::storage::keywords::set_keyword;
+
+        let conn = new_mem_connection();
+        let url = Url::parse("https://www.example.com/search?q=%s")?;
+        insert_bookmark(
+            &conn,
+            &InsertableItem::Bookmark(InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: url.clone(),
+                title: Some("Example search".into()),
+            }),
+        )?;
+        set_keyword(&conn, &url, "ex")?;
+
+        let with_params = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "EX rust docs".into(),
+                limit: 10,
+            },
+        )?;
+        assert_eq!(
+            with_params[0],
+            SearchResult {
+                search_string: "EX rust docs".into(),
+                url: Url::parse("https://www.example.com/search?q=rust+docs")?,
+                title: "Example search".into(),
+                icon_url: None,
+                frecency: with_params[0].frecency,
+                description: None,
+                preview_image_url: None,
+                reasons: vec![MatchReason::Keyword],
+            }
+        );
+
+        let without_params = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "ex".into(),
+                limit: 10,
+            },
+        )?;
+        assert_eq!(
+            without_params[0].url.as_str(),
+            "https://www.example.com/search?q="
+        );
+        assert_eq!(without_params[0].reasons, vec![MatchReason::Keyword]);
+
+        // Keywords must match exactly.
+        let no_match = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "exa rust".into(),
+                limit: 10,
+            },
+        )?;
+        assert!(no_match
+            .iter()
+            .all(|result| !result.reasons.contains(&MatchReason::Keyword)));
+
+        assert_eq!(
+            expand_keyword_url("https://example.com/%s/?q=%S", "a b&c"),
+            "https://example.com/a+b%26c/?q=a b&c"
+        );
+        Ok(())
+    }
+
     #[test]
     fn search_unicode() {
         let conn = new_mem_connection();
@@ -770,6 +1269,65 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn search_interrupt() -> Result<()> {
+        let conn = new_mem_connection();
+        apply_observation(
+            &conn,
+            VisitObservation::new(Url::parse("http://example.com/123")?)
+                .with_title("Example page 123".to_string())
+                .with_visit_type(VisitTransition::Typed)
+                .with_at(Timestamp::now()),
+        )?;
+        let handle = conn.new_search_interrupt_handle();
+
+        // Interrupting when a search isn't running shouldn't affect later
+        // searches, or other operations on the connection.
+        let conn_scope = conn.begin_interrupt_scope();
+        handle.interrupt();
+        let results = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "example page".into(),
+                limit: 10,
+            },
+        )?;
+        assert_eq!(results.len(), 1);
+        assert!(conn_scope.err_if_interrupted().is_ok());
+
+        // Interrupting a running search should stop it before running any
+        // more matchers.
+        let search_scope = conn.begin_search_interrupt_scope();
+        handle.interrupt();
+        let suggestions = Suggestions::with_behavior(
+            "example page",
+            MatchBehavior::Anywhere,
+            SearchBehavior::default(),
+        );
+        match match_with_limit(&conn, &[&suggestions], 10, &search_scope)
+            .expect_err("Should interrupt search")
+            .kind()
+        {
+            ErrorKind::InterruptedError(_) => {}
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+        drop(search_scope);
+        assert!(conn_scope.err_if_interrupted().is_ok());
+
+        // The next search should run normally.
+        let results = search_frecent(
+            &conn,
+            SearchParams {
+                search_string: "example page".into(),
+                limit: 10,
+            },
+        )?;
+        assert_eq!(results.len(), 1);
+
+        Ok(())
+    }
+
     // This panics in tests but not for "real" consumers. In an effort to ensure
     // we are panicing where we think we are, note the 'expected' string.
     // (Not really clear this test offers much value, but seems worth having...)
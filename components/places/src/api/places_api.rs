@@ -2,10 +2,16 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::api::history::HistoryPolicy;
 use crate::bookmark_sync::store::BookmarksStore;
 use crate::db::db::PlacesDb;
 use crate::error::*;
+use crate::events::{ObserverId, PlacesEventDispatcher, PlacesObserver};
+use crate::frecency::{self, FrecencySettings};
+use crate::history_sync::diagnostics::HistorySyncDiagnostics;
 use crate::history_sync::store::HistoryStore;
+use crate::history_sync::HistoryBackfillSettings;
+use crate::inputhistory_sync::store::InputHistoryStore;
 use crate::storage::{delete_meta, get_meta, put_meta};
 use crate::util::normalize_path;
 use lazy_static::lazy_static;
@@ -18,9 +24,9 @@ use std::mem;
 use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
-    Arc, Mutex, Weak,
+    Arc, Mutex, RwLock, Weak,
 };
-use sync15::{sync_multiple, telemetry, MemoryCachedState, SyncResult};
+use sync15::{sync_multiple, telemetry, MemoryCachedState, Store, SyncResult};
 
 // Not clear if this should be here, but this is the "global sync state"
 // which is persisted to disk and reused for all engines.
@@ -83,6 +89,13 @@ pub struct PlacesApi {
     coop_tx_lock: Arc<Mutex<()>>,
     sync_conn_active: AtomicBool,
     id: usize,
+    events: Arc<PlacesEventDispatcher>,
+    frecency_settings: Arc<RwLock<FrecencySettings>>,
+    history_policy: Arc<RwLock<HistoryPolicy>>,
+    history_backfill_settings: RwLock<HistoryBackfillSettings>,
+    history_sync_diagnostics_enabled: AtomicBool,
+    last_history_sync_diagnostics: Mutex<Option<HistorySyncDiagnostics>>,
+    input_history_sync_enabled: AtomicBool,
 }
 impl PlacesApi {
     /// Create a new, or fetch an already open, PlacesApi backed by a file on disk.
@@ -116,7 +129,13 @@ impl PlacesApi {
                     id,
                     coop_tx_lock.clone(),
                 ) {
-                    Ok(connection) => {
+                    Ok(mut connection) => {
+                        let events = Arc::new(PlacesEventDispatcher::default());
+                        connection.set_event_dispatcher(events.clone());
+                        let frecency_settings = Arc::new(RwLock::new(FrecencySettings::default()));
+                        connection.share_frecency_settings(frecency_settings.clone());
+                        let history_policy = Arc::new(RwLock::new(HistoryPolicy::default()));
+                        connection.share_history_policy(history_policy.clone());
                         let new = PlacesApi {
                             db_name: db_name.clone(),
                             write_connection: Mutex::new(Some(connection)),
@@ -124,6 +143,13 @@ impl PlacesApi {
                             sync_conn_active: AtomicBool::new(false),
                             id,
                             coop_tx_lock,
+                            events,
+                            frecency_settings,
+                            history_policy,
+                            history_backfill_settings: RwLock::default(),
+                            history_sync_diagnostics_enabled: AtomicBool::new(false),
+                            last_history_sync_diagnostics: Mutex::new(None),
+                            input_history_sync_enabled: AtomicBool::new(false),
                         };
                         let arc = Arc::new(new);
                         target.insert(db_name, Arc::downgrade(&arc));
@@ -183,12 +209,15 @@ impl PlacesApi {
         if prev_value {
             Err(ErrorKind::ConnectionAlreadyOpen.into())
         } else {
-            let db = PlacesDb::open(
+            let mut db = PlacesDb::open(
                 self.db_name.clone(),
                 ConnectionType::Sync,
                 self.id,
                 self.coop_tx_lock.clone(),
             )?;
+            db.set_event_dispatcher(self.events.clone());
+            db.share_frecency_settings(self.frecency_settings.clone());
+            db.share_history_policy(self.history_policy.clone());
             Ok(SyncConn {
                 db,
                 flag: &self.sync_conn_active,
@@ -196,6 +225,108 @@ impl PlacesApi {
         }
     }
 
+    /// Registers an observer for changes made by this API's write and sync
+    /// connections. See the `events` module for details.
+    pub fn add_observer(&self, observer: Arc<dyn PlacesObserver>) -> ObserverId {
+        self.events.add_observer(observer)
+    }
+
+    /// Unregisters an observer. Returns false if it wasn't registered.
+    pub fn remove_observer(&self, id: ObserverId) -> bool {
+        self.events.remove_observer(id)
+    }
+
+    /// Returns the settings used to calculate frecencies.
+    pub fn frecency_settings(&self) -> FrecencySettings {
+        self.frecency_settings.read().unwrap().clone()
+    }
+
+    /// Changes the settings used to calculate frecencies, for this API's
+    /// write and sync connections. Existing frecencies aren't changed until
+    /// they're next recalculated; use `recalculate_frecencies` to update all
+    /// of them.
+    pub fn set_frecency_settings(&self, settings: FrecencySettings) -> Result<()> {
+        settings.validate()?;
+        *self.frecency_settings.write().unwrap() = settings;
+        Ok(())
+    }
+
+    /// Returns the policy for which URLs are recorded in history.
+    pub fn history_policy(&self) -> HistoryPolicy {
+        self.history_policy.read().unwrap().clone()
+    }
+
+    /// Changes the policy for which URLs are recorded in history, for this
+    /// API's write and sync connections. The policy applies to new visits,
+    /// and to incoming synced history.
+    pub fn set_history_policy(&self, policy: HistoryPolicy) -> Result<()> {
+        let policy = policy.normalize()?;
+        *self.history_policy.write().unwrap() = policy;
+        Ok(())
+    }
+
+    /// Returns the settings that limit how much history is downloaded in
+    /// each sync.
+    pub fn history_backfill_settings(&self) -> HistoryBackfillSettings {
+        *self.history_backfill_settings.read().unwrap()
+    }
+
+    /// Changes the settings that limit how much history is downloaded in
+    /// each sync. See `HistoryBackfillSettings` for how a new device
+    /// downloads history over several syncs.
+    pub fn set_history_backfill_settings(&self, settings: HistoryBackfillSettings) -> Result<()> {
+        settings.validate()?;
+        *self.history_backfill_settings.write().unwrap() = settings;
+        Ok(())
+    }
+
+    /// Turns per-record diagnostics for history syncs on or off. They're off
+    /// by default. Turning them off also forgets the last sync's diagnostics.
+    pub fn set_history_sync_diagnostics_enabled(&self, enabled: bool) {
+        self.history_sync_diagnostics_enabled
+            .store(enabled, Ordering::SeqCst);
+        if !enabled {
+            *self.last_history_sync_diagnostics.lock().unwrap() = None;
+        }
+    }
+
+    /// Returns what the last history sync did with each incoming record, or
+    /// `None` if diagnostics weren't enabled for it.
+    pub fn last_history_sync_diagnostics(&self) -> Option<HistorySyncDiagnostics> {
+        self.last_history_sync_diagnostics.lock().unwrap().clone()
+    }
+
+    fn history_sync_diagnostics_enabled(&self) -> bool {
+        self.history_sync_diagnostics_enabled.load(Ordering::SeqCst)
+    }
+
+    /// Turns syncing adaptive autocomplete input history on or off. It's off
+    /// by default, since the inputs are what the user typed into the URL bar.
+    pub fn set_input_history_sync_enabled(&self, enabled: bool) {
+        self.input_history_sync_enabled
+            .store(enabled, Ordering::SeqCst);
+    }
+
+    fn input_history_sync_enabled(&self) -> bool {
+        self.input_history_sync_enabled.load(Ordering::SeqCst)
+    }
+
+    /// Returns the stores that `sync` should sync. Input history is only
+    /// synced if it's been turned on, and after history, so that we know
+    /// about the pages it refers to.
+    fn stores_to_sync<'a>(
+        &self,
+        history_store: &'a dyn Store,
+        bm_store: &'a dyn Store,
+        input_history_store: &'a dyn Store,
+    ) -> Vec<&'a dyn Store> {
+        let mut stores = vec![history_store, bm_store];
+        if self.input_history_sync_enabled() {
+            stores.push(input_history_store);
+        }
+        stores
+    }
+
     /// Close a connection to the database. If the connection is the write
     /// connection, you can re-fetch it using open_connection.
     pub fn close_connection(&self, connection: PlacesDb) -> Result<()> {
@@ -234,15 +365,19 @@ impl PlacesApi {
             "history",
             move |conn, mem_cached_state, disk_cached_state| {
                 let interruptee = conn.begin_interrupt_scope();
-                let store = HistoryStore::new(&conn, &interruptee);
-                sync_multiple(
+                let store = HistoryStore::new(&conn, &interruptee)
+                    .with_backfill_settings(self.history_backfill_settings())
+                    .with_diagnostics(self.history_sync_diagnostics_enabled());
+                let result = sync_multiple(
                     &[&store],
                     disk_cached_state,
                     mem_cached_state,
                     client_init,
                     key_bundle,
                     &interruptee,
-                )
+                );
+                *self.last_history_sync_diagnostics.lock().unwrap() = store.take_diagnostics();
+                result
             },
         )
     }
@@ -339,13 +474,18 @@ impl PlacesApi {
 
         let interruptee = conn.begin_interrupt_scope();
         let bm_store = BookmarksStore::new(&conn, &interruptee);
-        let history_store = HistoryStore::new(&conn, &interruptee);
+        let history_store = HistoryStore::new(&conn, &interruptee)
+            .with_backfill_settings(self.history_backfill_settings())
+            .with_diagnostics(self.history_sync_diagnostics_enabled());
+        let input_history_store = InputHistoryStore::new(&conn, &interruptee);
         let mut mem_cached_state = sync_state.mem_cached_state.take();
         let mut disk_cached_state = sync_state.disk_cached_state.take();
 
+        let stores = self.stores_to_sync(&history_store, &bm_store, &input_history_store);
+
         // NOTE: After here we must never return Err()!
         let result = sync15::sync_multiple(
-            &[&history_store, &bm_store],
+            &stores,
             &mut disk_cached_state,
             &mut mem_cached_state,
             client_init,
@@ -359,6 +499,7 @@ impl PlacesApi {
         }
         sync_state.mem_cached_state.replace(mem_cached_state);
         sync_state.disk_cached_state.replace(disk_cached_state);
+        *self.last_history_sync_diagnostics.lock().unwrap() = history_store.take_diagnostics();
 
         Ok(result)
     }
@@ -382,6 +523,19 @@ impl PlacesApi {
         Ok(())
     }
 
+    /// Recalculates the frecency of every page with the current settings,
+    /// returning the number of pages recalculated. Like syncing, this runs on
+    /// the sync connection, so it doesn't block the write connection for long,
+    /// and can be interrupted the same way. An interrupted recalculation
+    /// resumes where it left off the next time this is called.
+    pub fn recalculate_frecencies(&self) -> Result<usize> {
+        // Take the lock to prevent syncing while we're doing this.
+        let _guard = self.sync_state.lock().unwrap();
+        let conn = self.open_sync_connection()?;
+        let scope = conn.begin_interrupt_scope();
+        frecency::recalculate_frecencies(&conn, &scope)
+    }
+
     /// Get a new interrupt handle for the sync connection.
     pub fn new_sync_conn_interrupt_handle(&self) -> Result<SqlInterruptHandle> {
         // Probably not necessary to lock here, since this should only get
@@ -455,6 +609,33 @@ mod tests {
     use super::*;
     use sql_support::ConnExt;
 
+    #[test]
+    fn test_input_history_sync_enabled() {
+        let api = new_mem_api();
+        let conn = api
+            .open_sync_connection()
+            .expect("should get a sync connection");
+        let scope = conn.begin_interrupt_scope();
+        let history_store = HistoryStore::new(&conn, &scope);
+        let bm_store = BookmarksStore::new(&conn, &scope);
+        let input_history_store = InputHistoryStore::new(&conn, &scope);
+        let collections = || -> Vec<&'static str> {
+            api.stores_to_sync(&history_store, &bm_store, &input_history_store)
+                .iter()
+                .map(|store| store.collection_name())
+                .collect()
+        };
+
+        // Input history isn't synced unless it's been turned on.
+        assert_eq!(collections(), vec!["history", "bookmarks"]);
+
+        api.set_input_history_sync_enabled(true);
+        assert_eq!(collections(), vec!["history", "bookmarks", "inputhistory"]);
+
+        api.set_input_history_sync_enabled(false);
+        assert_eq!(collections(), vec!["history", "bookmarks"]);
+    }
+
     #[test]
     fn test_multi_writers_fails() {
         let api = new_mem_api();
@@ -491,6 +672,40 @@ mod tests {
         assert_eq!(val, 999);
     }
 
+    #[test]
+    fn test_history_policy() -> Result<()> {
+        use crate::observation::VisitObservation;
+        use crate::storage::history::{apply_observation, url_to_guid};
+        use crate::types::VisitTransition;
+        use url::Url;
+        let api = new_mem_api();
+        api.set_history_policy(HistoryPolicy {
+            blocked_hosts: vec!["Private.Example.com".into()],
+            ..HistoryPolicy::default()
+        })?;
+        assert_eq!(api.history_policy().blocked_hosts, &["private.example.com"]);
+        api.set_history_policy(HistoryPolicy {
+            blocked_schemes: vec!["not a scheme".into()],
+            ..HistoryPolicy::default()
+        })
+        .expect_err("should fail to set an invalid policy");
+
+        let writer = api.open_connection(ConnectionType::ReadWrite)?;
+        for (url, allowed) in &[
+            ("https://example.com/", true),
+            ("https://private.example.com/secret", false),
+            ("data:text/html,hello", false),
+        ] {
+            let url = Url::parse(url)?;
+            apply_observation(
+                &writer,
+                VisitObservation::new(url.clone()).with_visit_type(VisitTransition::Link),
+            )?;
+            assert_eq!(url_to_guid(&writer, &url)?.is_some(), *allowed, "{}", url);
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_reader_before_writer() {
         let api = new_mem_api();
@@ -5,6 +5,7 @@
 mod incoming;
 pub mod record;
 pub mod store;
+mod validation;
 
 #[cfg(test)]
 mod tests;
@@ -8,11 +8,15 @@ use super::record::{
     BookmarkItemRecord, BookmarkRecord, BookmarkRecordId, FolderRecord, QueryRecord,
     SeparatorRecord,
 };
+use super::validation::{
+    should_validate, validate_synced_bookmarks, ValidationSettings, VALIDATION_VERSION,
+};
 use super::{SyncedBookmarkKind, SyncedBookmarkValidity};
 use crate::api::places_api::ConnectionType;
 use crate::db::PlacesDb;
 use crate::error::*;
-use crate::frecency::{calculate_frecency, DEFAULT_FRECENCY_SETTINGS};
+use crate::events::PlacesEvent;
+use crate::frecency::calculate_frecency;
 use crate::storage::{bookmarks::BookmarkRootGuid, delete_meta, get_meta, put_meta};
 use crate::types::{BookmarkType, SyncGuid, SyncStatus, Timestamp};
 use dogear::{
@@ -58,12 +62,22 @@ where
 pub struct BookmarksStore<'a> {
     pub db: &'a PlacesDb,
     interruptee: &'a SqlInterruptScope,
+    validation_settings: ValidationSettings,
 }
 
 impl<'a> BookmarksStore<'a> {
     pub fn new(db: &'a PlacesDb, interruptee: &'a SqlInterruptScope) -> Self {
         assert_eq!(db.conn_type(), ConnectionType::Sync);
-        Self { db, interruptee }
+        Self {
+            db,
+            interruptee,
+            validation_settings: ValidationSettings::default(),
+        }
+    }
+
+    pub(crate) fn with_validation_settings(mut self, settings: ValidationSettings) -> Self {
+        self.validation_settings = settings;
+        self
     }
 
     fn stage_incoming(
@@ -86,6 +100,16 @@ impl<'a> BookmarksStore<'a> {
         Ok(timestamp)
     }
 
+    /// Validates the merged tree, and records the problems in `validation`.
+    /// Validation is only for telemetry, so failing to validate doesn't fail
+    /// the sync.
+    fn validate(&self, validation: &mut telemetry::Validation) {
+        match validate_synced_bookmarks(self.db) {
+            Ok(problems) => problems.record(validation),
+            Err(e) => log::warn!("Failed to validate bookmarks: {}", e),
+        }
+    }
+
     fn has_changes(&self) -> Result<bool> {
         // In the first subquery, we check incoming items with needsMerge = true
         // except the tombstones who don't correspond to any local bookmark because
@@ -244,11 +268,11 @@ impl<'a> BookmarksStore<'a> {
                                        kind, url, keyword, position)
              SELECT s.id, s.guid, s.syncChangeCounter, s.parentGuid,
                     s.parentTitle, s.dateAdded, s.title, s.placeId,
-                    {kind}, h.url, v.keyword, s.position
+                    {kind}, h.url, k.keyword, s.position
              FROM localItems s
              JOIN mergedTree r ON r.mergedGuid = s.guid
-             LEFT JOIN moz_bookmarks_synced v ON v.guid = r.remoteGuid
              LEFT JOIN moz_places h ON h.id = s.placeId
+             LEFT JOIN moz_keywords k ON k.place_id = s.placeId
              LEFT JOIN idsToWeaklyUpload w ON w.id = s.id
              WHERE s.guid <> '{root_guid}' AND
                    (s.syncChangeCounter > 0 OR w.id NOT NULL)",
@@ -456,6 +480,7 @@ impl<'a> BookmarksStore<'a> {
     pub(crate) fn update_frecencies(&self) -> Result<()> {
         let mut tx = self.db.begin_transaction()?;
 
+        let settings = self.db.frecency_settings();
         let mut frecencies = Vec::with_capacity(MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK);
         loop {
             let sql = format!(
@@ -471,12 +496,7 @@ impl<'a> BookmarksStore<'a> {
                 // Frecency recalculation runs several statements, so check to
                 // make sure we aren't interrupted before each calculation.
                 self.interruptee.err_if_interrupted()?;
-                let frecency = calculate_frecency(
-                    &self.db,
-                    &DEFAULT_FRECENCY_SETTINGS,
-                    place_id,
-                    Some(false),
-                )?;
+                let frecency = calculate_frecency(&self.db, &settings, place_id, Some(false))?;
                 frecencies.push((place_id, frecency));
             }
             if frecencies.is_empty() {
@@ -537,6 +557,8 @@ impl<'a> BookmarksStore<'a> {
 
              DELETE FROM moz_bookmarks_deleted;
 
+             DELETE FROM moz_bookmarks_deleted_journal;
+
              UPDATE moz_bookmarks
              SET syncChangeCounter = 1,
                  syncStatus = {}",
@@ -642,7 +664,9 @@ impl<'a> Store for BookmarksStore<'a> {
              WHERE guid IN {roots};
 
              DELETE FROM moz_bookmarks
-             WHERE guid NOT IN {roots};",
+             WHERE guid NOT IN {roots};
+
+             DELETE FROM moz_bookmarks_deleted_journal;",
             roots = RootsFragment(&[
                 BookmarkRootGuid::Root,
                 BookmarkRootGuid::Menu,
@@ -739,17 +763,39 @@ impl<'a> Merger<'a> {
 
     pub(crate) fn merge(&mut self) -> Result<()> {
         use dogear::Store;
+        // We only validate when syncing, since other callers don't report
+        // telemetry.
+        let validate = self.telem.is_some()
+            && should_validate(self.store.db, &self.store.validation_settings)?;
         if !self.store.has_changes()? {
+            // There's nothing to merge, but the trees might still have
+            // problems.
+            if let (Some(telem), true) = (&mut self.telem, validate) {
+                let mut validation = telemetry::Validation::with_version(VALIDATION_VERSION);
+                self.store.validate(&mut validation);
+                telem.validation(validation);
+            }
             return Ok(());
         }
         // Merge and stage outgoing items via dogear.
-        let driver = Driver::default();
+        let driver = if validate {
+            Driver {
+                validation: RefCell::new(telemetry::Validation::with_version(VALIDATION_VERSION)),
+            }
+        } else {
+            Driver::default()
+        };
         let result = self.merge_with_driver(&driver, &MergeInterruptee(self.store.interruptee));
         log::debug!("merge completed");
 
-        // Record telemetry in all cases, even if the merge fails.
+        // Record telemetry in all cases, even if the merge fails. We only
+        // validate the merged tree if the merge succeeded, though.
+        let mut validation = driver.validation.into_inner();
+        if validate && result.is_ok() {
+            self.store.validate(&mut validation);
+        }
         if let Some(ref mut telem) = self.telem {
-            telem.validation(driver.validation.into_inner());
+            telem.validation(validation);
         }
         result
     }
@@ -1033,6 +1079,9 @@ impl<'a> dogear::Store<Error> for Merger<'a> {
             "DELETE FROM mergedTree;
              DELETE FROM idsToWeaklyUpload;",
         )?;
+        self.store.db.note_event(PlacesEvent::SyncApplied {
+            collection: "bookmarks".into(),
+        });
         if let Some(tx) = tx {
             tx.commit()?;
         }
@@ -1173,11 +1222,12 @@ mod tests {
     use super::*;
     use crate::api::places_api::{test::new_mem_api, ConnectionType, PlacesApi};
     use crate::bookmark_sync::store::BookmarksStore;
+    use crate::bookmark_sync::validation::ValidationProblems;
     use crate::db::PlacesDb;
     use crate::storage::{
         bookmarks::{get_raw_bookmark, update_bookmark, UpdatableBookmark, USER_CONTENT_ROOTS},
         history::frecency_stale_at,
-        tags,
+        keywords, tags,
     };
     use crate::tests::{
         assert_json_tree as assert_local_json_tree, insert_json_tree as insert_local_json_tree,
@@ -1185,6 +1235,7 @@ mod tests {
     use dogear::{Store as DogearStore, Validity};
     use pretty_assertions::assert_eq;
     use serde_json::{json, Value};
+    use std::time::Duration;
     use url::Url;
 
     use sync15::{random_guid, CollSyncIds, Payload};
@@ -1810,6 +1861,29 @@ mod tests {
         assert_eq!(outgoing.changes[0].id, "bookmarkAAAA");
         assert_eq!(outgoing.changes[0].data["keyword"], "a");
 
+        // The incoming keyword should have been applied locally.
+        let url_for_a = Url::parse("http://example.com/a").expect("Should parse URL for A");
+        assert_eq!(
+            keywords::get_keyword_for_url(&writer, &url_for_a)?,
+            Some("a".to_string())
+        );
+
+        store
+            .sync_finished(ServerTimestamp(1000), vec!["bookmarkAAAA".into()])
+            .expect("Should push synced changes back to the store");
+
+        // Changing the keyword locally should flag the bookmark for upload.
+        keywords::set_keyword(&writer, &url_for_a, "b")?;
+        let outgoing = store
+            .apply_incoming(
+                IncomingChangeset::new(store.collection_name().to_string(), ServerTimestamp(2000)),
+                &mut telemetry::Engine::new("bookmarks"),
+            )
+            .expect("Should fetch outgoing records after changing keyword");
+        assert_eq!(outgoing.changes.len(), 1);
+        assert_eq!(outgoing.changes[0].id, "bookmarkAAAA");
+        assert_eq!(outgoing.changes[0].data["keyword"], "b");
+
         Ok(())
     }
 
@@ -2448,4 +2522,131 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_validation() -> Result<()> {
+        let api = new_mem_api();
+        let conn = api.open_sync_connection()?;
+
+        apply_incoming(
+            &conn,
+            ServerTimestamp(1000),
+            json!([{
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "title": "Unfiled",
+                "children": ["bookmarkAAAA", "folderBBBBBB"],
+            }, {
+                "id": "bookmarkAAAA",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "title": "A",
+                "bmkUri": "http://example.com/a",
+            }, {
+                "id": "folderBBBBBB",
+                "type": "folder",
+                "parentid": "unfiled",
+                "title": "B",
+                "children": ["bookmarkCCCC"],
+            }, {
+                "id": "bookmarkCCCC",
+                "type": "bookmark",
+                "parentid": "folderBBBBBB",
+                "title": "C",
+                "bmkUri": "http://example.com/c",
+            }]),
+        );
+        assert_eq!(
+            validate_synced_bookmarks(&conn)?,
+            ValidationProblems::default()
+        );
+
+        // Swap A and B in the synced tree, and make sure the next sync
+        // reports it.
+        conn.execute_batch(
+            "UPDATE moz_bookmarks_synced_structure SET
+               position = 1 - position
+             WHERE parentGuid = 'unfiled'",
+        )?;
+        let sync_with_settings = |settings: ValidationSettings| -> Value {
+            let interrupt_scope = conn.begin_interrupt_scope();
+            let store =
+                BookmarksStore::new(&conn, &interrupt_scope).with_validation_settings(settings);
+            let mut telem = telemetry::Engine::new("bookmarks");
+            store
+                .apply_incoming(
+                    IncomingChangeset::new(
+                        store.collection_name().to_string(),
+                        ServerTimestamp(2000),
+                    ),
+                    &mut telem,
+                )
+                .expect("Should apply incoming and stage outgoing records");
+            serde_json::to_value(&telem).unwrap()["validation"].clone()
+        };
+        let always = ValidationSettings {
+            interval: Duration::from_secs(0),
+            percentage_chance: 100,
+            max_records: 1000,
+        };
+        assert_eq!(
+            sync_with_settings(always),
+            json!({
+                "version": VALIDATION_VERSION,
+                "problems": [{ "name": "structuralDifferences", "count": 1 }],
+            })
+        );
+        // We don't validate again until the interval has passed, or if the
+        // synced tree is too big, or if we're unlucky.
+        assert_eq!(
+            sync_with_settings(ValidationSettings {
+                interval: Duration::from_secs(60 * 60),
+                ..always
+            }),
+            Value::Null
+        );
+        assert_eq!(
+            sync_with_settings(ValidationSettings {
+                max_records: 1,
+                ..always
+            }),
+            Value::Null
+        );
+        assert_eq!(
+            sync_with_settings(ValidationSettings {
+                percentage_chance: 0,
+                ..always
+            }),
+            Value::Null
+        );
+
+        conn.execute_batch(&format!(
+            "DELETE FROM moz_bookmarks_synced WHERE guid = 'bookmarkAAAA';
+             UPDATE moz_bookmarks_synced SET
+               parentGuid = 'unfiled'
+             WHERE guid = 'bookmarkCCCC';
+             INSERT INTO moz_bookmarks_synced(guid, parentGuid, kind)
+             VALUES('bookmarkDDDD', 'folderEEEEEE', {});",
+            SyncedBookmarkKind::Bookmark as u8
+        ))?;
+        assert_eq!(
+            validate_synced_bookmarks(&conn)?,
+            ValidationProblems {
+                // A is still in unfiled's children, but was deleted from the
+                // synced tree, even though we have it locally.
+                missing_children: 1,
+                server_missing: 1,
+                // C is in B's children, but its parent is unfiled.
+                parent_child_disagreements: 1,
+                // D's parent doesn't exist, and we don't have D locally.
+                orphans: 1,
+                client_missing: 1,
+                // Unfiled's children are in a different order.
+                structural_differences: 1,
+            }
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,291 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Validates the synced bookmark tree in `moz_bookmarks_synced`, and compares
+//! it with the local tree, for telemetry. After merging, the synced tree
+//! should match what's on the server, and, except for items that we're about
+//! to upload, the local tree.
+//!
+//! Like Desktop, we only validate some syncs, since validating scans both
+//! trees. The problems are reported along with the problems Dogear finds in
+//! the remote tree when it merges. Problems that Dogear also reports are
+//! prefixed with `synced`, so that they're counted separately; the others
+//! match Desktop's bookmark validator.
+//!
+//! The synced tree can't have duplicate children, since a folder's children
+//! are keyed by GUID in `moz_bookmarks_synced_structure`, so we don't check
+//! for them.
+
+use super::SyncedBookmarkKind;
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::bookmarks::BookmarkRootGuid;
+use crate::storage::{get_meta, put_meta};
+use crate::types::{BookmarkType, SyncGuid, SyncStatus, Timestamp};
+use sql_support::ConnExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use sync15::telemetry;
+
+/// The version of our validation telemetry. Bump this when adding or changing
+/// problems, so that we can tell the results apart.
+pub const VALIDATION_VERSION: u32 = 1;
+
+const LAST_VALIDATION_META_KEY: &str = "bookmarks_last_validation";
+
+/// How often we validate. The defaults match Desktop's
+/// `services.sync.engine.bookmarks.validation.*` preferences.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValidationSettings {
+    /// The least time between validations.
+    pub interval: Duration,
+    /// The chance, as a percentage, that we validate on a sync once the
+    /// interval has passed.
+    pub percentage_chance: u32,
+    /// We don't validate synced trees with more items than this.
+    pub max_records: usize,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(24 * 60 * 60),
+            percentage_chance: 10,
+            max_records: 1000,
+        }
+    }
+}
+
+/// Decides whether to validate on this sync, and, if so, notes the time, so
+/// that we wait at least `settings.interval` before validating again.
+pub fn should_validate(db: &PlacesDb, settings: &ValidationSettings) -> Result<bool> {
+    let now = Timestamp::now();
+    if let Some(last_validation) = get_meta::<Timestamp>(db, LAST_VALIDATION_META_KEY)? {
+        let since = now.duration_since(last_validation).unwrap_or_default();
+        if since < settings.interval {
+            return Ok(false);
+        }
+    }
+    let lucky = db.query_one::<bool>(&format!(
+        "SELECT abs(random() % 100) < {}",
+        settings.percentage_chance
+    ))?;
+    if !lucky {
+        return Ok(false);
+    }
+    let records = count(
+        db,
+        "SELECT COUNT(*) FROM moz_bookmarks_synced WHERE NOT isDeleted",
+    )?;
+    if records > settings.max_records {
+        log::debug!("Not validating {} synced bookmarks", records);
+        return Ok(false);
+    }
+    put_meta(db, LAST_VALIDATION_META_KEY, &now)?;
+    Ok(true)
+}
+
+/// The number of each kind of problem we found.
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationProblems {
+    /// Children in a synced folder's `children` that we don't have a record
+    /// for, or that are deleted.
+    pub missing_children: usize,
+    /// Synced items that aren't in their parent's `children`, or that are in
+    /// the `children` of a folder that isn't their parent.
+    pub parent_child_disagreements: usize,
+    /// Synced items whose parent doesn't exist, is deleted, or isn't a folder.
+    pub orphans: usize,
+    /// Local folders whose children are different from the synced folder's.
+    pub structural_differences: usize,
+    /// Synced items that don't exist locally.
+    pub client_missing: usize,
+    /// Local items that have been synced, but don't exist in the synced tree.
+    pub server_missing: usize,
+}
+
+impl ValidationProblems {
+    /// Records the problems in `validation`, skipping any we didn't find.
+    pub fn record(&self, validation: &mut telemetry::Validation) {
+        validation
+            .problem("syncedMissingChildren", self.missing_children)
+            .problem(
+                "syncedParentChildDisagreements",
+                self.parent_child_disagreements,
+            )
+            .problem("syncedOrphans", self.orphans)
+            .problem("structuralDifferences", self.structural_differences)
+            .problem("clientMissing", self.client_missing)
+            .problem("serverMissing", self.server_missing);
+    }
+}
+
+/// Validates the synced tree, and compares it with the local tree. This
+/// should only be called after merging, or between syncs: before merging,
+/// the trees are expected to differ.
+pub fn validate_synced_bookmarks(db: &PlacesDb) -> Result<ValidationProblems> {
+    let root = BookmarkRootGuid::Root.as_guid();
+    let root = root.as_ref();
+    let folder_kind = SyncedBookmarkKind::Folder as u8;
+
+    let missing_children = count(
+        db,
+        &format!(
+            "SELECT COUNT(*) FROM moz_bookmarks_synced_structure s
+             WHERE s.guid <> '{root}' AND
+                   NOT EXISTS(SELECT 1 FROM moz_bookmarks_synced v
+                              WHERE v.guid = s.guid AND
+                                    NOT v.isDeleted)",
+            root = root
+        ),
+    )?;
+
+    let parent_child_disagreements = count(
+        db,
+        &format!(
+            "SELECT COUNT(*) FROM moz_bookmarks_synced v
+             WHERE NOT v.isDeleted AND
+                   v.guid <> '{root}' AND
+                   (EXISTS(SELECT 1 FROM moz_bookmarks_synced_structure s
+                           WHERE s.guid = v.guid AND
+                                 s.parentGuid IS NOT v.parentGuid) OR
+                    (EXISTS(SELECT 1 FROM moz_bookmarks_synced p
+                            WHERE p.guid = v.parentGuid AND
+                                  NOT p.isDeleted AND
+                                  p.kind = {folder_kind}) AND
+                     NOT EXISTS(SELECT 1 FROM moz_bookmarks_synced_structure s
+                                WHERE s.guid = v.guid AND
+                                      s.parentGuid = v.parentGuid)))",
+            root = root,
+            folder_kind = folder_kind
+        ),
+    )?;
+
+    let orphans = count(
+        db,
+        &format!(
+            "SELECT COUNT(*) FROM moz_bookmarks_synced v
+             WHERE NOT v.isDeleted AND
+                   v.guid <> '{root}' AND
+                   NOT EXISTS(SELECT 1 FROM moz_bookmarks_synced p
+                              WHERE p.guid = v.parentGuid AND
+                                    NOT p.isDeleted AND
+                                    p.kind = {folder_kind})",
+            root = root,
+            folder_kind = folder_kind
+        ),
+    )?;
+
+    // Items with unmerged changes on either side are expected to be missing
+    // from, or different in, the other tree.
+    let client_missing = count(
+        db,
+        &format!(
+            "SELECT COUNT(*) FROM moz_bookmarks_synced v
+             WHERE NOT v.isDeleted AND
+                   NOT v.needsMerge AND
+                   v.guid <> '{root}' AND
+                   NOT EXISTS(SELECT 1 FROM moz_bookmarks b
+                              WHERE b.guid = v.guid) AND
+                   NOT EXISTS(SELECT 1 FROM moz_bookmarks_deleted d
+                              WHERE d.guid = v.guid)",
+            root = root
+        ),
+    )?;
+
+    let server_missing = count(
+        db,
+        &format!(
+            "SELECT COUNT(*) FROM moz_bookmarks b
+             WHERE b.guid <> '{root}' AND
+                   b.syncStatus = {sync_status} AND
+                   b.syncChangeCounter = 0 AND
+                   NOT EXISTS(SELECT 1 FROM moz_bookmarks_synced v
+                              WHERE v.guid = b.guid AND
+                                    NOT v.isDeleted)",
+            root = root,
+            sync_status = SyncStatus::Normal as u8
+        ),
+    )?;
+
+    let structural_differences = count_structural_differences(db)?;
+
+    Ok(ValidationProblems {
+        missing_children,
+        parent_child_disagreements,
+        orphans,
+        structural_differences,
+        client_missing,
+        server_missing,
+    })
+}
+
+fn count(db: &PlacesDb, sql: &str) -> Result<usize> {
+    Ok(db.query_one::<i64>(sql)? as usize)
+}
+
+/// Counts the local folders that haven't changed since the last sync, but
+/// whose children are different from the synced folder's. We don't compare
+/// the roots, since the user content roots are always children of the root.
+fn count_structural_differences(db: &PlacesDb) -> Result<usize> {
+    let root = BookmarkRootGuid::Root.as_guid();
+    let folders: Vec<SyncGuid> = db.query_rows_into(
+        &format!(
+            "SELECT b.guid FROM moz_bookmarks b
+             JOIN moz_bookmarks_synced v ON v.guid = b.guid
+             WHERE b.type = {folder_type} AND
+                   b.guid <> '{root}' AND
+                   b.syncStatus = {sync_status} AND
+                   b.syncChangeCounter = 0 AND
+                   NOT v.isDeleted AND
+                   NOT v.needsMerge",
+            folder_type = BookmarkType::Folder as u8,
+            root = root.as_ref(),
+            sync_status = SyncStatus::Normal as u8
+        ),
+        &[],
+        |row| row.get::<_, SyncGuid>(0),
+    )?;
+    if folders.is_empty() {
+        return Ok(0);
+    }
+    let local_children = fetch_children(
+        db,
+        "SELECT p.guid, b.guid FROM moz_bookmarks b
+         JOIN moz_bookmarks p ON p.id = b.parent
+         ORDER BY b.parent, b.position",
+    )?;
+    let synced_children = fetch_children(
+        db,
+        &format!(
+            "SELECT parentGuid, guid FROM moz_bookmarks_synced_structure
+             WHERE guid <> '{root}'
+             ORDER BY parentGuid, position",
+            root = root.as_ref()
+        ),
+    )?;
+    let no_children = Vec::new();
+    Ok(folders
+        .iter()
+        .filter(|guid| {
+            let local = local_children.get(*guid).unwrap_or(&no_children);
+            let synced = synced_children.get(*guid).unwrap_or(&no_children);
+            local != synced
+        })
+        .count())
+}
+
+/// Returns the children of each folder, in order, from a query that selects
+/// parent and child GUIDs.
+fn fetch_children(db: &PlacesDb, sql: &str) -> Result<HashMap<SyncGuid, Vec<SyncGuid>>> {
+    let rows: Vec<(SyncGuid, SyncGuid)> =
+        db.query_rows_into(sql, &[], |row| -> rusqlite::Result<_> {
+            Ok((row.get::<_, SyncGuid>(0)?, row.get::<_, SyncGuid>(1)?))
+        })?;
+    let mut children: HashMap<SyncGuid, Vec<SyncGuid>> = HashMap::new();
+    for (parent_guid, guid) in rows {
+        children.entry(parent_guid).or_default().push(guid);
+    }
+    Ok(children)
+}
@@ -3,14 +3,22 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use super::schema;
+use crate::api::history::HistoryPolicy;
 use crate::api::places_api::ConnectionType;
 use crate::error::*;
+use crate::events::{PendingEvents, PlacesEvent, PlacesEventDispatcher};
+use crate::frecency::FrecencySettings;
+use interrupt::Interruptee;
 use rusqlite::Connection;
 use sql_support::{ConnExt, SqlInterruptHandle, SqlInterruptScope};
 use std::ops::Deref;
 use std::path::Path;
+use url::Url;
 
-use std::sync::{atomic::AtomicUsize, Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, RwLock,
+};
 
 pub const MAX_VARIABLE_NUMBER: usize = 999;
 
@@ -19,9 +27,13 @@ pub struct PlacesDb {
     pub db: Connection,
     conn_type: ConnectionType,
     interrupt_counter: Arc<AtomicUsize>,
+    search_state: Arc<SearchState>,
     api_id: usize,
     in_memory: bool,
     pub(super) coop_tx_lock: Arc<Mutex<()>>,
+    events: PendingEvents,
+    frecency_settings: Arc<RwLock<FrecencySettings>>,
+    history_policy: Arc<RwLock<HistoryPolicy>>,
 }
 
 impl PlacesDb {
@@ -77,8 +89,12 @@ impl PlacesDb {
             // The API sets this explicitly.
             api_id,
             interrupt_counter: Arc::new(AtomicUsize::new(0)),
+            search_state: Arc::new(SearchState::default()),
             coop_tx_lock,
             in_memory,
+            events: PendingEvents::default(),
+            frecency_settings: Arc::new(RwLock::new(FrecencySettings::default())),
+            history_policy: Arc::new(RwLock::new(HistoryPolicy::default())),
         };
         match res.conn_type() {
             // For read-only connections, we can avoid opening a transaction,
@@ -136,6 +152,27 @@ impl PlacesDb {
         SqlInterruptScope::new(self.interrupt_counter.clone())
     }
 
+    /// Returns a handle that interrupts autocomplete searches running on
+    /// this connection, without affecting any other operations.
+    pub fn new_search_interrupt_handle(&self) -> SearchInterruptHandle {
+        SearchInterruptHandle {
+            db_handle: self.db.get_interrupt_handle(),
+            state: self.search_state.clone(),
+        }
+    }
+
+    /// Marks the start of an autocomplete search. The search is considered
+    /// running until the returned scope is dropped.
+    pub fn begin_search_interrupt_scope(&self) -> SearchInterruptScope {
+        let mut in_progress = self.search_state.in_progress.lock().unwrap();
+        debug_assert!(!*in_progress, "Searches shouldn't be nested");
+        *in_progress = true;
+        SearchInterruptScope {
+            start_value: self.search_state.counter.load(Ordering::SeqCst),
+            state: self.search_state.clone(),
+        }
+    }
+
     #[inline]
     pub fn conn_type(&self) -> ConnectionType {
         self.conn_type
@@ -150,6 +187,116 @@ impl PlacesDb {
     pub fn is_in_memory(&self) -> bool {
         self.in_memory
     }
+
+    /// Delivers this connection's events to the observers registered with
+    /// `dispatcher`. The API sets this for its write and sync connections.
+    pub(crate) fn set_event_dispatcher(&mut self, dispatcher: Arc<PlacesEventDispatcher>) {
+        self.events.set_dispatcher(dispatcher);
+    }
+
+    /// Returns true if there are any observers for this connection's events.
+    #[inline]
+    pub(crate) fn has_event_observers(&self) -> bool {
+        self.events.is_observed()
+    }
+
+    /// Notes a change made on this connection. If we're in a transaction, the
+    /// event is delivered when it commits; otherwise, it's delivered now.
+    pub(crate) fn note_event(&self, event: PlacesEvent) {
+        self.events.push(event);
+        if self.db.is_autocommit() {
+            self.flush_events();
+        }
+    }
+
+    pub(crate) fn flush_events(&self) {
+        self.events.flush(self.conn_type == ConnectionType::Sync);
+    }
+
+    pub(crate) fn discard_events(&self) {
+        self.events.discard();
+    }
+
+    /// Shares the API's frecency settings with this connection, so that
+    /// changing them affects all of its connections.
+    pub(crate) fn share_frecency_settings(&mut self, settings: Arc<RwLock<FrecencySettings>>) {
+        self.frecency_settings = settings;
+    }
+
+    /// Returns the settings used to calculate frecencies on this connection.
+    pub fn frecency_settings(&self) -> FrecencySettings {
+        self.frecency_settings.read().unwrap().clone()
+    }
+
+    /// Shares the API's history policy with this connection, like
+    /// `share_frecency_settings`.
+    pub(crate) fn share_history_policy(&mut self, policy: Arc<RwLock<HistoryPolicy>>) {
+        self.history_policy = policy;
+    }
+
+    /// Returns true if this connection's history policy allows recording
+    /// visits to `url`.
+    pub fn can_add_url(&self, url: &Url) -> bool {
+        self.history_policy.read().unwrap().can_add_url(url)
+    }
+}
+
+/// Tracks interrupt requests for autocomplete searches on a connection.
+#[derive(Debug, Default)]
+struct SearchState {
+    // Incremented for every interrupt request, like `interrupt_counter`.
+    counter: AtomicUsize,
+    // Whether a search is currently running. We hold this lock while calling
+    // `sqlite3_interrupt()`, so that a search can't finish (and another
+    // statement start) in the meantime.
+    in_progress: Mutex<bool>,
+}
+
+/// A Sync+Send type that can be used to cancel a running autocomplete search
+/// from another thread. Unlike `SqlInterruptHandle`, this only aborts
+/// statements if a search is in progress, so it's safe to call while the
+/// connection is being used for something else.
+pub struct SearchInterruptHandle {
+    db_handle: rusqlite::InterruptHandle,
+    state: Arc<SearchState>,
+}
+
+impl SearchInterruptHandle {
+    pub fn interrupt(&self) {
+        let in_progress = self.state.in_progress.lock().unwrap();
+        self.state.counter.fetch_add(1, Ordering::SeqCst);
+        if *in_progress {
+            self.db_handle.interrupt();
+        }
+    }
+}
+
+/// Like `SqlInterruptScope`, but for autocomplete searches. Dropping the
+/// scope marks the search as finished.
+#[derive(Debug)]
+pub struct SearchInterruptScope {
+    start_value: usize,
+    state: Arc<SearchState>,
+}
+
+impl SearchInterruptScope {
+    #[inline]
+    pub fn err_if_interrupted(&self) -> std::result::Result<(), interrupt::Interrupted> {
+        <Self as Interruptee>::err_if_interrupted(self)
+    }
+}
+
+impl Interruptee for SearchInterruptScope {
+    #[inline]
+    fn was_interrupted(&self) -> bool {
+        self.state.counter.load(Ordering::SeqCst) != self.start_value
+    }
+}
+
+impl Drop for SearchInterruptScope {
+    fn drop(&mut self) {
+        *self.state.in_progress.lock().unwrap() = false;
+    }
 }
 
 impl Drop for PlacesDb {
@@ -16,7 +16,7 @@ use crate::types::SyncStatus;
 use rusqlite::NO_PARAMS;
 use sql_support::ConnExt;
 
-const VERSION: i64 = 9;
+const VERSION: i64 = 15;
 
 // Shared schema and temp tables for the read-write and Sync connections.
 const CREATE_SHARED_SCHEMA_SQL: &str = include_str!("../../sql/create_shared_schema.sql");
@@ -219,6 +219,58 @@ fn upgrade(db: &PlacesDb, from: i64) -> Result<()> {
         ],
         || Ok(()),
     )?;
+    migration(
+        db,
+        9,
+        10,
+        &[
+            CREATE_SHARED_SCHEMA_SQL,
+            // Keywords were previously only stored in the synced bookmarks
+            // table, so seed the new local table with them. The foreign count
+            // triggers don't exist yet, so we need to bump the counts
+            // ourselves.
+            "INSERT OR IGNORE INTO moz_keywords(keyword, place_id)
+             SELECT lower(trim(v.keyword)), v.placeId
+             FROM moz_bookmarks_synced v
+             JOIN moz_bookmarks b ON b.fk = v.placeId
+             WHERE v.keyword NOT NULL AND
+                   trim(v.keyword) <> '' AND
+                   NOT v.isDeleted",
+            "UPDATE moz_places SET
+                 foreign_count = foreign_count + 1
+             WHERE id IN (SELECT place_id FROM moz_keywords)",
+        ],
+        || Ok(()),
+    )?;
+    migration(db, 10, 11, &[CREATE_SHARED_SCHEMA_SQL], || Ok(()))?; // annotations.
+    migration(db, 11, 12, &[CREATE_SHARED_SCHEMA_SQL], || Ok(()))?; // favicons.
+    migration(
+        db,
+        12,
+        13,
+        &[
+            // Top sites.
+            "ALTER TABLE moz_places ADD COLUMN is_error_page INTEGER NOT NULL DEFAULT 0",
+            "ALTER TABLE moz_places ADD COLUMN is_redirect_source INTEGER NOT NULL DEFAULT 0",
+            CREATE_SHARED_SCHEMA_SQL,
+        ],
+        || Ok(()),
+    )?;
+    // Deleted bookmarks journal.
+    migration(db, 13, 14, &[CREATE_SHARED_SCHEMA_SQL], || Ok(()))?;
+    migration(
+        db,
+        14,
+        15,
+        &[
+            // Input history syncing.
+            "ALTER TABLE moz_inputhistory ADD COLUMN last_used INTEGER NOT NULL DEFAULT 0",
+            "ALTER TABLE moz_inputhistory
+             ADD COLUMN sync_change_counter INTEGER NOT NULL DEFAULT 1",
+            CREATE_SHARED_SCHEMA_SQL,
+        ],
+        || Ok(()),
+    )?;
     // Add more migrations here...
 
     if get_current_schema_version(db)? == VERSION {
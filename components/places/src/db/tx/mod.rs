@@ -20,7 +20,10 @@ macro_rules! debug_complaint {
 }
 /// High level transaction type which "does the right thing" for you.
 /// Construct one with `PlacesDb::begin_transaction()`.
-pub struct PlacesTransaction<'conn>(PlacesTransactionRepr<'conn>);
+///
+/// The representation is only `None` once the transaction has been
+/// committed or rolled back.
+pub struct PlacesTransaction<'conn>(Option<PlacesTransactionRepr<'conn>>, &'conn super::PlacesDb);
 
 /// Only separated from PlacesTransaction so that the internals of the former
 /// are private (so that it can't be `matched` on, for example)
@@ -40,8 +43,10 @@ impl<'conn> PlacesTransaction<'conn> {
     ///   warning and does nothing.
     #[inline]
     pub fn maybe_commit(&mut self) -> Result<()> {
-        if let PlacesTransactionRepr::ChunkedWrite(tx) = &mut self.0 {
-            tx.maybe_commit()?;
+        if let Some(PlacesTransactionRepr::ChunkedWrite(tx)) = &mut self.0 {
+            if tx.maybe_commit()? {
+                self.1.flush_events();
+            }
         } else {
             debug_complaint!("maybe_commit called on a non-chunked transaction");
         }
@@ -49,24 +54,26 @@ impl<'conn> PlacesTransaction<'conn> {
     }
 
     /// Consumes and commits a PlacesTransaction transaction.
-    pub fn commit(self) -> Result<()> {
-        match self.0 {
+    pub fn commit(mut self) -> Result<()> {
+        match self.0.take().expect("Transaction should still be open") {
             PlacesTransactionRepr::ChunkedWrite(t) => t.commit()?,
             PlacesTransactionRepr::UnchunkedWrite(t) => t.commit()?,
             PlacesTransactionRepr::ReadOnly(t) => t.commit()?,
         };
+        self.1.flush_events();
         Ok(())
     }
 
     /// Consumes and attempst to roll back a PlacesTransaction. Note that if
     /// maybe_commit has been called, this may only roll back as far as that
     /// call.
-    pub fn rollback(self) -> Result<()> {
-        match self.0 {
+    pub fn rollback(mut self) -> Result<()> {
+        match self.0.take().expect("Transaction should still be open") {
             PlacesTransactionRepr::ChunkedWrite(t) => t.rollback()?,
             PlacesTransactionRepr::UnchunkedWrite(t) => t.rollback()?,
             PlacesTransactionRepr::ReadOnly(t) => t.rollback()?,
         };
+        self.1.discard_events();
         Ok(())
     }
 }
@@ -78,7 +85,7 @@ impl super::PlacesDb {
     /// - for ReadWrite connections, begins a normal coop transaction
     /// - for ReadOnly connections, begins an unchecked transaction.
     pub fn begin_transaction(&self) -> Result<PlacesTransaction<'_>> {
-        Ok(PlacesTransaction(match self.conn_type() {
+        let repr = match self.conn_type() {
             ConnectionType::Sync => {
                 PlacesTransactionRepr::ChunkedWrite(self.chunked_coop_trransaction()?)
             }
@@ -89,7 +96,17 @@ impl super::PlacesDb {
                 // Use an unchecked transaction with no locking.
                 PlacesTransactionRepr::ReadOnly(self.unchecked_transaction()?)
             }
-        }))
+        };
+        Ok(PlacesTransaction(Some(repr), self))
+    }
+}
+
+impl<'conn> Drop for PlacesTransaction<'conn> {
+    fn drop(&mut self) {
+        // Committing flushes the transaction's events, so any that are left
+        // are from a transaction that was rolled back, or failed to commit.
+        // Either way, they never happened.
+        self.1.discard_events();
     }
 }
 
@@ -97,7 +114,7 @@ impl<'conn> std::ops::Deref for PlacesTransaction<'conn> {
     type Target = Connection;
 
     fn deref(&self) -> &Connection {
-        match &self.0 {
+        match self.0.as_ref().expect("Transaction should still be open") {
             PlacesTransactionRepr::ChunkedWrite(t) => &t,
             PlacesTransactionRepr::UnchunkedWrite(t) => &t,
             PlacesTransactionRepr::ReadOnly(t) => &t,
@@ -71,6 +71,15 @@ pub enum ErrorKind {
 
     #[fail(display = "Database cannot be upgraded")]
     DatabaseUpgradeError,
+
+    #[fail(display = "Invalid frecency settings: {}", _0)]
+    InvalidFrecencySettings(&'static str),
+
+    #[fail(display = "Invalid history policy: {}", _0)]
+    InvalidHistoryPolicy(&'static str),
+
+    #[fail(display = "Invalid history backfill settings: {}", _0)]
+    InvalidHistoryBackfillSettings(&'static str),
 }
 
 error_support::define_error! {
@@ -121,6 +130,17 @@ pub enum InvalidPlaceInfo {
     // Like Urls, a tag is considered private info, so the value isn't in the error.
     #[fail(display = "The tag value is invalid")]
     InvalidTag,
+
+    // Keywords are also private info.
+    #[fail(display = "The keyword value is invalid")]
+    InvalidKeyword,
+
+    #[fail(display = "Invalid annotation name: {:?}", _0)]
+    InvalidAnnotationName(String),
+
+    #[fail(display = "Invalid history search cursor")]
+    InvalidCursor,
+
     #[fail(
         display = "Cannot change the '{}' property of a bookmark of type {:?}",
         _0, _1
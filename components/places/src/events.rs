@@ -0,0 +1,531 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Notifications for changes to history and bookmarks.
+//!
+//! Storage functions on the write and sync connections note an event for
+//! each change they make. Events are held on the connection until the
+//! transaction that made them commits, and are then delivered to observers
+//! as a single `PlacesEventBatch`; events from rolled-back transactions are
+//! discarded. Observers are registered on the `PlacesApi`, and see changes
+//! from all its connections.
+//!
+//! Changes applied by Sync are reported as a single `SyncApplied` event per
+//! collection, rather than an event for each item, since a sync can change
+//! thousands of items at once. Observers should re-query in that case.
+//!
+//! A few maintenance paths don't report everything they change:
+//!
+//! - `expire_history` reports the pages it removes, but not the visits it
+//!   removes from pages that are kept.
+//! - `check_and_repair_bookmarks` doesn't report its repairs, since the
+//!   items it moves or removes aren't in a valid tree to begin with.
+//!   Observers should re-query after a repair.
+//!
+//! For consumers that can't take a callback, like our FFI, `PlacesEventQueue`
+//! is an observer that holds batches until they're polled.
+
+use crate::types::{BookmarkType, SyncGuid, Timestamp, VisitTransition};
+use serde_derive::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+/// A change to history or bookmarks.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PlacesEvent {
+    /// A visit was added to a page.
+    #[serde(rename_all = "camelCase")]
+    VisitAdded {
+        url: String,
+        visit_date: Timestamp,
+        visit_type: VisitTransition,
+        is_remote: bool,
+    },
+    /// A page was removed from history, along with all its visits.
+    PageDeleted { url: String, guid: SyncGuid },
+    /// All history was removed. Bookmarked pages are kept, but lose their
+    /// visits.
+    HistoryCleared,
+    /// A page's title changed.
+    TitleChanged { url: String, title: String },
+    /// A bookmark, folder or separator was added. For bookmarks, `url` is
+    /// the bookmarked URL.
+    #[serde(rename_all = "camelCase")]
+    BookmarkAdded {
+        guid: SyncGuid,
+        parent_guid: SyncGuid,
+        position: u32,
+        kind: BookmarkType,
+        url: Option<String>,
+    },
+    /// An item was moved, either to a new position in the same folder, or to
+    /// a different folder.
+    #[serde(rename_all = "camelCase")]
+    BookmarkMoved {
+        guid: SyncGuid,
+        old_parent_guid: SyncGuid,
+        old_position: u32,
+        parent_guid: SyncGuid,
+        position: u32,
+    },
+    /// An item's title or URL changed. The fields are the new values.
+    BookmarkUpdated {
+        guid: SyncGuid,
+        title: Option<String>,
+        url: Option<String>,
+    },
+    /// An item was removed. If it was a folder, its descendants were removed
+    /// too, without events of their own.
+    #[serde(rename_all = "camelCase")]
+    BookmarkRemoved {
+        guid: SyncGuid,
+        parent_guid: SyncGuid,
+        position: u32,
+        kind: BookmarkType,
+    },
+    /// Sync applied incoming changes for a collection ("history" or
+    /// "bookmarks").
+    SyncApplied { collection: String },
+}
+
+/// The events from a single transaction.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacesEventBatch {
+    /// Whether the changes were made by the sync connection.
+    pub from_sync: bool,
+    pub events: Vec<PlacesEvent>,
+}
+
+/// Something that wants to know about changes. Observers are called on the
+/// thread that committed the changes, after the commit, so they must not use
+/// the connection that made them.
+pub trait PlacesObserver: Send + Sync {
+    fn on_events(&self, batch: &PlacesEventBatch);
+}
+
+/// Identifies a registered observer, so that it can be removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+/// The observers for a `PlacesApi`, shared by all its connections.
+#[derive(Default)]
+pub struct PlacesEventDispatcher {
+    next_id: AtomicUsize,
+    observers: Mutex<Vec<(ObserverId, Arc<dyn PlacesObserver>)>>,
+}
+
+impl PlacesEventDispatcher {
+    pub fn add_observer(&self, observer: Arc<dyn PlacesObserver>) -> ObserverId {
+        let id = ObserverId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.observers.lock().unwrap().push((id, observer));
+        id
+    }
+
+    /// Returns true if the observer was registered.
+    pub fn remove_observer(&self, id: ObserverId) -> bool {
+        let mut observers = self.observers.lock().unwrap();
+        let len = observers.len();
+        observers.retain(|(other, _)| *other != id);
+        observers.len() != len
+    }
+
+    fn has_observers(&self) -> bool {
+        !self.observers.lock().unwrap().is_empty()
+    }
+
+    fn dispatch(&self, batch: &PlacesEventBatch) {
+        // Clone the list, so that observers can add and remove observers
+        // without deadlocking.
+        let observers: Vec<_> = self
+            .observers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, observer)| observer.clone())
+            .collect();
+        for observer in observers {
+            observer.on_events(batch);
+        }
+    }
+}
+
+impl std::fmt::Debug for PlacesEventDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlacesEventDispatcher")
+            .field("observers", &self.observers.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// The events noted on a connection that haven't been delivered yet.
+#[derive(Debug, Default)]
+pub(crate) struct PendingEvents {
+    dispatcher: Option<Arc<PlacesEventDispatcher>>,
+    events: RefCell<Vec<PlacesEvent>>,
+}
+
+impl PendingEvents {
+    pub(crate) fn set_dispatcher(&mut self, dispatcher: Arc<PlacesEventDispatcher>) {
+        self.dispatcher = Some(dispatcher);
+    }
+
+    /// Returns true if anyone is listening. Callers can use this to avoid
+    /// extra queries to build events.
+    pub(crate) fn is_observed(&self) -> bool {
+        self.dispatcher
+            .as_ref()
+            .map_or(false, |dispatcher| dispatcher.has_observers())
+    }
+
+    pub(crate) fn push(&self, event: PlacesEvent) {
+        if self.is_observed() {
+            self.events.borrow_mut().push(event);
+        }
+    }
+
+    pub(crate) fn flush(&self, from_sync: bool) {
+        let events = self.events.replace(Vec::new());
+        if events.is_empty() {
+            return;
+        }
+        if let Some(dispatcher) = &self.dispatcher {
+            dispatcher.dispatch(&PlacesEventBatch { from_sync, events });
+        }
+    }
+
+    pub(crate) fn discard(&self) {
+        self.events.borrow_mut().clear();
+    }
+}
+
+/// The maximum number of batches a `PlacesEventQueue` holds. If the queue
+/// isn't polled often enough, the oldest batches are dropped, and a
+/// `dropped` count is reported with the next poll.
+pub const MAX_QUEUED_EVENT_BATCHES: usize = 500;
+
+/// An observer that queues batches until they're polled.
+#[derive(Debug, Default)]
+pub struct PlacesEventQueue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    batches: VecDeque<PlacesEventBatch>,
+    dropped: usize,
+}
+
+/// The result of polling a `PlacesEventQueue`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueuedEvents {
+    pub batches: Vec<PlacesEventBatch>,
+    /// The number of batches dropped since the last poll, because the queue
+    /// was full. If this isn't zero, consumers should re-query everything
+    /// they show.
+    pub dropped: usize,
+}
+
+impl PlacesEventQueue {
+    /// Returns and clears the queued batches, oldest first.
+    pub fn take(&self) -> QueuedEvents {
+        let mut state = self.state.lock().unwrap();
+        QueuedEvents {
+            batches: state.batches.drain(..).collect(),
+            dropped: std::mem::replace(&mut state.dropped, 0),
+        }
+    }
+}
+
+impl PlacesObserver for PlacesEventQueue {
+    fn on_events(&self, batch: &PlacesEventBatch) {
+        let mut state = self.state.lock().unwrap();
+        if state.batches.len() >= MAX_QUEUED_EVENT_BATCHES {
+            state.batches.pop_front();
+            state.dropped += 1;
+        }
+        state.batches.push_back(batch.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::{test::new_mem_api, ConnectionType};
+    use crate::observation::VisitObservation;
+    use crate::storage::bookmarks::{
+        self, BookmarkPosition, BookmarkRootGuid, InsertableBookmark, InsertableItem,
+        UpdatableBookmark, UpdateTreeLocation,
+    };
+    use crate::storage::expiration::{expire_history, HistoryExpirationPolicy};
+    use crate::storage::history::{
+        apply_observation, delete_place_by_guid, note_page_metadata, url_to_guid, PageMetadata,
+    };
+    use crate::tests::{bookmark_url, insert_json_tree, visit_url_at};
+    use serde_json::json;
+    use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn test_no_observers() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        assert!(!conn.has_event_observers());
+        let queue = Arc::new(PlacesEventQueue::default());
+        let id = api.add_observer(queue.clone());
+        assert!(conn.has_event_observers());
+        assert!(api.remove_observer(id));
+        assert!(!api.remove_observer(id));
+
+        // Events aren't recorded when no one is observing.
+        bookmark_url(&conn, &Url::parse("https://www.example.com/").unwrap());
+        assert_eq!(queue.take(), QueuedEvents::default());
+    }
+
+    #[test]
+    fn test_batches() {
+        let _ = env_logger::try_init();
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let queue = Arc::new(PlacesEventQueue::default());
+        api.add_observer(queue.clone());
+
+        let url = Url::parse("https://www.example.com/").unwrap();
+        apply_observation(
+            &conn,
+            VisitObservation::new(url.clone())
+                .with_title("Example".to_string())
+                .with_visit_type(VisitTransition::Link),
+        )
+        .expect("should apply");
+        insert_json_tree(
+            &conn,
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [{
+                    "guid": "bookmarkAAAA",
+                    "url": url.as_str(),
+                }],
+            }),
+        );
+        bookmarks::update_bookmark(
+            &conn,
+            &"bookmarkAAAA".into(),
+            &UpdatableBookmark {
+                location: UpdateTreeLocation::Parent(
+                    BookmarkRootGuid::Menu.into(),
+                    BookmarkPosition::Append,
+                ),
+                title: Some("New title".into()),
+                ..UpdatableBookmark::default()
+            }
+            .into(),
+        )
+        .expect("should update");
+
+        // Failed writes are rolled back, and don't report anything.
+        bookmarks::insert_bookmark(
+            &conn,
+            &InsertableItem::Bookmark(InsertableBookmark {
+                parent_guid: "doesNotExist".into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: url.clone(),
+                title: None,
+            }),
+        )
+        .expect_err("should fail to insert into a missing folder");
+
+        bookmarks::delete_bookmark(&conn, &"bookmarkAAAA".into()).expect("should delete");
+        let guid = url_to_guid(&conn, &url).unwrap().unwrap();
+        delete_place_by_guid(&conn, &guid).expect("should delete");
+
+        let queued = queue.take();
+        assert_eq!(queued.dropped, 0);
+        let events: Vec<Vec<PlacesEvent>> = queued
+            .batches
+            .into_iter()
+            .map(|batch| {
+                assert!(!batch.from_sync);
+                batch.events
+            })
+            .collect();
+        let visit_date = match events[0][1] {
+            PlacesEvent::VisitAdded { visit_date, .. } => visit_date,
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+        assert_eq!(
+            events,
+            vec![
+                vec![
+                    PlacesEvent::TitleChanged {
+                        url: url.to_string(),
+                        title: "Example".into(),
+                    },
+                    PlacesEvent::VisitAdded {
+                        url: url.to_string(),
+                        visit_date,
+                        visit_type: VisitTransition::Link,
+                        is_remote: false,
+                    },
+                ],
+                vec![PlacesEvent::BookmarkAdded {
+                    guid: "bookmarkAAAA".into(),
+                    parent_guid: BookmarkRootGuid::Unfiled.into(),
+                    position: 0,
+                    kind: BookmarkType::Bookmark,
+                    url: Some(url.to_string()),
+                }],
+                vec![
+                    PlacesEvent::BookmarkMoved {
+                        guid: "bookmarkAAAA".into(),
+                        old_parent_guid: BookmarkRootGuid::Unfiled.into(),
+                        old_position: 0,
+                        parent_guid: BookmarkRootGuid::Menu.into(),
+                        position: 0,
+                    },
+                    PlacesEvent::BookmarkUpdated {
+                        guid: "bookmarkAAAA".into(),
+                        title: Some("New title".into()),
+                        url: Some(url.to_string()),
+                    },
+                ],
+                vec![PlacesEvent::BookmarkRemoved {
+                    guid: "bookmarkAAAA".into(),
+                    parent_guid: BookmarkRootGuid::Menu.into(),
+                    position: 0,
+                    kind: BookmarkType::Bookmark,
+                }],
+                vec![PlacesEvent::PageDeleted {
+                    url: url.to_string(),
+                    guid,
+                }],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_page_metadata_title() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let queue = Arc::new(PlacesEventQueue::default());
+        api.add_observer(queue.clone());
+
+        let url = Url::parse("https://www.example.com/").unwrap();
+        let metadata = PageMetadata {
+            title: Some("Example".into()),
+            ..PageMetadata::default()
+        };
+        note_page_metadata(&conn, &url, &metadata).expect("should note metadata");
+        let queued = queue.take();
+        assert_eq!(queued.batches.len(), 1);
+        assert_eq!(
+            queued.batches[0].events,
+            vec![PlacesEvent::TitleChanged {
+                url: url.to_string(),
+                title: "Example".into(),
+            }]
+        );
+
+        // Only changes to the title are reported.
+        note_page_metadata(&conn, &url, &metadata).expect("should note same title");
+        note_page_metadata(
+            &conn,
+            &url,
+            &PageMetadata {
+                description: Some("An example page".into()),
+                ..PageMetadata::default()
+            },
+        )
+        .expect("should note description");
+        assert_eq!(queue.take(), QueuedEvents::default());
+    }
+
+    #[test]
+    fn test_expiration() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let url = Url::parse("https://www.example.com/old").unwrap();
+        let ago = Timestamp::now()
+            .checked_sub(Duration::from_secs(100 * 24 * 60 * 60))
+            .unwrap();
+        visit_url_at(&conn, &url, ago);
+        let guid = url_to_guid(&conn, &url).unwrap().unwrap();
+
+        let queue = Arc::new(PlacesEventQueue::default());
+        api.add_observer(queue.clone());
+        expire_history(
+            &conn,
+            &HistoryExpirationPolicy {
+                max_visit_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+                ..HistoryExpirationPolicy::default()
+            },
+        )
+        .expect("should expire");
+
+        let queued = queue.take();
+        assert_eq!(queued.batches.len(), 1);
+        assert_eq!(
+            queued.batches[0].events,
+            vec![PlacesEvent::PageDeleted {
+                url: url.to_string(),
+                guid,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dropped_transaction() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let queue = Arc::new(PlacesEventQueue::default());
+        api.add_observer(queue.clone());
+
+        // Dropping a transaction rolls it back, so its events shouldn't be
+        // delivered along with the next autocommit write's.
+        let tx = conn.begin_transaction().unwrap();
+        conn.note_event(PlacesEvent::BookmarkRemoved {
+            guid: "bookmarkAAAA".into(),
+            parent_guid: BookmarkRootGuid::Unfiled.into(),
+            position: 0,
+            kind: BookmarkType::Bookmark,
+        });
+        drop(tx);
+        conn.note_event(PlacesEvent::HistoryCleared);
+
+        let queued = queue.take();
+        assert_eq!(queued.batches.len(), 1);
+        assert_eq!(queued.batches[0].events, vec![PlacesEvent::HistoryCleared]);
+    }
+
+    #[test]
+    fn test_queue_limit() {
+        let queue = PlacesEventQueue::default();
+        let batch = PlacesEventBatch {
+            from_sync: true,
+            events: vec![PlacesEvent::HistoryCleared],
+        };
+        for _ in 0..MAX_QUEUED_EVENT_BATCHES + 3 {
+            queue.on_events(&batch);
+        }
+        let queued = queue.take();
+        assert_eq!(queued.batches.len(), MAX_QUEUED_EVENT_BATCHES);
+        assert_eq!(queued.dropped, 3);
+        assert_eq!(queue.take(), QueuedEvents::default());
+        assert_eq!(
+            serde_json::to_value(&batch).unwrap(),
+            serde_json::json!({
+                "fromSync": true,
+                "events": [{ "type": "historyCleared" }],
+            })
+        );
+    }
+}
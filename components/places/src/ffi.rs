@@ -5,11 +5,13 @@
 // This module implement the traits that make the FFI code easier to manage.
 
 use crate::api::matcher::SearchResult;
+use crate::db::SearchInterruptHandle;
 use crate::error::{Error, ErrorKind, InvalidPlaceInfo};
+use crate::import::DesktopImportMetrics;
 use crate::msg_types;
 use ffi_support::{
-    implement_into_ffi_by_delegation, implement_into_ffi_by_json, implement_into_ffi_by_protobuf,
-    ErrorCode, ExternError,
+    implement_into_ffi_by_delegation, implement_into_ffi_by_json, implement_into_ffi_by_pointer,
+    implement_into_ffi_by_protobuf, ErrorCode, ExternError,
 };
 
 pub mod error_codes {
@@ -56,6 +58,12 @@ pub mod error_codes {
     /// to root________, updating properties of a root, deleting a root, etc.
     pub const INVALID_PLACE_INFO_CANNOT_UPDATE_ROOT: i32 = 64 + 4;
 
+    /// `InvalidTag`: The provided tag is empty, too long, or contains
+    /// whitespace.
+    pub const INVALID_PLACE_INFO_INVALID_TAG: i32 = 64 + 5;
+
+    /// `NoSuchUrl`: The provided URL is not in the database.
+    pub const INVALID_PLACE_INFO_NO_SUCH_URL: i32 = 64 + 6;
 }
 
 fn get_code(err: &Error) -> ErrorCode {
@@ -74,6 +82,8 @@ fn get_code(err: &Error) -> ErrorCode {
                 InvalidPlaceInfo::CannotUpdateRoot(..) => {
                     error_codes::INVALID_PLACE_INFO_CANNOT_UPDATE_ROOT
                 }
+                InvalidPlaceInfo::InvalidTag => error_codes::INVALID_PLACE_INFO_INVALID_TAG,
+                InvalidPlaceInfo::NoSuchUrl => error_codes::INVALID_PLACE_INFO_NO_SUCH_URL,
                 _ => error_codes::UNEXPECTED,
             };
             ErrorCode::new(code)
@@ -141,10 +151,13 @@ impl From<Error> for ExternError {
 }
 
 implement_into_ffi_by_json!(SearchResult);
+implement_into_ffi_by_json!(DesktopImportMetrics);
 implement_into_ffi_by_protobuf!(msg_types::HistoryVisitInfos);
+implement_into_ffi_by_protobuf!(msg_types::HistorySearchResults);
 implement_into_ffi_by_protobuf!(msg_types::BookmarkNode);
 implement_into_ffi_by_protobuf!(msg_types::BookmarkNodeList);
 implement_into_ffi_by_delegation!(
     crate::storage::bookmarks::PublicNode,
     msg_types::BookmarkNode
 );
+implement_into_ffi_by_pointer!(SearchInterruptHandle);
@@ -2,9 +2,14 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::db::PlacesDb;
 use crate::error::*;
+use crate::storage::{delete_meta, delete_pending_temp_tables, get_meta, put_meta};
 use crate::types::VisitTransition;
+use interrupt::Interruptee;
 use rusqlite::Connection;
+use serde_derive::*;
+use sql_support::ConnExt;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum RedirectBonus {
@@ -13,14 +18,18 @@ enum RedirectBonus {
     Normal,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The weights and bonuses used to calculate frecency. Each `PlacesApi` has
+/// its own settings, which can be changed with
+/// `PlacesApi::set_frecency_settings`; connections opened without an API use
+/// the defaults. When deserialized, missing fields take their default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct FrecencySettings {
-    // TODO: These probably should not all be i32s...
-    pub num_visits: i32,                     // from "places.frecency.numVisits"
-    pub first_bucket_cutoff_days: i32,       // from "places.frecency.firstBucketCutoff"
-    pub second_bucket_cutoff_days: i32,      // from "places.frecency.secondBucketCutoff"
-    pub third_bucket_cutoff_days: i32,       // from "places.frecency.thirdBucketCutoff"
-    pub fourth_bucket_cutoff_days: i32,      // from "places.frecency.fourthBucketCutoff"
+    pub num_visits: u32,                     // from "places.frecency.numVisits"
+    pub first_bucket_cutoff_days: u32,       // from "places.frecency.firstBucketCutoff"
+    pub second_bucket_cutoff_days: u32,      // from "places.frecency.secondBucketCutoff"
+    pub third_bucket_cutoff_days: u32,       // from "places.frecency.thirdBucketCutoff"
+    pub fourth_bucket_cutoff_days: u32,      // from "places.frecency.fourthBucketCutoff"
     pub first_bucket_weight: i32,            // from "places.frecency.firstBucketWeight"
     pub second_bucket_weight: i32,           // from "places.frecency.secondBucketWeight"
     pub third_bucket_weight: i32,            // from "places.frecency.thirdBucketWeight"
@@ -76,6 +85,24 @@ impl Default for FrecencySettings {
 }
 
 impl FrecencySettings {
+    /// Checks that we sample at least one visit, and that the bucket cutoffs
+    /// are in increasing order.
+    pub fn validate(&self) -> Result<()> {
+        if self.num_visits == 0 {
+            return Err(ErrorKind::InvalidFrecencySettings("numVisits must be positive").into());
+        }
+        if self.first_bucket_cutoff_days >= self.second_bucket_cutoff_days
+            || self.second_bucket_cutoff_days >= self.third_bucket_cutoff_days
+            || self.third_bucket_cutoff_days >= self.fourth_bucket_cutoff_days
+        {
+            return Err(ErrorKind::InvalidFrecencySettings(
+                "bucket cutoffs must be in increasing order",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
     // Note: in Places, `redirect` defaults to false.
     pub fn get_transition_bonus(
         &self,
@@ -103,7 +130,7 @@ impl FrecencySettings {
         }
     }
 
-    fn get_frecency_aged_weight(&self, age_in_days: i32) -> i32 {
+    fn get_frecency_aged_weight(&self, age_in_days: u32) -> i32 {
         if age_in_days <= self.first_bucket_cutoff_days {
             self.first_bucket_weight
         } else if age_in_days <= self.second_bucket_cutoff_days {
@@ -199,7 +226,8 @@ impl<'db, 's> FrecencyComputation<'db, 's> {
                 Ok((
                     VisitTransition::from_primitive(visit_type),
                     VisitTransition::from_primitive(target_visit_type),
-                    age_in_days as i32,
+                    // Visits in the future are treated as visits today.
+                    age_in_days.max(0.0) as u32,
                 ))
             },
         )?;
@@ -314,3 +342,185 @@ pub fn calculate_frecency(
         fc.compute_unvisited_bookmark_frecency()
     })
 }
+
+/// The number of pages to recalculate before we check whether to commit.
+const RECALCULATE_FRECENCIES_CHUNK_SIZE: u32 = 500;
+
+/// Remembers how far we got, so that an interrupted recalculation picks up
+/// where it left off.
+const RECALCULATE_FRECENCIES_CURSOR_META_KEY: &str = "frecency_recalculation_last_id";
+
+/// Recalculates the frecency of every page, using the connection's current
+/// settings, and clears `moz_places_stale_frecencies`. Returns the number of
+/// pages recalculated.
+///
+/// Pages are recalculated in chunks, in order of their IDs. This is meant to
+/// run on the Sync connection, where the transaction is committed between
+/// chunks to let other writers in; use `PlacesApi::recalculate_frecencies`.
+/// If it's interrupted, the next call continues from the last committed
+/// chunk.
+pub fn recalculate_frecencies(db: &PlacesDb, interruptee: &impl Interruptee) -> Result<usize> {
+    let settings = db.frecency_settings();
+    let mut tx = db.begin_transaction()?;
+    let mut last_id = get_meta::<i64>(db, RECALCULATE_FRECENCIES_CURSOR_META_KEY)?.unwrap_or(0);
+    let mut recalculated = 0;
+    loop {
+        let ids = db.query_rows_and_then_named_cached(
+            "SELECT id FROM moz_places
+             WHERE id > :last_id
+             ORDER BY id
+             LIMIT :chunk_size",
+            &[
+                (":last_id", &last_id),
+                (":chunk_size", &RECALCULATE_FRECENCIES_CHUNK_SIZE),
+            ],
+            |row| row.get::<_, i64>(0),
+        )?;
+        let (first_id, chunk_last_id) = match (ids.first(), ids.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => break,
+        };
+        for &id in &ids {
+            interruptee.err_if_interrupted()?;
+            let frecency = calculate_frecency(db.conn(), &settings, id, None)?;
+            db.execute_named_cached(
+                "UPDATE moz_places SET frecency = :frecency
+                 WHERE id = :id AND frecency <> :frecency",
+                &[(":frecency", &frecency), (":id", &id)],
+            )?;
+        }
+        // We've recalculated every page in this range, including any stale
+        // ones.
+        db.execute_named_cached(
+            "DELETE FROM moz_places_stale_frecencies
+             WHERE place_id BETWEEN :first_id AND :last_id",
+            &[(":first_id", &first_id), (":last_id", &chunk_last_id)],
+        )?;
+        last_id = chunk_last_id;
+        put_meta(db, RECALCULATE_FRECENCIES_CURSOR_META_KEY, &last_id)?;
+        recalculated += ids.len();
+        // Update the origins for the pages we changed before committing.
+        delete_pending_temp_tables(db)?;
+        tx.maybe_commit()?;
+        if ids.len() < RECALCULATE_FRECENCIES_CHUNK_SIZE as usize {
+            break;
+        }
+    }
+    delete_meta(db, RECALCULATE_FRECENCIES_CURSOR_META_KEY)?;
+    delete_pending_temp_tables(db)?;
+    tx.commit()?;
+    Ok(recalculated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_api;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::apply_observation;
+    use interrupt::NeverInterrupts;
+    use url::Url;
+
+    fn get_frecency(db: &PlacesDb, url: &str) -> i64 {
+        db.query_row_named(
+            "SELECT frecency FROM moz_places WHERE url = :url",
+            &[(":url", &url)],
+            |row| row.get(0),
+        )
+        .expect("should have a frecency")
+    }
+
+    fn get_origin_frecency(db: &PlacesDb, host: &str) -> i64 {
+        db.query_row_named(
+            "SELECT frecency FROM moz_origins WHERE host = :host",
+            &[(":host", &host)],
+            |row| row.get(0),
+        )
+        .expect("should have an origin frecency")
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(FrecencySettings::default().validate().is_ok());
+        let settings = FrecencySettings {
+            num_visits: 0,
+            ..FrecencySettings::default()
+        };
+        assert!(settings.validate().is_err());
+        let settings = FrecencySettings {
+            third_bucket_cutoff_days: 100,
+            ..FrecencySettings::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings: FrecencySettings =
+            serde_json::from_str(r#"{"typedVisitBonus": 500}"#).expect("should deserialize");
+        assert_eq!(
+            settings,
+            FrecencySettings {
+                typed_visit_bonus: 500,
+                ..FrecencySettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_recalculate_frecencies() -> Result<()> {
+        let _ = env_logger::try_init();
+        let api = new_mem_api();
+        let db = api.open_sync_connection()?;
+        for url in &["https://typed.example.com/", "https://link.example.com/"] {
+            let visit_type = if url.contains("typed") {
+                VisitTransition::Typed
+            } else {
+                VisitTransition::Link
+            };
+            apply_observation(
+                &db,
+                VisitObservation::new(Url::parse(url)?).with_visit_type(visit_type),
+            )?;
+        }
+        // `apply_observation` leaves the origins for the next write to update.
+        delete_pending_temp_tables(&db)?;
+        db.execute_batch(
+            "INSERT INTO moz_places_stale_frecencies(place_id, stale_at)
+             SELECT id, now() FROM moz_places",
+        )?;
+        let typed_frecency = get_frecency(&db, "https://typed.example.com/");
+        let link_frecency = get_frecency(&db, "https://link.example.com/");
+        assert!(typed_frecency > link_frecency);
+        assert_eq!(
+            get_origin_frecency(&db, "typed.example.com"),
+            typed_frecency
+        );
+        assert_eq!(get_origin_frecency(&db, "link.example.com"), link_frecency);
+
+        api.set_frecency_settings(FrecencySettings {
+            typed_visit_bonus: 100,
+            link_visit_bonus: 2000,
+            ..FrecencySettings::default()
+        })?;
+        assert_eq!(recalculate_frecencies(&db, &NeverInterrupts)?, 2);
+
+        assert_eq!(
+            get_frecency(&db, "https://typed.example.com/"),
+            link_frecency
+        );
+        assert_eq!(
+            get_frecency(&db, "https://link.example.com/"),
+            typed_frecency
+        );
+        // The origins should be updated, too.
+        assert_eq!(get_origin_frecency(&db, "typed.example.com"), link_frecency);
+        assert_eq!(get_origin_frecency(&db, "link.example.com"), typed_frecency);
+        assert_eq!(
+            db.query_one::<i64>("SELECT COUNT(*) FROM moz_places_stale_frecencies")?,
+            0
+        );
+        assert_eq!(
+            get_meta::<i64>(&db, RECALCULATE_FRECENCIES_CURSOR_META_KEY)?,
+            None
+        );
+        Ok(())
+    }
+}
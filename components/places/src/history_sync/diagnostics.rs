@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Optional, per-record diagnostics for incoming history, to help debug
+//! reports of missing history. Telemetry only counts how many records were
+//! applied or failed; these say what we did with each record, and why.
+//!
+//! Records are identified by their GUID. The diagnostics don't include URLs
+//! or titles, so they can be attached to bug reports.
+
+use crate::types::SyncGuid;
+use serde_derive::*;
+
+/// What we did with an incoming record. These match `IncomingPlan`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlanKind {
+    /// We ignored the record, because the history policy doesn't allow
+    /// its URL.
+    Skip,
+    /// The record is malformed: it couldn't be parsed, or has an invalid
+    /// GUID or URL.
+    Invalid,
+    /// The record looks fine, but we hit an error planning it.
+    Failed,
+    /// The record is a tombstone, so we deleted the page.
+    Delete,
+    /// We added visits to the page, or changed its GUID.
+    Apply,
+    /// We already had everything in the record.
+    Reconciled,
+}
+
+/// What happened to the visits in an incoming record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VisitDiagnostics {
+    /// Visits we added.
+    pub applied: usize,
+    /// Visits we already had.
+    pub duplicate: usize,
+    /// Visits in the future, which we moved to the current time. These are
+    /// also counted as applied or duplicate.
+    pub clamped: usize,
+    /// Visits before 1993-01-23, which we dropped.
+    pub too_old: usize,
+    /// Visits with a transition type we don't know, which we dropped.
+    pub unknown_transition: usize,
+    /// Visits older than all of the `MAX_VISITS` most recent visits we
+    /// already had for the page, which we dropped.
+    pub beyond_max_visits: usize,
+}
+
+/// The plan for one incoming record.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordDiagnostics {
+    pub guid: SyncGuid,
+    pub plan: PlanKind,
+    /// Why we skipped the record, or the error for invalid and failed
+    /// records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub visits: VisitDiagnostics,
+}
+
+/// The plans for all incoming records in a sync, in the order we received
+/// them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySyncDiagnostics {
+    pub records: Vec<RecordDiagnostics>,
+}
@@ -2,11 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::error::*;
 use crate::types::Timestamp;
 use serde_derive::*;
 use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod diagnostics;
 mod plan;
 pub mod record;
 pub mod store;
@@ -16,6 +18,51 @@ const MAX_OUTGOING_PLACES: usize = 5000;
 const MAX_VISITS: usize = 20;
 pub const HISTORY_TTL: u32 = 5_184_000; // 60 days in milliseconds
 
+/// Controls how much history we download from the server at once.
+///
+/// The first sync on a new device downloads only the most recently modified
+/// `max_records_per_sync` records, so that it finishes quickly on big
+/// accounts. If there are more, later syncs backfill older records, newest
+/// first, until there are none left or we reach the horizon. Backfills
+/// alternate with regular syncs, so new history still arrives in the
+/// meantime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistoryBackfillSettings {
+    /// The most records to download in a first sync or a backfill. Regular
+    /// syncs download everything changed since the last sync.
+    pub max_records_per_sync: usize,
+    /// How far back, in days, to backfill. Records last modified before
+    /// then aren't downloaded on a new device.
+    pub horizon_days: u32,
+}
+
+impl Default for HistoryBackfillSettings {
+    fn default() -> Self {
+        Self {
+            max_records_per_sync: MAX_INCOMING_PLACES,
+            // Records expire from the server after `HISTORY_TTL` anyway.
+            horizon_days: HISTORY_TTL / (24 * 60 * 60),
+        }
+    }
+}
+
+impl HistoryBackfillSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_records_per_sync == 0 {
+            return Err(ErrorKind::InvalidHistoryBackfillSettings(
+                "maxRecordsPerSync must be positive",
+            )
+            .into());
+        }
+        if self.horizon_days == 0 {
+            return Err(
+                ErrorKind::InvalidHistoryBackfillSettings("horizonDays must be positive").into(),
+            );
+        }
+        Ok(())
+    }
+}
+
 /// Visit timestamps on the server are *microseconds* since the epoch.
 #[derive(
     Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize, Default,
@@ -2,11 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use super::diagnostics::{HistorySyncDiagnostics, PlanKind, RecordDiagnostics, VisitDiagnostics};
 use super::record::{HistoryRecord, HistoryRecordVisit, HistorySyncRecord};
 use super::{HISTORY_TTL, MAX_OUTGOING_PLACES, MAX_VISITS};
 use crate::api::history::can_add_url;
 use crate::db::PlacesDb;
 use crate::error::*;
+use crate::events::PlacesEvent;
 use crate::storage::history::history_sync::{
     apply_synced_deletion, apply_synced_reconciliation, apply_synced_visits, fetch_outgoing,
     fetch_visits, finish_incoming, finish_outgoing, FetchedVisit, FetchedVisitPage, OutgoingInfo,
@@ -59,7 +61,36 @@ pub enum IncomingPlan {
     Reconciled,
 }
 
-fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usize) -> IncomingPlan {
+impl IncomingPlan {
+    fn diagnose(&self, guid: SyncGuid, visits: VisitDiagnostics) -> RecordDiagnostics {
+        let (plan, reason) = match self {
+            IncomingPlan::Skip => (
+                PlanKind::Skip,
+                Some("URL not allowed by the history policy".to_string()),
+            ),
+            IncomingPlan::Invalid(err) => (PlanKind::Invalid, Some(err.to_string())),
+            IncomingPlan::Failed(err) => (PlanKind::Failed, Some(err.to_string())),
+            IncomingPlan::Delete => (PlanKind::Delete, None),
+            IncomingPlan::Apply { .. } => (PlanKind::Apply, None),
+            IncomingPlan::Reconciled => (PlanKind::Reconciled, None),
+        };
+        RecordDiagnostics {
+            guid,
+            plan,
+            reason,
+            visits,
+        }
+    }
+}
+
+/// Plans what to do with an incoming record, counting what happens to its
+/// visits in `visit_diagnostics`.
+fn plan_incoming_record(
+    conn: &PlacesDb,
+    record: HistoryRecord,
+    max_visits: usize,
+    visit_diagnostics: &mut VisitDiagnostics,
+) -> IncomingPlan {
     let url = match Url::parse(&record.hist_uri) {
         Ok(u) => u,
         Err(e) => return IncomingPlan::Invalid(e.into()),
@@ -69,7 +100,7 @@ fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usiz
         return IncomingPlan::Invalid(InvalidPlaceInfo::InvalidGuid.into());
     }
 
-    match can_add_url(&url) {
+    match can_add_url(conn, &url) {
         Ok(can) => {
             if !can {
                 return IncomingPlan::Skip;
@@ -133,13 +164,21 @@ fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usiz
     for incoming_visit in record.visits {
         let transition = match VisitTransition::from_primitive(incoming_visit.transition) {
             Some(v) => v,
-            None => continue,
+            None => {
+                visit_diagnostics.unknown_transition += 1;
+                continue;
+            }
         };
-        match clamp_visit_date(incoming_visit.date.into()) {
+        let visit_date: Timestamp = incoming_visit.date.into();
+        match clamp_visit_date(visit_date) {
             Ok(timestamp) => {
                 if earliest_allowed > timestamp.into() {
+                    visit_diagnostics.beyond_max_visits += 1;
                     continue;
                 }
+                if timestamp != visit_date {
+                    visit_diagnostics.clamped += 1;
+                }
                 // If the entry isn't in our map we should add it.
                 let key = (transition, timestamp);
                 if !cur_visit_map.contains(&key) {
@@ -148,10 +187,14 @@ fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usiz
                         transition: transition as u8,
                     });
                     cur_visit_map.insert(key);
+                    visit_diagnostics.applied += 1;
+                } else {
+                    visit_diagnostics.duplicate += 1;
                 }
             }
             Err(()) => {
                 log::warn!("Ignored visit before 1993-01-23");
+                visit_diagnostics.too_old += 1;
             }
         }
     }
@@ -175,11 +218,13 @@ pub fn apply_plan(
     inbound: IncomingChangeset,
     telem: &mut telemetry::EngineIncoming,
     interruptee: &impl Interruptee,
+    mut diagnostics: Option<&mut HistorySyncDiagnostics>,
 ) -> Result<OutgoingChangeset> {
     // for a first-cut, let's do this in the most naive way possible...
     let mut plans: Vec<(SyncGuid, IncomingPlan)> = Vec::with_capacity(inbound.changes.len());
     for incoming in inbound.changes {
         interruptee.err_if_interrupted()?;
+        let id = incoming.0.id.clone();
         let item = match HistorySyncRecord::from_payload(incoming.0) {
             Ok(item) => item,
             Err(e) => {
@@ -187,20 +232,31 @@ pub fn apply_plan(
                 // know the guid - just skip it.
                 log::warn!("Error deserializing incoming record: {}", e);
                 telem.failed(1);
+                if let Some(diagnostics) = diagnostics.as_mut() {
+                    let plan = IncomingPlan::Invalid(e);
+                    let record_diagnostics = plan.diagnose(id.into(), VisitDiagnostics::default());
+                    diagnostics.records.push(record_diagnostics);
+                }
                 continue;
             }
         };
+        let mut visit_diagnostics = VisitDiagnostics::default();
         let plan = match item.record {
-            Some(record) => plan_incoming_record(db, record, MAX_VISITS),
+            Some(record) => plan_incoming_record(db, record, MAX_VISITS, &mut visit_diagnostics),
             None => IncomingPlan::Delete,
         };
         let guid = item.guid.clone();
+        if let Some(diagnostics) = diagnostics.as_mut() {
+            let record_diagnostics = plan.diagnose(guid.clone(), visit_diagnostics);
+            diagnostics.records.push(record_diagnostics);
+        }
         plans.push((guid, plan));
     }
 
     let mut tx = db.begin_transaction()?;
 
     let mut outgoing = OutgoingChangeset::new("history".into(), inbound.timestamp);
+    let mut changed = false;
     for (guid, plan) in plans {
         interruptee.err_if_interrupted()?;
         tx.maybe_commit()?;
@@ -225,6 +281,7 @@ pub fn apply_plan(
                 log::trace!("incoming: deleting {:?}", guid);
                 apply_synced_deletion(&db, &guid)?;
                 telem.applied(1);
+                changed = true;
             }
             IncomingPlan::Apply {
                 url,
@@ -240,6 +297,7 @@ pub fn apply_plan(
                 );
                 apply_synced_visits(&db, &guid, &url, new_title, visits)?;
                 telem.applied(1);
+                changed = true;
             }
             IncomingPlan::Reconciled => {
                 telem.reconciled(1);
@@ -249,6 +307,11 @@ pub fn apply_plan(
         };
     }
     finish_incoming(&db)?;
+    if changed {
+        db.note_event(PlacesEvent::SyncApplied {
+            collection: "history".into(),
+        });
+    }
     tx.commit()?;
     // It might make sense for fetch_outgoing to manage its own
     // begin_transaction - even though doesn't seem a large bottleneck
@@ -347,10 +410,12 @@ mod tests {
             visits: vec![],
         };
 
-        assert!(match plan_incoming_record(&conn, record, 10) {
-            IncomingPlan::Invalid(_) => true,
-            _ => false,
-        });
+        assert!(
+            match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                IncomingPlan::Invalid(_) => true,
+                _ => false,
+            }
+        );
         Ok(())
     }
 
@@ -367,10 +432,12 @@ mod tests {
             visits: vec![],
         };
 
-        assert!(match plan_incoming_record(&conn, record, 10) {
-            IncomingPlan::Invalid(_) => true,
-            _ => false,
-        });
+        assert!(
+            match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                IncomingPlan::Invalid(_) => true,
+                _ => false,
+            }
+        );
         Ok(())
     }
 
@@ -391,10 +458,45 @@ mod tests {
             visits,
         };
 
-        assert!(match plan_incoming_record(&conn, record, 10) {
-            IncomingPlan::Apply { .. } => true,
-            _ => false,
-        });
+        assert!(
+            match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                IncomingPlan::Apply { .. } => true,
+                _ => false,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocked_url() -> Result<()> {
+        use crate::api::history::HistoryPolicy;
+        use std::sync::{Arc, RwLock};
+        let _ = env_logger::try_init();
+        let mut conn = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let policy = HistoryPolicy {
+            blocked_hosts: vec!["private.example.com".into()],
+            ..HistoryPolicy::default()
+        };
+        conn.share_history_policy(Arc::new(RwLock::new(policy.normalize()?)));
+        for url in &["about:robots", "https://www.private.example.com/"] {
+            let record = HistoryRecord {
+                id: "aaaaaaaaaaaa".into(),
+                title: "title".into(),
+                hist_uri: url.to_string(),
+                sortindex: 0,
+                ttl: 100,
+                visits: vec![HistoryRecordVisit {
+                    date: SystemTime::now().into(),
+                    transition: 1,
+                }],
+            };
+            assert!(
+                match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                    IncomingPlan::Skip => true,
+                    _ => false,
+                }
+            );
+        }
         Ok(())
     }
 
@@ -428,10 +530,12 @@ mod tests {
             visits,
         };
         // We should have reconciled it.
-        assert!(match plan_incoming_record(&conn, record, 10) {
-            IncomingPlan::Reconciled => true,
-            _ => false,
-        });
+        assert!(
+            match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                IncomingPlan::Reconciled => true,
+                _ => false,
+            }
+        );
         Ok(())
     }
 
@@ -460,10 +564,12 @@ mod tests {
         };
         // Even though there are no visits we should record that it will be
         // applied with the guid change.
-        assert!(match plan_incoming_record(&conn, record, 10) {
-            IncomingPlan::Apply { .. } => true,
-            _ => false,
-        });
+        assert!(
+            match plan_incoming_record(&conn, record, 10, &mut Default::default()) {
+                IncomingPlan::Apply { .. } => true,
+                _ => false,
+            }
+        );
     }
 
     // These "dupe" tests all do the full application of the plan and checks
@@ -509,6 +615,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(
             outgoing.changes.len(),
@@ -575,6 +682,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 1, "should have guid1 as outgoing");
         assert_eq!(outgoing.changes[0].id, guid1.as_ref());
@@ -637,6 +745,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(
             outgoing.changes.len(),
@@ -673,6 +782,7 @@ mod tests {
             result,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 0, "nothing outgoing");
 
@@ -708,6 +818,7 @@ mod tests {
             result,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 0, "should skip the invalid entry");
         Ok(())
@@ -728,7 +839,7 @@ mod tests {
             ttl: 100,
             visits,
         };
-        let plan = plan_incoming_record(&db, record, 10);
+        let plan = plan_incoming_record(&db, record, 10, &mut Default::default());
         // We expect "Reconciled" because after skipping the invalid visit
         // we found nothing to apply.
         assert!(match plan {
@@ -760,6 +871,7 @@ mod tests {
             result,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
 
         // should have applied it locally.
@@ -806,6 +918,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
 
         assert_eq!(outgoing.changes.len(), 1);
@@ -848,6 +961,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
 
         // should still have only 1 visit and it should still be local.
@@ -894,6 +1008,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
 
         // should now have both visits locally.
@@ -947,6 +1062,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 0, "should be nothing outgoing");
         assert_eq!(get_tombstone_count(&db), 0, "should be no tombstones");
@@ -970,6 +1086,7 @@ mod tests {
             IncomingChangeset::new("history".to_string(), ServerTimestamp(0i64)),
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         // It should have changed to normal but still have the initial counter.
         assert_eq!(get_sync(&db, &url), (SyncStatus::Normal, 1));
@@ -989,6 +1106,7 @@ mod tests {
             incoming,
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 0, "should be nothing outgoing");
         Ok(())
@@ -1011,6 +1129,7 @@ mod tests {
             IncomingChangeset::new("history".to_string(), ServerTimestamp(0i64)),
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         // It should have changed to normal but still have the initial counter.
         assert_eq!(get_sync(&db, &url), (SyncStatus::Normal, 1));
@@ -1026,6 +1145,7 @@ mod tests {
             IncomingChangeset::new("history".to_string(), ServerTimestamp(0i64)),
             &mut telemetry::EngineIncoming::new(),
             &NeverInterrupts,
+            None,
         )?;
         assert_eq!(outgoing.changes.len(), 1, "tombstone should be uploaded");
         finish_plan(&db)?;
@@ -1043,4 +1163,135 @@ mod tests {
         let ts = Timestamp::now();
         assert_eq!(clamp_visit_date(ts), Ok(ts));
     }
+
+    #[test]
+    fn test_apply_plan_diagnostics() -> Result<()> {
+        let _ = env_logger::try_init();
+        let db = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let url = Url::parse("https://example.com")?;
+        let now = Timestamp::now();
+
+        // Fill the page with the most visits we'll compare against.
+        let local_visits = (1..=MAX_VISITS as u64)
+            .map(|i| Timestamp(now.0 - i * 1000))
+            .collect::<Vec<_>>();
+        for date in &local_visits {
+            let obs = VisitObservation::new(url.clone())
+                .with_visit_type(VisitTransition::Link)
+                .with_at(Some(*date));
+            apply_observation(&db, obs)?;
+        }
+        let guid = get_existing_guid(&db, &url);
+
+        let mut incoming = IncomingChangeset::new("history".to_string(), ServerTimestamp(0i64));
+        let payloads = vec![
+            json!({
+                "id": guid,
+                "title": "title",
+                "histUri": url.as_str(),
+                "sortindex": 0,
+                "ttl": 100,
+                "visits": [
+                    // Already have this one.
+                    {"date": ServerVisitTimestamp::from(local_visits[0]), "type": 1},
+                    // In the future.
+                    {"date": ServerVisitTimestamp::from(Timestamp(now.0 + 60_000)), "type": 2},
+                    // Older than all our visits.
+                    {"date": ServerVisitTimestamp::from(Timestamp(now.0 - 3_600_000)), "type": 1},
+                    // Before 1993.
+                    {"date": ServerVisitTimestamp(1000), "type": 1},
+                    // Unknown transition.
+                    {"date": ServerVisitTimestamp::from(now), "type": 99},
+                ]
+            }),
+            json!({
+                "id": "bad",
+                "title": "title",
+                "histUri": "https://example.org",
+                "sortindex": 0,
+                "ttl": 100,
+                "visits": [],
+            }),
+            json!({
+                "id": "bbbbbbbbbbbb",
+                "title": "title",
+                "histUri": "not a url",
+                "sortindex": 0,
+                "ttl": 100,
+                "visits": [],
+            }),
+            json!({
+                "id": "cccccccccccc",
+                "title": "title",
+                "histUri": "about:robots",
+                "sortindex": 0,
+                "ttl": 100,
+                "visits": [{"date": ServerVisitTimestamp::from(now), "type": 1}],
+            }),
+            json!({
+                "id": "dddddddddddd",
+                "visits": "not visits",
+            }),
+            json!({
+                "id": "eeeeeeeeeeee",
+                "deleted": true,
+            }),
+        ];
+        for payload in payloads {
+            incoming
+                .changes
+                .push((Payload::from_json(payload)?, ServerTimestamp(0i64)));
+        }
+
+        let mut diagnostics = HistorySyncDiagnostics::default();
+        apply_plan(
+            &db,
+            incoming,
+            &mut telemetry::EngineIncoming::new(),
+            &NeverInterrupts,
+            Some(&mut diagnostics),
+        )?;
+
+        let records = &diagnostics.records;
+        assert_eq!(records.len(), 6);
+
+        assert_eq!(records[0].guid, guid);
+        assert_eq!(records[0].plan, PlanKind::Apply);
+        assert_eq!(records[0].reason, None);
+        assert_eq!(
+            records[0].visits,
+            VisitDiagnostics {
+                applied: 1,
+                duplicate: 1,
+                clamped: 1,
+                too_old: 1,
+                unknown_transition: 1,
+                beyond_max_visits: 1,
+            }
+        );
+
+        assert_eq!(records[1].guid.as_ref(), "bad");
+        assert_eq!(records[1].plan, PlanKind::Invalid);
+        assert!(records[1].reason.is_some());
+
+        assert_eq!(records[2].guid.as_ref(), "bbbbbbbbbbbb");
+        assert_eq!(records[2].plan, PlanKind::Invalid);
+        assert!(records[2].reason.is_some());
+
+        assert_eq!(records[3].guid.as_ref(), "cccccccccccc");
+        assert_eq!(records[3].plan, PlanKind::Skip);
+        assert_eq!(records[3].visits, VisitDiagnostics::default());
+
+        assert_eq!(records[4].guid.as_ref(), "dddddddddddd");
+        assert_eq!(records[4].plan, PlanKind::Invalid);
+
+        assert_eq!(records[5].guid.as_ref(), "eeeeeeeeeeee");
+        assert_eq!(records[5].plan, PlanKind::Delete);
+
+        // URLs and titles don't end up in the diagnostics.
+        let json = serde_json::to_string(&diagnostics)?;
+        assert!(!json.contains("example"));
+        assert!(!json.contains("robots"));
+        Ok(())
+    }
 }
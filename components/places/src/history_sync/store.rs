@@ -6,37 +6,90 @@ use crate::api::places_api::{ConnectionType, GLOBAL_STATE_META_KEY};
 use crate::db::PlacesDb;
 use crate::error::*;
 use crate::storage::history::history_sync::reset_storage;
+use crate::types::Timestamp;
 use rusqlite::types::{FromSql, ToSql};
 use rusqlite::Connection;
 use sql_support::SqlInterruptScope;
+use std::cell::{Cell, RefCell};
 use std::ops::Deref;
 use std::result;
+use std::time::Duration;
 use sync15::telemetry;
 use sync15::{
     extract_v1_state, CollSyncIds, CollectionRequest, IncomingChangeset, OutgoingChangeset,
-    ServerTimestamp, Store, StoreSyncAssociation,
+    RequestOrder, ServerTimestamp, Store, StoreSyncAssociation,
 };
 
+use super::diagnostics::HistorySyncDiagnostics;
 use super::plan::{apply_plan, finish_plan};
-use super::MAX_INCOMING_PLACES;
+use super::HistoryBackfillSettings;
 
 const LAST_SYNC_META_KEY: &str = "history_last_sync_time";
+// The server timestamp to continue backfilling from, if there are older
+// records left to download.
+const BACKFILL_CURSOR_META_KEY: &str = "history_backfill_cursor";
+// Whether the next sync should backfill, instead of fetching new records.
+const BACKFILL_TURN_META_KEY: &str = "history_backfill_turn";
+// The limit for the next backfill, if it's more than `max_records_per_sync`.
+const BACKFILL_LIMIT_META_KEY: &str = "history_backfill_limit";
 // Note that all engines in this crate should use a *different* meta key
 // for the global sync ID, because engines are reset individually.
 const GLOBAL_SYNCID_META_KEY: &str = "history_global_sync_id";
 const COLLECTION_SYNCID_META_KEY: &str = "history_sync_id";
 
+/// The kind of request we made for this sync.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FetchKind {
+    /// Records changed since the last sync.
+    Incremental,
+    /// The newest records, for a first sync.
+    Initial,
+    /// Older records than we've already fetched, continuing a first sync.
+    Backfill,
+}
+
 // A HistoryStore is short-lived and constructed each sync by something which
 // owns the connection and ClientInfo.
 pub struct HistoryStore<'a> {
     pub db: &'a PlacesDb,
     interruptee: &'a SqlInterruptScope,
+    backfill_settings: HistoryBackfillSettings,
+    fetch_kind: Cell<FetchKind>,
+    fetch_limit: Cell<usize>,
+    diagnostics: RefCell<Option<HistorySyncDiagnostics>>,
 }
 
 impl<'a> HistoryStore<'a> {
     pub fn new(db: &'a PlacesDb, interruptee: &'a SqlInterruptScope) -> Self {
         assert_eq!(db.conn_type(), ConnectionType::Sync);
-        Self { db, interruptee }
+        Self {
+            db,
+            interruptee,
+            backfill_settings: HistoryBackfillSettings::default(),
+            fetch_kind: Cell::new(FetchKind::Incremental),
+            fetch_limit: Cell::new(0),
+            diagnostics: RefCell::new(None),
+        }
+    }
+
+    pub fn with_backfill_settings(mut self, settings: HistoryBackfillSettings) -> Self {
+        self.backfill_settings = settings;
+        self
+    }
+
+    /// If `enabled`, records the plan for each incoming record. Use
+    /// `take_diagnostics` to get them after syncing.
+    pub fn with_diagnostics(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.diagnostics = RefCell::new(Some(HistorySyncDiagnostics::default()));
+        }
+        self
+    }
+
+    /// Returns the diagnostics recorded so far, or `None` if they aren't
+    /// enabled.
+    pub fn take_diagnostics(&self) -> Option<HistorySyncDiagnostics> {
+        self.diagnostics.borrow_mut().take()
     }
 
     fn put_meta(&self, key: &str, value: &dyn ToSql) -> Result<()> {
@@ -57,15 +110,62 @@ impl<'a> HistoryStore<'a> {
         telem: &mut telemetry::Engine,
     ) -> Result<OutgoingChangeset> {
         let timestamp = inbound.timestamp;
+        let fetch_kind = self.fetch_kind.get();
+        let backfill_cursor = if fetch_kind == FetchKind::Incremental {
+            None
+        } else {
+            next_backfill_page(
+                &inbound,
+                self.fetch_limit.get(),
+                self.backfill_settings.max_records_per_sync,
+            )
+        };
         let outgoing = {
             let mut incoming_telemetry = telemetry::EngineIncoming::new();
-            let result = apply_plan(&self.db, inbound, &mut incoming_telemetry, self.interruptee);
+            let mut diagnostics = self.diagnostics.borrow_mut();
+            let result = apply_plan(
+                &self.db,
+                inbound,
+                &mut incoming_telemetry,
+                self.interruptee,
+                diagnostics.as_mut(),
+            );
             telem.incoming(incoming_telemetry);
             result
         }?;
         // write the timestamp now, so if we are interrupted creating outgoing
-        // changesets we don't need to re-reconcile what we just did.
-        self.put_meta(LAST_SYNC_META_KEY, &(timestamp.as_millis() as i64))?;
+        // changesets we don't need to re-reconcile what we just did. A
+        // backfill doesn't fetch anything changed since the last sync, so it
+        // leaves the timestamp alone.
+        if fetch_kind != FetchKind::Backfill {
+            self.put_meta(LAST_SYNC_META_KEY, &(timestamp.as_millis() as i64))?;
+        }
+        match fetch_kind {
+            FetchKind::Incremental => {
+                if self.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?.is_some() {
+                    self.put_meta(BACKFILL_TURN_META_KEY, &true)?;
+                }
+            }
+            FetchKind::Initial | FetchKind::Backfill => {
+                match backfill_cursor {
+                    Some((cursor, limit)) => {
+                        log::info!("history backfill will continue from {}", cursor);
+                        self.put_meta(BACKFILL_CURSOR_META_KEY, &cursor.as_millis())?;
+                        if limit > self.backfill_settings.max_records_per_sync {
+                            self.put_meta(BACKFILL_LIMIT_META_KEY, &(limit as i64))?;
+                        } else {
+                            self.delete_meta(BACKFILL_LIMIT_META_KEY)?;
+                        }
+                    }
+                    None => {
+                        log::info!("history backfill is complete");
+                        self.delete_meta(BACKFILL_CURSOR_META_KEY)?;
+                        self.delete_meta(BACKFILL_LIMIT_META_KEY)?;
+                    }
+                }
+                self.put_meta(BACKFILL_TURN_META_KEY, &false)?;
+            }
+        }
         Ok(outgoing)
     }
 
@@ -80,8 +180,13 @@ impl<'a> HistoryStore<'a> {
         );
         finish_plan(&self.db)?;
 
-        // write timestamp to reflect what we just wrote.
-        self.put_meta(LAST_SYNC_META_KEY, &(new_timestamp.as_millis() as i64))?;
+        // write timestamp to reflect what we just wrote. As above, a backfill
+        // leaves it alone, so we'll download our own records again next time,
+        // but won't miss records changed by other clients since the last
+        // sync.
+        if self.fetch_kind.get() != FetchKind::Backfill {
+            self.put_meta(LAST_SYNC_META_KEY, &(new_timestamp.as_millis() as i64))?;
+        }
 
         self.db.pragma_update(None, "wal_checkpoint", &"PASSIVE")?;
 
@@ -92,6 +197,9 @@ impl<'a> HistoryStore<'a> {
         let tx = self.db.begin_transaction()?;
         reset_storage(self.db)?;
         self.put_meta(LAST_SYNC_META_KEY, &0)?;
+        self.delete_meta(BACKFILL_CURSOR_META_KEY)?;
+        self.delete_meta(BACKFILL_TURN_META_KEY)?;
+        self.delete_meta(BACKFILL_LIMIT_META_KEY)?;
         match assoc {
             StoreSyncAssociation::Disconnected => {
                 self.delete_meta(GLOBAL_SYNCID_META_KEY)?;
@@ -170,10 +278,53 @@ impl<'a> Store for HistoryStore<'a> {
         let since = self
             .get_meta::<i64>(LAST_SYNC_META_KEY)?
             .unwrap_or_default();
-        Ok(CollectionRequest::new("history")
-            .full()
-            .newer_than(ServerTimestamp(since))
-            .limit(MAX_INCOMING_PLACES))
+        let request = CollectionRequest::new("history").full();
+        let horizon = ServerTimestamp(
+            Timestamp::now()
+                .checked_sub(Duration::from_secs(
+                    u64::from(self.backfill_settings.horizon_days) * 24 * 60 * 60,
+                ))
+                .unwrap_or_default()
+                .as_millis() as i64,
+        );
+        let backfill_cursor = if self.get_meta(BACKFILL_TURN_META_KEY)?.unwrap_or(false) {
+            self.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?
+        } else {
+            None
+        };
+        // Only first syncs and backfills are limited. Regular syncs fetch
+        // everything changed since the last sync, since we'd never fetch
+        // the rest otherwise.
+        let (fetch_kind, limit, request) = if since == 0 {
+            let limit = self.backfill_settings.max_records_per_sync;
+            (
+                FetchKind::Initial,
+                limit,
+                request.sort_by(RequestOrder::Newest).newer_than(horizon),
+            )
+        } else if let Some(cursor) = backfill_cursor {
+            let limit = self
+                .get_meta::<i64>(BACKFILL_LIMIT_META_KEY)?
+                .map(|limit| limit as usize)
+                .unwrap_or(self.backfill_settings.max_records_per_sync);
+            (
+                FetchKind::Backfill,
+                limit,
+                request
+                    .sort_by(RequestOrder::Newest)
+                    .newer_than(horizon)
+                    .older_than(ServerTimestamp(cursor)),
+            )
+        } else {
+            (
+                FetchKind::Incremental,
+                0,
+                request.newer_than(ServerTimestamp(since)),
+            )
+        };
+        self.fetch_kind.set(fetch_kind);
+        self.fetch_limit.set(limit);
+        Ok(request.limit(limit))
     }
 
     fn get_sync_assoc(&self) -> result::Result<StoreSyncAssociation, failure::Error> {
@@ -196,3 +347,206 @@ impl<'a> Store for HistoryStore<'a> {
         Ok(())
     }
 }
+
+/// Returns the server timestamp to continue backfilling from, and the limit
+/// for the next backfill, or `None` if we fetched all the records the server
+/// has for this range. `limit` is the limit for the request we just made, and
+/// `max_records_per_sync` the configured limit.
+fn next_backfill_page(
+    inbound: &IncomingChangeset,
+    limit: usize,
+    max_records_per_sync: usize,
+) -> Option<(ServerTimestamp, usize)> {
+    if inbound.changes.len() < limit {
+        return None;
+    }
+    let oldest = inbound.changes.iter().map(|(_, ts)| ts.0).min()?;
+    let newest = inbound.changes.iter().map(|(_, ts)| ts.0).max()?;
+    // Records uploaded in the same batch share a timestamp, and we might
+    // have only fetched some of the oldest ones, so we fetch the rest of them
+    // next time. Applying the records we already have is harmless.
+    let cursor = ServerTimestamp(oldest + 1);
+    if oldest == newest {
+        // All the records have the same timestamp, so we can't page through
+        // them. Instead, we fetch them again with a bigger limit, until we
+        // get them all.
+        log::info!("history backfill found a large batch; raising the limit");
+        Some((cursor, limit.saturating_mul(2)))
+    } else {
+        Some((cursor, max_records_per_sync))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_api;
+    use sync15::Payload;
+
+    fn changeset(timestamp: i64, modified: &[i64]) -> IncomingChangeset {
+        let mut changeset = IncomingChangeset::new("history".into(), ServerTimestamp(timestamp));
+        for (i, &modified) in modified.iter().enumerate() {
+            changeset.changes.push((
+                Payload::new_tombstone(format!("{:012}", i)),
+                ServerTimestamp(modified),
+            ));
+        }
+        changeset
+    }
+
+    fn sync(store: &HistoryStore<'_>, incoming: IncomingChangeset) -> CollectionRequest {
+        let request = store
+            .get_collection_request()
+            .expect("should build request");
+        let timestamp = incoming.timestamp;
+        store
+            .apply_incoming(incoming, &mut telemetry::Engine::new("history"))
+            .expect("should apply incoming");
+        store
+            .sync_finished(timestamp, vec![])
+            .expect("should finish sync");
+        request
+    }
+
+    #[test]
+    fn test_backfill() -> Result<()> {
+        let api = new_mem_api();
+        let conn = api.open_sync_connection()?;
+        let interruptee = conn.begin_interrupt_scope();
+        let store = HistoryStore::new(&conn, &interruptee).with_backfill_settings(
+            HistoryBackfillSettings {
+                max_records_per_sync: 3,
+                horizon_days: 30,
+            },
+        );
+
+        // The first sync fetches the newest records.
+        let request = sync(&store, changeset(1000, &[1000, 900, 900]));
+        assert_eq!(request.order, Some(RequestOrder::Newest));
+        assert_eq!(request.limit, 3);
+        assert_eq!(request.older, None);
+        assert!(request.newer.unwrap().as_millis() > 0);
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, Some(901));
+
+        // Then we alternate between fetching new records and backfilling.
+        let request = sync(&store, changeset(1100, &[1100]));
+        assert_eq!(request.order, None);
+        assert_eq!(request.newer, Some(ServerTimestamp(1000)));
+
+        let request = sync(&store, changeset(1200, &[900, 900, 800]));
+        assert_eq!(request.order, Some(RequestOrder::Newest));
+        assert_eq!(request.older, Some(ServerTimestamp(901)));
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, Some(801));
+        // Backfilling doesn't change the last sync time.
+        assert_eq!(store.get_meta::<i64>(LAST_SYNC_META_KEY)?, Some(1100));
+
+        let request = sync(&store, changeset(1100, &[]));
+        assert_eq!(request.newer, Some(ServerTimestamp(1100)));
+
+        // Fetching fewer records than the limit finishes the backfill.
+        let request = sync(&store, changeset(1100, &[700]));
+        assert_eq!(request.older, Some(ServerTimestamp(801)));
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, None);
+
+        for _ in 0..2 {
+            let request = sync(&store, changeset(1100, &[]));
+            assert_eq!(request.order, None);
+            assert_eq!(request.older, None);
+            assert_eq!(request.newer, Some(ServerTimestamp(1100)));
+        }
+
+        // Resetting starts over.
+        store.do_reset(&StoreSyncAssociation::Disconnected)?;
+        let request = store
+            .get_collection_request()
+            .expect("should build request");
+        assert_eq!(request.order, Some(RequestOrder::Newest));
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_backfill_page() {
+        assert_eq!(next_backfill_page(&changeset(0, &[30, 20]), 3, 3), None);
+        assert_eq!(
+            next_backfill_page(&changeset(0, &[30, 20, 20]), 3, 3),
+            Some((ServerTimestamp(21), 3))
+        );
+        // We can't page through records with the same timestamp, so we fetch
+        // them again with a bigger limit.
+        assert_eq!(
+            next_backfill_page(&changeset(0, &[20, 20, 20]), 3, 3),
+            Some((ServerTimestamp(21), 6))
+        );
+        // Once we get past them, we go back to the configured limit.
+        assert_eq!(
+            next_backfill_page(&changeset(0, &[20, 20, 20, 20, 10, 10]), 6, 3),
+            Some((ServerTimestamp(11), 3))
+        );
+    }
+
+    #[test]
+    fn test_backfill_large_batch() -> Result<()> {
+        let api = new_mem_api();
+        let conn = api.open_sync_connection()?;
+        let interruptee = conn.begin_interrupt_scope();
+        let store = HistoryStore::new(&conn, &interruptee).with_backfill_settings(
+            HistoryBackfillSettings {
+                max_records_per_sync: 2,
+                horizon_days: 30,
+            },
+        );
+
+        // The newest records were all uploaded in one batch.
+        sync(&store, changeset(1000, &[1000, 1000]));
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, Some(1001));
+        sync(&store, changeset(1100, &[]));
+
+        // So we fetch them again, with a bigger limit, until we get past
+        // them.
+        let request = sync(&store, changeset(1100, &[1000, 1000, 1000, 1000]));
+        assert_eq!(request.older, Some(ServerTimestamp(1001)));
+        assert_eq!(request.limit, 4);
+        assert_eq!(store.get_meta::<i64>(BACKFILL_LIMIT_META_KEY)?, Some(8));
+        sync(&store, changeset(1100, &[]));
+
+        let request = sync(
+            &store,
+            changeset(1100, &[1000, 1000, 1000, 1000, 1000, 900]),
+        );
+        assert_eq!(request.older, Some(ServerTimestamp(1001)));
+        assert_eq!(request.limit, 8);
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, None);
+        assert_eq!(store.get_meta::<i64>(BACKFILL_LIMIT_META_KEY)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_not_limited() -> Result<()> {
+        let api = new_mem_api();
+        let conn = api.open_sync_connection()?;
+        let interruptee = conn.begin_interrupt_scope();
+        let store = HistoryStore::new(&conn, &interruptee).with_backfill_settings(
+            HistoryBackfillSettings {
+                max_records_per_sync: 2,
+                horizon_days: 30,
+            },
+        );
+
+        sync(&store, changeset(1000, &[1000]));
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, None);
+
+        // More records changed since the last sync than the limit, and we
+        // fetch them all.
+        let request = sync(&store, changeset(1100, &[1010, 1020, 1030, 1040, 1050]));
+        assert_eq!(request.limit, 0);
+        assert_eq!(request.order, None);
+        assert_eq!(request.newer, Some(ServerTimestamp(1000)));
+        assert_eq!(store.get_meta::<i64>(LAST_SYNC_META_KEY)?, Some(1100));
+        assert_eq!(store.get_meta::<i64>(BACKFILL_CURSOR_META_KEY)?, None);
+
+        let request = sync(&store, changeset(1200, &[]));
+        assert_eq!(request.limit, 0);
+        assert_eq!(request.newer, Some(ServerTimestamp(1100)));
+        Ok(())
+    }
+}
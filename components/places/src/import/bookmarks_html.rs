@@ -0,0 +1,788 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Import and export for the Netscape `bookmarks.html` format, which every
+//! browser can read and write. This is loosely based on Desktop's
+//! `BookmarkHTMLUtils.jsm`.
+//!
+//! The format is a fairly loose tag soup: folders are `<H3>` headings
+//! followed by a `<DL>` list of their children, bookmarks are `<A>` links,
+//! and separators are `<HR>` tags. Dates are in seconds since the epoch. We
+//! don't use a real HTML parser, since files in the wild are often malformed,
+//! and all we need is the tags.
+
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::bookmarks::{
+    fetch_tree, insert_tree_in_tx, BookmarkNode, BookmarkRootGuid, BookmarkTreeNode, FolderNode,
+    SeparatorNode,
+};
+use crate::storage::{keywords, tags, URL_LENGTH_MAX};
+use crate::types::Timestamp;
+use std::collections::HashMap;
+use std::fmt::Write;
+use url::Url;
+
+const PERSONAL_TOOLBAR_FOLDER: &str = "PERSONAL_TOOLBAR_FOLDER";
+const UNFILED_BOOKMARKS_FOLDER: &str = "UNFILED_BOOKMARKS_FOLDER";
+// Desktop doesn't export the mobile root, but we do, so that exported files
+// round-trip. Other browsers ignore unknown attributes.
+const MOBILE_BOOKMARKS_FOLDER: &str = "MOBILE_BOOKMARKS_FOLDER";
+
+/// Imports bookmarks from a `bookmarks.html` file, appending them to the
+/// existing roots. Top-level items go into the menu, and the special toolbar,
+/// "Other Bookmarks" and mobile folders are merged into their roots.
+///
+/// The structure is inserted like `insert_tree`, then tags and keywords are
+/// added for the imported URLs. Invalid URLs, tags and keywords are skipped.
+/// Everything is imported in one transaction, so a failed import doesn't
+/// leave some of the bookmarks behind.
+pub fn import_bookmarks_html(db: &PlacesDb, html: &str) -> Result<()> {
+    let mut parsed = parse_bookmarks_html(html);
+    let tx = db.begin_transaction()?;
+    for &root in &[
+        BookmarkRootGuid::Menu,
+        BookmarkRootGuid::Toolbar,
+        BookmarkRootGuid::Unfiled,
+        BookmarkRootGuid::Mobile,
+    ] {
+        let children = match parsed.roots.remove(&root) {
+            Some(children) if !children.is_empty() => children,
+            _ => continue,
+        };
+        log::debug!(
+            "Importing {} top-level items into {}",
+            children.len(),
+            root.as_str()
+        );
+        insert_tree_in_tx(
+            db,
+            &FolderNode {
+                guid: Some(root.as_guid()),
+                children,
+                ..Default::default()
+            },
+        )?;
+    }
+    for extra in &parsed.extras {
+        for tag in &extra.tags {
+            if let Err(e) = tags::tag_url_in_tx(db, &extra.url, tag) {
+                log::warn!(
+                    "Skipping invalid tag {:?} for imported bookmark: {}",
+                    tag,
+                    e
+                );
+            }
+        }
+        if let Some(keyword) = &extra.keyword {
+            if let Err(e) = keywords::set_keyword_in_tx(db, &extra.url, keyword) {
+                log::warn!(
+                    "Skipping invalid keyword {:?} for imported bookmark: {}",
+                    keyword,
+                    e
+                );
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Exports all bookmarks, with their tags and keywords, to a string in the
+/// `bookmarks.html` format. Menu items are written at the top level, followed
+/// by the other roots as special folders, like Desktop.
+pub fn export_bookmarks_html(db: &PlacesDb) -> Result<String> {
+    let mut out = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+         <!-- This is an automatically generated file.\n     \
+         It will be read and overwritten.\n     \
+         DO NOT EDIT! -->\n\
+         <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+         <TITLE>Bookmarks</TITLE>\n\
+         <H1>Bookmarks Menu</H1>\n\n\
+         <DL><p>\n",
+    );
+    let menu = fetch_root(db, BookmarkRootGuid::Menu)?;
+    write_children(db, &mut out, &menu.children, 1)?;
+    for &(root, attr) in &[
+        (BookmarkRootGuid::Toolbar, PERSONAL_TOOLBAR_FOLDER),
+        (BookmarkRootGuid::Unfiled, UNFILED_BOOKMARKS_FOLDER),
+        (BookmarkRootGuid::Mobile, MOBILE_BOOKMARKS_FOLDER),
+    ] {
+        let folder = fetch_root(db, root)?;
+        if folder.children.is_empty() {
+            continue;
+        }
+        write_folder(db, &mut out, &folder, Some(attr), 1)?;
+    }
+    out.push_str("</DL>\n");
+    Ok(out)
+}
+
+fn fetch_root(db: &PlacesDb, root: BookmarkRootGuid) -> Result<FolderNode> {
+    match fetch_tree(db, root.guid())? {
+        Some(BookmarkTreeNode::Folder(folder)) => Ok(folder),
+        _ => Err(Corruption::InvalidLocalRoots.into()),
+    }
+}
+
+fn write_children(
+    db: &PlacesDb,
+    out: &mut String,
+    children: &[BookmarkTreeNode],
+    depth: usize,
+) -> Result<()> {
+    for child in children {
+        match child {
+            BookmarkTreeNode::Bookmark(b) => write_bookmark(db, out, b, depth)?,
+            BookmarkTreeNode::Separator(_) => {
+                write_indent(out, depth);
+                out.push_str("<HR>\n");
+            }
+            BookmarkTreeNode::Folder(f) => write_folder(db, out, f, None, depth)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_folder(
+    db: &PlacesDb,
+    out: &mut String,
+    folder: &FolderNode,
+    special_attr: Option<&str>,
+    depth: usize,
+) -> Result<()> {
+    write_indent(out, depth);
+    out.push_str("<DT><H3");
+    write_dates(out, folder.date_added, folder.last_modified);
+    if let Some(attr) = special_attr {
+        write!(out, " {}=\"true\"", attr).unwrap();
+    }
+    out.push('>');
+    push_escaped(out, folder.title.as_ref().map_or("", String::as_str));
+    out.push_str("</H3>\n");
+    write_indent(out, depth);
+    out.push_str("<DL><p>\n");
+    write_children(db, out, &folder.children, depth + 1)?;
+    write_indent(out, depth);
+    out.push_str("</DL><p>\n");
+    Ok(())
+}
+
+fn write_bookmark(
+    db: &PlacesDb,
+    out: &mut String,
+    bookmark: &BookmarkNode,
+    depth: usize,
+) -> Result<()> {
+    write_indent(out, depth);
+    out.push_str("<DT><A HREF=\"");
+    push_escaped(out, bookmark.url.as_str());
+    out.push('"');
+    write_dates(out, bookmark.date_added, bookmark.last_modified);
+    if let Some(keyword) = keywords::get_keyword_for_url(db, &bookmark.url)? {
+        out.push_str(" SHORTCUTURL=\"");
+        push_escaped(out, &keyword);
+        out.push('"');
+    }
+    let tags = tags::get_tags_for_url(db, &bookmark.url)?;
+    if !tags.is_empty() {
+        out.push_str(" TAGS=\"");
+        push_escaped(out, &tags.join(","));
+        out.push('"');
+    }
+    out.push('>');
+    push_escaped(out, bookmark.title.as_ref().map_or("", String::as_str));
+    out.push_str("</A>\n");
+    Ok(())
+}
+
+fn write_dates(out: &mut String, date_added: Option<Timestamp>, last_modified: Option<Timestamp>) {
+    if let Some(date_added) = date_added {
+        write!(out, " ADD_DATE=\"{}\"", date_added.as_millis() / 1000).unwrap();
+    }
+    if let Some(last_modified) = last_modified {
+        write!(
+            out,
+            " LAST_MODIFIED=\"{}\"",
+            last_modified.as_millis() / 1000
+        )
+        .unwrap();
+    }
+}
+
+fn write_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("    ");
+    }
+}
+
+fn push_escaped(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Tags and keywords for an imported bookmark, which aren't part of the
+/// bookmark tree.
+#[derive(Debug)]
+struct BookmarkExtras {
+    url: Url,
+    tags: Vec<String>,
+    keyword: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct ParsedBookmarks {
+    roots: HashMap<BookmarkRootGuid, Vec<BookmarkTreeNode>>,
+    extras: Vec<BookmarkExtras>,
+}
+
+/// A `<DL>` list that we're currently parsing.
+enum Frame {
+    /// The top-level list, or a special folder, whose children are moved
+    /// into a root.
+    Root(BookmarkRootGuid),
+    /// A regular folder.
+    Folder(FolderNode),
+    /// A list that didn't follow a heading. Its children belong to the
+    /// parent list.
+    Anonymous(Vec<BookmarkTreeNode>),
+}
+
+/// An `<H3>` or `<A>` tag whose title we're collecting.
+enum Pending {
+    Folder(FolderNode, Option<BookmarkRootGuid>),
+    Bookmark(BookmarkNode, BookmarkExtras),
+    // A bookmark with an invalid URL, which we skip.
+    Invalid,
+}
+
+#[derive(Default)]
+struct Parser {
+    parsed: ParsedBookmarks,
+    stack: Vec<Frame>,
+    // A folder heading that we've seen, waiting for its `<DL>`.
+    header: Option<(FolderNode, Option<BookmarkRootGuid>)>,
+    pending: Option<Pending>,
+    text: String,
+}
+
+impl Parser {
+    fn start_tag(&mut self, name: &str, attrs: &HashMap<String, String>) {
+        match name {
+            "DL" | "DT" | "DD" | "H3" | "A" | "HR" => {}
+            // Other tags, like `<p>`, don't affect the structure, and
+            // formatting tags can appear in titles.
+            _ => return,
+        }
+        // Unclosed `<A>` and `<H3>` tags end at the next structural tag.
+        self.finish_pending();
+        match name {
+            "DL" => {
+                let frame = match self.header.take() {
+                    Some((_, Some(root))) => Frame::Root(root),
+                    Some((folder, None)) => Frame::Folder(folder),
+                    // The first list holds the menu.
+                    None if self.stack.is_empty() => Frame::Root(BookmarkRootGuid::Menu),
+                    None => Frame::Anonymous(Vec::new()),
+                };
+                self.stack.push(frame);
+            }
+            "H3" => {
+                self.flush_header();
+                let root = if attrs.contains_key(PERSONAL_TOOLBAR_FOLDER) {
+                    Some(BookmarkRootGuid::Toolbar)
+                } else if attrs.contains_key(UNFILED_BOOKMARKS_FOLDER) {
+                    Some(BookmarkRootGuid::Unfiled)
+                } else if attrs.contains_key(MOBILE_BOOKMARKS_FOLDER) {
+                    Some(BookmarkRootGuid::Mobile)
+                } else {
+                    None
+                };
+                let (date_added, last_modified) = parse_dates(attrs);
+                let folder = FolderNode {
+                    date_added,
+                    last_modified,
+                    ..Default::default()
+                };
+                self.pending = Some(Pending::Folder(folder, root));
+            }
+            "A" => {
+                self.flush_header();
+                let url = attrs
+                    .get("HREF")
+                    .filter(|href| href.len() <= URL_LENGTH_MAX)
+                    .and_then(|href| Url::parse(href).ok());
+                let url = match url {
+                    Some(url) => url,
+                    None => {
+                        log::warn!("Skipping imported bookmark with an invalid URL");
+                        self.pending = Some(Pending::Invalid);
+                        return;
+                    }
+                };
+                let (date_added, last_modified) = parse_dates(attrs);
+                let tags = attrs
+                    .get("TAGS")
+                    .map(|tags| {
+                        tags.split(',')
+                            .map(str::trim)
+                            .filter(|tag| !tag.is_empty())
+                            .map(str::to_owned)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let bookmark = BookmarkNode {
+                    guid: None,
+                    date_added,
+                    last_modified,
+                    title: None,
+                    url: url.clone(),
+                };
+                let extras = BookmarkExtras {
+                    url,
+                    tags,
+                    keyword: attrs.get("SHORTCUTURL").cloned(),
+                };
+                self.pending = Some(Pending::Bookmark(bookmark, extras));
+            }
+            "HR" => {
+                self.flush_header();
+                self.push_child(SeparatorNode::default().into());
+            }
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        match name {
+            "A" | "H3" => self.finish_pending(),
+            "DL" => {
+                self.finish_pending();
+                self.flush_header();
+                self.close_list();
+            }
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.pending.is_some() {
+            self.text.push_str(text);
+        }
+    }
+
+    fn finish(mut self) -> ParsedBookmarks {
+        // Close anything that was left open at the end of the file.
+        self.finish_pending();
+        self.flush_header();
+        while !self.stack.is_empty() {
+            self.close_list();
+        }
+        self.parsed
+    }
+
+    fn finish_pending(&mut self) {
+        let title = decode_entities(self.text.trim());
+        self.text.clear();
+        let title = if title.is_empty() { None } else { Some(title) };
+        match self.pending.take() {
+            Some(Pending::Folder(mut folder, root)) => {
+                folder.title = title;
+                self.header = Some((folder, root));
+            }
+            Some(Pending::Bookmark(mut bookmark, extras)) => {
+                bookmark.title = title;
+                self.push_child(bookmark.into());
+                if !extras.tags.is_empty() || extras.keyword.is_some() {
+                    self.parsed.extras.push(extras);
+                }
+            }
+            Some(Pending::Invalid) | None => {}
+        }
+    }
+
+    // Adds a heading that wasn't followed by a list as an empty folder.
+    fn flush_header(&mut self) {
+        if let Some((folder, None)) = self.header.take() {
+            self.push_child(folder.into());
+        }
+    }
+
+    fn push_child(&mut self, child: BookmarkTreeNode) {
+        match self.stack.last_mut() {
+            Some(Frame::Root(root)) => self.parsed.roots.entry(*root).or_default().push(child),
+            Some(Frame::Folder(folder)) => folder.children.push(child),
+            Some(Frame::Anonymous(children)) => children.push(child),
+            // Items outside of any list go into the menu.
+            None => self
+                .parsed
+                .roots
+                .entry(BookmarkRootGuid::Menu)
+                .or_default()
+                .push(child),
+        }
+    }
+
+    fn close_list(&mut self) {
+        match self.stack.pop() {
+            // Special folders were filled in as we went.
+            Some(Frame::Root(_)) | None => {}
+            Some(Frame::Folder(folder)) => self.push_child(folder.into()),
+            Some(Frame::Anonymous(children)) => {
+                for child in children {
+                    self.push_child(child);
+                }
+            }
+        }
+    }
+}
+
+fn parse_bookmarks_html(html: &str) -> ParsedBookmarks {
+    let mut parser = Parser::default();
+    for token in Tokenizer::new(html) {
+        match token {
+            Token::StartTag(name, attrs) => parser.start_tag(&name, &attrs),
+            Token::EndTag(name) => parser.end_tag(&name),
+            Token::Text(text) => parser.push_text(text),
+        }
+    }
+    parser.finish()
+}
+
+fn parse_dates(attrs: &HashMap<String, String>) -> (Option<Timestamp>, Option<Timestamp>) {
+    let parse = |name: &str| -> Option<Timestamp> {
+        attrs
+            .get(name)
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|&secs| secs > 0)
+            .map(|secs| Timestamp(secs.saturating_mul(1000)))
+    };
+    let date_added = parse("ADD_DATE");
+    let last_modified = parse("LAST_MODIFIED").or(date_added);
+    (date_added, last_modified)
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                entity if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                }
+                entity if entity.starts_with('#') => entity[1..]
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(std::char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// A start tag, with its uppercased name and attributes. Attribute
+    /// values have their entities decoded.
+    StartTag(String, HashMap<String, String>),
+    EndTag(String),
+    Text(&'a str),
+}
+
+struct Tokenizer<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Tokenizer { rest: html }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        loop {
+            let s = self.rest;
+            if s.is_empty() {
+                return None;
+            }
+            if !s.starts_with('<') {
+                let end = s.find('<').unwrap_or_else(|| s.len());
+                let (text, rest) = s.split_at(end);
+                self.rest = rest;
+                return Some(Token::Text(text));
+            }
+            if s.starts_with("<!--") {
+                self.rest = match s.find("-->") {
+                    Some(end) => &s[end + 3..],
+                    None => "",
+                };
+                continue;
+            }
+            // Find the end of the tag, skipping over quoted attribute values.
+            let mut quote = None;
+            let end = s.char_indices().skip(1).find(|&(_, c)| {
+                match quote {
+                    Some(q) if c == q => quote = None,
+                    Some(_) => {}
+                    None if c == '"' || c == '\'' => quote = Some(c),
+                    None => return c == '>',
+                }
+                false
+            });
+            let (tag, rest) = match end {
+                Some((end, _)) => (&s[1..end], &s[end + 1..]),
+                None => (&s[1..], ""),
+            };
+            self.rest = rest;
+            if tag.starts_with('!') || tag.starts_with('?') {
+                // `<!DOCTYPE>`, or a processing instruction.
+                continue;
+            }
+            if tag.starts_with('/') {
+                let name = tag[1..].trim().to_ascii_uppercase();
+                return Some(Token::EndTag(name));
+            }
+            return Some(parse_start_tag(tag));
+        }
+    }
+}
+
+fn parse_start_tag(tag: &str) -> Token<'_> {
+    let name_end = tag
+        .find(|c: char| c == '/' || c.is_ascii_whitespace())
+        .unwrap_or_else(|| tag.len());
+    let name = tag[..name_end].to_ascii_uppercase();
+    let mut attrs = HashMap::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_ascii_whitespace())
+            .unwrap_or_else(|| rest.len());
+        let key = rest[..key_end].to_ascii_uppercase();
+        rest = rest[key_end..].trim_start();
+        let value = if rest.starts_with('=') {
+            rest = rest[1..].trim_start();
+            let (value, remainder) = match rest.chars().next() {
+                Some(q) if q == '"' || q == '\'' => match rest[1..].find(q) {
+                    Some(end) => (&rest[1..=end], &rest[end + 2..]),
+                    None => (&rest[1..], ""),
+                },
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or_else(|| rest.len());
+                    rest.split_at(end)
+                }
+            };
+            rest = remainder.trim_start();
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+        // Skip the `/` in self-closing tags, like `<HR/>`.
+        if !key.is_empty() && key != "/" {
+            attrs.insert(key, value);
+        }
+    }
+    Token::StartTag(name, attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const HTML: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+
+<DL><p>
+    <DT><A HREF="https://www.mozilla.org/" ADD_DATE="1500000000" LAST_MODIFIED="1500000100" SHORTCUTURL="moz" TAGS="foo,bar">Mozilla &amp; friends</A>
+    <HR>
+    <DT><H3 ADD_DATE="1400000000" LAST_MODIFIED="1400000001">Folder</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/">Example</A>
+        <DD>A description that we ignore.
+        <DT><A HREF="not a url">Invalid</A>
+    </DL><p>
+    <DT><H3 PERSONAL_TOOLBAR_FOLDER="true">Bookmarks Toolbar</H3>
+    <DL><p>
+        <DT><A HREF='https://example.org/'>Toolbar bookmark</a>
+    </DL><p>
+</DL>
+"#;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = Tokenizer::new(r#"<dt><A HREF="a&amp;b" x='y>z' flag>Hi</a><!-- <HR> -->"#)
+            .collect::<Vec<_>>();
+        let mut attrs = HashMap::new();
+        attrs.insert("HREF".to_string(), "a&b".to_string());
+        attrs.insert("X".to_string(), "y>z".to_string());
+        attrs.insert("FLAG".to_string(), String::new());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::StartTag("DT".into(), HashMap::new()),
+                Token::StartTag("A".into(), attrs),
+                Token::Text("Hi"),
+                Token::EndTag("A".into()),
+            ]
+        );
+        assert_eq!(decode_entities("&lt;&#65;&#x42;&bogus; &"), "<AB&bogus; &");
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        import_bookmarks_html(&conn, HTML)?;
+
+        let menu = fetch_tree(&conn, &BookmarkRootGuid::Menu.as_guid())?.expect("should exist");
+        let expected_menu: BookmarkTreeNode = serde_json::from_value(json!({
+            "guid": &BookmarkRootGuid::Menu.as_guid(),
+            "children": [
+                {
+                    "title": "Mozilla & friends",
+                    "url": "https://www.mozilla.org/",
+                    "date_added": 1_500_000_000_000u64,
+                    "last_modified": 1_500_000_100_000u64,
+                },
+                {
+                    "type": 3,
+                },
+                {
+                    "title": "Folder",
+                    "date_added": 1_400_000_000_000u64,
+                    "last_modified": 1_400_000_001_000u64,
+                    "children": [
+                        {
+                            "title": "Example",
+                            "url": "https://example.com/",
+                        },
+                    ],
+                },
+            ],
+        }))?;
+        assert_eq!(menu, expected_menu);
+
+        let toolbar =
+            fetch_tree(&conn, &BookmarkRootGuid::Toolbar.as_guid())?.expect("should exist");
+        let expected_toolbar: BookmarkTreeNode = serde_json::from_value(json!({
+            "guid": &BookmarkRootGuid::Toolbar.as_guid(),
+            "children": [
+                {
+                    "title": "Toolbar bookmark",
+                    "url": "https://example.org/",
+                },
+            ],
+        }))?;
+        assert_eq!(toolbar, expected_toolbar);
+
+        let url = Url::parse("https://www.mozilla.org/")?;
+        let mut tags = tags::get_tags_for_url(&conn, &url)?;
+        tags.sort();
+        assert_eq!(tags, vec!["bar".to_string(), "foo".to_string()]);
+        assert_eq!(
+            keywords::get_keyword_for_url(&conn, &url)?,
+            Some("moz".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        import_bookmarks_html(&conn, HTML)?;
+        let exported = export_bookmarks_html(&conn)?;
+
+        let conn2 = new_mem_connection();
+        import_bookmarks_html(&conn2, &exported)?;
+        for &root in &[BookmarkRootGuid::Menu, BookmarkRootGuid::Toolbar] {
+            let mut children = fetch_root(&conn, root)?.children;
+            let mut children2 = fetch_root(&conn2, root)?.children;
+            children.iter_mut().for_each(clear_generated_fields);
+            children2.iter_mut().for_each(clear_generated_fields);
+            assert_eq!(children, children2);
+        }
+        let url = Url::parse("https://www.mozilla.org/")?;
+        assert_eq!(
+            keywords::get_keyword_for_url(&conn2, &url)?,
+            Some("moz".to_string())
+        );
+        assert_eq!(tags::get_tags_for_url(&conn2, &url)?.len(), 2);
+        Ok(())
+    }
+
+    // GUIDs differ between the trees, separators don't have dates in the
+    // file, and other dates are only stored to the second.
+    fn clear_generated_fields(node: &mut BookmarkTreeNode) {
+        fn truncate(date: &mut Option<Timestamp>) {
+            *date = date.map(|d| Timestamp(d.as_millis() / 1000 * 1000));
+        }
+        match node {
+            BookmarkTreeNode::Bookmark(b) => {
+                b.guid = None;
+                truncate(&mut b.date_added);
+                truncate(&mut b.last_modified);
+            }
+            BookmarkTreeNode::Separator(s) => {
+                s.guid = None;
+                s.date_added = None;
+                s.last_modified = None;
+            }
+            BookmarkTreeNode::Folder(f) => {
+                f.guid = None;
+                truncate(&mut f.date_added);
+                truncate(&mut f.last_modified);
+                for child in &mut f.children {
+                    clear_generated_fields(child);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,802 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::common::{attached_database, define_sql_functions, ExecuteOnDrop};
+use crate::api::places_api::PlacesApi;
+use crate::bookmark_sync::store::BookmarksStore;
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::{delete_pending_temp_tables, keywords::validate_keyword, RowId};
+use crate::types::{BookmarkType, SyncStatus, Timestamp, VisitTransition};
+use rusqlite::{named_params, NO_PARAMS};
+use serde_derive::*;
+use sql_support::ConnExt;
+use url::Url;
+
+/// The number of rows we saw in one of the desktop tables, and what happened
+/// to them. Rows that were neither imported nor skipped already existed
+/// locally, usually because the same database was imported before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct TableImportCounts {
+    /// The number of rows in the desktop table that we looked at.
+    pub total: u32,
+    /// The number of rows that we added to our database.
+    pub imported: u32,
+    /// The number of rows that we couldn't import, because they were invalid
+    /// or referenced something that we skipped.
+    pub skipped: u32,
+}
+
+/// Per-table results of a desktop `places.sqlite` import.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DesktopImportMetrics {
+    pub places: TableImportCounts,
+    pub visits: TableImportCounts,
+    pub bookmarks: TableImportCounts,
+    pub keywords: TableImportCounts,
+    pub input_history: TableImportCounts,
+}
+
+/// Imports history and bookmarks from a desktop Firefox `places.sqlite`,
+/// merging them into our database.
+///
+/// This is used for users moving their profile over from Desktop, and, like
+/// the iOS import, is meant for users who aren't connected to Sync.
+///
+/// ### Basic process
+///
+/// - Attach the desktop database.
+/// - Slurp valid places into a temp table "desktopPlacesStaging", and add the
+///   ones we don't already have to moz_places. Desktop's `moz_places.id`s are
+///   mapped to our ids via the staging table for all the tables below.
+/// - Stage and insert visits, converting their dates from microseconds to
+///   milliseconds, and then remap the referring visits.
+/// - Stage the bookmarks under the four user content roots, and insert them
+///   level by level, appending them to any existing children of their
+///   parents. Desktop's tags root is skipped; the tag folders below it are
+///   turned into tags instead.
+/// - Import keywords without POST data, and input history.
+/// - Recalculate frecencies and origins for all the places we touched.
+/// - Cleanup (drop the staging tables, detach the desktop database).
+///
+/// Items that we already have, by GUID for bookmarks and by URL for places,
+/// are left as they are, so importing the same database twice is harmless.
+pub fn import_desktop_places(
+    places_api: &PlacesApi,
+    path: impl AsRef<std::path::Path>,
+) -> Result<DesktopImportMetrics> {
+    let url = crate::util::ensure_url_path(path)?;
+    do_import_desktop_places(places_api, url)
+}
+
+fn do_import_desktop_places(
+    places_api: &PlacesApi,
+    desktop_db_file_url: Url,
+) -> Result<DesktopImportMetrics> {
+    let conn = places_api.open_sync_connection()?;
+
+    let scope = conn.begin_interrupt_scope();
+
+    define_sql_functions(&conn)?;
+
+    log::trace!("Attaching database {}", desktop_db_file_url);
+    let auto_detach = attached_database(&conn, &desktop_db_file_url, "desktop")?;
+
+    let tx = conn.begin_transaction()?;
+
+    let drop_staging_on_drop = ExecuteOnDrop::new(&conn, DROP_STAGING_TABLES);
+
+    log::debug!("Creating staging tables");
+    conn.execute_batch(&CREATE_STAGING_TABLES)?;
+
+    let mut metrics = DesktopImportMetrics::default();
+
+    log::debug!("Importing places");
+    metrics.places = import_places(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Importing visits");
+    metrics.visits = import_visits(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Importing bookmarks");
+    metrics.bookmarks = import_bookmarks(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Importing tags");
+    import_tags(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Importing keywords");
+    metrics.keywords = import_keywords(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Importing input history");
+    metrics.input_history = import_input_history(&conn)?;
+    scope.err_if_interrupted()?;
+
+    log::debug!("Marking frecencies as stale");
+    conn.execute_batch(
+        "INSERT OR REPLACE INTO main.moz_places_stale_frecencies(place_id, stale_at)
+         SELECT localId, now() FROM temp.desktopPlacesStaging
+         WHERE localId NOT NULL",
+    )?;
+
+    log::debug!("Updating origins");
+    delete_pending_temp_tables(&conn)?;
+
+    log::debug!("Cleaning up staging tables...");
+    drop_staging_on_drop.execute_now()?;
+    log::debug!("Committing...");
+    tx.commit()?;
+
+    // Note: update_frecencies manages its own transaction, which is fine,
+    // since nothing that bad will happen if it is aborted.
+    log::debug!("Updating frecencies");
+    let store = BookmarksStore::new(&conn, &scope);
+    store.update_frecencies()?;
+    delete_pending_temp_tables(&conn)?;
+
+    log::info!("Successfully imported desktop places: {:?}", metrics);
+
+    auto_detach.execute_now()?;
+
+    Ok(metrics)
+}
+
+fn count(db: &PlacesDb, sql: &str) -> Result<u32> {
+    Ok(db.query_one::<u32>(sql)?)
+}
+
+fn import_places(db: &PlacesDb) -> Result<TableImportCounts> {
+    let total = count(db, "SELECT COUNT(*) FROM desktop.moz_places")?;
+    db.execute_batch(
+        "INSERT INTO temp.desktopPlacesStaging(id, url, guid, title, hidden, typed)
+         SELECT id, url, guid, title, hidden, typed
+         FROM (SELECT p.id, validate_url(p.url) AS url, p.guid, p.title,
+                      p.hidden, p.typed
+               FROM desktop.moz_places p)
+         WHERE url NOT NULL",
+    )?;
+    let staged = count(db, "SELECT COUNT(*) FROM temp.desktopPlacesStaging")?;
+
+    // Desktop URLs are unique, but they might not be after we normalize
+    // them, so we only insert one place per URL. We keep the desktop GUID
+    // unless it's taken.
+    let imported = db.execute(
+        &format!(
+            "INSERT INTO main.moz_places(guid, url, url_hash, title, hidden, typed,
+                                         frecency, sync_status)
+             SELECT CASE WHEN length(s.guid) = 12 AND
+                              NOT EXISTS(SELECT 1 FROM main.moz_places h
+                                         WHERE h.guid = s.guid)
+                         THEN s.guid
+                         ELSE generate_guid()
+                    END,
+                    s.url, hash(s.url), s.title, IFNULL(s.hidden, 0),
+                    IFNULL(s.typed, 0), -1, {new}
+             FROM temp.desktopPlacesStaging s
+             WHERE s.id IN (SELECT MIN(id) FROM temp.desktopPlacesStaging
+                            GROUP BY url)
+               AND NOT EXISTS(SELECT 1 FROM main.moz_places h
+                              WHERE h.url_hash = hash(s.url) AND h.url = s.url)",
+            new = SyncStatus::New as u8
+        ),
+        NO_PARAMS,
+    )?;
+
+    db.execute_batch(
+        "UPDATE temp.desktopPlacesStaging SET
+           localId = (SELECT h.id FROM main.moz_places h
+                      WHERE h.url_hash = hash(desktopPlacesStaging.url) AND
+                            h.url = desktopPlacesStaging.url)",
+    )?;
+
+    Ok(TableImportCounts {
+        total,
+        imported: imported as u32,
+        skipped: total - staged,
+    })
+}
+
+fn import_visits(db: &PlacesDb) -> Result<TableImportCounts> {
+    let total = count(db, "SELECT COUNT(*) FROM desktop.moz_historyvisits")?;
+    // Desktop visit dates are in microseconds. Unlike bookmark dates, we
+    // don't clamp bad ones to now, since that would make them look like
+    // recent history.
+    db.execute_batch(&format!(
+        "INSERT INTO temp.desktopVisitsStaging(id, fromVisit, placeId, visitDate,
+                                               visitType)
+         SELECT v.id, v.from_visit, s.localId, v.visit_date / 1000, v.visit_type
+         FROM desktop.moz_historyvisits v
+         JOIN temp.desktopPlacesStaging s ON s.id = v.place_id
+         WHERE v.visit_type BETWEEN {min_type} AND {max_type}
+           AND v.visit_date / 1000 > {earliest}
+           AND v.visit_date / 1000 <= now()",
+        min_type = VisitTransition::Link as u8,
+        max_type = VisitTransition::Reload as u8,
+        earliest = Timestamp::EARLIEST.0,
+    ))?;
+    let staged = count(db, "SELECT COUNT(*) FROM temp.desktopVisitsStaging")?;
+
+    let max_visit_id =
+        db.query_one::<RowId>("SELECT IFNULL(MAX(id), 0) FROM main.moz_historyvisits")?;
+    let imported = db.execute(
+        "INSERT INTO main.moz_historyvisits(place_id, visit_date, visit_type, is_local)
+         SELECT v.placeId, v.visitDate, v.visitType, 1
+         FROM temp.desktopVisitsStaging v
+         WHERE v.id IN (SELECT MIN(id) FROM temp.desktopVisitsStaging
+                        GROUP BY placeId, visitDate)
+           AND NOT EXISTS(SELECT 1 FROM main.moz_historyvisits h
+                          WHERE h.place_id = v.placeId AND
+                                h.visit_date = v.visitDate)",
+        NO_PARAMS,
+    )?;
+
+    // Now that all the visits have ids, hook up the referrers for the new
+    // ones.
+    db.execute_batch(
+        "UPDATE temp.desktopVisitsStaging SET
+           localId = (SELECT MAX(h.id) FROM main.moz_historyvisits h
+                      WHERE h.place_id = desktopVisitsStaging.placeId AND
+                            h.visit_date = desktopVisitsStaging.visitDate)",
+    )?;
+    db.execute_named_cached(
+        "UPDATE main.moz_historyvisits SET
+           from_visit = (SELECT f.localId
+                         FROM temp.desktopVisitsStaging v
+                         JOIN temp.desktopVisitsStaging f ON f.id = v.fromVisit
+                         WHERE v.localId = moz_historyvisits.id)
+         WHERE id > :max_visit_id",
+        named_params! { ":max_visit_id": max_visit_id },
+    )?;
+
+    // Make sure history sync uploads the new visits.
+    db.execute_named_cached(
+        "UPDATE main.moz_places SET
+           sync_change_counter = sync_change_counter + 1
+         WHERE id IN (SELECT place_id FROM main.moz_historyvisits
+                      WHERE id > :max_visit_id)",
+        named_params! { ":max_visit_id": max_visit_id },
+    )?;
+
+    Ok(TableImportCounts {
+        total,
+        imported: imported as u32,
+        skipped: total - staged,
+    })
+}
+
+fn import_bookmarks(db: &PlacesDb) -> Result<TableImportCounts> {
+    db.execute_batch(&POPULATE_BOOKMARKS_STAGING)?;
+    let total = count(db, "SELECT COUNT(*) FROM temp.desktopBookmarksStaging")?;
+    let existing = count(
+        db,
+        "SELECT COUNT(*) FROM temp.desktopBookmarksStaging WHERE existed",
+    )?;
+
+    let max_bookmark_id =
+        db.query_one::<RowId>("SELECT IFNULL(MAX(id), 0) FROM main.moz_bookmarks")?;
+    let max_level =
+        db.query_one::<u32>("SELECT IFNULL(MAX(level), 0) FROM temp.desktopBookmarksStaging")?;
+
+    // Parents need to exist before we can insert their children, so we go
+    // one level at a time. Items whose parent we skipped are skipped, too.
+    let mut imported = 0;
+    for level in 1..=max_level {
+        imported += db.execute_named_cached(
+            &INSERT_BOOKMARKS_FOR_LEVEL,
+            named_params! { ":level": level },
+        )?;
+    }
+
+    // Folders that already existed have new children, so they need to be
+    // uploaded again.
+    db.execute_named_cached(
+        "UPDATE main.moz_bookmarks SET
+           syncChangeCounter = syncChangeCounter + 1,
+           lastModified = now()
+         WHERE id <= :max_bookmark_id
+           AND id IN (SELECT parent FROM main.moz_bookmarks
+                      WHERE id > :max_bookmark_id)",
+        named_params! { ":max_bookmark_id": max_bookmark_id },
+    )?;
+
+    Ok(TableImportCounts {
+        total,
+        imported: imported as u32,
+        skipped: total - existing - imported as u32,
+    })
+}
+
+// Tags on Desktop are folders under the tags root, containing a bookmark
+// for each tagged URL.
+fn import_tags(db: &PlacesDb) -> Result<()> {
+    use crate::storage::tags::{validate_tag, ValidatedTag};
+    let tagged: Vec<(String, RowId)> = db.query_rows_and_then_named(
+        &format!(
+            "SELECT t.title, s.localId
+             FROM desktop.moz_bookmarks r
+             JOIN desktop.moz_bookmarks t ON t.parent = r.id
+             JOIN desktop.moz_bookmarks b ON b.parent = t.id
+             JOIN temp.desktopPlacesStaging s ON s.id = b.fk
+             WHERE r.guid = 'tags________'
+               AND t.type = {folder_type}
+               AND b.type = {bookmark_type}
+               AND t.title NOT NULL",
+            folder_type = BookmarkType::Folder as u8,
+            bookmark_type = BookmarkType::Bookmark as u8,
+        ),
+        &[],
+        |row| -> Result<_> { Ok((row.get(0)?, row.get(1)?)) },
+    )?;
+    let mut tagged_count = 0;
+    for (tag, place_id) in tagged {
+        let tag = match validate_tag(&tag) {
+            ValidatedTag::Invalid(_) => {
+                log::warn!("Ignoring invalid tag");
+                log::trace!(" Bad tag was: {:?}", tag);
+                continue;
+            }
+            ValidatedTag::Original(t) | ValidatedTag::Normalized(t) => t.to_owned(),
+        };
+        db.execute_named_cached(
+            "INSERT OR IGNORE INTO main.moz_tags(tag, lastModified) VALUES(:tag, now())",
+            named_params! { ":tag": tag },
+        )?;
+        let changes = db.execute_named_cached(
+            "INSERT OR IGNORE INTO main.moz_tags_relation(tag_id, place_id)
+             VALUES((SELECT id FROM main.moz_tags WHERE tag = :tag), :place_id)",
+            named_params! { ":tag": tag, ":place_id": place_id },
+        )?;
+        if changes > 0 {
+            tagged_count += 1;
+            // Tags are synced as part of the bookmark record.
+            db.execute_named_cached(
+                "UPDATE main.moz_bookmarks SET
+                   syncChangeCounter = syncChangeCounter + 1
+                 WHERE fk = :place_id",
+                named_params! { ":place_id": place_id },
+            )?;
+        }
+    }
+    log::debug!("Added {} tags", tagged_count);
+    Ok(())
+}
+
+fn import_keywords(db: &PlacesDb) -> Result<TableImportCounts> {
+    let keywords: Vec<(String, Option<RowId>, bool)> = db.query_rows_and_then_named(
+        "SELECT k.keyword, s.localId, k.post_data NOT NULL
+         FROM desktop.moz_keywords k
+         LEFT JOIN temp.desktopPlacesStaging s ON s.id = k.place_id",
+        &[],
+        |row| -> Result<_> { Ok((row.get(0)?, row.get(1)?, row.get(2)?)) },
+    )?;
+    let mut counts = TableImportCounts {
+        total: keywords.len() as u32,
+        ..TableImportCounts::default()
+    };
+    for (keyword, place_id, has_post_data) in keywords {
+        // We don't support POST data, and keywords are only valid for
+        // bookmarked URLs.
+        let (keyword, place_id) = match (validate_keyword(&keyword), place_id) {
+            (Ok(keyword), Some(place_id)) if !has_post_data => (keyword, place_id),
+            _ => {
+                counts.skipped += 1;
+                continue;
+            }
+        };
+        let existed = db.try_query_row(
+            "SELECT 1 FROM main.moz_keywords
+             WHERE keyword = :keyword AND place_id = :place_id",
+            named_params! { ":keyword": keyword, ":place_id": place_id },
+            |row| row.get::<_, i64>(0),
+            true,
+        )?;
+        if existed.is_some() {
+            continue;
+        }
+        // Unlike `set_keyword`, we don't replace keywords that are already
+        // used locally.
+        let changes = db.execute_named_cached(
+            "INSERT OR IGNORE INTO main.moz_keywords(keyword, place_id)
+             SELECT :keyword, :place_id
+             WHERE EXISTS(SELECT 1 FROM main.moz_bookmarks
+                          WHERE fk = :place_id)",
+            named_params! { ":keyword": keyword, ":place_id": place_id },
+        )?;
+        if changes == 0 {
+            counts.skipped += 1;
+            continue;
+        }
+        counts.imported += 1;
+        db.execute_named_cached(
+            "UPDATE main.moz_bookmarks SET
+               syncChangeCounter = syncChangeCounter + 1
+             WHERE fk = :place_id",
+            named_params! { ":place_id": place_id },
+        )?;
+    }
+    Ok(counts)
+}
+
+fn import_input_history(db: &PlacesDb) -> Result<TableImportCounts> {
+    let total = count(db, "SELECT COUNT(*) FROM desktop.moz_inputhistory")?;
+    let mapped = count(
+        db,
+        "SELECT COUNT(*) FROM desktop.moz_inputhistory i
+         JOIN temp.desktopPlacesStaging s ON s.id = i.place_id",
+    )?;
+    // Keep the higher use count for inputs we already have.
+    db.execute_batch(
+        "UPDATE main.moz_inputhistory SET
+           use_count = (SELECT MAX(i.use_count)
+                        FROM desktop.moz_inputhistory i
+                        JOIN temp.desktopPlacesStaging s ON s.id = i.place_id
+                        WHERE s.localId = main.moz_inputhistory.place_id AND
+                              i.input = main.moz_inputhistory.input)
+         WHERE use_count < (SELECT MAX(i.use_count)
+                            FROM desktop.moz_inputhistory i
+                            JOIN temp.desktopPlacesStaging s ON s.id = i.place_id
+                            WHERE s.localId = main.moz_inputhistory.place_id AND
+                                  i.input = main.moz_inputhistory.input)",
+    )?;
+    let imported = db.execute(
+        "INSERT OR IGNORE INTO main.moz_inputhistory(place_id, input, use_count)
+         SELECT s.localId, i.input, i.use_count
+         FROM desktop.moz_inputhistory i
+         JOIN temp.desktopPlacesStaging s ON s.id = i.place_id",
+        NO_PARAMS,
+    )?;
+    Ok(TableImportCounts {
+        total,
+        imported: imported as u32,
+        skipped: total - mapped,
+    })
+}
+
+const DROP_STAGING_TABLES: &str = "
+    DROP TABLE IF EXISTS temp.desktopPlacesStaging;
+    DROP TABLE IF EXISTS temp.desktopVisitsStaging;
+    DROP TABLE IF EXISTS temp.desktopBookmarksStaging;
+";
+
+const ROOTS: &str = "('menu________', 'toolbar_____', 'unfiled_____', 'mobile______')";
+
+lazy_static::lazy_static! {
+    static ref CREATE_STAGING_TABLES: String = format!(
+        "{drop}
+        CREATE TEMP TABLE desktopPlacesStaging(
+            id INTEGER PRIMARY KEY, -- The desktop id.
+            url TEXT NOT NULL,
+            guid TEXT,
+            title TEXT,
+            hidden INTEGER,
+            typed INTEGER,
+            localId INTEGER
+        );
+        CREATE INDEX temp.desktopPlacesStagingUrlIndex
+            ON desktopPlacesStaging(url);
+
+        CREATE TEMP TABLE desktopVisitsStaging(
+            id INTEGER PRIMARY KEY, -- The desktop id.
+            fromVisit INTEGER, -- The desktop id of the referring visit.
+            placeId INTEGER NOT NULL,
+            visitDate INTEGER NOT NULL,
+            visitType INTEGER NOT NULL,
+            localId INTEGER
+        );
+        CREATE INDEX temp.desktopVisitsStagingLocalIdIndex
+            ON desktopVisitsStaging(localId);
+
+        CREATE TEMP TABLE desktopBookmarksStaging(
+            id INTEGER PRIMARY KEY, -- The desktop id.
+            guid TEXT NOT NULL,
+            parentGuid TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            type INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            title TEXT,
+            placeId INTEGER,
+            dateAdded INTEGER NOT NULL,
+            lastModified INTEGER NOT NULL,
+            -- Whether we already have an item with this GUID.
+            existed BOOLEAN NOT NULL,
+            -- Whether we can insert this item, assuming its parent exists.
+            insertable BOOLEAN NOT NULL
+        );
+        CREATE INDEX temp.desktopBookmarksStagingParentIndex
+            ON desktopBookmarksStaging(parentGuid, position);",
+        drop = DROP_STAGING_TABLES,
+    );
+
+    // Collects everything under the user content roots. Bookmarks for URLs
+    // we couldn't import, and types we don't support (like Desktop's
+    // long-deprecated dynamic containers) are staged, but not insertable.
+    static ref POPULATE_BOOKMARKS_STAGING: String = format!(
+        "WITH RECURSIVE items(id, level) AS (
+           SELECT b.id, 0 FROM desktop.moz_bookmarks b
+           WHERE b.guid IN {roots}
+           UNION ALL
+           SELECT b.id, i.level + 1
+           FROM desktop.moz_bookmarks b
+           JOIN items i ON b.parent = i.id
+         )
+         INSERT INTO temp.desktopBookmarksStaging(id, guid, parentGuid, level,
+                                                  type, position, title, placeId,
+                                                  dateAdded, lastModified,
+                                                  existed, insertable)
+         SELECT b.id, b.guid, p.guid, i.level, b.type, b.position, b.title,
+                s.localId, sanitize_timestamp(b.dateAdded / 1000),
+                sanitize_timestamp(b.lastModified / 1000),
+                EXISTS(SELECT 1 FROM main.moz_bookmarks e WHERE e.guid = b.guid),
+                length(b.guid) = 12 AND
+                CASE b.type
+                  WHEN {bookmark_type} THEN s.localId NOT NULL
+                  WHEN {folder_type} THEN 1
+                  WHEN {separator_type} THEN 1
+                  ELSE 0
+                END
+         FROM items i
+         JOIN desktop.moz_bookmarks b ON b.id = i.id
+         JOIN desktop.moz_bookmarks p ON p.id = b.parent
+         LEFT JOIN temp.desktopPlacesStaging s ON s.id = b.fk
+         WHERE i.level > 0",
+        roots = ROOTS,
+        bookmark_type = BookmarkType::Bookmark as u8,
+        folder_type = BookmarkType::Folder as u8,
+        separator_type = BookmarkType::Separator as u8,
+    );
+
+    // New items are appended after the existing children of their parent,
+    // in their original order. The position subqueries are evaluated before
+    // any rows are inserted, since we read from the table we're inserting
+    // into.
+    static ref INSERT_BOOKMARKS_FOR_LEVEL: String = format!(
+        "INSERT INTO main.moz_bookmarks(guid, type, fk, parent, position, title,
+                                        dateAdded, lastModified, syncStatus,
+                                        syncChangeCounter)
+         SELECT s.guid, s.type, s.placeId, p.id,
+                (SELECT COUNT(*) FROM main.moz_bookmarks c
+                 WHERE c.parent = p.id) +
+                (SELECT COUNT(*) FROM temp.desktopBookmarksStaging o
+                 WHERE o.parentGuid = s.parentGuid AND
+                       o.insertable AND NOT o.existed AND
+                       (o.position < s.position OR
+                        (o.position = s.position AND o.id < s.id))),
+                CASE WHEN s.type = {separator_type} THEN NULL ELSE s.title END,
+                s.dateAdded, MAX(s.lastModified, s.dateAdded), {new}, 1
+         FROM temp.desktopBookmarksStaging s
+         JOIN main.moz_bookmarks p ON p.guid = s.parentGuid
+         WHERE s.level = :level AND
+               s.insertable AND NOT s.existed AND
+               p.type = {folder_type}",
+        separator_type = BookmarkType::Separator as u8,
+        folder_type = BookmarkType::Folder as u8,
+        new = SyncStatus::New as u8,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::{test::new_mem_api, ConnectionType};
+    use crate::storage::bookmarks::{
+        fetch_tree, insert_bookmark, BookmarkPosition, BookmarkRootGuid, BookmarkTreeNode,
+        InsertableBookmark, InsertableItem,
+    };
+    use crate::storage::history::get_visit_chain_for_url;
+    use crate::storage::keywords::get_keyword_for_url;
+    use crate::storage::tags::get_tags_for_url;
+    use crate::types::SyncGuid;
+    use rusqlite::Connection;
+
+    // Enough of the desktop schema for our import.
+    const DESKTOP_SCHEMA: &str = "
+        CREATE TABLE moz_places(
+            id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+            rev_host LONGVARCHAR, visit_count INTEGER DEFAULT 0,
+            hidden INTEGER DEFAULT 0 NOT NULL, typed INTEGER DEFAULT 0 NOT NULL,
+            frecency INTEGER DEFAULT -1 NOT NULL, last_visit_date INTEGER,
+            guid TEXT, foreign_count INTEGER DEFAULT 0 NOT NULL,
+            url_hash INTEGER DEFAULT 0 NOT NULL, description TEXT,
+            preview_image_url TEXT, origin_id INTEGER
+        );
+        CREATE TABLE moz_historyvisits(
+            id INTEGER PRIMARY KEY, from_visit INTEGER, place_id INTEGER,
+            visit_date INTEGER, visit_type INTEGER, session INTEGER
+        );
+        CREATE TABLE moz_inputhistory(
+            place_id INTEGER NOT NULL, input LONGVARCHAR NOT NULL,
+            use_count INTEGER, PRIMARY KEY (place_id, input)
+        );
+        CREATE TABLE moz_bookmarks(
+            id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER DEFAULT NULL,
+            parent INTEGER, position INTEGER, title LONGVARCHAR,
+            keyword_id INTEGER, folder_type TEXT, dateAdded INTEGER,
+            lastModified INTEGER, guid TEXT, syncStatus INTEGER NOT NULL DEFAULT 0,
+            syncChangeCounter INTEGER NOT NULL DEFAULT 1
+        );
+        CREATE TABLE moz_keywords(
+            id INTEGER PRIMARY KEY AUTOINCREMENT, keyword TEXT UNIQUE,
+            place_id INTEGER, post_data TEXT
+        );
+        INSERT INTO moz_bookmarks(id, type, parent, position, title, guid,
+                                  dateAdded, lastModified)
+        VALUES(1, 2, 0, 0, '', 'root________', 0, 0),
+              (2, 2, 1, 0, 'menu', 'menu________', 0, 0),
+              (3, 2, 1, 1, 'toolbar', 'toolbar_____', 0, 0),
+              (4, 2, 1, 2, 'tags', 'tags________', 0, 0),
+              (5, 2, 1, 3, 'unfiled', 'unfiled_____', 0, 0),
+              (6, 2, 1, 4, 'mobile', 'mobile______', 0, 0);
+    ";
+
+    // 2019-05-01, in microseconds.
+    const VISIT_DATE_US: i64 = 1_556_668_800_000_000;
+
+    fn create_desktop_db(path: &std::path::Path) -> Result<()> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(DESKTOP_SCHEMA)?;
+        conn.execute_batch(&format!(
+            "INSERT INTO moz_places(id, url, title, guid, typed)
+             VALUES(1, 'https://www.example.com/', 'Example', 'placeAAAAAAA', 1),
+                   (2, 'https://www.example.com/page', 'Page', 'placeBBBBBBB', 0),
+                   (3, 'https://www.mozilla.org/', 'Mozilla', 'placeCCCCCCC', 0),
+                   (4, 'not a url', 'Invalid', 'placeDDDDDDD', 0),
+                   (5, 'https://search.example.com/?q=%s', NULL, 'placeEEEEEEE', 0);
+
+             INSERT INTO moz_historyvisits(id, from_visit, place_id, visit_date, visit_type)
+             VALUES(1, 0, 1, {date}, 2),
+                   (2, 1, 2, {date} + 1000000, 1),
+                   (3, 0, 4, {date}, 1),
+                   (4, 0, 3, 0, 1);
+
+             INSERT INTO moz_bookmarks(id, type, fk, parent, position, title, guid,
+                                       dateAdded, lastModified)
+             VALUES(10, 2, NULL, 3, 0, 'Folder', 'folderAAAAAA', {date}, {date}),
+                    (11, 1, 3, 10, 0, 'Mozilla', 'bookmarkAAAA', {date}, {date}),
+                    (12, 3, NULL, 10, 1, NULL, 'separatorAAA', {date}, {date}),
+                    (13, 1, 4, 10, 2, 'Invalid', 'bookmarkBBBB', {date}, {date}),
+                    (14, 1, 5, 5, 0, 'Search', 'bookmarkCCCC', {date}, {date}),
+                    -- Tag folder and tag entry.
+                    (20, 2, NULL, 4, 0, 'cool', 'tagfolderAAA', {date}, {date}),
+                    (21, 1, 3, 20, 0, NULL, 'tagentryAAAA', {date}, {date});
+
+             INSERT INTO moz_keywords(id, keyword, place_id, post_data)
+             VALUES(1, 'S', 5, NULL),
+                   (2, 'post', 3, 'q=%s');
+
+             INSERT INTO moz_inputhistory(place_id, input, use_count)
+             VALUES(1, 'exa', 3),
+                   (4, 'not', 1);",
+            date = VISIT_DATE_US,
+        ))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let _ = env_logger::try_init();
+        let tmpdir = tempfile::tempdir().unwrap();
+        let desktop_path = tmpdir.path().join("places.sqlite");
+        create_desktop_db(&desktop_path)?;
+
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite)?;
+
+        // An existing bookmark in the toolbar, so we can check that imported
+        // items are appended.
+        let existing_url = Url::parse("https://www.example.com/")?;
+        insert_bookmark(
+            &conn,
+            &InsertableItem::Bookmark(InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Toolbar.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: existing_url.clone(),
+                title: None,
+            }),
+        )?;
+
+        let metrics = import_desktop_places(&api, &desktop_path)?;
+        assert_eq!(
+            metrics,
+            DesktopImportMetrics {
+                // The invalid URL is skipped, and example.com already exists.
+                places: TableImportCounts {
+                    total: 5,
+                    imported: 3,
+                    skipped: 1,
+                },
+                // One visit is for the invalid URL, and one has a bad date.
+                visits: TableImportCounts {
+                    total: 4,
+                    imported: 2,
+                    skipped: 2,
+                },
+                // The bookmark for the invalid URL is skipped.
+                bookmarks: TableImportCounts {
+                    total: 5,
+                    imported: 4,
+                    skipped: 1,
+                },
+                // We don't support POST data.
+                keywords: TableImportCounts {
+                    total: 2,
+                    imported: 1,
+                    skipped: 1,
+                },
+                input_history: TableImportCounts {
+                    total: 2,
+                    imported: 1,
+                    skipped: 1,
+                },
+            }
+        );
+
+        // The referrer is remapped to the new visit.
+        let page_url = Url::parse("https://www.example.com/page")?;
+        let chain = get_visit_chain_for_url(&conn, &page_url)?.infos;
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].url, existing_url.as_str());
+        assert_eq!(chain[1].url, page_url.as_str());
+
+        let toolbar = match fetch_tree(&conn, &BookmarkRootGuid::Toolbar.as_guid())? {
+            Some(BookmarkTreeNode::Folder(f)) => f,
+            _ => panic!("should have a toolbar"),
+        };
+        assert_eq!(toolbar.children.len(), 2);
+        match &toolbar.children[1] {
+            BookmarkTreeNode::Folder(f) => {
+                assert_eq!(f.guid, Some(SyncGuid::from("folderAAAAAA")));
+                assert_eq!(f.children.len(), 2);
+                match &f.children[0] {
+                    BookmarkTreeNode::Bookmark(b) => {
+                        assert_eq!(b.url.as_str(), "https://www.mozilla.org/")
+                    }
+                    _ => panic!("should be a bookmark"),
+                }
+                match &f.children[1] {
+                    BookmarkTreeNode::Separator(_) => {}
+                    _ => panic!("should be a separator"),
+                }
+            }
+            _ => panic!("should be a folder"),
+        }
+
+        let mozilla_url = Url::parse("https://www.mozilla.org/")?;
+        assert_eq!(
+            get_tags_for_url(&conn, &mozilla_url)?,
+            vec!["cool".to_string()]
+        );
+        let search_url = Url::parse("https://search.example.com/?q=%s")?;
+        assert_eq!(
+            get_keyword_for_url(&conn, &search_url)?,
+            Some("s".to_string())
+        );
+
+        let frecency = conn.query_one::<i64>(
+            "SELECT frecency FROM moz_places WHERE url = 'https://www.mozilla.org/'",
+        )?;
+        assert!(frecency > 0);
+        let stale = conn.query_one::<i64>("SELECT COUNT(*) FROM moz_places_stale_frecencies")?;
+        assert_eq!(stale, 0);
+        let origins =
+            conn.query_one::<i64>("SELECT COUNT(*) FROM moz_places WHERE origin_id IS NULL")?;
+        assert_eq!(origins, 0);
+
+        // Importing again doesn't add anything.
+        let metrics = import_desktop_places(&api, &desktop_path)?;
+        assert_eq!(metrics.places.imported, 0);
+        assert_eq!(metrics.visits.imported, 0);
+        assert_eq!(metrics.bookmarks.imported, 0);
+        assert_eq!(metrics.bookmarks.skipped, 1);
+        assert_eq!(metrics.keywords.imported, 0);
+        assert_eq!(metrics.input_history.imported, 0);
+
+        Ok(())
+    }
+}
@@ -2,5 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+pub mod bookmarks_html;
+mod common;
+pub mod desktop_places;
 pub mod ios_bookmarks;
+pub use bookmarks_html::{export_bookmarks_html, import_bookmarks_html};
+pub use desktop_places::{import_desktop_places, DesktopImportMetrics, TableImportCounts};
 pub use ios_bookmarks::import_ios_bookmarks;
@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Syncs adaptive autocomplete history (`moz_inputhistory`), which records
+//! the pages picked for each typed input.
+//!
+//! Each record holds all the inputs for a page, and is keyed by the page's
+//! GUID. Use counts are merged using `InputUse::merge`, which decays both
+//! sides to the same time and keeps the larger count. This doesn't depend on
+//! the order records are merged in, and merging a record with itself doesn't
+//! change it, so our own uploads coming back don't inflate the counts.
+//!
+//! Deletions aren't synced. Removing a page removes its inputs locally, and
+//! we ignore incoming inputs for pages we don't know about.
+
+use crate::types::Timestamp;
+
+mod plan;
+pub mod record;
+pub mod store;
+
+const MAX_INCOMING_RECORDS: usize = 5000;
+const MAX_OUTGOING_PAGES: usize = 5000;
+
+/// How long it takes, in milliseconds, for a use count to decay to half its
+/// value. This is only used when merging: locally, each use decays the
+/// existing count instead, in `accept_result`.
+const USE_COUNT_HALF_LIFE: f64 = 30.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// The use count for a page and input, as of when it was last used.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InputUse {
+    pub use_count: f64,
+    pub last_used: Timestamp,
+}
+
+impl InputUse {
+    /// Returns the use count decayed to `at`.
+    pub fn decayed_to(&self, at: Timestamp) -> f64 {
+        let elapsed = at.0.saturating_sub(self.last_used.0) as f64;
+        self.use_count * 0.5f64.powf(elapsed / USE_COUNT_HALF_LIFE)
+    }
+
+    /// Merges two uses of the same input, by decaying both to the later
+    /// `last_used` time and taking the larger count.
+    pub fn merge(&self, other: &InputUse) -> InputUse {
+        let last_used = self.last_used.max(other.last_used);
+        InputUse {
+            use_count: self.decayed_to(last_used).max(other.decayed_to(last_used)),
+            last_used,
+        }
+    }
+
+    /// Returns true if the two uses are the same, allowing for rounding errors
+    /// in the use count.
+    pub fn same_as(&self, other: &InputUse) -> bool {
+        self.last_used == other.last_used && (self.use_count - other.use_count).abs() < 1e-6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60 * 1000;
+
+    #[test]
+    fn test_merge() {
+        let now = Timestamp::now();
+        let old = InputUse {
+            use_count: 4.0,
+            last_used: Timestamp(now.0 - 30 * DAY),
+        };
+        let new = InputUse {
+            use_count: 1.5,
+            last_used: now,
+        };
+
+        // The old use decays to 2, so the new one wins.
+        let merged = old.merge(&new);
+        assert!(merged.same_as(&new));
+        assert!(new.merge(&old).same_as(&merged));
+
+        // Merging again, in either order, doesn't change anything.
+        assert!(merged.merge(&old).same_as(&merged));
+        assert!(merged.merge(&new).same_as(&merged));
+        assert!(merged.merge(&merged).same_as(&merged));
+
+        // A larger count keeps its decayed value at the later time.
+        let frequent = InputUse {
+            use_count: 8.0,
+            last_used: Timestamp(now.0 - 30 * DAY),
+        };
+        let merged = frequent.merge(&new);
+        assert_eq!(merged.last_used, now);
+        assert!((merged.use_count - 4.0).abs() < 1e-6);
+        assert!(new.merge(&frequent).same_as(&merged));
+    }
+}
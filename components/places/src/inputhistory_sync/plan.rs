@@ -0,0 +1,477 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::record::{InputHistoryRecord, InputHistoryRecordInput};
+use super::{InputUse, MAX_OUTGOING_PAGES};
+use crate::api::history::can_add_url;
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::storage::RowId;
+use crate::types::{SyncGuid, Timestamp};
+use crate::valid_guid::is_valid_places_guid;
+use interrupt::Interruptee;
+use rusqlite::Row;
+use sql_support::ConnExt;
+use std::collections::HashMap;
+use sync15::telemetry;
+use sync15::{IncomingChangeset, OutgoingChangeset, Payload};
+use url::Url;
+
+/// What we did with an incoming record.
+#[derive(Debug)]
+enum IncomingOutcome {
+    /// The record is for a page we don't have, or can't store.
+    Skip,
+    /// The record is malformed.
+    Invalid(Error),
+    /// We merged the record into our local inputs, and changed them.
+    Applied,
+    /// Our local inputs already included everything in the record.
+    Reconciled,
+}
+
+/// Returns all the inputs for a page. Inputs recorded before we started
+/// tracking when they were used are treated as used when the page was last
+/// visited.
+fn fetch_page_inputs(db: &PlacesDb, place_id: RowId) -> Result<Vec<(String, InputUse)>> {
+    db.query_rows_and_then_named_cached(
+        "SELECT i.input, IFNULL(i.use_count, 0) AS use_count,
+                CASE WHEN i.last_used > 0 THEN i.last_used
+                     ELSE MAX(h.last_visit_date_local, h.last_visit_date_remote)
+                END AS last_used
+         FROM moz_inputhistory i
+         JOIN moz_places h ON h.id = i.place_id
+         WHERE i.place_id = :place_id",
+        &[(":place_id", &place_id)],
+        |row: &Row<'_>| -> Result<_> {
+            Ok((
+                row.get::<_, String>("input")?,
+                InputUse {
+                    use_count: row.get("use_count")?,
+                    last_used: row.get("last_used")?,
+                },
+            ))
+        },
+    )
+}
+
+/// Finds the local page for a record, by GUID or, if the page has a different
+/// GUID locally, by URL.
+fn find_page(db: &PlacesDb, guid: &SyncGuid, url: &Url) -> Result<Option<RowId>> {
+    if let Some(id) = db.try_query_row(
+        "SELECT id FROM moz_places WHERE guid = :guid",
+        &[(":guid", guid)],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )? {
+        return Ok(Some(id));
+    }
+    Ok(db.try_query_row(
+        "SELECT id FROM moz_places WHERE url_hash = hash(:url) AND url = :url",
+        &[(":url", &url.as_str())],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )?)
+}
+
+fn write_input(db: &PlacesDb, place_id: RowId, input: &str, input_use: InputUse) -> Result<()> {
+    // This keeps the change counter for existing inputs: merging doesn't
+    // need to be uploaded by itself.
+    db.execute_named_cached(
+        "INSERT OR REPLACE INTO moz_inputhistory(place_id, input, use_count, last_used,
+                                                 sync_change_counter)
+         VALUES(:place_id, :input, :use_count, :last_used,
+                IFNULL((SELECT sync_change_counter FROM moz_inputhistory
+                        WHERE place_id = :place_id AND input = :input), 0))",
+        &[
+            (":place_id", &place_id),
+            (":input", &input),
+            (":use_count", &input_use.use_count),
+            (":last_used", &input_use.last_used),
+        ],
+    )?;
+    Ok(())
+}
+
+fn apply_incoming_record(
+    db: &PlacesDb,
+    record: InputHistoryRecord,
+    now: Timestamp,
+) -> Result<IncomingOutcome> {
+    let url = match Url::parse(&record.hist_uri) {
+        Ok(u) => u,
+        Err(e) => return Ok(IncomingOutcome::Invalid(e.into())),
+    };
+    if !is_valid_places_guid(record.id.as_ref()) {
+        return Ok(IncomingOutcome::Invalid(
+            InvalidPlaceInfo::InvalidGuid.into(),
+        ));
+    }
+    if !can_add_url(db, &url)? {
+        return Ok(IncomingOutcome::Skip);
+    }
+    let place_id = match find_page(db, &record.id, &url)? {
+        Some(id) => id,
+        None => {
+            log::trace!("incoming: no local page for {:?}", record.id);
+            return Ok(IncomingOutcome::Skip);
+        }
+    };
+
+    let mut local: HashMap<String, InputUse> =
+        fetch_page_inputs(db, place_id)?.into_iter().collect();
+    let mut remote: HashMap<String, InputUse> = HashMap::with_capacity(record.inputs.len());
+    for input in record.inputs {
+        if input.input.is_empty() || !input.use_count.is_finite() || input.use_count < 0.0 {
+            log::warn!("incoming: ignoring invalid input for {:?}", record.id);
+            continue;
+        }
+        let input_use = InputUse {
+            use_count: input.use_count,
+            // Don't let a device with a clock set in the future pin its
+            // counts at full strength.
+            last_used: input.last_used.min(now),
+        };
+        remote
+            .entry(input.input)
+            .and_modify(|existing| *existing = existing.merge(&input_use))
+            .or_insert(input_use);
+    }
+
+    let mut changed = false;
+    // If we have inputs the record doesn't, we need to upload them.
+    let mut should_upload = local.keys().any(|input| !remote.contains_key(input));
+    for (input, remote_use) in remote {
+        let merged = match local.remove(&input) {
+            Some(local_use) => {
+                let merged = local_use.merge(&remote_use);
+                if merged.same_as(&local_use) {
+                    if !merged.same_as(&remote_use) {
+                        should_upload = true;
+                    }
+                    continue;
+                }
+                merged
+            }
+            None => remote_use,
+        };
+        if !merged.same_as(&remote_use) {
+            should_upload = true;
+        }
+        write_input(db, place_id, &input, merged)?;
+        changed = true;
+    }
+
+    if should_upload {
+        db.execute_named_cached(
+            "UPDATE moz_inputhistory SET sync_change_counter = sync_change_counter + 1
+             WHERE place_id = :place_id",
+            &[(":place_id", &place_id)],
+        )?;
+    }
+    Ok(if changed {
+        IncomingOutcome::Applied
+    } else {
+        IncomingOutcome::Reconciled
+    })
+}
+
+/// Returns records for pages with changed inputs, and notes which inputs
+/// we're uploading, so that `finish_outgoing` can mark them as synced.
+fn fetch_outgoing(db: &PlacesDb, max_pages: usize) -> Result<Vec<InputHistoryRecord>> {
+    // We might have been interrupted after fetching the last time.
+    db.execute_all(&[
+        "CREATE TEMP TABLE IF NOT EXISTS temp_inputhistory_sync_uploaded(
+            place_id INTEGER NOT NULL,
+            input TEXT NOT NULL,
+            change_delta INTEGER NOT NULL,
+            PRIMARY KEY(place_id, input)
+        )",
+        "DELETE FROM temp_inputhistory_sync_uploaded",
+    ])?;
+    let pages = db.query_rows_and_then_named(
+        "SELECT h.id, h.guid, h.url
+         FROM moz_places h
+         WHERE EXISTS(SELECT 1 FROM moz_inputhistory i
+                      WHERE i.place_id = h.id AND
+                            i.sync_change_counter > 0)
+         ORDER BY h.frecency DESC
+         LIMIT :max_pages",
+        &[(":max_pages", &(max_pages as u32))],
+        |row: &Row<'_>| -> Result<_> {
+            Ok((
+                row.get::<_, RowId>("id")?,
+                row.get::<_, SyncGuid>("guid")?,
+                row.get::<_, String>("url")?,
+            ))
+        },
+    )?;
+    let mut records = Vec::with_capacity(pages.len());
+    for (place_id, guid, url) in pages {
+        let inputs = fetch_page_inputs(db, place_id)?
+            .into_iter()
+            .map(|(input, input_use)| InputHistoryRecordInput {
+                input,
+                use_count: input_use.use_count,
+                last_used: input_use.last_used,
+            })
+            .collect();
+        db.execute_named_cached(
+            "INSERT INTO temp_inputhistory_sync_uploaded(place_id, input, change_delta)
+             SELECT place_id, input, sync_change_counter
+             FROM moz_inputhistory
+             WHERE place_id = :place_id",
+            &[(":place_id", &place_id)],
+        )?;
+        log::trace!("outgoing record {:?}", &guid);
+        records.push(InputHistoryRecord {
+            id: guid,
+            hist_uri: url,
+            inputs,
+        });
+    }
+    Ok(records)
+}
+
+fn finish_outgoing(db: &PlacesDb) -> Result<()> {
+    // Inputs used again since we fetched them keep the difference, so
+    // they'll be uploaded on the next sync.
+    db.execute_all(&[
+        "UPDATE moz_inputhistory SET
+           sync_change_counter = MAX(0, sync_change_counter -
+             (SELECT change_delta FROM temp_inputhistory_sync_uploaded u
+              WHERE u.place_id = moz_inputhistory.place_id AND
+                    u.input = moz_inputhistory.input))
+         WHERE EXISTS(SELECT 1 FROM temp_inputhistory_sync_uploaded u
+                      WHERE u.place_id = moz_inputhistory.place_id AND
+                            u.input = moz_inputhistory.input)",
+        "DELETE FROM temp_inputhistory_sync_uploaded",
+    ])?;
+    Ok(())
+}
+
+pub fn apply_plan(
+    db: &PlacesDb,
+    inbound: IncomingChangeset,
+    telem: &mut telemetry::EngineIncoming,
+    interruptee: &impl Interruptee,
+) -> Result<OutgoingChangeset> {
+    let now = Timestamp::now();
+    let mut tx = db.begin_transaction()?;
+    for (payload, _) in inbound.changes {
+        interruptee.err_if_interrupted()?;
+        tx.maybe_commit()?;
+        if payload.is_tombstone() {
+            // We don't sync deletions, but don't fail if another client does.
+            log::trace!("incoming: ignoring tombstone {:?}", payload.id);
+            continue;
+        }
+        let guid = payload.id.clone();
+        let record: InputHistoryRecord = match payload.into_record() {
+            Ok(record) => record,
+            Err(e) => {
+                log::warn!("Error deserializing incoming record: {}", e);
+                telem.failed(1);
+                continue;
+            }
+        };
+        match apply_incoming_record(db, record, now)? {
+            IncomingOutcome::Skip => log::trace!("incoming: skipping item {:?}", guid),
+            IncomingOutcome::Invalid(err) => {
+                log::warn!(
+                    "incoming: record {:?} skipped because it is invalid: {}",
+                    guid,
+                    err
+                );
+                telem.failed(1);
+            }
+            IncomingOutcome::Applied => {
+                log::trace!("incoming: applied {:?}", guid);
+                telem.applied(1);
+            }
+            IncomingOutcome::Reconciled => {
+                log::trace!("incoming: reconciled {:?}", guid);
+                telem.reconciled(1);
+            }
+        }
+    }
+    tx.commit()?;
+
+    let mut outgoing = OutgoingChangeset::new("inputhistory".into(), inbound.timestamp);
+    let tx = db.begin_transaction()?;
+    for record in fetch_outgoing(db, MAX_OUTGOING_PAGES)? {
+        outgoing.changes.push(Payload::from_record(record)?);
+    }
+    tx.commit()?;
+    Ok(outgoing)
+}
+
+pub fn finish_plan(db: &PlacesDb) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    finish_outgoing(db)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Flags every input for upload on the next sync.
+pub fn reset_storage(db: &PlacesDb) -> Result<()> {
+    db.execute_all(&["UPDATE moz_inputhistory SET sync_change_counter = 1"])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::matcher::{accept_result, SearchResult};
+    use crate::api::places_api::ConnectionType;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::{apply_observation, url_to_guid};
+    use crate::types::VisitTransition;
+    use interrupt::NeverInterrupts;
+    use serde_json::json;
+    use sync15::ServerTimestamp;
+
+    const DAY: u64 = 24 * 60 * 60 * 1000;
+
+    fn add_visit(db: &PlacesDb, url: &Url) -> SyncGuid {
+        apply_observation(
+            db,
+            VisitObservation::new(url.clone()).with_visit_type(VisitTransition::Link),
+        )
+        .expect("should apply");
+        url_to_guid(db, url)
+            .expect("should work")
+            .expect("should exist")
+    }
+
+    fn get_input(db: &PlacesDb, url: &Url, input: &str) -> Option<(f64, u32)> {
+        db.try_query_row(
+            "SELECT i.use_count, i.sync_change_counter
+             FROM moz_inputhistory i
+             JOIN moz_places h ON h.id = i.place_id
+             WHERE h.url = :url AND i.input = :input",
+            &[(":url", &url.as_str()), (":input", &input)],
+            |row| -> Result<_> { Ok((row.get::<_, f64>(0)?, row.get::<_, u32>(1)?)) },
+            false,
+        )
+        .expect("should work")
+    }
+
+    fn apply_incoming(db: &PlacesDb, records: Vec<serde_json::Value>) -> OutgoingChangeset {
+        let mut incoming = IncomingChangeset::new("inputhistory".into(), ServerTimestamp(0));
+        for record in records {
+            let payload = Payload::from_json(record).expect("should be a payload");
+            incoming.changes.push((payload, ServerTimestamp(0)));
+        }
+        let mut telem = telemetry::EngineIncoming::new();
+        apply_plan(db, incoming, &mut telem, &NeverInterrupts).expect("should apply")
+    }
+
+    #[test]
+    fn test_outgoing() -> Result<()> {
+        let _ = env_logger::try_init();
+        let db = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let url = Url::parse("https://example.com/")?;
+        let guid = add_visit(&db, &url);
+        let result = SearchResult {
+            search_string: "exa".into(),
+            url: url.clone(),
+            title: "Example".into(),
+            icon_url: None,
+            frecency: -1,
+            description: None,
+            preview_image_url: None,
+            reasons: vec![],
+        };
+        accept_result(&db, &result)?;
+        accept_result(&db, &result)?;
+        let (count, counter) = get_input(&db, &url, "exa").expect("should exist");
+        assert!((count - 1.9).abs() < 1e-6);
+        assert_eq!(counter, 2);
+
+        let outgoing = apply_incoming(&db, vec![]);
+        assert_eq!(outgoing.changes.len(), 1);
+        let record: InputHistoryRecord = outgoing.changes[0].clone().into_record()?;
+        assert_eq!(record.id, guid);
+        assert_eq!(record.hist_uri, url.as_str());
+        assert_eq!(record.inputs.len(), 1);
+        assert_eq!(record.inputs[0].input, "exa");
+        assert_eq!(record.inputs[0].use_count, count);
+
+        finish_plan(&db)?;
+        assert_eq!(get_input(&db, &url, "exa"), Some((count, 0)));
+        assert!(apply_incoming(&db, vec![]).changes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incoming_merge() -> Result<()> {
+        let _ = env_logger::try_init();
+        let db = PlacesDb::open_in_memory(ConnectionType::Sync)?;
+        let url = Url::parse("https://example.com/")?;
+        let guid = add_visit(&db, &url);
+        let now = Timestamp::now();
+        db.execute_named(
+            "INSERT INTO moz_inputhistory(place_id, input, use_count, last_used,
+                                          sync_change_counter)
+             SELECT id, 'ex', 1.0, :now, 0 FROM moz_places
+             UNION ALL
+             SELECT id, 'local', 1.0, :now, 0 FROM moz_places",
+            &[(":now", &now)],
+        )?;
+
+        let outgoing = apply_incoming(
+            &db,
+            vec![
+                json!({
+                    "id": guid,
+                    "histUri": url.as_str(),
+                    "inputs": [{
+                        // Used a lot, a month ago, so this wins.
+                        "input": "ex",
+                        "useCount": 6.0,
+                        "lastUsed": now.0 - 30 * DAY,
+                    }, {
+                        "input": "remote",
+                        "useCount": 2.0,
+                        "lastUsed": now.0,
+                    }],
+                }),
+                json!({
+                    "id": "bbbbbbbbbbbb",
+                    "histUri": "https://unknown.example.com/",
+                    "inputs": [{
+                        "input": "unknown",
+                        "useCount": 1.0,
+                        "lastUsed": now.0,
+                    }],
+                }),
+            ],
+        );
+
+        let (ex_count, ex_counter) = get_input(&db, &url, "ex").expect("should merge");
+        assert!((ex_count - 3.0).abs() < 1e-6);
+        assert_eq!(ex_counter, 1);
+        assert_eq!(get_input(&db, &url, "remote"), Some((2.0, 1)));
+        assert_eq!(get_input(&db, &url, "local"), Some((1.0, 1)));
+        assert_eq!(
+            url_to_guid(&db, &Url::parse("https://unknown.example.com/")?)?,
+            None
+        );
+
+        // We have an input the server doesn't, so we upload the merged record.
+        assert_eq!(outgoing.changes.len(), 1);
+        let record: InputHistoryRecord = outgoing.changes[0].clone().into_record()?;
+        assert_eq!(record.inputs.len(), 3);
+        finish_plan(&db)?;
+
+        // Our own record coming back doesn't change anything.
+        let outgoing = apply_incoming(&db, vec![serde_json::to_value(&record)?]);
+        assert!(outgoing.changes.is_empty());
+        let (count, counter) = get_input(&db, &url, "ex").expect("should exist");
+        assert!((count - ex_count).abs() < 1e-6);
+        assert_eq!(counter, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::types::{SyncGuid, Timestamp};
+use serde_derive::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputHistoryRecordInput {
+    pub input: String,
+    pub use_count: f64,
+    /// In milliseconds.
+    pub last_used: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputHistoryRecord {
+    /// The GUID of the page.
+    pub id: SyncGuid,
+
+    pub hist_uri: String,
+
+    pub inputs: Vec<InputHistoryRecordInput>,
+}
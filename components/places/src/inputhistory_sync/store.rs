@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::api::places_api::ConnectionType;
+use crate::db::PlacesDb;
+use crate::error::*;
+use rusqlite::types::{FromSql, ToSql};
+use sql_support::SqlInterruptScope;
+use std::result;
+use sync15::telemetry;
+use sync15::{
+    CollSyncIds, CollectionRequest, IncomingChangeset, OutgoingChangeset, ServerTimestamp, Store,
+    StoreSyncAssociation,
+};
+
+use super::plan::{apply_plan, finish_plan, reset_storage};
+use super::MAX_INCOMING_RECORDS;
+
+const LAST_SYNC_META_KEY: &str = "inputhistory_last_sync_time";
+const GLOBAL_SYNCID_META_KEY: &str = "inputhistory_global_sync_id";
+const COLLECTION_SYNCID_META_KEY: &str = "inputhistory_sync_id";
+
+/// Syncs adaptive autocomplete history. Like the other stores, this is
+/// constructed each sync by something which owns the Sync connection.
+pub struct InputHistoryStore<'a> {
+    pub db: &'a PlacesDb,
+    interruptee: &'a SqlInterruptScope,
+}
+
+impl<'a> InputHistoryStore<'a> {
+    pub fn new(db: &'a PlacesDb, interruptee: &'a SqlInterruptScope) -> Self {
+        assert_eq!(db.conn_type(), ConnectionType::Sync);
+        Self { db, interruptee }
+    }
+
+    fn put_meta(&self, key: &str, value: &dyn ToSql) -> Result<()> {
+        crate::storage::put_meta(self.db, key, value)
+    }
+
+    fn get_meta<T: FromSql>(&self, key: &str) -> Result<Option<T>> {
+        crate::storage::get_meta(self.db, key)
+    }
+
+    fn delete_meta(&self, key: &str) -> Result<()> {
+        crate::storage::delete_meta(self.db, key)
+    }
+
+    fn do_apply_incoming(
+        &self,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+    ) -> Result<OutgoingChangeset> {
+        let timestamp = inbound.timestamp;
+        let outgoing = {
+            let mut incoming_telemetry = telemetry::EngineIncoming::new();
+            let result = apply_plan(&self.db, inbound, &mut incoming_telemetry, self.interruptee);
+            telem.incoming(incoming_telemetry);
+            result
+        }?;
+        self.put_meta(LAST_SYNC_META_KEY, &(timestamp.as_millis() as i64))?;
+        Ok(outgoing)
+    }
+
+    fn do_sync_finished(
+        &self,
+        new_timestamp: ServerTimestamp,
+        records_synced: Vec<String>,
+    ) -> Result<()> {
+        log::info!(
+            "input history sync completed after uploading {} records",
+            records_synced.len()
+        );
+        finish_plan(&self.db)?;
+        self.put_meta(LAST_SYNC_META_KEY, &(new_timestamp.as_millis() as i64))?;
+        Ok(())
+    }
+
+    fn do_reset(&self, assoc: &StoreSyncAssociation) -> Result<()> {
+        let tx = self.db.begin_transaction()?;
+        reset_storage(self.db)?;
+        self.put_meta(LAST_SYNC_META_KEY, &0)?;
+        match assoc {
+            StoreSyncAssociation::Disconnected => {
+                self.delete_meta(GLOBAL_SYNCID_META_KEY)?;
+                self.delete_meta(COLLECTION_SYNCID_META_KEY)?;
+            }
+            StoreSyncAssociation::Connected(ids) => {
+                self.put_meta(GLOBAL_SYNCID_META_KEY, &ids.global)?;
+                self.put_meta(COLLECTION_SYNCID_META_KEY, &ids.coll)?;
+            }
+        };
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl<'a> Store for InputHistoryStore<'a> {
+    fn collection_name(&self) -> &'static str {
+        "inputhistory"
+    }
+
+    fn apply_incoming(
+        &self,
+        inbound: IncomingChangeset,
+        telem: &mut telemetry::Engine,
+    ) -> result::Result<OutgoingChangeset, failure::Error> {
+        Ok(self.do_apply_incoming(inbound, telem)?)
+    }
+
+    fn sync_finished(
+        &self,
+        new_timestamp: ServerTimestamp,
+        records_synced: Vec<String>,
+    ) -> result::Result<(), failure::Error> {
+        self.do_sync_finished(new_timestamp, records_synced)?;
+        Ok(())
+    }
+
+    fn get_collection_request(&self) -> result::Result<CollectionRequest, failure::Error> {
+        let since = self
+            .get_meta::<i64>(LAST_SYNC_META_KEY)?
+            .unwrap_or_default();
+        Ok(CollectionRequest::new("inputhistory")
+            .full()
+            .newer_than(ServerTimestamp(since))
+            .limit(MAX_INCOMING_RECORDS))
+    }
+
+    fn get_sync_assoc(&self) -> result::Result<StoreSyncAssociation, failure::Error> {
+        let global = self.get_meta(GLOBAL_SYNCID_META_KEY)?;
+        let coll = self.get_meta(COLLECTION_SYNCID_META_KEY)?;
+        Ok(if let (Some(global), Some(coll)) = (global, coll) {
+            StoreSyncAssociation::Connected(CollSyncIds { global, coll })
+        } else {
+            StoreSyncAssociation::Disconnected
+        })
+    }
+
+    fn reset(&self, assoc: &StoreSyncAssociation) -> result::Result<(), failure::Error> {
+        self.do_reset(assoc)?;
+        Ok(())
+    }
+
+    fn wipe(&self) -> result::Result<(), failure::Error> {
+        log::warn!("not implemented");
+        Ok(())
+    }
+}
@@ -7,6 +7,7 @@
 
 pub mod api;
 pub mod error;
+pub mod events;
 pub mod types;
 // Making these all pub for now while we flesh out the API.
 pub mod bookmark_sync;
@@ -15,6 +16,7 @@ pub mod ffi;
 pub mod frecency;
 pub mod hash;
 pub mod history_sync;
+pub mod inputhistory_sync;
 // match_impl is pub mostly for benchmarks (which have to run as a separate pseudo-crate).
 pub mod import;
 pub mod match_impl;
@@ -328,6 +328,13 @@ fn find_beginning_case_sensitive(token: &str, source: &str) -> bool {
     source.starts_with(token)
 }
 
+/// Splits a search string into the tokens that `AutocompleteMatch` matches
+/// separately. The autocomplete query parser uses this too, so that they
+/// agree on what a token is.
+pub fn split_search_tokens(search_str: &str) -> impl Iterator<Item = &str> {
+    search_str.split_ascii_whitespace()
+}
+
 // I can't wait for Rust 2018 when lifetime annotations are automatic.
 pub struct AutocompleteMatch<'search, 'url, 'title, 'tags> {
     pub search_str: &'search str,
@@ -412,7 +419,7 @@ impl<'search, 'url, 'title, 'tags> AutocompleteMatch<'search, 'url, 'title, 'tag
 
         let trimmed_url = util::slice_up_to(fixed_url.as_ref(), MAX_CHARS_TO_SEARCH_THROUGH);
         let trimmed_title = util::slice_up_to(self.title_str, MAX_CHARS_TO_SEARCH_THROUGH);
-        for token in self.search_str.split_ascii_whitespace() {
+        for token in split_search_tokens(self.search_str) {
             let matches = match (
                 self.has_behavior(SearchBehavior::TITLE),
                 self.has_behavior(SearchBehavior::URL),
@@ -0,0 +1,760 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Annotations are named values attached to pages or bookmark items, for
+//! metadata that doesn't warrant its own column or table - for example,
+//! whether a page was saved for reader mode. Like Desktop, each annotation
+//! has an expiration policy, which decides when it's removed.
+
+use super::RowId;
+use crate::db::PlacesDb;
+use crate::error::{InvalidPlaceInfo, Result};
+use crate::types::{SyncGuid, Timestamp};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Result as RusqliteResult, Row};
+use sql_support::ConnExt;
+use std::time::Duration;
+use url::Url;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// When an annotation should be removed. Annotations are always removed
+/// along with their page or bookmark item.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AnnotationExpiration {
+    /// Kept until it's explicitly removed.
+    Never = 0,
+    /// Removed when history is cleared, even if the page is kept because it's
+    /// bookmarked. Item annotations aren't affected by clearing history, so
+    /// this is the same as `Never` for them.
+    WithHistory = 1,
+    /// Removed 7 days after it was last set, or when history is cleared.
+    Days = 2,
+    /// Removed 30 days after it was last set, or when history is cleared.
+    Weeks = 3,
+    /// Removed 180 days after it was last set, or when history is cleared.
+    Months = 4,
+}
+
+impl AnnotationExpiration {
+    #[inline]
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(AnnotationExpiration::Never),
+            1 => Some(AnnotationExpiration::WithHistory),
+            2 => Some(AnnotationExpiration::Days),
+            3 => Some(AnnotationExpiration::Weeks),
+            4 => Some(AnnotationExpiration::Months),
+            _ => None,
+        }
+    }
+
+    /// How long after its last modification an annotation with this policy
+    /// is kept, if it expires with time.
+    fn max_age(self) -> Option<Duration> {
+        match self {
+            AnnotationExpiration::Days => Some(DAY * 7),
+            AnnotationExpiration::Weeks => Some(DAY * 30),
+            AnnotationExpiration::Months => Some(DAY * 180),
+            AnnotationExpiration::Never | AnnotationExpiration::WithHistory => None,
+        }
+    }
+}
+
+impl FromSql for AnnotationExpiration {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let v = value.as_i64()?;
+        if v < 0 || v > i64::from(u8::max_value()) {
+            return Err(FromSqlError::OutOfRange(v));
+        }
+        AnnotationExpiration::from_u8(v as u8).ok_or_else(|| FromSqlError::OutOfRange(v))
+    }
+}
+
+impl ToSql for AnnotationExpiration {
+    fn to_sql(&self) -> RusqliteResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(*self as u8))
+    }
+}
+
+/// The value of an annotation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnnotationValue {
+    Int(i64),
+    Double(f64),
+    Text(String),
+}
+
+impl AnnotationValue {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            AnnotationValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            AnnotationValue::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            AnnotationValue::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for AnnotationValue {
+    fn from(v: i64) -> Self {
+        AnnotationValue::Int(v)
+    }
+}
+
+impl From<f64> for AnnotationValue {
+    fn from(v: f64) -> Self {
+        AnnotationValue::Double(v)
+    }
+}
+
+impl From<String> for AnnotationValue {
+    fn from(v: String) -> Self {
+        AnnotationValue::Text(v)
+    }
+}
+
+impl From<&str> for AnnotationValue {
+    fn from(v: &str) -> Self {
+        AnnotationValue::Text(v.to_owned())
+    }
+}
+
+// The `content` columns don't have a declared type, so SQLite keeps the
+// storage class of the value we write.
+impl FromSql for AnnotationValue {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Integer(v) => Ok(AnnotationValue::Int(v)),
+            ValueRef::Real(v) => Ok(AnnotationValue::Double(v)),
+            ValueRef::Text(v) => Ok(AnnotationValue::Text(v.to_owned())),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for AnnotationValue {
+    fn to_sql(&self) -> RusqliteResult<ToSqlOutput<'_>> {
+        Ok(match self {
+            AnnotationValue::Int(v) => ToSqlOutput::from(*v),
+            AnnotationValue::Double(v) => ToSqlOutput::from(*v),
+            AnnotationValue::Text(v) => ToSqlOutput::from(v.as_str()),
+        })
+    }
+}
+
+/// An annotation, as returned by `get_page_annotations` and
+/// `get_item_annotations`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub value: AnnotationValue,
+    pub expiration: AnnotationExpiration,
+    pub date_added: Timestamp,
+    pub last_modified: Timestamp,
+}
+
+impl Annotation {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(Self {
+            name: row.get("name")?,
+            value: row.get("content")?,
+            expiration: row.get("expiration")?,
+            date_added: row.get("dateAdded")?,
+            last_modified: row.get("lastModified")?,
+        })
+    }
+}
+
+/// Checks the validity of the specified annotation name. Names can't be
+/// empty, or have leading or trailing whitespace.
+pub fn validate_annotation_name(name: &str) -> Result<&str> {
+    if name.is_empty() || name.trim().len() != name.len() {
+        return Err(InvalidPlaceInfo::InvalidAnnotationName(name.to_owned()).into());
+    }
+    Ok(name)
+}
+
+/// What an annotation is attached to. Page and item annotations live in
+/// separate tables, but otherwise work the same way.
+#[derive(Clone, Copy, Debug)]
+enum Owner {
+    Page(RowId),
+    Item(RowId),
+}
+
+impl Owner {
+    fn table(self) -> &'static str {
+        match self {
+            Owner::Page(_) => "moz_annos",
+            Owner::Item(_) => "moz_items_annos",
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Owner::Page(_) => "place_id",
+            Owner::Item(_) => "item_id",
+        }
+    }
+
+    fn id(self) -> RowId {
+        match self {
+            Owner::Page(id) | Owner::Item(id) => id,
+        }
+    }
+}
+
+fn page_owner(db: &PlacesDb, url: &Url) -> Result<Option<Owner>> {
+    Ok(db
+        .try_query_row(
+            "SELECT id FROM moz_places
+             WHERE url_hash = hash(:url) AND url = :url",
+            &[(":url", &url.as_str())],
+            |row| row.get::<_, RowId>(0),
+            true,
+        )?
+        .map(Owner::Page))
+}
+
+fn item_owner(db: &PlacesDb, guid: &SyncGuid) -> Result<Option<Owner>> {
+    Ok(db
+        .try_query_row(
+            "SELECT id FROM moz_bookmarks WHERE guid = :guid",
+            &[(":guid", guid)],
+            |row| row.get::<_, RowId>(0),
+            true,
+        )?
+        .map(Owner::Item))
+}
+
+fn set_annotation(
+    db: &PlacesDb,
+    owner: Owner,
+    name: &str,
+    value: &AnnotationValue,
+    expiration: AnnotationExpiration,
+) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    db.execute_named_cached(
+        "INSERT OR IGNORE INTO moz_anno_attributes(name) VALUES(:name)",
+        &[(":name", &name)],
+    )?;
+    db.execute_named_cached(
+        &format!(
+            "INSERT INTO {table}({column}, anno_attribute_id, content, expiration,
+                                 dateAdded, lastModified)
+             VALUES(:owner_id, (SELECT id FROM moz_anno_attributes WHERE name = :name),
+                    :content, :expiration, :now, :now)
+             ON CONFLICT({column}, anno_attribute_id) DO UPDATE SET
+               content = excluded.content,
+               expiration = excluded.expiration,
+               lastModified = excluded.lastModified",
+            table = owner.table(),
+            column = owner.column(),
+        ),
+        &[
+            (":owner_id", &owner.id()),
+            (":name", &name),
+            (":content", value),
+            (":expiration", &expiration),
+            (":now", &Timestamp::now()),
+        ],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn get_annotation(db: &PlacesDb, owner: Owner, name: &str) -> Result<Option<AnnotationValue>> {
+    Ok(db.try_query_row(
+        &format!(
+            "SELECT a.content FROM {table} a
+             JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+             WHERE a.{column} = :owner_id AND n.name = :name",
+            table = owner.table(),
+            column = owner.column(),
+        ),
+        &[(":owner_id", &owner.id()), (":name", &name)],
+        |row| row.get::<_, AnnotationValue>(0),
+        true,
+    )?)
+}
+
+fn get_annotations(db: &PlacesDb, owner: Owner) -> Result<Vec<Annotation>> {
+    db.query_rows_and_then_named_cached(
+        &format!(
+            "SELECT n.name, a.content, a.expiration, a.dateAdded, a.lastModified
+             FROM {table} a
+             JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+             WHERE a.{column} = :owner_id
+             ORDER BY n.name",
+            table = owner.table(),
+            column = owner.column(),
+        ),
+        &[(":owner_id", &owner.id())],
+        Annotation::from_row,
+    )
+}
+
+fn remove_annotation(db: &PlacesDb, owner: Owner, name: &str) -> Result<()> {
+    db.execute_named_cached(
+        &format!(
+            "DELETE FROM {table}
+             WHERE {column} = :owner_id AND
+                   anno_attribute_id = (SELECT id FROM moz_anno_attributes
+                                        WHERE name = :name)",
+            table = owner.table(),
+            column = owner.column(),
+        ),
+        &[(":owner_id", &owner.id()), (":name", &name)],
+    )?;
+    Ok(())
+}
+
+/// Sets an annotation for the specified page, replacing any existing value
+/// and expiration policy for the same name.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `url` - The URL of the page to annotate. This must already be in the
+///   database.
+///
+/// * `name` - The name of the annotation.
+///
+/// * `value` - The value of the annotation.
+///
+/// * `expiration` - When the annotation should be removed.
+///
+/// # Returns
+///
+/// There is no success return value.
+pub fn set_page_annotation(
+    db: &PlacesDb,
+    url: &Url,
+    name: &str,
+    value: impl Into<AnnotationValue>,
+    expiration: AnnotationExpiration,
+) -> Result<()> {
+    let name = validate_annotation_name(name)?;
+    match page_owner(db, url)? {
+        Some(owner) => set_annotation(db, owner, name, &value.into(), expiration),
+        None => Err(InvalidPlaceInfo::NoSuchUrl.into()),
+    }
+}
+
+/// Retrieves the value of an annotation for the specified page.
+///
+/// # Returns
+///
+/// * The value of the annotation, or `None` if the page doesn't exist, or
+///   doesn't have the annotation.
+pub fn get_page_annotation(
+    db: &PlacesDb,
+    url: &Url,
+    name: &str,
+) -> Result<Option<AnnotationValue>> {
+    match page_owner(db, url)? {
+        Some(owner) => get_annotation(db, owner, name),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves all the annotations for the specified page, ordered by name.
+pub fn get_page_annotations(db: &PlacesDb, url: &Url) -> Result<Vec<Annotation>> {
+    match page_owner(db, url)? {
+        Some(owner) => get_annotations(db, owner),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Removes an annotation from the specified page.
+///
+/// # Returns
+///
+/// There is no success return value - the operation is ignored if the page
+/// doesn't have the annotation.
+pub fn remove_page_annotation(db: &PlacesDb, url: &Url, name: &str) -> Result<()> {
+    match page_owner(db, url)? {
+        Some(owner) => remove_annotation(db, owner, name),
+        None => Ok(()),
+    }
+}
+
+/// Retrieves the URLs of all pages with the specified annotation.
+pub fn get_pages_with_annotation(db: &PlacesDb, name: &str) -> Result<Vec<Url>> {
+    let hrefs: Vec<String> = db.query_rows_and_then_named_cached(
+        "SELECT h.url FROM moz_annos a
+         JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+         JOIN moz_places h ON h.id = a.place_id
+         WHERE n.name = :name
+         ORDER BY h.id",
+        &[(":name", &name)],
+        |row| row.get::<_, String>(0),
+    )?;
+    Ok(hrefs
+        .iter()
+        .map(|href| Url::parse(href))
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// Sets an annotation for the specified bookmark item, replacing any
+/// existing value and expiration policy for the same name.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `guid` - The GUID of the bookmark, folder or separator to annotate.
+///
+/// * `name` - The name of the annotation.
+///
+/// * `value` - The value of the annotation.
+///
+/// * `expiration` - When the annotation should be removed.
+///
+/// # Returns
+///
+/// There is no success return value.
+pub fn set_item_annotation(
+    db: &PlacesDb,
+    guid: &SyncGuid,
+    name: &str,
+    value: impl Into<AnnotationValue>,
+    expiration: AnnotationExpiration,
+) -> Result<()> {
+    let name = validate_annotation_name(name)?;
+    match item_owner(db, guid)? {
+        Some(owner) => set_annotation(db, owner, name, &value.into(), expiration),
+        None => Err(InvalidPlaceInfo::NoSuchGuid(guid.to_string()).into()),
+    }
+}
+
+/// Retrieves the value of an annotation for the specified bookmark item.
+///
+/// # Returns
+///
+/// * The value of the annotation, or `None` if the item doesn't exist, or
+///   doesn't have the annotation.
+pub fn get_item_annotation(
+    db: &PlacesDb,
+    guid: &SyncGuid,
+    name: &str,
+) -> Result<Option<AnnotationValue>> {
+    match item_owner(db, guid)? {
+        Some(owner) => get_annotation(db, owner, name),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves all the annotations for the specified bookmark item, ordered by
+/// name.
+pub fn get_item_annotations(db: &PlacesDb, guid: &SyncGuid) -> Result<Vec<Annotation>> {
+    match item_owner(db, guid)? {
+        Some(owner) => get_annotations(db, owner),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Removes an annotation from the specified bookmark item.
+///
+/// # Returns
+///
+/// There is no success return value - the operation is ignored if the item
+/// doesn't have the annotation.
+pub fn remove_item_annotation(db: &PlacesDb, guid: &SyncGuid, name: &str) -> Result<()> {
+    match item_owner(db, guid)? {
+        Some(owner) => remove_annotation(db, owner, name),
+        None => Ok(()),
+    }
+}
+
+/// Retrieves the GUIDs of all bookmark items with the specified annotation.
+pub fn get_items_with_annotation(db: &PlacesDb, name: &str) -> Result<Vec<SyncGuid>> {
+    Ok(db.query_rows_and_then_named_cached(
+        "SELECT b.guid FROM moz_items_annos a
+         JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+         JOIN moz_bookmarks b ON b.id = a.item_id
+         WHERE n.name = :name
+         ORDER BY b.id",
+        &[(":name", &name)],
+        |row| row.get::<_, SyncGuid>(0),
+    )?)
+}
+
+/// Removes page and item annotations whose time-based expiration has passed,
+/// along with any names that are no longer used. This runs as part of
+/// `run_maintenance` and `prune_destructively`.
+pub fn expire_annotations(db: &PlacesDb) -> Result<()> {
+    let now = Timestamp::now();
+    for &expiration in &[
+        AnnotationExpiration::Days,
+        AnnotationExpiration::Weeks,
+        AnnotationExpiration::Months,
+    ] {
+        let cutoff = now
+            .checked_sub(expiration.max_age().expect("should have a max age"))
+            .unwrap_or_default();
+        for table in &["moz_annos", "moz_items_annos"] {
+            db.execute_named_cached(
+                &format!(
+                    "DELETE FROM {table}
+                     WHERE expiration = :expiration AND lastModified < :cutoff",
+                    table = table,
+                ),
+                &[(":expiration", &expiration), (":cutoff", &cutoff)],
+            )?;
+        }
+    }
+    delete_unused_annotation_names(db)
+}
+
+/// Removes page annotations that don't survive clearing history. Assumes a
+/// transaction is already set up by the caller.
+pub(crate) fn delete_history_annotations(db: &PlacesDb) -> Result<()> {
+    db.execute_named_cached(
+        "DELETE FROM moz_annos WHERE expiration <> :never",
+        &[(":never", &AnnotationExpiration::Never)],
+    )?;
+    delete_unused_annotation_names(db)
+}
+
+fn delete_unused_annotation_names(db: &PlacesDb) -> Result<()> {
+    db.execute_batch(
+        "DELETE FROM moz_anno_attributes
+         WHERE id NOT IN (SELECT anno_attribute_id FROM moz_annos
+                          UNION ALL
+                          SELECT anno_attribute_id FROM moz_items_annos)",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::bookmarks::delete_bookmark;
+    use crate::storage::history::{delete_everything, wipe_local};
+    use crate::tests::{bookmark_url, visit_url};
+
+    fn count_names(db: &PlacesDb) -> u32 {
+        db.query_one::<u32>("SELECT COUNT(*) FROM moz_anno_attributes")
+            .expect("should count")
+    }
+
+    #[test]
+    fn test_page_annotations() -> Result<()> {
+        let conn = new_mem_connection();
+        let url = Url::parse("https://www.example.com/")?;
+
+        // Can't annotate a page that doesn't exist.
+        assert!(
+            set_page_annotation(&conn, &url, "reader", 1i64, AnnotationExpiration::Never).is_err()
+        );
+        visit_url(&conn, &url);
+        assert!(set_page_annotation(&conn, &url, "", 1i64, AnnotationExpiration::Never).is_err());
+        assert!(
+            set_page_annotation(&conn, &url, " reader", 1i64, AnnotationExpiration::Never).is_err()
+        );
+
+        set_page_annotation(&conn, &url, "reader", 1i64, AnnotationExpiration::Never)?;
+        set_page_annotation(&conn, &url, "score", 0.5, AnnotationExpiration::Days)?;
+        set_page_annotation(
+            &conn,
+            &url,
+            "description",
+            "An example",
+            AnnotationExpiration::WithHistory,
+        )?;
+        assert_eq!(
+            get_page_annotation(&conn, &url, "reader")?,
+            Some(AnnotationValue::Int(1))
+        );
+        assert_eq!(
+            get_page_annotation(&conn, &url, "score")?.and_then(|v| v.as_double()),
+            Some(0.5)
+        );
+        assert_eq!(
+            get_page_annotation(&conn, &url, "description")?,
+            Some(AnnotationValue::Text("An example".to_string()))
+        );
+        assert_eq!(get_page_annotation(&conn, &url, "missing")?, None);
+
+        // Setting an annotation again replaces its value and expiration.
+        set_page_annotation(&conn, &url, "reader", "yes", AnnotationExpiration::Months)?;
+        let annos = get_page_annotations(&conn, &url)?;
+        assert_eq!(
+            annos.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(),
+            vec!["description", "reader", "score"]
+        );
+        assert_eq!(annos[1].value, AnnotationValue::Text("yes".to_string()));
+        assert_eq!(annos[1].expiration, AnnotationExpiration::Months);
+
+        assert_eq!(
+            get_pages_with_annotation(&conn, "reader")?,
+            vec![url.clone()]
+        );
+
+        remove_page_annotation(&conn, &url, "reader")?;
+        assert_eq!(get_page_annotation(&conn, &url, "reader")?, None);
+        assert!(get_pages_with_annotation(&conn, "reader")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_item_annotations() -> Result<()> {
+        let conn = new_mem_connection();
+        let url = Url::parse("https://www.example.com/")?;
+        let guid = bookmark_url(&conn, &url);
+
+        assert!(set_item_annotation(
+            &conn,
+            &SyncGuid::from("bookmarkAAAA"),
+            "saved",
+            1i64,
+            AnnotationExpiration::Never
+        )
+        .is_err());
+
+        set_item_annotation(&conn, &guid, "saved", 1i64, AnnotationExpiration::Never)?;
+        assert_eq!(
+            get_item_annotation(&conn, &guid, "saved")?,
+            Some(AnnotationValue::Int(1))
+        );
+        assert_eq!(
+            get_items_with_annotation(&conn, "saved")?,
+            vec![guid.clone()]
+        );
+        assert_eq!(get_item_annotations(&conn, &guid)?.len(), 1);
+        // Item and page annotations are separate.
+        assert_eq!(get_page_annotation(&conn, &url, "saved")?, None);
+
+        // Deleting the bookmark removes its annotations.
+        delete_bookmark(&conn, &guid)?;
+        assert!(get_items_with_annotation(&conn, "saved")?.is_empty());
+        expire_annotations(&conn)?;
+        assert_eq!(count_names(&conn), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wipe_local() -> Result<()> {
+        let conn = new_mem_connection();
+        let visited = Url::parse("https://www.example.com/")?;
+        let bookmarked = Url::parse("https://www.example.com/bookmarked")?;
+        visit_url(&conn, &visited);
+        visit_url(&conn, &bookmarked);
+        let guid = bookmark_url(&conn, &bookmarked);
+
+        set_page_annotation(&conn, &visited, "never", 1i64, AnnotationExpiration::Never)?;
+        set_page_annotation(
+            &conn,
+            &bookmarked,
+            "never",
+            1i64,
+            AnnotationExpiration::Never,
+        )?;
+        set_page_annotation(
+            &conn,
+            &bookmarked,
+            "history",
+            1i64,
+            AnnotationExpiration::WithHistory,
+        )?;
+        set_page_annotation(&conn, &bookmarked, "days", 1i64, AnnotationExpiration::Days)?;
+        set_item_annotation(
+            &conn,
+            &guid,
+            "item",
+            1i64,
+            AnnotationExpiration::WithHistory,
+        )?;
+
+        wipe_local(&conn)?;
+
+        // The page that's only visited is gone, along with its annotations.
+        // The bookmarked page only keeps its `Never` annotations, and item
+        // annotations aren't affected.
+        assert!(get_page_annotations(&conn, &visited)?.is_empty());
+        assert_eq!(
+            get_pages_with_annotation(&conn, "never")?,
+            vec![bookmarked.clone()]
+        );
+        assert_eq!(get_page_annotation(&conn, &bookmarked, "history")?, None);
+        assert_eq!(get_page_annotation(&conn, &bookmarked, "days")?, None);
+        assert_eq!(
+            get_item_annotation(&conn, &guid, "item")?,
+            Some(AnnotationValue::Int(1))
+        );
+        assert_eq!(count_names(&conn), 2);
+
+        set_page_annotation(
+            &conn,
+            &bookmarked,
+            "history",
+            1i64,
+            AnnotationExpiration::WithHistory,
+        )?;
+        delete_everything(&conn)?;
+        assert_eq!(get_page_annotation(&conn, &bookmarked, "history")?, None);
+        assert_eq!(
+            get_page_annotation(&conn, &bookmarked, "never")?,
+            Some(AnnotationValue::Int(1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire() -> Result<()> {
+        let conn = new_mem_connection();
+        let url = Url::parse("https://www.example.com/")?;
+        let guid = bookmark_url(&conn, &url);
+
+        set_page_annotation(&conn, &url, "days", 1i64, AnnotationExpiration::Days)?;
+        set_page_annotation(&conn, &url, "weeks", 1i64, AnnotationExpiration::Weeks)?;
+        set_page_annotation(&conn, &url, "never", 1i64, AnnotationExpiration::Never)?;
+        set_item_annotation(&conn, &guid, "months", 1i64, AnnotationExpiration::Months)?;
+
+        // Nothing has expired yet.
+        expire_annotations(&conn)?;
+        assert_eq!(get_page_annotations(&conn, &url)?.len(), 3);
+        assert_eq!(get_item_annotations(&conn, &guid)?.len(), 1);
+
+        // Pretend everything was set 10 days ago.
+        let ten_days_ago = Timestamp::now()
+            .checked_sub(DAY * 10)
+            .expect("should be valid");
+        conn.execute_named(
+            "UPDATE moz_annos SET lastModified = :date",
+            &[(":date", &ten_days_ago)],
+        )?;
+        conn.execute_named(
+            "UPDATE moz_items_annos SET lastModified = :date",
+            &[(":date", &ten_days_ago)],
+        )?;
+        expire_annotations(&conn)?;
+        let names = get_page_annotations(&conn, &url)?
+            .into_iter()
+            .map(|a| a.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["never".to_string(), "weeks".to_string()]);
+        assert_eq!(get_item_annotations(&conn, &guid)?.len(), 1);
+        assert_eq!(count_names(&conn), 3);
+        Ok(())
+    }
+}
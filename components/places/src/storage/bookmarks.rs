@@ -6,6 +6,7 @@ use super::RowId;
 use super::{fetch_page_info, new_page_info};
 use crate::db::PlacesDb;
 use crate::error::*;
+use crate::events::PlacesEvent;
 use crate::types::{BookmarkType, SyncGuid, SyncStatus, Timestamp};
 use rusqlite::types::ToSql;
 use rusqlite::{Connection, Row};
@@ -25,8 +26,11 @@ pub use public_node::PublicNode;
 pub use root_guid::{BookmarkRootGuid, USER_CONTENT_ROOTS};
 
 mod conversions;
+pub mod duplicates;
+pub mod integrity;
 pub mod public_node;
 mod root_guid;
+pub mod undo;
 
 fn create_root(
     db: &Connection,
@@ -353,6 +357,16 @@ fn insert_bookmark_in_tx(db: &PlacesDb, bm: &InsertableItem) -> Result<SyncGuid>
         WHERE id = :parent_id";
     db.execute_named_cached(sql_counter, &[(":parent_id", &parent.row_id)])?;
 
+    db.note_event(PlacesEvent::BookmarkAdded {
+        guid: guid.clone(),
+        parent_guid: parent.guid.clone(),
+        position,
+        kind: bookmark_type,
+        url: match bm {
+            InsertableItem::Bookmark(b) => Some(b.url.to_string()),
+            _ => None,
+        },
+    });
     Ok(guid)
 }
 
@@ -386,6 +400,12 @@ fn delete_bookmark_in_tx(db: &PlacesDb, guid: &SyncGuid) -> Result<bool> {
     let record_parent_id = record
         .parent_id
         .ok_or_else(|| Corruption::NonRootWithoutParent(guid.to_string()))?;
+    let record_parent_guid = record
+        .parent_guid
+        .clone()
+        .ok_or_else(|| Corruption::NonRootWithoutParent(guid.to_string()))?;
+    // Keep the subtree, so that the deletion can be undone.
+    undo::record_deletion(db, &record)?;
     // must reorder existing children.
     update_pos_for_deletion(db, record.position, record_parent_id)?;
     // and delete - children are recursively deleted.
@@ -394,6 +414,12 @@ fn delete_bookmark_in_tx(db: &PlacesDb, guid: &SyncGuid) -> Result<bool> {
         &[(":id", &record.row_id)],
     )?;
     super::delete_pending_temp_tables(db)?;
+    db.note_event(PlacesEvent::BookmarkRemoved {
+        guid: guid.clone(),
+        parent_guid: record_parent_guid,
+        position: record.position,
+        kind: record.bookmark_type,
+    });
     Ok(true)
 }
 
@@ -543,7 +569,9 @@ fn update_bookmark_in_tx(
             }
             let new_parent = get_raw_bookmark(db, &new_parent_guid)?
                 .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(new_parent_guid.to_string()))?;
-            if new_parent.bookmark_type != BookmarkType::Folder {
+            if new_parent.bookmark_type != BookmarkType::Folder
+                || is_self_or_descendant(db, new_parent.row_id, raw.row_id)?
+            {
                 return Err(InvalidPlaceInfo::InvalidParent(new_parent_guid.to_string()).into());
             }
             parent_id = new_parent.row_id;
@@ -639,6 +667,30 @@ fn update_bookmark_in_tx(
         set_ancestors_last_modified(db, parent_id, now)?;
         db.execute_named_cached(sql_counter, &[(":parent_id", &parent_id)])?;
     }
+
+    if parent_id != existing_parent_id || position != raw.position {
+        db.note_event(PlacesEvent::BookmarkMoved {
+            guid: guid.clone(),
+            old_parent_guid: existing_parent_guid.clone(),
+            old_position: raw.position,
+            parent_guid: match item.location() {
+                UpdateTreeLocation::Parent(new_parent_guid, _) => new_parent_guid.clone(),
+                _ => existing_parent_guid.clone(),
+            },
+            position,
+        });
+    }
+    if change_incr {
+        db.note_event(PlacesEvent::BookmarkUpdated {
+            guid: guid.clone(),
+            title: maybe_truncate_title(&title).map(String::from),
+            url: match item {
+                UpdatableItem::Bookmark(b) => b.url.as_ref().or_else(|| raw.url.as_ref()),
+                _ => None,
+            }
+            .map(Url::to_string),
+        });
+    }
     Ok(())
 }
 
@@ -666,6 +718,325 @@ fn set_ancestors_last_modified(db: &PlacesDb, parent_id: RowId, time: Timestamp)
     Ok(())
 }
 
+/// Returns true if `folder_id` is `item_id` or one of its descendants, in
+/// which case moving the item into the folder would create a cycle.
+fn is_self_or_descendant(db: &PlacesDb, folder_id: RowId, item_id: RowId) -> Result<bool> {
+    Ok(db.query_row_and_then_named(
+        "WITH RECURSIVE
+         ancestors(aid) AS (
+             SELECT :folder_id
+             UNION ALL
+             SELECT parent FROM moz_bookmarks
+             JOIN ancestors ON id = aid
+             WHERE parent NOT NULL
+         )
+         SELECT EXISTS(SELECT 1 FROM ancestors WHERE aid = :item_id)",
+        &[(":folder_id", &folder_id), (":item_id", &item_id)],
+        |row| row.get::<_, bool>(0),
+        true,
+    )?)
+}
+
+// Support for changing many items in a single transaction, so that callers
+// don't need to call `update_bookmark` for each one.
+
+/// Moves the items with the given GUIDs into `parent_guid`, keeping them
+/// together in the order given. With `BookmarkPosition::Specific`, they're
+/// moved in front of the child currently at that position, or to the end of
+/// the folder if every child from that position on is being moved.
+pub fn move_bookmarks(
+    db: &PlacesDb,
+    guids: &[SyncGuid],
+    parent_guid: &SyncGuid,
+    position: BookmarkPosition,
+) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    let result = move_bookmarks_in_tx(db, guids, parent_guid, position);
+    match result {
+        Ok(_) => tx.commit()?,
+        Err(_) => tx.rollback()?,
+    }
+    result
+}
+
+fn move_bookmarks_in_tx(
+    db: &PlacesDb,
+    guids: &[SyncGuid],
+    parent_guid: &SyncGuid,
+    position: BookmarkPosition,
+) -> Result<()> {
+    let parent = get_raw_bookmark(db, parent_guid)?
+        .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(parent_guid.to_string()))?;
+    if parent.bookmark_type != BookmarkType::Folder {
+        return Err(InvalidPlaceInfo::InvalidParent(parent_guid.to_string()).into());
+    }
+    // Rather than tracking how the indexes shift as we move each item, we
+    // find the first child that isn't being moved, and insert each item in
+    // front of it.
+    let anchor_guid = match position {
+        BookmarkPosition::Specific(pos) => get_raw_bookmarks_with_parent(db, parent.row_id)?
+            .into_iter()
+            .find(|child| child.position >= pos && !guids.contains(&child.guid))
+            .map(|child| child.guid),
+        BookmarkPosition::Append => None,
+    };
+    for guid in guids {
+        let raw = get_raw_bookmark(db, guid)?
+            .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(guid.to_string()))?;
+        let same_parent = raw.parent_id == Some(parent.row_id);
+        let pos = match &anchor_guid {
+            Some(anchor_guid) => {
+                let anchor = get_raw_bookmark(db, anchor_guid)?
+                    .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(anchor_guid.to_string()))?;
+                // When moving forward in the same folder, the anchor shifts
+                // back by one once the item is taken out.
+                if same_parent && raw.position < anchor.position {
+                    BookmarkPosition::Specific(anchor.position - 1)
+                } else {
+                    BookmarkPosition::Specific(anchor.position)
+                }
+            }
+            None => BookmarkPosition::Append,
+        };
+        let location = if same_parent {
+            UpdateTreeLocation::Position(pos)
+        } else {
+            UpdateTreeLocation::Parent(parent_guid.clone(), pos)
+        };
+        let item = match raw.bookmark_type {
+            BookmarkType::Bookmark => UpdatableBookmark {
+                location,
+                ..Default::default()
+            }
+            .into(),
+            BookmarkType::Separator => UpdatableSeparator { location }.into(),
+            BookmarkType::Folder => UpdatableFolder {
+                location,
+                ..Default::default()
+            }
+            .into(),
+        };
+        update_bookmark_in_tx(db, guid, &item, raw)?;
+    }
+    Ok(())
+}
+
+/// Copies a bookmark, separator or folder into `parent_guid`. Folders are
+/// copied with all their descendants. The copies get new GUIDs and
+/// timestamps. Returns the GUID of the copy.
+pub fn copy_bookmark(
+    db: &PlacesDb,
+    guid: &SyncGuid,
+    parent_guid: &SyncGuid,
+    position: BookmarkPosition,
+) -> Result<SyncGuid> {
+    let tx = db.begin_transaction()?;
+    let result = copy_bookmark_in_tx(db, guid, parent_guid, position);
+    super::delete_pending_temp_tables(db)?;
+    match result {
+        Ok(_) => tx.commit()?,
+        Err(_) => tx.rollback()?,
+    }
+    result
+}
+
+fn copy_bookmark_in_tx(
+    db: &PlacesDb,
+    guid: &SyncGuid,
+    parent_guid: &SyncGuid,
+    position: BookmarkPosition,
+) -> Result<SyncGuid> {
+    if let Some(root) = guid.as_root() {
+        return Err(InvalidPlaceInfo::CannotUpdateRoot(root).into());
+    }
+    let raw = get_raw_bookmark(db, guid)?
+        .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(guid.to_string()))?;
+    let new_guid = SyncGuid::new();
+    let mut insert_infos: Vec<InsertableItem> = Vec::new();
+    match raw.bookmark_type {
+        BookmarkType::Bookmark => insert_infos.push(
+            InsertableBookmark {
+                parent_guid: parent_guid.clone(),
+                position,
+                date_added: None,
+                last_modified: None,
+                guid: Some(new_guid.clone()),
+                url: raw.url.ok_or(InvalidPlaceInfo::NoUrl)?,
+                title: raw.title,
+            }
+            .into(),
+        ),
+        BookmarkType::Separator => insert_infos.push(
+            InsertableSeparator {
+                parent_guid: parent_guid.clone(),
+                position,
+                date_added: None,
+                last_modified: None,
+                guid: Some(new_guid.clone()),
+            }
+            .into(),
+        ),
+        BookmarkType::Folder => {
+            // We fetch the whole tree before inserting anything, so copying
+            // a folder into itself doesn't recurse forever.
+            let mut folder = match fetch_tree(db, guid)? {
+                Some(BookmarkTreeNode::Folder(folder)) => folder,
+                _ => return Err(InvalidPlaceInfo::NoSuchGuid(guid.to_string()).into()),
+            };
+            clear_guids_and_dates(&mut folder);
+            insert_infos.push(
+                InsertableFolder {
+                    parent_guid: parent_guid.clone(),
+                    position,
+                    date_added: None,
+                    last_modified: None,
+                    guid: Some(new_guid.clone()),
+                    title: folder.title.clone(),
+                }
+                .into(),
+            );
+            add_subtree_infos(&new_guid, &folder, &mut insert_infos);
+        }
+    }
+    for insertable in insert_infos {
+        insert_bookmark_in_tx(db, &insertable)?;
+    }
+    Ok(new_guid)
+}
+
+fn clear_guids_and_dates(folder: &mut FolderNode) {
+    for child in &mut folder.children {
+        match child {
+            BookmarkTreeNode::Bookmark(b) => {
+                b.guid = None;
+                b.date_added = None;
+                b.last_modified = None;
+            }
+            BookmarkTreeNode::Separator(s) => {
+                s.guid = None;
+                s.date_added = None;
+                s.last_modified = None;
+            }
+            BookmarkTreeNode::Folder(f) => {
+                f.guid = None;
+                f.date_added = None;
+                f.last_modified = None;
+                clear_guids_and_dates(f);
+            }
+        }
+    }
+}
+
+/// How `sort_folder_children` orders a folder's children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ChildrenSortOrder {
+    /// By title, ignoring case. Bookmarks without a title are sorted by URL.
+    Title = 0,
+    /// Oldest first.
+    DateAdded = 1,
+    /// Least recently modified first.
+    LastModified = 2,
+}
+
+impl ChildrenSortOrder {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        Some(match v {
+            0 => ChildrenSortOrder::Title,
+            1 => ChildrenSortOrder::DateAdded,
+            2 => ChildrenSortOrder::LastModified,
+            _ => return None,
+        })
+    }
+}
+
+/// Sorts the children of a folder. Like desktop, separators stay where they
+/// are, and the items between them are sorted separately.
+pub fn sort_folder_children(
+    db: &PlacesDb,
+    folder_guid: &SyncGuid,
+    order: ChildrenSortOrder,
+) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    let (folder, mut children) = get_folder_and_children(db, folder_guid)?;
+    for group in children.split_mut(|child| child.bookmark_type == BookmarkType::Separator) {
+        // `sort_by_key` is stable, so items that compare equal keep their
+        // current order.
+        match order {
+            ChildrenSortOrder::Title => group.sort_by_key(|child| {
+                child
+                    .title
+                    .as_ref()
+                    .map(|title| title.to_lowercase())
+                    .or_else(|| child.url.as_ref().map(|url| url.as_str().to_lowercase()))
+                    .unwrap_or_default()
+            }),
+            ChildrenSortOrder::DateAdded => group.sort_by_key(|child| child.date_added),
+            ChildrenSortOrder::LastModified => group.sort_by_key(|child| child.date_modified),
+        }
+    }
+    reorder_children(db, &folder, &children)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Reverses the order of a folder's children.
+pub fn reverse_folder_children(db: &PlacesDb, folder_guid: &SyncGuid) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    let (folder, mut children) = get_folder_and_children(db, folder_guid)?;
+    children.reverse();
+    reorder_children(db, &folder, &children)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn get_folder_and_children(
+    db: &PlacesDb,
+    folder_guid: &SyncGuid,
+) -> Result<(RawBookmark, Vec<RawBookmark>)> {
+    let folder = get_raw_bookmark(db, folder_guid)?
+        .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(folder_guid.to_string()))?;
+    if folder.bookmark_type != BookmarkType::Folder {
+        return Err(InvalidPlaceInfo::InvalidParent(folder_guid.to_string()).into());
+    }
+    let children = get_raw_bookmarks_with_parent(db, folder.row_id)?;
+    Ok((folder, children))
+}
+
+/// Sets the positions of a folder's children to their order in `children`,
+/// which must include all of them. If the order changed, the folder's change
+/// counter is bumped, since it's the folder's record that holds the order.
+/// Assumes a transaction is already set up by the caller.
+fn reorder_children(db: &PlacesDb, folder: &RawBookmark, children: &[RawBookmark]) -> Result<()> {
+    let mut changed = false;
+    for (position, child) in children.iter().enumerate() {
+        let position = position as u32;
+        if child.position != position {
+            db.execute_named_cached(
+                "UPDATE moz_bookmarks SET position = :position WHERE id = :id",
+                &[(":position", &position), (":id", &child.row_id)],
+            )?;
+            db.note_event(PlacesEvent::BookmarkMoved {
+                guid: child.guid.clone(),
+                old_parent_guid: folder.guid.clone(),
+                old_position: child.position,
+                parent_guid: folder.guid.clone(),
+                position,
+            });
+            changed = true;
+        }
+    }
+    if changed {
+        set_ancestors_last_modified(db, folder.row_id, Timestamp::now())?;
+        db.execute_named_cached(
+            "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+             WHERE id = :parent_id",
+            &[(":parent_id", &folder.row_id)],
+        )?;
+    }
+    Ok(())
+}
+
 /// Support for inserting and fetching a tree. Same limitations as desktop.
 /// Note that the guids are optional when inserting a tree. They will always
 /// have values when fetching it.
@@ -992,7 +1363,6 @@ mod test_serialize {
 
         Ok(())
     }
-
 }
 
 fn add_subtree_infos(parent: &SyncGuid, tree: &FolderNode, insert_infos: &mut Vec<InsertableItem>) {
@@ -1046,6 +1416,14 @@ fn add_subtree_infos(parent: &SyncGuid, tree: &FolderNode, insert_infos: &mut Ve
 }
 
 pub fn insert_tree(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    insert_tree_in_tx(db, tree)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Assumes a transaction is already set up by the caller.
+pub(crate) fn insert_tree_in_tx(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
     let parent_guid = match &tree.guid {
         Some(guid) => guid,
         None => return Err(InvalidPlaceInfo::InvalidParent("<no guid>".into()).into()),
@@ -1054,13 +1432,11 @@ pub fn insert_tree(db: &PlacesDb, tree: &FolderNode) -> Result<()> {
     let mut insert_infos: Vec<InsertableItem> = Vec::new();
     add_subtree_infos(&parent_guid, tree, &mut insert_infos);
     log::info!("insert_tree inserting {} records", insert_infos.len());
-    let tx = db.begin_transaction()?;
 
     for insertable in insert_infos {
         insert_bookmark_in_tx(db, &insertable)?;
     }
     super::delete_pending_temp_tables(db)?;
-    tx.commit()?;
     Ok(())
 }
 
@@ -1266,6 +1642,8 @@ pub(crate) struct RawBookmark {
     pub sync_change_counter: u32,
     pub child_count: u32,
     pub grandparent_id: Option<RowId>,
+    pub description: Option<String>,
+    pub preview_image_url: Option<Url>,
 }
 
 impl RawBookmark {
@@ -1294,6 +1672,11 @@ impl RawBookmark {
                 .unwrap_or_default(),
             child_count: row.get("_childCount")?,
             grandparent_id: row.get("_grandparentId")?,
+            description: row.get("description")?,
+            preview_image_url: match row.get::<_, Option<String>>("preview_image_url")? {
+                Some(s) => Some(Url::parse(&s)?),
+                None => None,
+            },
         })
     }
 }
@@ -1318,7 +1701,9 @@ const RAW_BOOKMARK_SQL: &str = "
         b.syncStatus AS _syncStatus,
         -- the columns below don't appear in the desktop query
         b.fk,
-        b.syncChangeCounter
+        b.syncChangeCounter,
+        h.description,
+        h.preview_image_url
     FROM moz_bookmarks b
     LEFT JOIN moz_bookmarks p ON p.id = b.parent
     LEFT JOIN moz_places h ON h.id = b.fk
@@ -1361,7 +1746,7 @@ mod tests {
     use super::*;
     use crate::api::places_api::test::new_mem_connection;
     use crate::db::PlacesDb;
-    use crate::tests::{assert_json_tree, insert_json_tree};
+    use crate::tests::{assert_json_tree, change_counter, insert_json_tree};
     use pretty_assertions::assert_eq;
     use rusqlite::NO_PARAMS;
     use serde_json::Value;
@@ -2164,4 +2549,319 @@ mod tests {
         );
         Ok(())
     }
+
+    fn child_guids(conn: &PlacesDb, folder: &str) -> Vec<String> {
+        let folder = get_raw_bookmark(conn, &folder.into())
+            .expect("should work")
+            .expect("should exist");
+        get_raw_bookmarks_with_parent(conn, folder.row_id)
+            .expect("should work")
+            .into_iter()
+            .map(|child| child.guid.0)
+            .collect()
+    }
+
+    fn insert_batch_test_tree(conn: &PlacesDb) {
+        insert_json_tree(
+            conn,
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "title": "m",
+                        "url": "https://www.example.com/a",
+                        "date_added": 500,
+                    },
+                    {
+                        "guid": "bookmarkBBBB",
+                        "title": "C",
+                        "url": "https://www.example.com/b",
+                        "date_added": 1000,
+                    },
+                    {
+                        "guid": "bookmarkCCCC",
+                        "url": "https://www.example.com/a",
+                        "date_added": 2000,
+                    },
+                    {
+                        "guid": "separator1__",
+                        "type": BookmarkType::Separator as u8,
+                    },
+                    {
+                        "guid": "bookmarkDDDD",
+                        "title": "z",
+                        "url": "https://www.example.com/d",
+                        "date_added": 4000,
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "date_added": 500,
+                        "children": [
+                            {
+                                "guid": "bookmarkEEEE",
+                                "title": "in folder",
+                                "url": "https://www.example.com/e",
+                            },
+                            {
+                                "guid": "folderBBBBBB",
+                                "title": "nested",
+                                "children": [
+                                    {
+                                        "guid": "bookmarkFFFF",
+                                        "title": "in nested",
+                                        "url": "https://www.example.com/f",
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                ]
+            }),
+        );
+        conn.execute("UPDATE moz_bookmarks SET syncChangeCounter = 0", NO_PARAMS)
+            .expect("should work");
+    }
+
+    #[test]
+    fn test_move_bookmarks() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_batch_test_tree(&conn);
+
+        // Within the same folder, the items end up in front of the first
+        // child at or after the position that isn't being moved.
+        move_bookmarks(
+            &conn,
+            &["bookmarkDDDD".into(), "bookmarkAAAA".into()],
+            &BookmarkRootGuid::Unfiled.as_guid(),
+            BookmarkPosition::Specific(2),
+        )?;
+        assert_eq!(
+            child_guids(&conn, BookmarkRootGuid::Unfiled.as_str()),
+            vec![
+                "bookmarkBBBB",
+                "bookmarkDDDD",
+                "bookmarkAAAA",
+                "bookmarkCCCC",
+                "separator1__",
+                "folderAAAAAA",
+            ]
+        );
+        assert_eq!(change_counter(&conn, BookmarkRootGuid::Unfiled.as_str()), 2);
+        assert_eq!(change_counter(&conn, "bookmarkAAAA"), 0);
+
+        // Into another folder.
+        move_bookmarks(
+            &conn,
+            &[
+                "separator1__".into(),
+                "bookmarkCCCC".into(),
+                "bookmarkBBBB".into(),
+            ],
+            &"folderBBBBBB".into(),
+            BookmarkPosition::Specific(0),
+        )?;
+        assert_eq!(
+            child_guids(&conn, BookmarkRootGuid::Unfiled.as_str()),
+            vec!["bookmarkDDDD", "bookmarkAAAA", "folderAAAAAA"]
+        );
+        assert_eq!(
+            child_guids(&conn, "folderBBBBBB"),
+            vec![
+                "separator1__",
+                "bookmarkCCCC",
+                "bookmarkBBBB",
+                "bookmarkFFFF",
+            ]
+        );
+        crate::tests::check_positions(&conn);
+
+        // Appending, with items from different folders.
+        move_bookmarks(
+            &conn,
+            &["bookmarkFFFF".into(), "bookmarkAAAA".into()],
+            &"folderAAAAAA".into(),
+            BookmarkPosition::Append,
+        )?;
+        assert_eq!(
+            child_guids(&conn, "folderAAAAAA"),
+            vec![
+                "bookmarkEEEE",
+                "folderBBBBBB",
+                "bookmarkFFFF",
+                "bookmarkAAAA",
+            ]
+        );
+        crate::tests::check_positions(&conn);
+
+        // A folder can't be moved into itself or its descendants, and a
+        // failure leaves everything as it was.
+        assert!(move_bookmarks(
+            &conn,
+            &["bookmarkDDDD".into(), "folderAAAAAA".into()],
+            &"folderBBBBBB".into(),
+            BookmarkPosition::Append,
+        )
+        .is_err());
+        assert!(move_bookmarks(
+            &conn,
+            &["folderAAAAAA".into()],
+            &"folderAAAAAA".into(),
+            BookmarkPosition::Append,
+        )
+        .is_err());
+        assert_eq!(
+            child_guids(&conn, BookmarkRootGuid::Unfiled.as_str()),
+            vec!["bookmarkDDDD", "folderAAAAAA"]
+        );
+        assert!(move_bookmarks(
+            &conn,
+            &["bookmarkDDDD".into()],
+            &"bookmarkEEEE".into(),
+            BookmarkPosition::Append,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_bookmark() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_batch_test_tree(&conn);
+
+        let copy_guid = copy_bookmark(
+            &conn,
+            &"folderAAAAAA".into(),
+            &"folderBBBBBB".into(),
+            BookmarkPosition::Specific(0),
+        )?;
+        assert_json_tree(
+            &conn,
+            &"folderBBBBBB".into(),
+            json!({
+                "guid": "folderBBBBBB",
+                "title": "nested",
+                "children": [
+                    {
+                        "guid": copy_guid,
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "title": "in folder",
+                                "url": "https://www.example.com/e",
+                            },
+                            {
+                                "title": "nested",
+                                "children": [
+                                    {
+                                        "title": "in nested",
+                                        "url": "https://www.example.com/f",
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkFFFF",
+                        "title": "in nested",
+                        "url": "https://www.example.com/f",
+                    },
+                ],
+            }),
+        );
+        // The copies, and the folder they were copied into, are marked as
+        // changed.
+        let count = conn
+            .query_one::<u32>("SELECT COUNT(*) FROM moz_bookmarks WHERE syncChangeCounter > 0")?;
+        assert_eq!(count, 5);
+        let copy = get_raw_bookmark(&conn, &copy_guid)?.expect("should exist");
+        assert_ne!(copy.date_added, Timestamp(500));
+        assert_eq!(child_guids(&conn, "folderAAAAAA").len(), 2);
+
+        let copy_guid = copy_bookmark(
+            &conn,
+            &"separator1__".into(),
+            &BookmarkRootGuid::Unfiled.as_guid(),
+            BookmarkPosition::Append,
+        )?;
+        let copy = get_raw_bookmark(&conn, &copy_guid)?.expect("should exist");
+        assert_eq!(copy.bookmark_type, BookmarkType::Separator);
+        assert_eq!(copy.position, 6);
+
+        assert!(copy_bookmark(
+            &conn,
+            &BookmarkRootGuid::Menu.as_guid(),
+            &BookmarkRootGuid::Unfiled.as_guid(),
+            BookmarkPosition::Append,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_and_reverse_children() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_batch_test_tree(&conn);
+        let unfiled = BookmarkRootGuid::Unfiled.as_str();
+
+        // Bookmarks are sorted between separators, ignoring case, and a
+        // bookmark without a title is sorted by its URL.
+        sort_folder_children(&conn, &unfiled.into(), ChildrenSortOrder::Title)?;
+        assert_eq!(
+            child_guids(&conn, unfiled),
+            vec![
+                "bookmarkBBBB",
+                "bookmarkCCCC",
+                "bookmarkAAAA",
+                "separator1__",
+                "folderAAAAAA",
+                "bookmarkDDDD",
+            ]
+        );
+        assert_eq!(change_counter(&conn, unfiled), 1);
+
+        // Sorting again doesn't change anything.
+        sort_folder_children(&conn, &unfiled.into(), ChildrenSortOrder::Title)?;
+        assert_eq!(change_counter(&conn, unfiled), 1);
+
+        sort_folder_children(&conn, &unfiled.into(), ChildrenSortOrder::DateAdded)?;
+        assert_eq!(
+            child_guids(&conn, unfiled),
+            vec![
+                "bookmarkAAAA",
+                "bookmarkBBBB",
+                "bookmarkCCCC",
+                "separator1__",
+                "folderAAAAAA",
+                "bookmarkDDDD",
+            ]
+        );
+        assert_eq!(change_counter(&conn, unfiled), 2);
+
+        reverse_folder_children(&conn, &unfiled.into())?;
+        assert_eq!(
+            child_guids(&conn, unfiled),
+            vec![
+                "bookmarkDDDD",
+                "folderAAAAAA",
+                "separator1__",
+                "bookmarkCCCC",
+                "bookmarkBBBB",
+                "bookmarkAAAA",
+            ]
+        );
+        assert_eq!(change_counter(&conn, unfiled), 3);
+        // Only the folder's change counter is bumped.
+        let count = conn
+            .query_one::<u32>("SELECT COUNT(*) FROM moz_bookmarks WHERE syncChangeCounter > 0")?;
+        assert_eq!(count, 1);
+        crate::tests::check_positions(&conn);
+
+        assert!(reverse_folder_children(&conn, &"bookmarkAAAA".into()).is_err());
+        Ok(())
+    }
 }
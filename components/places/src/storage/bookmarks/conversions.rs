@@ -86,6 +86,8 @@ impl From<PublicNode> for msg_types::BookmarkNode {
                     .collect()
             }),
             have_child_nodes,
+            description: n.description,
+            preview_image_url: n.preview_image_url.map(url::Url::into_string),
         }
     }
 }
@@ -104,6 +106,8 @@ impl From<RawBookmark> for PublicNode {
             title: rb.title,
             child_guids: None,
             child_nodes: None,
+            description: rb.description,
+            preview_image_url: rb.preview_image_url,
         }
     }
 }
@@ -0,0 +1,361 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Finds and merges duplicate bookmarks in the local tree. Dogear dedupes
+//! incoming items against new local items when we sync, but users can still
+//! bookmark the same URL many times, in the same or different folders.
+//!
+//! Bookmarks are duplicates if they have the same URL. Tags and keywords
+//! belong to the URL, not the bookmark, so the bookmark we keep already has
+//! them, and Sync uploads them with it.
+
+use super::*;
+use crate::storage::{keywords, tags};
+
+/// Bookmarks with the same URL, as returned by `find_duplicate_bookmarks`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateBookmarks {
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    /// The bookmarks' GUIDs, oldest first. Merging keeps the first.
+    pub guids: Vec<SyncGuid>,
+}
+
+/// What `merge_duplicates` changed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedDuplicates {
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    /// The oldest bookmark, which we kept.
+    pub kept: SyncGuid,
+    /// The other bookmarks, which we deleted.
+    pub removed: Vec<SyncGuid>,
+    /// The kept bookmark's new title, if it didn't have one, and took it
+    /// from one of the removed bookmarks.
+    pub title: Option<String>,
+    /// The names of the annotations that the kept bookmark took from the
+    /// removed bookmarks.
+    pub annotations: Vec<String>,
+    /// The URL's tags, which the kept bookmark has.
+    pub tags: Vec<String>,
+    /// The URL's keyword, which the kept bookmark has.
+    pub keyword: Option<String>,
+}
+
+/// Returns all bookmarked URLs with more than one bookmark, ordered by URL.
+pub fn find_duplicate_bookmarks(db: &PlacesDb) -> Result<Vec<DuplicateBookmarks>> {
+    let rows = db.query_rows_and_then_named(
+        "SELECT h.url, b.guid
+         FROM moz_bookmarks b
+         JOIN moz_places h ON h.id = b.fk
+         WHERE b.fk IN (SELECT fk FROM moz_bookmarks
+                        WHERE type = :type
+                        GROUP BY fk
+                        HAVING COUNT(*) > 1)
+         ORDER BY h.url, b.dateAdded, b.id",
+        &[(":type", &BookmarkType::Bookmark)],
+        |row| -> Result<(String, SyncGuid)> {
+            Ok((
+                row.get::<_, String>("url")?,
+                row.get::<_, String>("guid")?.into(),
+            ))
+        },
+    )?;
+    let mut duplicates: Vec<DuplicateBookmarks> = Vec::new();
+    for (url, guid) in rows {
+        match duplicates.last_mut() {
+            Some(last) if last.url.as_str() == url => last.guids.push(guid),
+            _ => duplicates.push(DuplicateBookmarks {
+                url: Url::parse(&url)?,
+                guids: vec![guid],
+            }),
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Merges all bookmarks for `url` into the oldest one, in a single
+/// transaction. The kept bookmark takes its title from the removed
+/// bookmarks if it doesn't have one, along with any annotations it doesn't
+/// have. The others are deleted like `delete_bookmark` deletes them, so they
+/// can be restored from the journal, and synced bookmarks leave tombstones.
+///
+/// # Returns
+///
+/// * What changed, or `None` if `url` has fewer than two bookmarks.
+pub fn merge_duplicates(db: &PlacesDb, url: &Url) -> Result<Option<MergedDuplicates>> {
+    let tx = db.begin_transaction()?;
+    let result = merge_duplicates_in_tx(db, url);
+    match result {
+        Ok(Some(_)) => tx.commit()?,
+        _ => tx.rollback()?,
+    }
+    result
+}
+
+fn merge_duplicates_in_tx(db: &PlacesDb, url: &Url) -> Result<Option<MergedDuplicates>> {
+    let mut bookmarks = get_raw_bookmarks_for_url(db, url)?;
+    if bookmarks.len() < 2 {
+        return Ok(None);
+    }
+    bookmarks.sort_by_key(|b| (b.date_added, b.row_id));
+    let kept = bookmarks.remove(0);
+
+    let title = if maybe_truncate_title(&kept.title).is_none() {
+        bookmarks
+            .iter()
+            .find_map(|b| maybe_truncate_title(&b.title))
+            .map(String::from)
+    } else {
+        None
+    };
+    if let Some(title) = &title {
+        db.execute_named_cached(
+            "UPDATE moz_bookmarks SET
+                 title = :title,
+                 lastModified = now(),
+                 syncChangeCounter = syncChangeCounter + 1
+             WHERE id = :id",
+            &[(":title", title), (":id", &kept.row_id)],
+        )?;
+        db.note_event(PlacesEvent::BookmarkUpdated {
+            guid: kept.guid.clone(),
+            title: Some(title.clone()),
+            url: Some(url.to_string()),
+        });
+    }
+
+    let mut annotations = Vec::new();
+    for bookmark in &bookmarks {
+        // The removed bookmarks are oldest first, so if more than one has
+        // the same annotation, we keep the oldest bookmark's.
+        let names = db.query_rows_and_then_named(
+            "SELECT n.name FROM moz_items_annos a
+             JOIN moz_anno_attributes n ON n.id = a.anno_attribute_id
+             WHERE a.item_id = :id AND
+                   a.anno_attribute_id NOT IN (SELECT anno_attribute_id
+                                               FROM moz_items_annos
+                                               WHERE item_id = :kept_id)
+             ORDER BY n.name",
+            &[(":id", &bookmark.row_id), (":kept_id", &kept.row_id)],
+            |row| row.get::<_, String>(0),
+        )?;
+        if names.is_empty() {
+            continue;
+        }
+        db.execute_named_cached(
+            "UPDATE moz_items_annos SET item_id = :kept_id
+             WHERE item_id = :id AND
+                   anno_attribute_id NOT IN (SELECT anno_attribute_id
+                                             FROM moz_items_annos
+                                             WHERE item_id = :kept_id)",
+            &[(":id", &bookmark.row_id), (":kept_id", &kept.row_id)],
+        )?;
+        annotations.extend(names);
+    }
+    annotations.sort();
+
+    for bookmark in &bookmarks {
+        // Deleting a child changes its parent's children, so we upload the
+        // parent, too.
+        if let Some(parent_id) = bookmark.parent_id {
+            db.execute_named_cached(
+                "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+                 WHERE id = :id",
+                &[(":id", &parent_id)],
+            )?;
+        }
+        delete_bookmark_in_tx(db, &bookmark.guid)?;
+    }
+
+    Ok(Some(MergedDuplicates {
+        url: url.clone(),
+        kept: kept.guid,
+        removed: bookmarks.into_iter().map(|b| b.guid).collect(),
+        title,
+        annotations,
+        tags: tags::get_tags_for_url(db, url)?,
+        keyword: keywords::get_keyword_for_url(db, url)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::annotations::{
+        get_item_annotation, set_item_annotation, AnnotationExpiration, AnnotationValue,
+    };
+    use crate::tests::{assert_json_tree, change_counter, check_positions, insert_json_tree};
+    use rusqlite::NO_PARAMS;
+
+    fn insert_test_tree(conn: &PlacesDb) {
+        insert_json_tree(
+            conn,
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "url": "https://www.example.com/a",
+                    },
+                    {
+                        "guid": "bookmarkBBBB",
+                        "title": "B",
+                        "url": "https://www.example.com/b",
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "guid": "bookmarkAAA2",
+                                "title": "A",
+                                "url": "https://www.example.com/a",
+                            },
+                            {
+                                "guid": "bookmarkCCCC",
+                                "title": "C",
+                                "url": "https://www.example.com/c",
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkAAA3",
+                        "title": "Another A",
+                        "url": "https://www.example.com/a",
+                    },
+                ]
+            }),
+        );
+        // Make the items look synced, so that we can check for tombstones.
+        conn.execute_named(
+            "UPDATE moz_bookmarks SET syncChangeCounter = 0, syncStatus = :status",
+            &[(":status", &SyncStatus::Normal)],
+        )
+        .expect("should work");
+    }
+
+    #[test]
+    fn test_find_duplicates() -> Result<()> {
+        let conn = new_mem_connection();
+        insert_test_tree(&conn);
+        assert_eq!(
+            find_duplicate_bookmarks(&conn)?,
+            vec![DuplicateBookmarks {
+                url: Url::parse("https://www.example.com/a")?,
+                guids: vec![
+                    "bookmarkAAAA".into(),
+                    "bookmarkAAA2".into(),
+                    "bookmarkAAA3".into()
+                ],
+            }]
+        );
+        let url = Url::parse("https://www.example.com/b")?;
+        assert_eq!(merge_duplicates(&conn, &url)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_duplicates() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn);
+        let url = Url::parse("https://www.example.com/a")?;
+        tags::tag_url(&conn, &url, "foo")?;
+        keywords::set_keyword(&conn, &url, "a")?;
+        set_item_annotation(
+            &conn,
+            &"bookmarkAAAA".into(),
+            "test/kept",
+            "kept",
+            AnnotationExpiration::Never,
+        )?;
+        set_item_annotation(
+            &conn,
+            &"bookmarkAAA2".into(),
+            "test/kept",
+            "removed",
+            AnnotationExpiration::Never,
+        )?;
+        set_item_annotation(
+            &conn,
+            &"bookmarkAAA3".into(),
+            "test/carried",
+            1i64,
+            AnnotationExpiration::Never,
+        )?;
+        conn.execute("UPDATE moz_bookmarks SET syncChangeCounter = 0", NO_PARAMS)?;
+
+        assert_eq!(
+            merge_duplicates(&conn, &url)?,
+            Some(MergedDuplicates {
+                url: url.clone(),
+                kept: "bookmarkAAAA".into(),
+                removed: vec!["bookmarkAAA2".into(), "bookmarkAAA3".into()],
+                title: Some("A".into()),
+                annotations: vec!["test/carried".into()],
+                tags: vec!["foo".into()],
+                keyword: Some("a".into()),
+            })
+        );
+        assert_eq!(find_duplicate_bookmarks(&conn)?, vec![]);
+        check_positions(&conn);
+
+        assert_json_tree(
+            &conn,
+            &BookmarkRootGuid::Unfiled.into(),
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "title": "A",
+                        "url": "https://www.example.com/a",
+                    },
+                    {
+                        "guid": "bookmarkBBBB",
+                        "title": "B",
+                        "url": "https://www.example.com/b",
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "guid": "bookmarkCCCC",
+                                "title": "C",
+                                "url": "https://www.example.com/c",
+                            },
+                        ],
+                    },
+                ]
+            }),
+        );
+        assert!(change_counter(&conn, "bookmarkAAAA") > 0);
+        assert!(change_counter(&conn, "unfiled_____") > 0);
+        assert!(change_counter(&conn, "folderAAAAAA") > 0);
+        assert_eq!(change_counter(&conn, "bookmarkBBBB"), 0);
+
+        assert_eq!(
+            get_item_annotation(&conn, &"bookmarkAAAA".into(), "test/kept")?,
+            Some(AnnotationValue::Text("kept".into()))
+        );
+        assert_eq!(
+            get_item_annotation(&conn, &"bookmarkAAAA".into(), "test/carried")?,
+            Some(AnnotationValue::Int(1))
+        );
+
+        let tombstones = conn.query_rows_and_then_named(
+            "SELECT guid FROM moz_bookmarks_deleted ORDER BY guid",
+            &[],
+            |row| row.get::<_, String>(0),
+        )?;
+        assert_eq!(tombstones, vec!["bookmarkAAA2", "bookmarkAAA3"]);
+        assert_eq!(undo::get_deleted_bookmarks(&conn)?.len(), 2);
+        Ok(())
+    }
+}
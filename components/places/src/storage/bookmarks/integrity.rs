@@ -0,0 +1,615 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Checks the local bookmark tree for problems, and optionally repairs them.
+//! Dogear validates the merged tree when we sync, but this checks
+//! `moz_bookmarks` on its own, so it also works for users who don't sync.
+//!
+//! Most of these problems can't be caused by our own code, because of the
+//! constraints and foreign keys on `moz_bookmarks`, but they can come from
+//! older databases, imports, or bugs that we don't know about yet.
+
+use super::*;
+use crate::valid_guid::is_valid_places_guid;
+use std::collections::HashSet;
+
+/// A problem found by `check_and_repair_bookmarks`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum BookmarkProblem {
+    /// One of the roots doesn't exist. Repaired by creating it.
+    MissingRoot { guid: SyncGuid },
+    /// One of the roots isn't a folder, or isn't where it should be: the root
+    /// must not have a parent, and the other roots must be children of the
+    /// root. Repaired by turning it into a folder and moving it there.
+    MisplacedRoot { guid: SyncGuid },
+    /// An item that isn't in the tree, because its parent doesn't exist, its
+    /// parent isn't a folder, or it's part of a cycle. Repaired by moving it,
+    /// with all its descendants, to the end of the "Other Bookmarks" root.
+    Orphan { guid: SyncGuid },
+    /// A folder whose children's positions have gaps or duplicates. Repaired
+    /// by renumbering the children, keeping their current order.
+    MisorderedChildren { guid: SyncGuid },
+    /// A bookmark whose URL no longer exists in `moz_places`. Repaired by
+    /// deleting the bookmark, since we can't know what its URL was.
+    MissingPlace { guid: SyncGuid },
+    /// An item with a GUID that isn't a valid Places GUID. Repaired by giving
+    /// the item a new GUID.
+    InvalidGuid { guid: SyncGuid },
+}
+
+const ROOT_GUIDS: [BookmarkRootGuid; 5] = [
+    BookmarkRootGuid::Root,
+    BookmarkRootGuid::Menu,
+    BookmarkRootGuid::Toolbar,
+    BookmarkRootGuid::Unfiled,
+    BookmarkRootGuid::Mobile,
+];
+
+/// The columns of `moz_bookmarks` that we need to check the tree.
+#[derive(Debug)]
+struct Item {
+    id: RowId,
+    guid: SyncGuid,
+    parent: Option<RowId>,
+    kind: u8,
+    position: i64,
+    has_place: bool,
+}
+
+impl Item {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            guid: row.get::<_, String>("guid")?.into(),
+            parent: row.get("parent")?,
+            kind: row.get("type")?,
+            position: row.get("position")?,
+            has_place: row.get("hasPlace")?,
+        })
+    }
+
+    fn is_folder(&self) -> bool {
+        self.kind == BookmarkType::Folder as u8
+    }
+}
+
+/// The whole tree, loaded into memory. Even large bookmark collections only
+/// have tens of thousands of items, and it's much easier to find cycles and
+/// unreachable items in Rust than in SQL.
+struct Tree {
+    items: HashMap<RowId, Item>,
+    children: HashMap<RowId, Vec<RowId>>,
+}
+
+impl Tree {
+    fn load(db: &PlacesDb) -> Result<Self> {
+        let rows = db.query_rows_and_then_named(
+            "SELECT b.id, b.guid, b.parent, b.type, b.position,
+                    (b.fk IS NULL OR h.id NOT NULL) AS hasPlace
+             FROM moz_bookmarks b
+             LEFT JOIN moz_places h ON h.id = b.fk
+             ORDER BY b.parent, b.position, b.id",
+            &[],
+            Item::from_row,
+        )?;
+        let mut items = HashMap::new();
+        let mut children: HashMap<RowId, Vec<RowId>> = HashMap::new();
+        for item in rows {
+            if let Some(parent) = item.parent {
+                children.entry(parent).or_default().push(item.id);
+            }
+            items.insert(item.id, item);
+        }
+        Ok(Self { items, children })
+    }
+
+    fn find_by_guid(&self, guid: &SyncGuid) -> Option<&Item> {
+        self.items.values().find(|item| &item.guid == guid)
+    }
+
+    fn check_roots(&self, problems: &mut Vec<BookmarkProblem>) {
+        let root_id = self
+            .find_by_guid(&BookmarkRootGuid::Root.into())
+            .map(|r| r.id);
+        for root_guid in &ROOT_GUIDS {
+            let guid = root_guid.as_guid();
+            match self.find_by_guid(&guid) {
+                None => problems.push(BookmarkProblem::MissingRoot { guid }),
+                Some(root) => {
+                    let expected_parent = match root_guid {
+                        BookmarkRootGuid::Root => None,
+                        _ => root_id,
+                    };
+                    if !root.is_folder() || root.parent != expected_parent {
+                        problems.push(BookmarkProblem::MisplacedRoot { guid });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the items that can be reached from the root through folders.
+    fn reachable(&self) -> HashSet<RowId> {
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<RowId> = self
+            .find_by_guid(&BookmarkRootGuid::Root.into())
+            .map(|root| root.id)
+            .into_iter()
+            .collect();
+        while let Some(id) = pending.pop() {
+            if !reachable.insert(id) {
+                continue;
+            }
+            if self.items[&id].is_folder() {
+                if let Some(children) = self.children.get(&id) {
+                    pending.extend(children);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Returns the IDs of the top-most unreachable items. Moving these back
+    /// into the tree also brings back all the other unreachable items.
+    fn orphans(&self) -> Vec<RowId> {
+        let reachable = self.reachable();
+        let mut orphans = HashSet::new();
+        for item in self.items.values() {
+            if reachable.contains(&item.id) || item.guid == BookmarkRootGuid::Root {
+                continue;
+            }
+            // Walk up until we find an ancestor whose parent is missing or
+            // isn't a folder, or until we go around a cycle, in which case we
+            // pick the cycle's item with the lowest ID.
+            let mut seen = vec![item.id];
+            let mut current = item;
+            let top = loop {
+                let parent = match current.parent.and_then(|p| self.items.get(&p)) {
+                    Some(parent) if parent.is_folder() => parent,
+                    _ => break current.id,
+                };
+                if let Some(index) = seen.iter().position(|&id| id == parent.id) {
+                    break *seen[index..].iter().min().unwrap();
+                }
+                seen.push(parent.id);
+                current = parent;
+            };
+            // Roots in the wrong place are reported as misplaced instead.
+            if self.items[&top].guid.as_root().is_none() {
+                orphans.insert(top);
+            }
+        }
+        let mut orphans: Vec<RowId> = orphans.into_iter().collect();
+        orphans.sort();
+        orphans
+    }
+
+    /// Returns the IDs of folders whose children's positions aren't
+    /// `0..child_count`.
+    fn misordered_folders(&self) -> Vec<RowId> {
+        let mut folders: Vec<RowId> = self
+            .children
+            .iter()
+            .filter(|(parent, children)| {
+                self.items.contains_key(*parent)
+                    && children
+                        .iter()
+                        .enumerate()
+                        .any(|(index, id)| self.items[id].position != index as i64)
+            })
+            .map(|(parent, _)| *parent)
+            .collect();
+        folders.sort();
+        folders
+    }
+
+    fn find_problems(&self) -> Vec<BookmarkProblem> {
+        let mut problems = Vec::new();
+        self.check_roots(&mut problems);
+        for id in self.orphans() {
+            problems.push(BookmarkProblem::Orphan {
+                guid: self.items[&id].guid.clone(),
+            });
+        }
+        for id in self.misordered_folders() {
+            problems.push(BookmarkProblem::MisorderedChildren {
+                guid: self.items[&id].guid.clone(),
+            });
+        }
+        let mut items: Vec<&Item> = self.items.values().collect();
+        items.sort_by_key(|item| item.id);
+        for item in &items {
+            if !item.has_place {
+                problems.push(BookmarkProblem::MissingPlace {
+                    guid: item.guid.clone(),
+                });
+            }
+        }
+        for item in &items {
+            if !is_valid_places_guid(item.guid.as_ref()) {
+                problems.push(BookmarkProblem::InvalidGuid {
+                    guid: item.guid.clone(),
+                });
+            }
+        }
+        problems
+    }
+}
+
+/// Checks the local bookmark tree, and returns the problems found. If
+/// `repair` is true, also repairs them, in a single transaction. Repaired
+/// items are marked as changed, so that the repairs are uploaded on the next
+/// sync. Repairs aren't reported to observers as `PlacesEvent`s.
+pub fn check_and_repair_bookmarks(db: &PlacesDb, repair: bool) -> Result<Vec<BookmarkProblem>> {
+    let tree = Tree::load(db)?;
+    let problems = tree.find_problems();
+    if !repair || problems.is_empty() {
+        return Ok(problems);
+    }
+    log::info!("Repairing {} bookmark problems", problems.len());
+    let tx = db.begin_transaction()?;
+    repair_roots(db, &problems)?;
+    // The IDs of the items we're repairing might have changed, so we look
+    // them up again for each step.
+    for problem in &problems {
+        if let BookmarkProblem::MissingPlace { guid } = problem {
+            db.execute_named_cached(
+                "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+                 WHERE id = (SELECT parent FROM moz_bookmarks WHERE guid = :guid)",
+                &[(":guid", guid)],
+            )?;
+            db.execute_named_cached(
+                "DELETE FROM moz_bookmarks WHERE guid = :guid",
+                &[(":guid", guid)],
+            )?;
+        }
+    }
+    repair_orphans(db, &Tree::load(db)?)?;
+    for problem in &problems {
+        if let BookmarkProblem::InvalidGuid { guid } = problem {
+            replace_guid(db, guid)?;
+        }
+    }
+    let tree = Tree::load(db)?;
+    for folder in tree.misordered_folders() {
+        renumber_children(db, folder)?;
+    }
+    crate::storage::delete_pending_temp_tables(db)?;
+    tx.commit()?;
+    Ok(problems)
+}
+
+fn bump_change_counter(db: &PlacesDb, id: RowId) -> Result<()> {
+    db.execute_named_cached(
+        "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+         WHERE id = :id",
+        &[(":id", &id)],
+    )?;
+    Ok(())
+}
+
+fn repair_roots(db: &PlacesDb, problems: &[BookmarkProblem]) -> Result<()> {
+    let now = Timestamp::now();
+    for problem in problems {
+        match problem {
+            BookmarkProblem::MissingRoot { guid } => {
+                let title = match guid.as_root() {
+                    Some(BookmarkRootGuid::Root) => "root",
+                    Some(BookmarkRootGuid::Menu) => "menu",
+                    Some(BookmarkRootGuid::Toolbar) => "toolbar",
+                    Some(BookmarkRootGuid::Unfiled) => "unfiled",
+                    Some(BookmarkRootGuid::Mobile) => "mobile",
+                    None => continue,
+                };
+                // The position is fixed up when we renumber the root's
+                // children.
+                create_root(db, title, guid, u32::max_value(), now)?;
+            }
+            BookmarkProblem::MisplacedRoot { guid } => {
+                db.execute_named_cached(
+                    "UPDATE moz_bookmarks SET
+                         type = :type,
+                         fk = NULL,
+                         parent = CASE WHEN guid = :root_guid THEN NULL
+                                  ELSE (SELECT id FROM moz_bookmarks
+                                        WHERE guid = :root_guid)
+                                  END,
+                         position = :position,
+                         syncChangeCounter = syncChangeCounter + 1
+                     WHERE guid = :guid",
+                    &[
+                        (":type", &BookmarkType::Folder),
+                        (":root_guid", &BookmarkRootGuid::Root.as_guid()),
+                        (":position", &u32::max_value()),
+                        (":guid", guid),
+                    ],
+                )?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn repair_orphans(db: &PlacesDb, tree: &Tree) -> Result<()> {
+    let unfiled = match tree.find_by_guid(&BookmarkRootGuid::Unfiled.into()) {
+        Some(unfiled) => unfiled.id,
+        None => return Ok(()),
+    };
+    let orphans = tree.orphans();
+    if orphans.is_empty() {
+        return Ok(());
+    }
+    for id in orphans {
+        db.execute_named_cached(
+            "UPDATE moz_bookmarks SET
+                 parent = :parent,
+                 position = :position,
+                 syncChangeCounter = syncChangeCounter + 1
+             WHERE id = :id",
+            &[
+                (":parent", &unfiled),
+                (":position", &u32::max_value()),
+                (":id", &id),
+            ],
+        )?;
+    }
+    bump_change_counter(db, unfiled)?;
+    Ok(())
+}
+
+/// Gives an item a new GUID. We can't just update the GUID, because the main
+/// connection doesn't allow that, so we insert a copy of the item with a new
+/// GUID, move its children and annotations to the copy, and delete it. If the
+/// item was synced, this uploads a tombstone for the old GUID.
+fn replace_guid(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
+    let id = match db.try_query_row(
+        "SELECT id FROM moz_bookmarks WHERE guid = :guid",
+        &[(":guid", guid)],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )? {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    db.execute_named_cached(
+        "INSERT INTO moz_bookmarks(fk, type, parent, position, title, dateAdded,
+                                   lastModified, guid, syncStatus, syncChangeCounter)
+         SELECT fk, type, parent, position, title, dateAdded, lastModified,
+                :new_guid, :sync_status, 1
+         FROM moz_bookmarks
+         WHERE id = :id",
+        &[
+            (":new_guid", &SyncGuid::new()),
+            (":sync_status", &SyncStatus::New),
+            (":id", &id),
+        ],
+    )?;
+    let new_id = RowId(db.conn().last_insert_rowid());
+    db.execute_named_cached(
+        "UPDATE moz_bookmarks SET
+             parent = :new_id,
+             syncChangeCounter = syncChangeCounter + 1
+         WHERE parent = :id",
+        &[(":new_id", &new_id), (":id", &id)],
+    )?;
+    db.execute_named_cached(
+        "UPDATE moz_items_annos SET item_id = :new_id WHERE item_id = :id",
+        &[(":new_id", &new_id), (":id", &id)],
+    )?;
+    db.execute_named_cached("DELETE FROM moz_bookmarks WHERE id = :id", &[(":id", &id)])?;
+    db.execute_named_cached(
+        "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+         WHERE id = (SELECT parent FROM moz_bookmarks WHERE id = :new_id)",
+        &[(":new_id", &new_id)],
+    )?;
+    Ok(())
+}
+
+/// Renumbers a folder's children, keeping their current order.
+fn renumber_children(db: &PlacesDb, folder: RowId) -> Result<()> {
+    let ids = db.query_rows_and_then_named(
+        "SELECT id FROM moz_bookmarks WHERE parent = :parent ORDER BY position, id",
+        &[(":parent", &folder)],
+        |row| row.get::<_, RowId>(0),
+    )?;
+    for (position, id) in ids.iter().enumerate() {
+        db.execute_named_cached(
+            "UPDATE moz_bookmarks SET position = :position WHERE id = :id",
+            &[(":position", &(position as u32)), (":id", id)],
+        )?;
+    }
+    bump_change_counter(db, folder)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::tests::{assert_json_tree, change_counter, check_positions, insert_json_tree};
+    use rusqlite::NO_PARAMS;
+
+    fn insert_test_tree(conn: &PlacesDb) {
+        insert_json_tree(
+            conn,
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "title": "A",
+                        "url": "https://www.example.com/a",
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "guid": "bookmarkBBBB",
+                                "title": "B",
+                                "url": "https://www.example.com/b",
+                            },
+                            {
+                                "guid": "folderBBBBBB",
+                                "title": "B folder",
+                                "children": [
+                                    {
+                                        "guid": "bookmarkCCCC",
+                                        "title": "C",
+                                        "url": "https://www.example.com/c",
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkDDDD",
+                        "title": "D",
+                        "url": "https://www.example.com/d",
+                    },
+                ]
+            }),
+        );
+        conn.execute("UPDATE moz_bookmarks SET syncChangeCounter = 0", NO_PARAMS)
+            .expect("should work");
+    }
+
+    #[test]
+    fn test_no_problems() -> Result<()> {
+        let conn = new_mem_connection();
+        insert_test_tree(&conn);
+        assert_eq!(check_and_repair_bookmarks(&conn, true)?, vec![]);
+        assert_eq!(change_counter(&conn, "unfiled_____"), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn);
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             -- A cycle between the two folders.
+             UPDATE moz_bookmarks SET
+                 parent = (SELECT id FROM moz_bookmarks WHERE guid = 'folderBBBBBB')
+             WHERE guid = 'folderAAAAAA';
+             -- A bookmark whose parent doesn't exist.
+             UPDATE moz_bookmarks SET parent = 12345
+             WHERE guid = 'bookmarkDDDD';
+             -- A bookmark whose place doesn't exist.
+             UPDATE moz_bookmarks SET fk = 12345
+             WHERE guid = 'bookmarkAAAA';
+             -- The toolbar is in the menu.
+             UPDATE moz_bookmarks SET
+                 parent = (SELECT id FROM moz_bookmarks WHERE guid = 'menu________')
+             WHERE guid = 'toolbar_____';
+             DELETE FROM moz_bookmarks WHERE guid = 'mobile______';
+             PRAGMA foreign_keys = ON;",
+        )?;
+        conn.execute(
+            "INSERT INTO moz_bookmarks(type, parent, position, guid)
+             SELECT 2, id, 5, 'bad$guid$abc'
+             FROM moz_bookmarks WHERE guid = 'menu________'",
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            "INSERT INTO moz_bookmarks(type, parent, position, guid)
+             SELECT 3, id, 0, 'separatorAAA'
+             FROM moz_bookmarks WHERE guid = 'bad$guid$abc'",
+            NO_PARAMS,
+        )?;
+
+        let expected = vec![
+            BookmarkProblem::MissingRoot {
+                guid: BookmarkRootGuid::Mobile.into(),
+            },
+            BookmarkProblem::MisplacedRoot {
+                guid: BookmarkRootGuid::Toolbar.into(),
+            },
+            BookmarkProblem::Orphan {
+                guid: "folderAAAAAA".into(),
+            },
+            BookmarkProblem::Orphan {
+                guid: "bookmarkDDDD".into(),
+            },
+            BookmarkProblem::MisorderedChildren {
+                guid: BookmarkRootGuid::Root.into(),
+            },
+            BookmarkProblem::MisorderedChildren {
+                guid: BookmarkRootGuid::Menu.into(),
+            },
+            BookmarkProblem::MissingPlace {
+                guid: "bookmarkAAAA".into(),
+            },
+            BookmarkProblem::InvalidGuid {
+                guid: "bad$guid$abc".into(),
+            },
+        ];
+        // Checking without repairing doesn't change anything.
+        assert_eq!(check_and_repair_bookmarks(&conn, false)?, expected);
+        assert_eq!(check_and_repair_bookmarks(&conn, false)?, expected);
+
+        assert_eq!(check_and_repair_bookmarks(&conn, true)?, expected);
+        assert_eq!(check_and_repair_bookmarks(&conn, false)?, vec![]);
+        check_positions(&conn);
+
+        let root = get_raw_bookmark(&conn, &BookmarkRootGuid::Root.into())?.unwrap();
+        assert_eq!(root.child_count, 4);
+        let toolbar = get_raw_bookmark(&conn, &BookmarkRootGuid::Toolbar.into())?.unwrap();
+        assert_eq!(toolbar.parent_guid, Some(BookmarkRootGuid::Root.into()));
+        assert!(toolbar.sync_change_counter > 0);
+
+        assert_json_tree(
+            &conn,
+            &BookmarkRootGuid::Unfiled.into(),
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "guid": "bookmarkBBBB",
+                                "title": "B",
+                                "url": "https://www.example.com/b",
+                            },
+                            {
+                                "guid": "folderBBBBBB",
+                                "title": "B folder",
+                                "children": [
+                                    {
+                                        "guid": "bookmarkCCCC",
+                                        "title": "C",
+                                        "url": "https://www.example.com/c",
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkDDDD",
+                        "title": "D",
+                        "url": "https://www.example.com/d",
+                    },
+                ]
+            }),
+        );
+        assert!(change_counter(&conn, "unfiled_____") > 0);
+        assert!(change_counter(&conn, "folderAAAAAA") > 0);
+        assert!(change_counter(&conn, "bookmarkDDDD") > 0);
+
+        // The folder with the invalid GUID has a new one, and kept its child.
+        assert!(get_raw_bookmark(&conn, &"bad$guid$abc".into())?.is_none());
+        let separator = get_raw_bookmark(&conn, &"separatorAAA".into())?.unwrap();
+        let folder = get_raw_bookmark(&conn, separator.parent_guid.as_ref().unwrap())?.unwrap();
+        assert_eq!(folder.parent_guid, Some(BookmarkRootGuid::Menu.into()));
+        assert!(is_valid_places_guid(folder.guid.as_ref()));
+        assert_eq!(folder.sync_status, SyncStatus::New);
+        Ok(())
+    }
+}
@@ -21,6 +21,10 @@ pub struct PublicNode {
     pub title: Option<String>,
     pub child_guids: Option<Vec<SyncGuid>>,
     pub child_nodes: Option<Vec<PublicNode>>,
+    /// The page metadata recorded with `history::note_page_metadata`. Only
+    /// present for bookmarks.
+    pub description: Option<String>,
+    pub preview_image_url: Option<Url>,
 }
 
 impl Default for PublicNode {
@@ -38,6 +42,8 @@ impl Default for PublicNode {
             title: None,
             child_guids: None,
             child_nodes: None,
+            description: None,
+            preview_image_url: None,
         }
     }
 }
@@ -74,6 +80,8 @@ pub fn fetch_bookmarks_by_url(db: &PlacesDb, url: &Url) -> Result<Vec<PublicNode
                 title: rb.title,
                 child_guids: None,
                 child_nodes: None,
+                description: rb.description,
+                preview_image_url: rb.preview_image_url,
             }
         })
         .collect::<Vec<_>>();
@@ -223,6 +231,12 @@ pub fn fetch_public_tree(db: &PlacesDb, item_guid: &SyncGuid) -> Result<Option<P
     // still need to fill in it's own `parent_guid` and `position`.
     let mut proto = PublicNode::from(tree);
 
+    // The tree doesn't include page metadata, so we look that up separately.
+    let metadata = fetch_tree_page_metadata(db, item_guid)?;
+    if !metadata.is_empty() {
+        add_page_metadata(&mut proto, &metadata);
+    }
+
     if item_guid != BookmarkRootGuid::Root {
         let sql = "
             SELECT
@@ -244,6 +258,52 @@ pub fn fetch_public_tree(db: &PlacesDb, item_guid: &SyncGuid) -> Result<Option<P
     Ok(Some(proto))
 }
 
+type PageMetadataMap = HashMap<SyncGuid, (Option<String>, Option<Url>)>;
+
+/// Returns the page descriptions and preview images for the bookmarks in the
+/// tree rooted at `item_guid`, keyed by bookmark guid.
+fn fetch_tree_page_metadata(db: &PlacesDb, item_guid: &SyncGuid) -> Result<PageMetadataMap> {
+    let rows = db.query_rows_and_then_named_cached(
+        "WITH RECURSIVE
+         descendants(id) AS (
+           SELECT id FROM moz_bookmarks WHERE guid = :item_guid
+           UNION ALL
+           SELECT b.id FROM moz_bookmarks b
+           JOIN descendants d ON b.parent = d.id
+         )
+         SELECT b.guid, h.description, h.preview_image_url
+         FROM descendants d
+         JOIN moz_bookmarks b ON b.id = d.id
+         JOIN moz_places h ON h.id = b.fk
+         WHERE h.description NOT NULL OR h.preview_image_url NOT NULL",
+        &[(":item_guid", item_guid)],
+        |row| -> Result<_> {
+            Ok((
+                row.get::<_, SyncGuid>("guid")?,
+                (
+                    row.get::<_, Option<String>>("description")?,
+                    row.get::<_, Option<String>>("preview_image_url")?
+                        .map(|href| Url::parse(&href))
+                        .transpose()?,
+                ),
+            ))
+        },
+    )?;
+    Ok(rows.into_iter().collect())
+}
+
+fn add_page_metadata(node: &mut PublicNode, metadata: &PageMetadataMap) {
+    if let Some((description, preview_image_url)) = metadata.get(&node.guid) {
+        node.description = description.clone();
+        node.preview_image_url = preview_image_url.clone();
+    }
+    if let Some(children) = node.child_nodes.as_mut() {
+        for child in children {
+            add_page_metadata(child, metadata);
+        }
+    }
+}
+
 pub fn search_bookmarks(db: &PlacesDb, search: &str, limit: u32) -> Result<Vec<PublicNode>> {
     let scope = db.begin_interrupt_scope();
     Ok(db.query_rows_into_cached(
@@ -265,6 +325,11 @@ pub fn search_bookmarks(db: &PlacesDb, search: &str, limit: u32) -> Result<Vec<P
                     .transpose()?,
                 child_guids: None,
                 child_nodes: None,
+                description: row.get("description")?,
+                preview_image_url: row
+                    .get::<_, Option<String>>("preview_image_url")?
+                    .map(|href| url::Url::parse(&href))
+                    .transpose()?,
             })
         },
     )?)
@@ -280,7 +345,9 @@ pub fn recent_bookmarks(db: &PlacesDb, limit: u32) -> Result<Vec<PublicNode>> {
             b.dateAdded,
             b.lastModified,
             NULLIF(b.title, '') AS title,
-            h.url AS url
+            h.url AS url,
+            h.description,
+            h.preview_image_url
         FROM moz_bookmarks b
         JOIN moz_bookmarks p ON p.id = b.parent
         JOIN moz_places h ON h.id = b.fk
@@ -306,6 +373,11 @@ pub fn recent_bookmarks(db: &PlacesDb, limit: u32) -> Result<Vec<PublicNode>> {
                     .transpose()?,
                 child_guids: None,
                 child_nodes: None,
+                description: row.get("description")?,
+                preview_image_url: row
+                    .get::<_, Option<String>>("preview_image_url")?
+                    .map(|href| url::Url::parse(&href))
+                    .transpose()?,
             })
         })?,
     )
@@ -321,7 +393,9 @@ lazy_static::lazy_static! {
             b.lastModified,
             -- Note we return null for titles with an empty string.
             NULLIF(b.title, '') AS title,
-            h.url AS url
+            h.url AS url,
+            h.description,
+            h.preview_image_url
         FROM moz_bookmarks b
         JOIN moz_bookmarks p ON p.id = b.parent
         JOIN moz_places h ON h.id = b.fk
@@ -401,6 +475,8 @@ mod test {
                 position: 1,
                 child_guids: None,
                 child_nodes: None,
+                description: None,
+                preview_image_url: None,
                 // Ignored by our PartialEq
                 date_added: Timestamp(0),
                 last_modified: Timestamp(0),
@@ -417,6 +493,8 @@ mod test {
                 position: 3,
                 child_guids: None,
                 child_nodes: None,
+                description: None,
+                preview_image_url: None,
                 // Ignored by our PartialEq
                 date_added: Timestamp(0),
                 last_modified: Timestamp(0),
@@ -626,6 +704,59 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_page_metadata() -> Result<()> {
+        let conns = new_mem_connections();
+        let _ = env_logger::try_init();
+
+        insert_json_tree(
+            &conns.write,
+            json!({
+                "guid": BookmarkRootGuid::Mobile.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmark1___",
+                        "url": "https://www.example1.com/"
+                    },
+                    {
+                        "guid": "bookmark2___",
+                        "url": "https://www.example2.com/"
+                    },
+                ]
+            }),
+        );
+        let url = Url::parse("https://www.example1.com/")?;
+        let image = Url::parse("https://www.example1.com/preview.png")?;
+        crate::storage::history::note_page_metadata(
+            &conns.write,
+            &url,
+            &crate::storage::history::PageMetadata {
+                description: Some("Example one".into()),
+                preview_image_url: Some(image.clone()),
+                ..Default::default()
+            },
+        )?;
+
+        let bmk = fetch_bookmark(&conns.read, &"bookmark1___".into(), false)?.unwrap();
+        assert_eq!(bmk.description, Some("Example one".into()));
+        assert_eq!(bmk.preview_image_url, Some(image.clone()));
+
+        let bmks = fetch_bookmarks_by_url(&conns.read, &url)?;
+        assert_eq!(bmks[0].description, Some("Example one".into()));
+
+        let mobile = fetch_public_tree(&conns.read, BookmarkRootGuid::Mobile.guid())?.unwrap();
+        let children = mobile.child_nodes.unwrap();
+        assert_eq!(children[0].description, Some("Example one".into()));
+        assert_eq!(children[0].preview_image_url, Some(image.clone()));
+        assert_eq!(children[1].description, None);
+        assert_eq!(children[1].preview_image_url, None);
+
+        let recent = recent_bookmarks(&conns.read, 10)?;
+        let bmk = recent.iter().find(|b| b.url == Some(url.clone())).unwrap();
+        assert_eq!(bmk.preview_image_url, Some(image));
+        Ok(())
+    }
     #[test]
     fn test_recent() -> Result<()> {
         let conns = new_mem_connections();
@@ -687,6 +818,8 @@ mod test {
                 position: 5,
                 child_guids: None,
                 child_nodes: None,
+                description: None,
+                preview_image_url: None,
                 // Ignored by our PartialEq
                 date_added: Timestamp(0),
                 last_modified: Timestamp(0),
@@ -703,6 +836,8 @@ mod test {
                 position: 3,
                 child_guids: None,
                 child_nodes: None,
+                description: None,
+                preview_image_url: None,
                 // Ignored by our PartialEq
                 date_added: Timestamp(0),
                 last_modified: Timestamp(0),
@@ -719,6 +854,8 @@ mod test {
                 position: 2,
                 child_guids: None,
                 child_nodes: None,
+                description: None,
+                preview_image_url: None,
                 // Ignored by our PartialEq
                 date_added: Timestamp(0),
                 last_modified: Timestamp(0),
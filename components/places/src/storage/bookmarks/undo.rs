@@ -0,0 +1,491 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A journal of recently deleted bookmarks, so that deletions can be undone.
+//! Each call to `delete_bookmark` records the deleted item, with all its
+//! descendants if it's a folder, and where it was in the tree. Only the most
+//! recent `DELETED_BOOKMARKS_JOURNAL_SIZE` deletions are kept.
+//!
+//! The journal is local: deletions made by Sync aren't recorded, and it isn't
+//! synced to other devices.
+
+use super::*;
+
+/// The number of deletions kept in the journal.
+pub const DELETED_BOOKMARKS_JOURNAL_SIZE: u32 = 20;
+
+/// A deleted item, as recorded in the journal.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedBookmark {
+    pub guid: SyncGuid,
+    pub parent_guid: SyncGuid,
+    pub position: u32,
+    pub date_removed: Timestamp,
+    /// The deleted item, including all its descendants if it's a folder.
+    pub node: BookmarkTreeNode,
+}
+
+impl DeletedBookmark {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        let tree = row.get::<_, String>("tree")?;
+        Ok(Self {
+            guid: row.get::<_, String>("guid")?.into(),
+            parent_guid: row.get::<_, String>("parentGuid")?.into(),
+            position: row.get("position")?,
+            date_removed: row.get("dateRemoved")?,
+            node: serde_json::from_str(&tree)?,
+        })
+    }
+}
+
+/// Records an item that's about to be deleted. Assumes a transaction is
+/// already set up by the caller.
+pub(super) fn record_deletion(db: &PlacesDb, record: &RawBookmark) -> Result<()> {
+    let parent_guid = record
+        .parent_guid
+        .as_ref()
+        .ok_or_else(|| Corruption::NonRootWithoutParent(record.guid.to_string()))?;
+    let node: BookmarkTreeNode = match record.bookmark_type {
+        BookmarkType::Bookmark => match &record.url {
+            Some(url) => BookmarkNode {
+                guid: Some(record.guid.clone()),
+                date_added: Some(record.date_added),
+                last_modified: Some(record.date_modified),
+                title: record.title.clone(),
+                url: url.clone(),
+            }
+            .into(),
+            None => {
+                log::warn!("Not journaling malformed bookmark {} - no URL", record.guid);
+                return Ok(());
+            }
+        },
+        BookmarkType::Separator => SeparatorNode {
+            guid: Some(record.guid.clone()),
+            date_added: Some(record.date_added),
+            last_modified: Some(record.date_modified),
+        }
+        .into(),
+        BookmarkType::Folder => match fetch_tree(db, &record.guid)? {
+            Some(tree) => tree,
+            None => return Ok(()),
+        },
+    };
+    db.execute_named_cached(
+        "INSERT OR REPLACE INTO moz_bookmarks_deleted_journal
+             (guid, parentGuid, position, dateRemoved, tree)
+         VALUES (:guid, :parent_guid, :position, now(), :tree)",
+        &[
+            (":guid", &record.guid),
+            (":parent_guid", parent_guid),
+            (":position", &record.position),
+            (":tree", &serde_json::to_string(&node)?),
+        ],
+    )?;
+    db.execute_named_cached(
+        "DELETE FROM moz_bookmarks_deleted_journal
+         WHERE id NOT IN (SELECT id FROM moz_bookmarks_deleted_journal
+                          ORDER BY id DESC
+                          LIMIT :size)",
+        &[(":size", &DELETED_BOOKMARKS_JOURNAL_SIZE)],
+    )?;
+    Ok(())
+}
+
+/// Returns the deletions in the journal, most recent first.
+pub fn get_deleted_bookmarks(db: &PlacesDb) -> Result<Vec<DeletedBookmark>> {
+    db.query_rows_and_then_named_cached(
+        "SELECT guid, parentGuid, position, dateRemoved, tree
+         FROM moz_bookmarks_deleted_journal
+         ORDER BY id DESC",
+        &[],
+        DeletedBookmark::from_row,
+    )
+}
+
+/// Restores a deleted item from the journal, and removes it from the
+/// journal. Returns the GUID of the restored item.
+///
+/// The item is restored to its original folder and position if the folder
+/// still exists, or appended to the "Other Bookmarks" root if it doesn't.
+/// Items keep their original GUIDs, unless another item has been added with
+/// the same GUID since, in which case they get new ones. Restored items are
+/// uploaded as new items on the next sync, which replaces any tombstones
+/// uploaded for them.
+pub fn restore_deleted_bookmark(db: &PlacesDb, guid: &SyncGuid) -> Result<SyncGuid> {
+    let tx = db.begin_transaction()?;
+    let result = restore_deleted_bookmark_in_tx(db, guid);
+    crate::storage::delete_pending_temp_tables(db)?;
+    match result {
+        Ok(_) => tx.commit()?,
+        Err(_) => tx.rollback()?,
+    }
+    result
+}
+
+fn restore_deleted_bookmark_in_tx(db: &PlacesDb, guid: &SyncGuid) -> Result<SyncGuid> {
+    let deleted = db
+        .try_query_row(
+            "SELECT guid, parentGuid, position, dateRemoved, tree
+             FROM moz_bookmarks_deleted_journal
+             WHERE guid = :guid",
+            &[(":guid", guid)],
+            DeletedBookmark::from_row,
+            true,
+        )?
+        .ok_or_else(|| InvalidPlaceInfo::NoSuchGuid(guid.to_string()))?;
+    let mut node = deleted.node;
+    clear_existing_guids(db, &mut node)?;
+
+    let (parent_guid, position) = match get_raw_bookmark(db, &deleted.parent_guid)? {
+        Some(ref parent) if parent.bookmark_type == BookmarkType::Folder => (
+            deleted.parent_guid,
+            BookmarkPosition::Specific(deleted.position),
+        ),
+        _ => (BookmarkRootGuid::Unfiled.into(), BookmarkPosition::Append),
+    };
+    let mut insert_infos: Vec<InsertableItem> = Vec::new();
+    let new_guid = match node {
+        BookmarkTreeNode::Bookmark(b) => {
+            let new_guid = b.guid.unwrap_or_else(SyncGuid::new);
+            insert_infos.push(
+                InsertableBookmark {
+                    parent_guid,
+                    position,
+                    date_added: b.date_added,
+                    last_modified: b.last_modified,
+                    guid: Some(new_guid.clone()),
+                    url: b.url,
+                    title: b.title,
+                }
+                .into(),
+            );
+            new_guid
+        }
+        BookmarkTreeNode::Separator(s) => {
+            let new_guid = s.guid.unwrap_or_else(SyncGuid::new);
+            insert_infos.push(
+                InsertableSeparator {
+                    parent_guid,
+                    position,
+                    date_added: s.date_added,
+                    last_modified: s.last_modified,
+                    guid: Some(new_guid.clone()),
+                }
+                .into(),
+            );
+            new_guid
+        }
+        BookmarkTreeNode::Folder(f) => {
+            let new_guid = f.guid.clone().unwrap_or_else(SyncGuid::new);
+            insert_infos.push(
+                InsertableFolder {
+                    parent_guid,
+                    position,
+                    date_added: f.date_added,
+                    last_modified: f.last_modified,
+                    guid: Some(new_guid.clone()),
+                    title: f.title.clone(),
+                }
+                .into(),
+            );
+            add_subtree_infos(&new_guid, &f, &mut insert_infos);
+            new_guid
+        }
+    };
+    for insertable in insert_infos {
+        insert_bookmark_in_tx(db, &insertable)?;
+    }
+    db.execute_named_cached(
+        "DELETE FROM moz_bookmarks_deleted_journal WHERE guid = :guid",
+        &[(":guid", guid)],
+    )?;
+    Ok(new_guid)
+}
+
+/// Clears the GUIDs of any items in `node` that are already in the tree, so
+/// that they're restored with new GUIDs.
+fn clear_existing_guids(db: &PlacesDb, node: &mut BookmarkTreeNode) -> Result<()> {
+    let guid = match node {
+        BookmarkTreeNode::Bookmark(b) => &mut b.guid,
+        BookmarkTreeNode::Separator(s) => &mut s.guid,
+        BookmarkTreeNode::Folder(f) => &mut f.guid,
+    };
+    if let Some(existing) = guid.as_ref() {
+        if get_raw_bookmark(db, existing)?.is_some() {
+            *guid = None;
+        }
+    }
+    if let BookmarkTreeNode::Folder(f) = node {
+        for child in &mut f.children {
+            clear_existing_guids(db, child)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::bookmark_sync::store::BookmarksStore;
+    use crate::storage::history::delete_everything;
+    use crate::tests::{assert_json_tree, insert_json_tree};
+    use rusqlite::NO_PARAMS;
+    use sync15::{Store, StoreSyncAssociation};
+
+    fn sync_status(db: &PlacesDb, guid: &str) -> Option<SyncStatus> {
+        get_raw_bookmark(db, &guid.into())
+            .expect("should work")
+            .map(|b| b.sync_status)
+    }
+
+    fn tombstones(db: &PlacesDb) -> u32 {
+        db.query_one::<u32>("SELECT COUNT(*) FROM moz_bookmarks_deleted")
+            .expect("should count")
+    }
+
+    #[test]
+    fn test_restore_folder() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_json_tree(
+            &conn,
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "title": "A",
+                        "url": "https://www.example.com/a",
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "date_added": 1000,
+                        "children": [
+                            {
+                                "guid": "bookmarkBBBB",
+                                "title": "B",
+                                "url": "https://www.example.com/b",
+                                "date_added": 2000,
+                            },
+                            {
+                                "guid": "separatorAAA",
+                                "type": BookmarkType::Separator as u8,
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkCCCC",
+                        "title": "C",
+                        "url": "https://www.example.com/c",
+                    },
+                ]
+            }),
+        );
+        // Pretend everything has been synced, so that deletions write
+        // tombstones.
+        conn.execute(
+            &format!(
+                "UPDATE moz_bookmarks SET syncStatus = {}, syncChangeCounter = 0",
+                SyncStatus::Normal as u8
+            ),
+            NO_PARAMS,
+        )?;
+
+        assert!(delete_bookmark(&conn, &"folderAAAAAA".into())?);
+        assert_eq!(tombstones(&conn), 3);
+        let deleted = get_deleted_bookmarks(&conn)?;
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].guid, "folderAAAAAA".into());
+        assert_eq!(deleted[0].parent_guid, BookmarkRootGuid::Unfiled);
+        assert_eq!(deleted[0].position, 1);
+
+        // Someone else takes one of the GUIDs while the folder is deleted.
+        insert_json_tree(
+            &conn,
+            json!({
+                "guid": &BookmarkRootGuid::Mobile.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkBBBB",
+                        "url": "https://www.example.com/other",
+                    },
+                ]
+            }),
+        );
+
+        let guid = restore_deleted_bookmark(&conn, &"folderAAAAAA".into())?;
+        assert_eq!(guid, "folderAAAAAA".into());
+        assert_json_tree(
+            &conn,
+            &BookmarkRootGuid::Unfiled.into(),
+            json!({
+                "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                "children": [
+                    {
+                        "guid": "bookmarkAAAA",
+                        "title": "A",
+                        "url": "https://www.example.com/a",
+                    },
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "date_added": 1000,
+                        "children": [
+                            {
+                                "title": "B",
+                                "url": "https://www.example.com/b",
+                                "date_added": 2000,
+                            },
+                            {
+                                "guid": "separatorAAA",
+                                "type": BookmarkType::Separator as u8,
+                            },
+                        ],
+                    },
+                    {
+                        "guid": "bookmarkCCCC",
+                        "title": "C",
+                        "url": "https://www.example.com/c",
+                    },
+                ]
+            }),
+        );
+        // The restored items are new to Sync, and their tombstones are gone.
+        assert_eq!(sync_status(&conn, "folderAAAAAA"), Some(SyncStatus::New));
+        assert_eq!(sync_status(&conn, "separatorAAA"), Some(SyncStatus::New));
+        assert_eq!(tombstones(&conn), 0);
+        let unfiled =
+            get_raw_bookmark(&conn, &BookmarkRootGuid::Unfiled.as_guid())?.expect("should exist");
+        assert!(unfiled.sync_change_counter > 0);
+
+        // It can only be restored once.
+        assert!(get_deleted_bookmarks(&conn)?.is_empty());
+        assert!(restore_deleted_bookmark(&conn, &"folderAAAAAA".into()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_without_parent() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        insert_json_tree(
+            &conn,
+            json!({
+                "guid": &BookmarkRootGuid::Menu.as_guid(),
+                "children": [
+                    {
+                        "guid": "folderAAAAAA",
+                        "title": "A folder",
+                        "children": [
+                            {
+                                "guid": "bookmarkAAAA",
+                                "title": "A",
+                                "url": "https://www.example.com/a",
+                            },
+                        ],
+                    },
+                ]
+            }),
+        );
+        assert!(delete_bookmark(&conn, &"bookmarkAAAA".into())?);
+        assert!(delete_bookmark(&conn, &"folderAAAAAA".into())?);
+        assert_eq!(get_deleted_bookmarks(&conn)?.len(), 2);
+
+        // The folder is gone, so the bookmark is restored to "Other
+        // Bookmarks".
+        restore_deleted_bookmark(&conn, &"bookmarkAAAA".into())?;
+        let restored = get_raw_bookmark(&conn, &"bookmarkAAAA".into())?.expect("should exist");
+        assert_eq!(restored.parent_guid, Some(BookmarkRootGuid::Unfiled.into()));
+        assert_eq!(restored.url, Some(Url::parse("https://www.example.com/a")?));
+
+        // And then restoring the folder restores it empty, since its child
+        // was deleted before it.
+        restore_deleted_bookmark(&conn, &"folderAAAAAA".into())?;
+        let folder = get_raw_bookmark(&conn, &"folderAAAAAA".into())?.expect("should exist");
+        assert_eq!(folder.parent_guid, Some(BookmarkRootGuid::Menu.into()));
+        assert_eq!(folder.child_count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_size() -> Result<()> {
+        let conn = new_mem_connection();
+        let unfiled = BookmarkRootGuid::Unfiled.as_guid();
+        for i in 0..DELETED_BOOKMARKS_JOURNAL_SIZE + 5 {
+            let guid = insert_bookmark(
+                &conn,
+                &InsertableBookmark {
+                    parent_guid: unfiled.clone(),
+                    position: BookmarkPosition::Append,
+                    date_added: None,
+                    last_modified: None,
+                    guid: None,
+                    url: Url::parse(&format!("https://www.example.com/{}", i))?,
+                    title: None,
+                }
+                .into(),
+            )?;
+            delete_bookmark(&conn, &guid)?;
+        }
+        let deleted = get_deleted_bookmarks(&conn)?;
+        assert_eq!(deleted.len(), DELETED_BOOKMARKS_JOURNAL_SIZE as usize);
+        match &deleted[0].node {
+            BookmarkTreeNode::Bookmark(b) => assert_eq!(
+                b.url.as_str(),
+                format!(
+                    "https://www.example.com/{}",
+                    DELETED_BOOKMARKS_JOURNAL_SIZE + 4
+                )
+            ),
+            _ => panic!("should be a bookmark"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_cleared() -> Result<()> {
+        let conn = new_mem_connection();
+        let delete_some = || -> Result<()> {
+            insert_json_tree(
+                &conn,
+                json!({
+                    "guid": &BookmarkRootGuid::Unfiled.as_guid(),
+                    "children": [
+                        {
+                            "guid": "bookmarkAAAA",
+                            "title": "A",
+                            "url": "https://www.example.com/a",
+                        },
+                    ]
+                }),
+            );
+            assert!(delete_bookmark(&conn, &"bookmarkAAAA".into())?);
+            assert_eq!(get_deleted_bookmarks(&conn)?.len(), 1);
+            Ok(())
+        };
+
+        // Clearing history clears the journal, too.
+        delete_some()?;
+        delete_everything(&conn)?;
+        assert!(get_deleted_bookmarks(&conn)?.is_empty());
+
+        // As do wiping and resetting the bookmarks store.
+        let scope = conn.begin_interrupt_scope();
+        let store = BookmarksStore::new(&conn, &scope);
+
+        delete_some()?;
+        store.wipe().expect("should wipe");
+        assert!(get_deleted_bookmarks(&conn)?.is_empty());
+
+        delete_some()?;
+        store
+            .reset(&StoreSyncAssociation::Disconnected)
+            .expect("should reset");
+        assert!(get_deleted_bookmarks(&conn)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Policy-driven history expiration. Unlike `prune_destructively`, this only
+//! removes old and infrequently visited history, and the work is done in
+//! chunks, each in its own transaction, so that it can be interrupted with
+//! the connection's interrupt handle without losing the work done so far.
+//!
+//! Expiration only affects the local database: we don't write tombstones for
+//! expired pages or visits, so they aren't removed from other devices.
+//! Removed pages are reported to observers as `PlacesEvent::PageDeleted`, but
+//! removed visits aren't reported.
+
+use super::{delete_pending_temp_tables, RowId};
+use crate::db::PlacesDb;
+use crate::error::Result;
+use crate::events::PlacesEvent;
+use crate::storage::history::update_frecency;
+use crate::types::Timestamp;
+use serde_derive::*;
+use sql_support::{self, ConnExt};
+use std::time::Duration;
+
+/// Limits on how much history we keep. A policy with no limits set doesn't
+/// expire anything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HistoryExpirationPolicy {
+    /// The maximum number of pages to keep. If there are more, the pages with
+    /// the lowest frecency are removed first. Bookmarked pages count toward
+    /// this limit, but are never removed.
+    pub max_pages: Option<u32>,
+    /// Visits older than this are removed, along with their pages if they
+    /// have no other visits and aren't bookmarked.
+    pub max_visit_age: Option<Duration>,
+}
+
+/// The number of pages and visits removed by `expire_history`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HistoryExpirationMetrics {
+    /// Pages removed, either because they were over the page limit, or
+    /// because all their visits expired.
+    pub pages_removed: usize,
+    /// Visits removed because they were older than the maximum age. This
+    /// doesn't include the visits of pages removed for being over the page
+    /// limit.
+    pub visits_removed: usize,
+}
+
+/// Expires history according to `policy`. This should be called on the
+/// write connection, outside of a transaction, and may be interrupted with
+/// `PlacesDb::new_interrupt_handle`.
+pub fn expire_history(
+    db: &PlacesDb,
+    policy: &HistoryExpirationPolicy,
+) -> Result<HistoryExpirationMetrics> {
+    let scope = db.begin_interrupt_scope();
+    let mut metrics = HistoryExpirationMetrics::default();
+
+    if let Some(max_visit_age) = policy.max_visit_age {
+        let cutoff = Timestamp::now()
+            .checked_sub(max_visit_age)
+            .unwrap_or_default();
+        let place_ids = db.query_rows_and_then_named(
+            "SELECT DISTINCT place_id FROM moz_historyvisits
+             WHERE visit_date < :cutoff",
+            &[(":cutoff", &cutoff)],
+            |row| row.get::<_, RowId>(0),
+        )?;
+        sql_support::each_chunk(&place_ids, |chunk, _| -> Result<()> {
+            scope.err_if_interrupted()?;
+            let tx = db.begin_transaction()?;
+            metrics.visits_removed += db.conn().execute(
+                &format!(
+                    "DELETE FROM moz_historyvisits
+                     WHERE place_id IN ({ids}) AND visit_date < {cutoff}",
+                    ids = sql_support::repeat_sql_vars(chunk.len()),
+                    cutoff = cutoff,
+                ),
+                chunk,
+            )?;
+            // Remove pages that no longer have any visits, and update the
+            // frecencies of the rest.
+            let unvisited = format!(
+                "id IN ({})
+                 AND foreign_count = 0
+                 AND last_visit_date_local = 0
+                 AND last_visit_date_remote = 0",
+                sql_support::repeat_sql_vars(chunk.len()),
+            );
+            metrics.pages_removed += delete_pages(db, &unvisited, chunk)?;
+            for id in existing_pages(db, chunk)? {
+                update_frecency(db, id, None)?;
+            }
+            delete_pending_temp_tables(db)?;
+            tx.commit()?;
+            Ok(())
+        })?;
+    }
+
+    if let Some(max_pages) = policy.max_pages {
+        let page_count = db.query_one::<u32>("SELECT COUNT(*) FROM moz_places")?;
+        if page_count > max_pages {
+            let candidates = db.query_rows_and_then_named(
+                "SELECT id FROM moz_places
+                 WHERE foreign_count = 0
+                 ORDER BY frecency,
+                          MAX(last_visit_date_local, last_visit_date_remote)
+                 LIMIT :excess",
+                &[(":excess", &(page_count - max_pages))],
+                |row| row.get::<_, RowId>(0),
+            )?;
+            sql_support::each_chunk(&candidates, |chunk, _| -> Result<()> {
+                scope.err_if_interrupted()?;
+                let tx = db.begin_transaction()?;
+                let unbookmarked = format!(
+                    "id IN ({}) AND foreign_count = 0",
+                    sql_support::repeat_sql_vars(chunk.len()),
+                );
+                metrics.pages_removed += delete_pages(db, &unbookmarked, chunk)?;
+                delete_pending_temp_tables(db)?;
+                tx.commit()?;
+                Ok(())
+            })?;
+        }
+    }
+
+    scope.err_if_interrupted()?;
+    let tx = db.begin_transaction()?;
+    delete_orphans(db)?;
+    tx.commit()?;
+
+    log::info!(
+        "Expired {} pages and {} visits",
+        metrics.pages_removed,
+        metrics.visits_removed
+    );
+    Ok(metrics)
+}
+
+/// Deletes the pages that match `condition`, reporting them to observers,
+/// and returns how many were deleted. Assumes a transaction is already set
+/// up by the caller.
+fn delete_pages(db: &PlacesDb, condition: &str, ids: &[RowId]) -> Result<usize> {
+    if db.has_event_observers() {
+        let mut stmt = db.conn().prepare(&format!(
+            "SELECT url, guid FROM moz_places WHERE {}",
+            condition
+        ))?;
+        let mut rows = stmt.query(ids)?;
+        while let Some(row) = rows.next()? {
+            db.note_event(PlacesEvent::PageDeleted {
+                url: row.get("url")?,
+                guid: row.get("guid")?,
+            });
+        }
+    }
+    Ok(db
+        .conn()
+        .execute(&format!("DELETE FROM moz_places WHERE {}", condition), ids)?)
+}
+
+fn existing_pages(db: &PlacesDb, ids: &[RowId]) -> Result<Vec<RowId>> {
+    let mut stmt = db.conn().prepare(&format!(
+        "SELECT id FROM moz_places WHERE id IN ({})",
+        sql_support::repeat_sql_vars(ids.len()),
+    ))?;
+    let existing = stmt
+        .query_and_then(ids, |row| row.get::<_, RowId>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(existing)
+}
+
+/// Removes rows that refer to pages that no longer exist. Most of these are
+/// also removed by `ON DELETE CASCADE`, but origins aren't, and
+/// older databases might have rows left over from before foreign keys were
+/// enforced. Assumes a transaction is already set up by the caller.
+fn delete_orphans(db: &PlacesDb) -> Result<()> {
+    db.execute_all(&[
+        // `origin_id` can be NULL, so we can't use `NOT IN` here: it's never
+        // true if the subquery returns a NULL.
+        "DELETE FROM moz_origins
+         WHERE NOT EXISTS(SELECT 1 FROM moz_places
+                          WHERE origin_id = moz_origins.id)",
+        "DELETE FROM moz_inputhistory
+         WHERE place_id NOT IN (SELECT id FROM moz_places)",
+        "DELETE FROM moz_historyvisit_tombstones
+         WHERE place_id NOT IN (SELECT id FROM moz_places)",
+    ])?;
+    delete_pending_temp_tables(db)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::history::get_visit_count;
+    use crate::tests::{bookmark_url, visit_url_at};
+    use crate::types::VisitTransitionSet;
+    use url::Url;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn visit(db: &PlacesDb, url: &str, days_ago: u32) {
+        let at = Timestamp::now()
+            .checked_sub(DAY * days_ago)
+            .expect("should be valid");
+        visit_url_at(db, &Url::parse(url).unwrap(), at);
+    }
+
+    fn urls(db: &PlacesDb) -> Vec<String> {
+        db.query_rows_and_then_named("SELECT url FROM moz_places ORDER BY url", &[], |row| {
+            row.get::<_, String>(0)
+        })
+        .expect("should query")
+    }
+
+    fn frecency(db: &PlacesDb, url: &str) -> i64 {
+        db.query_row_and_then_named(
+            "SELECT frecency FROM moz_places WHERE url = :url",
+            &[(":url", &url)],
+            |row| row.get::<_, i64>(0),
+            false,
+        )
+        .expect("should query")
+    }
+
+    fn count(db: &PlacesDb, table: &str) -> u32 {
+        db.query_one::<u32>(&format!("SELECT COUNT(*) FROM {}", table))
+            .expect("should count")
+    }
+
+    #[test]
+    fn test_no_limits() -> Result<()> {
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/old", 400);
+        let metrics = expire_history(&conn, &HistoryExpirationPolicy::default())?;
+        assert_eq!(metrics, HistoryExpirationMetrics::default());
+        assert_eq!(urls(&conn), vec!["https://example.com/old"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_visit_age() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/old", 100);
+        visit(&conn, "https://example.com/mixed", 100);
+        visit(&conn, "https://example.com/mixed", 1);
+        visit(&conn, "https://example.org/bookmarked", 100);
+        bookmark_url(&conn, &Url::parse("https://example.org/bookmarked")?);
+        visit(&conn, "https://example.net/new", 1);
+        conn.execute_all(&["INSERT INTO moz_inputhistory(place_id, input, use_count)
+             SELECT id, 'ex', 1 FROM moz_places"])?;
+        assert_eq!(count(&conn, "moz_origins"), 3);
+        let old_frecency = frecency(&conn, "https://example.org/bookmarked");
+
+        let metrics = expire_history(
+            &conn,
+            &HistoryExpirationPolicy {
+                max_visit_age: Some(DAY * 30),
+                ..HistoryExpirationPolicy::default()
+            },
+        )?;
+        assert_eq!(
+            metrics,
+            HistoryExpirationMetrics {
+                pages_removed: 1,
+                visits_removed: 3,
+            }
+        );
+        assert_eq!(
+            urls(&conn),
+            vec![
+                "https://example.com/mixed",
+                "https://example.net/new",
+                "https://example.org/bookmarked",
+            ]
+        );
+        assert_eq!(get_visit_count(&conn, VisitTransitionSet::empty())?, 2);
+        assert_eq!(count(&conn, "moz_inputhistory"), 3);
+        // Expired visits aren't synced as deletions.
+        assert_eq!(count(&conn, "moz_places_tombstones"), 0);
+        assert_eq!(count(&conn, "moz_historyvisit_tombstones"), 0);
+
+        // The bookmarked page no longer has visits, so its frecency should
+        // have been recalculated.
+        assert_ne!(
+            frecency(&conn, "https://example.org/bookmarked"),
+            old_frecency
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_pages() -> Result<()> {
+        let conn = new_mem_connection();
+        for i in 0..10 {
+            visit(&conn, &format!("https://example.com/{}", i), 10 - i);
+        }
+        // Visit the first page often, so that it has the highest frecency.
+        for _ in 0..10 {
+            visit(&conn, "https://example.com/0", 1);
+        }
+        bookmark_url(&conn, &Url::parse("https://example.com/1")?);
+        visit(&conn, "https://example.org/", 1);
+
+        let metrics = expire_history(
+            &conn,
+            &HistoryExpirationPolicy {
+                max_pages: Some(4),
+                ..HistoryExpirationPolicy::default()
+            },
+        )?;
+        assert_eq!(metrics.pages_removed, 7);
+        assert_eq!(metrics.visits_removed, 0);
+        assert_eq!(
+            urls(&conn),
+            vec![
+                "https://example.com/0",
+                "https://example.com/1",
+                "https://example.com/9",
+                "https://example.org/",
+            ]
+        );
+        assert_eq!(count(&conn, "moz_origins"), 2);
+
+        // We're under the limit now, so running again does nothing.
+        let metrics = expire_history(
+            &conn,
+            &HistoryExpirationPolicy {
+                max_pages: Some(4),
+                ..HistoryExpirationPolicy::default()
+            },
+        )?;
+        assert_eq!(metrics.pages_removed, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphaned_origins() -> Result<()> {
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/old", 100);
+        visit(&conn, "https://example.net/new", 1);
+        // A page without an origin shouldn't keep us from removing origins
+        // that no page uses.
+        conn.execute_all(&[
+            "UPDATE moz_places SET
+               origin_id = NULL
+             WHERE url = 'https://example.net/new'",
+            "INSERT INTO moz_origins(prefix, host, rev_host, frecency)
+             VALUES('https://', 'example.org', 'gro.elpmaxe.', 0)",
+        ])?;
+        assert_eq!(count(&conn, "moz_origins"), 3);
+
+        expire_history(
+            &conn,
+            &HistoryExpirationPolicy {
+                max_visit_age: Some(DAY * 30),
+                ..HistoryExpirationPolicy::default()
+            },
+        )?;
+        assert_eq!(urls(&conn), vec!["https://example.net/new"]);
+        assert_eq!(count(&conn, "moz_origins"), 0);
+        Ok(())
+    }
+}
@@ -4,17 +4,23 @@
 
 use super::{fetch_page_info, new_page_info, PageInfo, RowId};
 use crate::db::PlacesDb;
-use crate::error::Result;
+use crate::error::{InvalidPlaceInfo, Result};
+use crate::events::PlacesEvent;
 use crate::frecency;
 use crate::hash;
-use crate::msg_types::{HistoryVisitInfo, HistoryVisitInfos};
+use crate::match_impl::{MatchBehavior, SearchBehavior};
+use crate::msg_types::{
+    HistorySearchResult, HistorySearchResults, HistoryVisitInfo, HistoryVisitInfos,
+};
 use crate::observation::VisitObservation;
-use crate::storage::{delete_pending_temp_tables, get_meta, put_meta};
+use crate::storage::{annotations, delete_pending_temp_tables, get_meta, put_meta};
 use crate::types::{SyncGuid, SyncStatus, Timestamp, VisitTransition, VisitTransitionSet};
 use rusqlite::types::ToSql;
 use rusqlite::Result as RusqliteResult;
 use rusqlite::{Row, NO_PARAMS};
+use serde_derive::*;
 use sql_support::{self, ConnExt};
+use std::time::Duration;
 use url::Url;
 
 /// When `delete_everything` is called (to perform a permanent local deletion), in
@@ -25,6 +31,17 @@ use url::Url;
 /// add visits to them remotely.
 static DELETION_HIGH_WATER_MARK_META_KEY: &str = "history_deleted_hwm";
 
+/// A visit is only linked to the visit of its referrer if that visit happened
+/// within this many milliseconds of it. This matches RECENT_EVENT_THRESHOLD in
+/// desktop's History.cpp - older visits to the referrer are most likely
+/// unrelated.
+const REFERRER_VISIT_THRESHOLD_MS: u64 = 15 * 60 * 1000;
+
+/// The maximum number of visits `get_visit_chain` will walk back through.
+/// `from_visit` always points at an earlier visit, so this is just a
+/// safety net against a malformed database.
+const MAX_VISIT_CHAIN_LENGTH: u32 = 100;
+
 /// Returns the RowId of a new visit in moz_historyvisits, or None if no new visit was added.
 pub fn apply_observation(db: &PlacesDb, visit_ob: VisitObservation) -> Result<Option<RowId>> {
     let tx = db.begin_transaction()?;
@@ -39,8 +56,9 @@ pub fn apply_observation_direct(
     visit_ob: VisitObservation,
 ) -> Result<Option<RowId>> {
     let url = Url::parse(&visit_ob.url)?;
-    // Don't insert urls larger than our length max.
-    if url.as_str().len() > super::URL_LENGTH_MAX {
+    // Don't insert urls that the history policy blocks, including urls
+    // larger than our length max.
+    if !db.can_add_url(&url) {
         return Ok(None);
     }
     let mut page_info = match fetch_page_info(db, &url)? {
@@ -49,10 +67,20 @@ pub fn apply_observation_direct(
     };
     let mut update_change_counter = false;
     let mut update_frec = false;
+    let is_error_page = visit_ob.is_error.unwrap_or(false);
+    let is_redirect_source = visit_ob.is_redirect_source.unwrap_or(false)
+        || visit_ob.is_permanent_redirect_source.unwrap_or(false);
     let mut updates: Vec<(&str, &str, &dyn ToSql)> = Vec::new();
 
     if let Some(ref title) = visit_ob.title {
-        page_info.title = crate::util::slice_up_to(title, super::TITLE_LENGTH_MAX).into();
+        let title = crate::util::slice_up_to(title, super::TITLE_LENGTH_MAX);
+        if title != page_info.title {
+            db.note_event(PlacesEvent::TitleChanged {
+                url: url.to_string(),
+                title: title.into(),
+            });
+        }
+        page_info.title = title.into();
         updates.push(("title", ":title", &page_info.title));
         update_change_counter = true;
     }
@@ -64,6 +92,14 @@ pub fn apply_observation_direct(
             if !visit_ob.get_is_hidden() {
                 updates.push(("hidden", ":hidden", &false));
             }
+            // Top sites leaves out pages whose latest visit failed or
+            // redirected.
+            updates.push(("is_error_page", ":is_error_page", &is_error_page));
+            updates.push((
+                "is_redirect_source",
+                ":is_redirect_source",
+                &is_redirect_source,
+            ));
             if visit_type == VisitTransition::Typed {
                 page_info.typed += 1;
                 updates.push(("typed", ":typed", &page_info.typed));
@@ -71,7 +107,17 @@ pub fn apply_observation_direct(
 
             let at = visit_ob.at.unwrap_or_else(Timestamp::now);
             let is_remote = visit_ob.is_remote.unwrap_or(false);
-            let row_id = add_visit(db, page_info.row_id, None, at, visit_type, !is_remote)?;
+            let from_visit = match visit_ob.referrer {
+                Some(ref referrer) => find_referring_visit(db, referrer, at)?,
+                None => None,
+            };
+            let row_id = add_visit(db, page_info.row_id, from_visit, at, visit_type, !is_remote)?;
+            db.note_event(PlacesEvent::VisitAdded {
+                url: url.to_string(),
+                visit_date: at,
+                visit_type,
+                is_remote,
+            });
             // a new visit implies new frecency except in error cases.
             if !visit_ob.is_error.unwrap_or(false) {
                 update_frec = true;
@@ -121,7 +167,7 @@ pub fn apply_observation_direct(
 pub fn update_frecency(db: &PlacesDb, id: RowId, redirect_boost: Option<bool>) -> Result<()> {
     let score = frecency::calculate_frecency(
         db.conn(),
-        &frecency::DEFAULT_FRECENCY_SETTINGS,
+        &db.frecency_settings(),
         id.0, // TODO: calculate_frecency should take a RowId here.
         redirect_boost,
     )?;
@@ -137,6 +183,68 @@ pub fn update_frecency(db: &PlacesDb, id: RowId, redirect_boost: Option<bool>) -
     Ok(())
 }
 
+/// Metadata about a page, recorded with `note_page_metadata`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub preview_image_url: Option<Url>,
+}
+
+/// Records the title, description and preview image for a page, without
+/// adding a visit. Fields that are `None` are left unchanged. Like
+/// `apply_observation`, this adds the page if it's not already known, and
+/// ignores URLs that the history policy blocks.
+pub fn note_page_metadata(db: &PlacesDb, url: &Url, metadata: &PageMetadata) -> Result<()> {
+    if !db.can_add_url(url) {
+        return Ok(());
+    }
+    let tx = db.begin_transaction()?;
+    let page_info = match fetch_page_info(db, url)? {
+        Some(info) => info.page,
+        None => new_page_info(db, url, None)?,
+    };
+    let title = metadata
+        .title
+        .as_ref()
+        .map(|title| crate::util::slice_up_to(title, super::TITLE_LENGTH_MAX));
+    let description = metadata
+        .description
+        .as_ref()
+        .map(|description| crate::util::slice_up_to(description, super::DESCRIPTION_LENGTH_MAX));
+    // We can't truncate a URL, so preview images with long URLs are ignored.
+    let preview_image_url = metadata
+        .preview_image_url
+        .as_ref()
+        .map(Url::as_str)
+        .filter(|href| href.len() <= super::URL_LENGTH_MAX);
+    // Titles are synced, but descriptions and preview images aren't.
+    let title_changed = title.map_or(false, |title| title != page_info.title);
+    db.execute_named_cached(
+        "UPDATE moz_places
+         SET title = IFNULL(:title, title),
+             description = IFNULL(:description, description),
+             preview_image_url = IFNULL(:preview_image_url, preview_image_url),
+             sync_change_counter = sync_change_counter + :change_counter_delta
+         WHERE id = :id",
+        &[
+            (":title", &title),
+            (":description", &description),
+            (":preview_image_url", &preview_image_url),
+            (":change_counter_delta", &(title_changed as u32)),
+            (":id", &page_info.row_id),
+        ],
+    )?;
+    if let Some(title) = title.filter(|_| title_changed) {
+        db.note_event(PlacesEvent::TitleChanged {
+            url: url.to_string(),
+            title: title.into(),
+        });
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 /// Indicates if and when a URL's frecency was marked as stale.
 pub fn frecency_stale_at(db: &PlacesDb, url: &Url) -> Result<Option<Timestamp>> {
     let result = db.try_query_row(
@@ -186,6 +294,40 @@ fn add_visit(
     Ok(RowId(rid))
 }
 
+// Find the visit which should be recorded as the `from_visit` of a visit to
+// some page made at `at`, given the referrer reported for that visit. This is
+// the most recent visit to the referrer which happened before (or at the same
+// time as) the new visit, as long as it's recent enough to plausibly be the
+// one that led there.
+fn find_referring_visit(db: &PlacesDb, referrer: &str, at: Timestamp) -> Result<Option<RowId>> {
+    let referrer = match Url::parse(referrer) {
+        Ok(url) => url,
+        Err(e) => {
+            // A bad referrer shouldn't cause us to lose the visit itself.
+            log::warn!("Ignoring invalid referrer: {}", e);
+            return Ok(None);
+        }
+    };
+    let earliest = at
+        .checked_sub(Duration::from_millis(REFERRER_VISIT_THRESHOLD_MS))
+        .unwrap_or(Timestamp(0));
+    Ok(db.try_query_row(
+        "SELECT v.id FROM moz_historyvisits v
+         JOIN moz_places h ON h.id = v.place_id
+         WHERE h.url_hash = hash(:url) AND h.url = :url
+           AND v.visit_date BETWEEN :earliest AND :at
+         ORDER BY v.visit_date DESC, v.id DESC
+         LIMIT 1",
+        &[
+            (":url", &referrer.as_str()),
+            (":earliest", &earliest),
+            (":at", &at),
+        ],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )?)
+}
+
 /// Returns the GUID for the specified Url, or None if it doesn't exist.
 pub fn url_to_guid(db: &PlacesDb, url: &Url) -> Result<Option<SyncGuid>> {
     href_to_guid(db, url.clone().as_str())
@@ -208,8 +350,31 @@ pub fn href_to_guid(db: &PlacesDb, url: &str) -> Result<Option<SyncGuid>> {
 /// Internal function for deleting a place, creating a tombstone if necessary.
 /// Assumes a transaction is already set up by the caller.
 fn do_delete_place_by_guid(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
+    delete_place_and_tombstone(db, guid)?;
+    delete_pending_temp_tables(db)?;
+    Ok(())
+}
+
+/// Deletes a place and creates a tombstone for it, but leaves cleaning up
+/// the origins and icons to the caller, so that it can be done once when
+/// deleting many places.
+fn delete_place_and_tombstone(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
     // We only create tombstones for history which exists and with sync_status
     // == SyncStatus::Normal
+    if db.has_event_observers() {
+        let url = db.try_query_row(
+            "SELECT url FROM moz_places WHERE guid = :guid",
+            &[(":guid", guid)],
+            |row| row.get::<_, String>(0),
+            true,
+        )?;
+        if let Some(url) = url {
+            db.note_event(PlacesEvent::PageDeleted {
+                url,
+                guid: guid.clone(),
+            });
+        }
+    }
     let sql = "INSERT OR IGNORE INTO moz_places_tombstones (guid)
                SELECT guid FROM moz_places
                WHERE guid = :guid AND sync_status = :status";
@@ -217,7 +382,6 @@ fn do_delete_place_by_guid(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
     // and try the delete - it might not exist, but that's ok.
     let delete_sql = "DELETE FROM moz_places WHERE guid = :guid";
     db.execute_named_cached(delete_sql, &[(":guid", guid)])?;
-    delete_pending_temp_tables(db)?;
     Ok(())
 }
 
@@ -237,6 +401,17 @@ pub fn delete_visits_between(db: &PlacesDb, start: Timestamp, end: Timestamp) ->
     Ok(())
 }
 
+/// Delete all history for a host and its subdomains, like Desktop's "Forget
+/// About This Site". Pages that are only in history are deleted along with
+/// their visits, creating tombstones if necessary. Bookmarked pages can't be
+/// deleted, so only their visits are removed, and tombstoned.
+pub fn delete_history_for_host(db: &PlacesDb, host: &str) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    delete_history_for_host_in_tx(db, host)?;
+    tx.commit()?;
+    Ok(())
+}
+
 pub fn delete_place_visit_at_time(db: &PlacesDb, place: &Url, visit: Timestamp) -> Result<()> {
     delete_place_visit_at_time_by_href(db, place.as_str(), visit)
 }
@@ -254,7 +429,8 @@ pub fn delete_place_visit_at_time_by_href(
 
 pub fn prune_destructively(db: &PlacesDb) -> Result<()> {
     // For now, just fall back to wipe_local until we decide how this should work.
-    wipe_local(db)
+    wipe_local(db)?;
+    annotations::expire_annotations(db)
 }
 
 pub fn wipe_local(db: &PlacesDb) -> Result<()> {
@@ -264,7 +440,7 @@ pub fn wipe_local(db: &PlacesDb) -> Result<()> {
 }
 
 fn wipe_local_in_tx(db: &PlacesDb, tx: crate::db::PlacesTransaction<'_>) -> Result<()> {
-    use crate::frecency::DEFAULT_FRECENCY_SETTINGS;
+    let unvisited_bookmark_frec = db.frecency_settings().unvisited_bookmark_bonus;
     db.execute_all(&[
         "DELETE FROM moz_places WHERE foreign_count == 0",
         "DELETE FROM moz_historyvisits",
@@ -277,10 +453,14 @@ fn wipe_local_in_tx(db: &PlacesDb, tx: crate::db::PlacesTransaction<'_>) -> Resu
             "UPDATE moz_places SET
                 frecency = {unvisited_bookmark_frec},
                 sync_change_counter = 0",
-            unvisited_bookmark_frec = DEFAULT_FRECENCY_SETTINGS.unvisited_bookmark_bonus
+            unvisited_bookmark_frec = unvisited_bookmark_frec
         ),
     ])?;
 
+    // Annotations for the bookmarked pages that survived are cleared unless
+    // they're meant to outlive history.
+    annotations::delete_history_annotations(db)?;
+
     let need_frecency_update =
         db.query_rows_and_then_named("SELECT id FROM moz_places", &[], |r| r.get::<_, RowId>(0))?;
     // Update the frecency for any remaining items, which basically means just
@@ -289,6 +469,7 @@ fn wipe_local_in_tx(db: &PlacesDb, tx: crate::db::PlacesTransaction<'_>) -> Resu
         update_frecency(db, row_id, None)?;
     }
     delete_pending_temp_tables(db)?;
+    db.note_event(PlacesEvent::HistoryCleared);
     tx.commit()?;
     // Note: SQLite cannot VACUUM within a transaction.
     db.conn().execute("VACUUM", NO_PARAMS)?;
@@ -313,6 +494,11 @@ pub fn delete_everything(db: &PlacesDb) -> Result<()> {
 
     put_meta(db, DELETION_HIGH_WATER_MARK_META_KEY, &new_mark)?;
 
+    // The journal of deleted bookmarks keeps their URLs and titles around, so
+    // it's cleared along with history.
+    db.conn()
+        .execute_cached("DELETE FROM moz_bookmarks_deleted_journal", NO_PARAMS)?;
+
     wipe_local_in_tx(db, tx)?;
     Ok(())
 }
@@ -442,6 +628,109 @@ pub fn delete_visits_between_in_tx(db: &PlacesDb, start: Timestamp, end: Timesta
     Ok(())
 }
 
+fn delete_history_for_host_in_tx(db: &PlacesDb, host: &str) -> Result<()> {
+    let origin_ids = find_origins_for_host(db, host)?;
+    let mut pages: Vec<(RowId, SyncGuid, bool)> = Vec::new();
+    sql_support::each_chunk(&origin_ids, |chunk, _| -> Result<()> {
+        let mut stmt = db.conn().prepare(&format!(
+            "SELECT id, guid, (foreign_count != 0) AS has_foreign
+             FROM moz_places
+             WHERE origin_id IN ({})",
+            sql_support::repeat_sql_vars(chunk.len()),
+        ))?;
+        let rows = stmt.query_and_then(chunk, |row| -> Result<_> {
+            Ok((
+                row.get::<_, RowId>("id")?,
+                row.get::<_, SyncGuid>("guid")?,
+                row.get::<_, bool>("has_foreign")?,
+            ))
+        })?;
+        for row in rows {
+            pages.push(row?);
+        }
+        Ok(())
+    })?;
+
+    let mut kept_ids = Vec::new();
+    for (id, guid, has_foreign) in pages {
+        if has_foreign {
+            kept_ids.push(id);
+        } else {
+            delete_place_and_tombstone(db, &guid)?;
+        }
+    }
+
+    // The pages we're keeping still need tombstones for their visits, so
+    // that we don't sync them back down.
+    sql_support::each_chunk(&kept_ids, |chunk, _| -> Result<()> {
+        let ids = sql_support::repeat_sql_vars(chunk.len());
+        db.conn().execute(
+            &format!(
+                "INSERT OR IGNORE INTO moz_historyvisit_tombstones(place_id, visit_date)
+                 SELECT place_id, visit_date
+                 FROM moz_historyvisits
+                 WHERE place_id IN ({})",
+                ids
+            ),
+            chunk,
+        )?;
+        db.conn().execute(
+            &format!("DELETE FROM moz_historyvisits WHERE place_id IN ({})", ids),
+            chunk,
+        )?;
+        Ok(())
+    })?;
+    let pages: Vec<PageToClean> = kept_ids
+        .into_iter()
+        .map(|id| PageToClean {
+            id,
+            has_foreign: true,
+            has_visits: false,
+        })
+        .collect();
+    cleanup_pages(db, &pages)?;
+    delete_pending_temp_tables(db)?;
+    Ok(())
+}
+
+/// Returns the ids of the origins for a host and its subdomains, on any
+/// port and with any prefix.
+fn find_origins_for_host(db: &PlacesDb, host: &str) -> Result<Vec<RowId>> {
+    // This normalizes the host the same way as the hosts in `moz_origins`.
+    let host = url::Host::parse(host)?;
+    let include_subdomains = match host {
+        url::Host::Domain(_) => true,
+        _ => false,
+    };
+    let host = host.to_string();
+    // `moz_origins.host` includes the port, so we can't use `rev_host` to
+    // find subdomains. Instead, we find all the origins containing the host,
+    // and check them here.
+    let origins = db.query_rows_and_then_named_cached(
+        "SELECT id, host FROM moz_origins WHERE instr(host, :host) > 0",
+        &[(":host", &host)],
+        |row| -> rusqlite::Result<_> { Ok((row.get::<_, RowId>(0)?, row.get::<_, String>(1)?)) },
+    )?;
+    Ok(origins
+        .into_iter()
+        .filter(|(_, origin_host)| origin_matches_host(origin_host, &host, include_subdomains))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+fn origin_matches_host(origin_host: &str, host: &str, include_subdomains: bool) -> bool {
+    // IPv6 addresses are in brackets, so a colon after the closing bracket
+    // starts the port.
+    let origin_host = match origin_host.rfind(':') {
+        Some(pos) if !origin_host[pos..].contains(']') => &origin_host[..pos],
+        _ => origin_host,
+    };
+    origin_host == host
+        || (include_subdomains
+            && origin_host.ends_with(host)
+            && origin_host[..origin_host.len() - host.len()].ends_with('.'))
+}
+
 #[derive(Debug)]
 struct PageToClean {
     id: RowId,
@@ -487,6 +776,23 @@ fn cleanup_pages(db: &PlacesDb, pages: &[PageToClean]) -> Result<()> {
         .map(|p| p.id)
         .collect();
     sql_support::each_chunk(&remove_ids, |chunk, _| -> Result<()> {
+        if db.has_event_observers() {
+            let mut stmt = db.conn().prepare(&format!(
+                "SELECT url, guid FROM moz_places
+                 WHERE id IN ({})
+                    AND foreign_count = 0
+                    AND last_visit_date_local = 0
+                    AND last_visit_date_remote = 0",
+                sql_support::repeat_sql_vars(chunk.len()),
+            ))?;
+            let mut rows = stmt.query(chunk)?;
+            while let Some(row) = rows.next()? {
+                db.note_event(PlacesEvent::PageDeleted {
+                    url: row.get("url")?,
+                    guid: row.get("guid")?,
+                });
+            }
+        }
         // tombstones first.
         db.conn().execute(
             &format!(
@@ -1057,7 +1363,8 @@ pub fn get_visit_infos(
 ) -> Result<HistoryVisitInfos> {
     let allowed_types = exclude_types.complement();
     let infos = db.query_rows_and_then_named_cached(
-        "SELECT h.url, h.title, v.visit_date, v.visit_type
+        "SELECT h.url, h.title, h.description, h.preview_image_url,
+                v.visit_date, v.visit_type
          FROM moz_places h
          JOIN moz_historyvisits v
            ON h.id = v.place_id
@@ -1093,6 +1400,45 @@ pub fn get_visit_count(db: &PlacesDb, exclude_types: VisitTransitionSet) -> Resu
     Ok(count)
 }
 
+/// A host with visits, returned by `get_visited_domains`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VisitedDomain {
+    /// The host, including the port if it isn't the default.
+    pub host: String,
+    /// The total number of visits to pages on the host, not counting the
+    /// visit types excluded from `visit_count`.
+    pub visit_count: i64,
+    /// The time of the most recent local or remote visit to the host.
+    pub last_visit_date: Timestamp,
+}
+
+/// Returns the hosts that have been visited, most recently visited first.
+/// Pages on the same host with different prefixes, like `http://` and
+/// `https://`, are counted together.
+pub fn get_visited_domains(db: &PlacesDb, limit: u32) -> Result<Vec<VisitedDomain>> {
+    db.query_rows_and_then_named_cached(
+        "SELECT o.host AS host,
+                SUM(h.visit_count_local + h.visit_count_remote) AS visit_count,
+                MAX(MAX(h.last_visit_date_local, h.last_visit_date_remote)) AS last_visit_date
+         FROM moz_origins o
+         JOIN moz_places h ON h.origin_id = o.id
+         WHERE o.host <> ''
+         GROUP BY o.host
+         HAVING last_visit_date > 0
+         ORDER BY last_visit_date DESC
+         LIMIT :limit",
+        &[(":limit", &limit)],
+        |row| -> Result<_> {
+            Ok(VisitedDomain {
+                host: row.get("host")?,
+                visit_count: row.get("visit_count")?,
+                last_visit_date: row.get("last_visit_date")?,
+            })
+        },
+    )
+}
+
 pub fn get_visit_page(
     db: &PlacesDb,
     offset: i64,
@@ -1101,7 +1447,8 @@ pub fn get_visit_page(
 ) -> Result<HistoryVisitInfos> {
     let allowed_types = exclude_types.complement();
     let infos = db.query_rows_and_then_named_cached(
-        "SELECT h.url, h.title, v.visit_date, v.visit_type
+        "SELECT h.url, h.title, h.description, h.preview_image_url,
+                v.visit_date, v.visit_type
          FROM moz_places h
          JOIN moz_historyvisits v
            ON h.id = v.place_id
@@ -1119,6 +1466,238 @@ pub fn get_visit_page(
     Ok(HistoryVisitInfos { infos })
 }
 
+/// The date buckets for grouping history search results, based on the date of
+/// each page's most recent matching visit.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistorySearchBucket {
+    /// Visited in the last 24 hours.
+    Day = 0,
+    /// Visited in the last 7 days.
+    Week = 1,
+    /// Visited more than 7 days ago.
+    Older = 2,
+}
+
+impl HistorySearchBucket {
+    fn for_visit_date(visit_date: Timestamp, now: Timestamp) -> Self {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        match now.duration_since(visit_date) {
+            Some(age) if age > DAY * 7 => HistorySearchBucket::Older,
+            Some(age) if age > DAY => HistorySearchBucket::Week,
+            // Visits in the future are most likely from a device with a bad
+            // clock, so we treat them as recent.
+            _ => HistorySearchBucket::Day,
+        }
+    }
+}
+
+/// Parameters for `search_history`.
+#[derive(Clone, Debug)]
+pub struct HistorySearchParams {
+    /// Words to search for in page titles and URLs. Each word must match
+    /// either the title or the URL. An empty query matches every page.
+    pub query: String,
+    /// Only visits in this range (inclusive) are considered.
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub exclude_types: VisitTransitionSet,
+    /// Whether to set the `bucket` of each result.
+    pub group_by_date: bool,
+    /// The `next_cursor` from the previous page of results, or `None` for
+    /// the first page.
+    pub cursor: Option<String>,
+    /// The maximum number of results to return.
+    pub limit: u32,
+}
+
+impl Default for HistorySearchParams {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            start: Timestamp(0),
+            end: Timestamp(i64::max_value() as u64),
+            exclude_types: VisitTransitionSet::empty(),
+            group_by_date: false,
+            cursor: None,
+            limit: 100,
+        }
+    }
+}
+
+/// The position of the last result on a page of history search results. Pages
+/// are ordered by their most recent matching visit, and then by id, so new
+/// visits don't shift the results like they would with an offset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HistorySearchCursor {
+    last_visit_date: Timestamp,
+    place_id: RowId,
+}
+
+impl HistorySearchCursor {
+    fn parse(cursor: &str) -> Result<Self> {
+        let mut parts = cursor.splitn(2, ':');
+        match (
+            parts.next().and_then(|s| s.parse::<u64>().ok()),
+            parts.next().and_then(|s| s.parse::<i64>().ok()),
+        ) {
+            (Some(last_visit_date), Some(place_id)) => Ok(Self {
+                last_visit_date: Timestamp(last_visit_date),
+                place_id: RowId(place_id),
+            }),
+            _ => Err(InvalidPlaceInfo::InvalidCursor.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for HistorySearchCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.last_visit_date, self.place_id.0)
+    }
+}
+
+/// Searches history, returning one result per page, with the number of
+/// matching visits to it. Results are ordered by the date of each page's most
+/// recent matching visit, newest first, and can optionally be grouped into
+/// `HistorySearchBucket`s.
+pub fn search_history(db: &PlacesDb, params: &HistorySearchParams) -> Result<HistorySearchResults> {
+    let cursor = match &params.cursor {
+        Some(cursor) => HistorySearchCursor::parse(cursor)?,
+        None => HistorySearchCursor {
+            last_visit_date: Timestamp(i64::max_value() as u64),
+            place_id: RowId(i64::max_value()),
+        },
+    };
+    let allowed_types = params.exclude_types.complement();
+    // Fetch an extra row so we know if there's another page.
+    let limit = i64::from(params.limit) + 1;
+    let query = params.query.trim();
+
+    let mut sql_params: Vec<(&str, &dyn ToSql)> = vec![
+        (":start", &params.start),
+        (":end", &params.end),
+        (":allowed_types", &allowed_types),
+        (":cursor_date", &cursor.last_visit_date),
+        (":cursor_id", &cursor.place_id),
+        (":limit", &limit),
+    ];
+    let match_clause = if query.is_empty() {
+        ""
+    } else {
+        sql_params.push((":query", &query));
+        sql_params.push((":match_behavior", &MatchBehavior::Anywhere));
+        sql_params.push((":search_behavior", &SearchBehavior::HISTORY));
+        "AND autocomplete_match(:query, h.url, h.title, NULL, 1, 0, 0, NULL,
+                                :match_behavior, :search_behavior)"
+    };
+    let rows = db.query_rows_and_then_named(
+        &format!(
+            "SELECT h.id, h.url, h.title,
+                    MAX(v.visit_date) AS last_visit_date,
+                    COUNT(*) AS visit_count
+             FROM moz_historyvisits v
+             JOIN moz_places h ON h.id = v.place_id
+             WHERE v.visit_date BETWEEN :start AND :end
+               AND ((1 << v.visit_type) & :allowed_types) != 0
+               {match_clause}
+             GROUP BY h.id
+             HAVING last_visit_date < :cursor_date OR
+                    (last_visit_date = :cursor_date AND h.id < :cursor_id)
+             ORDER BY last_visit_date DESC, h.id DESC
+             LIMIT :limit",
+            match_clause = match_clause,
+        ),
+        &sql_params,
+        |row| -> RusqliteResult<_> {
+            Ok((
+                row.get::<_, RowId>("id")?,
+                row.get::<_, Timestamp>("last_visit_date")?,
+                HistorySearchResult {
+                    url: row.get("url")?,
+                    title: row.get("title")?,
+                    last_visit_date: row.get("last_visit_date")?,
+                    visit_count: row.get("visit_count")?,
+                    bucket: None,
+                },
+            ))
+        },
+    )?;
+
+    let now = Timestamp::now();
+    let has_more = rows.len() > params.limit as usize;
+    let mut last_cursor = None;
+    let mut results = Vec::with_capacity(rows.len());
+    for (place_id, last_visit_date, mut result) in rows.into_iter().take(params.limit as usize) {
+        if params.group_by_date {
+            result.bucket = Some(HistorySearchBucket::for_visit_date(last_visit_date, now) as i32);
+        }
+        results.push(result);
+        last_cursor = Some(HistorySearchCursor {
+            last_visit_date,
+            place_id,
+        });
+    }
+    Ok(HistorySearchResults {
+        results,
+        next_cursor: if has_more {
+            last_cursor.map(|c| c.to_string())
+        } else {
+            None
+        },
+    })
+}
+
+/// Returns the chain of visits which led to the visit with the given id,
+/// found by following each visit's referring visit (`from_visit`) back
+/// through any links and redirects. The result is ordered from the start of
+/// the chain, and always ends with the requested visit itself. An unknown
+/// visit id gives an empty list.
+pub fn get_visit_chain(db: &PlacesDb, visit_id: RowId) -> Result<HistoryVisitInfos> {
+    let infos = db.query_rows_and_then_named_cached(
+        "WITH RECURSIVE chain(id, from_visit, depth) AS (
+            SELECT id, from_visit, 0
+            FROM moz_historyvisits
+            WHERE id = :visit_id
+            UNION ALL
+            SELECT v.id, v.from_visit, c.depth + 1
+            FROM moz_historyvisits v
+            JOIN chain c ON v.id = c.from_visit
+            WHERE c.depth < :max_length
+         )
+         SELECT h.url, h.title, h.description, h.preview_image_url,
+                v.visit_date, v.visit_type
+         FROM chain c
+         JOIN moz_historyvisits v ON v.id = c.id
+         JOIN moz_places h ON h.id = v.place_id
+         ORDER BY c.depth DESC",
+        rusqlite::named_params! {
+            ":visit_id": visit_id,
+            ":max_length": MAX_VISIT_CHAIN_LENGTH,
+        },
+        HistoryVisitInfo::from_row,
+    )?;
+    Ok(HistoryVisitInfos { infos })
+}
+
+/// Like `get_visit_chain`, but for the most recent visit to `url`. Returns an
+/// empty list if the url has never been visited.
+pub fn get_visit_chain_for_url(db: &PlacesDb, url: &Url) -> Result<HistoryVisitInfos> {
+    let latest_visit = db.try_query_row(
+        "SELECT v.id FROM moz_historyvisits v
+         JOIN moz_places h ON h.id = v.place_id
+         WHERE h.url_hash = hash(:url) AND h.url = :url
+         ORDER BY v.visit_date DESC, v.id DESC
+         LIMIT 1",
+        &[(":url", &url.as_str())],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )?;
+    match latest_visit {
+        Some(visit_id) => get_visit_chain(db, visit_id),
+        None => Ok(HistoryVisitInfos { infos: vec![] }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::history_sync::*;
@@ -1689,6 +2268,135 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_delete_history_for_host() -> Result<()> {
+        use crate::storage::bookmarks::{
+            insert_bookmark, BookmarkPosition, BookmarkRootGuid, InsertableBookmark, InsertableItem,
+        };
+        let _ = env_logger::try_init();
+        let mut conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let urls = &[
+            "https://example.com/1",
+            "http://www.example.com:8080/2",
+            "https://sub.example.com/3",
+            "https://example.com/bookmarked",
+            "https://notexample.com/4",
+            "https://example.com.au/5",
+        ];
+        for url in urls {
+            get_observed_page(&mut conn, url)?;
+        }
+        insert_bookmark(
+            &conn,
+            &InsertableItem::Bookmark(InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                url: Url::parse("https://example.com/bookmarked")?,
+                title: None,
+            }),
+        )?;
+        conn.execute_all(&[&format!(
+            "UPDATE moz_places SET sync_status = {}",
+            (SyncStatus::Normal as u8)
+        )])?;
+
+        delete_history_for_host(&conn, "EXAMPLE.com")?;
+
+        for url in &urls[..3] {
+            assert!(fetch_page_info(&conn, &Url::parse(url)?)?.is_none());
+        }
+        assert_eq!(get_tombstone_count(&conn), 3);
+
+        // The bookmarked page is kept, but its visits are removed.
+        let bookmarked = fetch_page_info(&conn, &Url::parse(urls[3])?)?
+            .expect("should still exist")
+            .page;
+        assert_eq!(bookmarked.visit_count_local, 0);
+        assert_eq!(bookmarked.last_visit_date_local, Timestamp(0));
+        let visit_tombstones: i64 =
+            conn.query_one("SELECT COUNT(*) FROM moz_historyvisit_tombstones")?;
+        assert_eq!(visit_tombstones, 1);
+
+        for url in &urls[4..] {
+            assert!(fetch_page_info(&conn, &Url::parse(url)?)?.is_some());
+        }
+
+        // Only the origin for the bookmarked page is left.
+        let hosts = conn.query_rows_and_then_named(
+            "SELECT host FROM moz_origins ORDER BY host",
+            &[],
+            |row| row.get::<_, String>(0),
+        )?;
+        assert_eq!(hosts, &["example.com", "example.com.au", "notexample.com"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_origin_matches_host() {
+        assert!(origin_matches_host("example.com", "example.com", true));
+        assert!(origin_matches_host("example.com:8080", "example.com", true));
+        assert!(origin_matches_host("a.b.example.com", "example.com", true));
+        assert!(!origin_matches_host(
+            "a.b.example.com",
+            "example.com",
+            false
+        ));
+        assert!(!origin_matches_host("notexample.com", "example.com", true));
+        assert!(!origin_matches_host("example.com.au", "example.com", true));
+        assert!(origin_matches_host("[::1]:8080", "[::1]", false));
+        assert!(origin_matches_host("[::1]", "[::1]", false));
+    }
+
+    #[test]
+    fn test_get_visited_domains() -> Result<()> {
+        let _ = env_logger::try_init();
+        let mut conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let now = Timestamp::now();
+        let visits = &[
+            ("https://example.com/1", now.0 - 3000),
+            ("http://example.com/2", now.0 - 2000),
+            ("https://example.com/1", now.0 - 1000),
+            ("https://www.example.com/", now.0 - 4000),
+            ("https://mozilla.org/", now.0),
+        ];
+        for &(url, date) in visits {
+            get_custom_observed_page(&mut conn, url, |o| o.with_at(Timestamp(date)))?;
+        }
+        // Pages without visits don't count.
+        apply_observation(
+            &conn,
+            VisitObservation::new(Url::parse("https://unvisited.com/")?)
+                .with_title(Some("x".into())),
+        )?;
+
+        let domains = get_visited_domains(&conn, 10)?;
+        assert_eq!(
+            domains,
+            &[
+                VisitedDomain {
+                    host: "mozilla.org".into(),
+                    visit_count: 1,
+                    last_visit_date: now,
+                },
+                VisitedDomain {
+                    host: "example.com".into(),
+                    visit_count: 3,
+                    last_visit_date: Timestamp(now.0 - 1000),
+                },
+                VisitedDomain {
+                    host: "www.example.com".into(),
+                    visit_count: 1,
+                    last_visit_date: Timestamp(now.0 - 4000),
+                },
+            ]
+        );
+        assert_eq!(get_visited_domains(&conn, 1)?.len(), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_sync_reset() -> Result<()> {
         let _ = env_logger::try_init();
@@ -2166,4 +2874,365 @@ mod tests {
         assert_eq!(db_title.len(), crate::storage::TITLE_LENGTH_MAX);
         assert!(title.starts_with(&db_title));
     }
+
+    #[test]
+    fn test_visit_chain() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let now = Timestamp::now();
+        let ago = |secs| now.checked_sub(Duration::from_secs(secs)).unwrap();
+
+        let search = Url::parse("https://search.example.com/?q=rust")?;
+        let redirect = Url::parse("https://example.com/r")?;
+        let target = Url::parse("https://www.example.com/")?;
+        let unrelated = Url::parse("https://www.example.com/unrelated")?;
+
+        let search_visit = apply_observation(
+            &conn,
+            VisitObservation::new(search.clone())
+                .with_visit_type(VisitTransition::Typed)
+                .with_at(ago(30)),
+        )?
+        .expect("should add visit");
+        let redirect_visit = apply_observation(
+            &conn,
+            VisitObservation::new(redirect.clone())
+                .with_visit_type(VisitTransition::Link)
+                .with_is_redirect_source(true)
+                .with_referrer(search.clone())
+                .with_at(ago(20)),
+        )?
+        .expect("should add visit");
+        let target_visit = apply_observation(
+            &conn,
+            VisitObservation::new(target.clone())
+                .with_visit_type(VisitTransition::RedirectTemporary)
+                .with_referrer(redirect.clone())
+                .with_at(ago(10)),
+        )?
+        .expect("should add visit");
+        // A referrer we've never seen doesn't link to anything.
+        let unrelated_visit = apply_observation(
+            &conn,
+            VisitObservation::new(unrelated.clone())
+                .with_visit_type(VisitTransition::Link)
+                .with_referrer(Url::parse("https://never-visited.example.com/")?)
+                .with_at(now),
+        )?
+        .expect("should add visit");
+
+        let get_from_visit = |id: RowId| -> Option<RowId> {
+            conn.query_row_and_then_named(
+                "SELECT from_visit FROM moz_historyvisits WHERE id = :id",
+                &[(":id", &id)],
+                |row| row.get(0),
+                false,
+            )
+            .expect("should work")
+        };
+        assert_eq!(get_from_visit(search_visit), None);
+        assert_eq!(get_from_visit(redirect_visit), Some(search_visit));
+        assert_eq!(get_from_visit(target_visit), Some(redirect_visit));
+        assert_eq!(get_from_visit(unrelated_visit), None);
+
+        let urls = |infos: HistoryVisitInfos| -> Vec<String> {
+            infos.infos.into_iter().map(|info| info.url).collect()
+        };
+        assert_eq!(
+            urls(get_visit_chain(&conn, target_visit)?),
+            vec![
+                search.as_str().to_string(),
+                redirect.as_str().to_string(),
+                target.as_str().to_string(),
+            ]
+        );
+        assert_eq!(
+            urls(get_visit_chain_for_url(&conn, &redirect)?),
+            vec![search.as_str().to_string(), redirect.as_str().to_string()]
+        );
+        assert_eq!(
+            urls(get_visit_chain_for_url(&conn, &unrelated)?),
+            vec![unrelated.as_str().to_string()]
+        );
+        assert!(get_visit_chain(&conn, RowId(9999))?.infos.is_empty());
+
+        // Deleting a visit in the middle of the chain unlinks the visits it
+        // referred.
+        delete_place_visit_at_time(&conn, &redirect, ago(20))?;
+        assert_eq!(get_from_visit(target_visit), None);
+        assert_eq!(
+            urls(get_visit_chain(&conn, target_visit)?),
+            vec![target.as_str().to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_referrer_too_old() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let now = Timestamp::now();
+        let referrer = Url::parse("https://www.example.com/referrer")?;
+        apply_observation(
+            &conn,
+            VisitObservation::new(referrer.clone())
+                .with_visit_type(VisitTransition::Link)
+                .with_at(now.checked_sub(Duration::from_secs(60 * 60)).unwrap()),
+        )?;
+        let visit = apply_observation(
+            &conn,
+            VisitObservation::new(Url::parse("https://www.example.com/page")?)
+                .with_visit_type(VisitTransition::Link)
+                .with_referrer(referrer)
+                .with_at(now),
+        )?
+        .expect("should add visit");
+        assert_eq!(get_visit_chain(&conn, visit)?.infos.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_history() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let now = Timestamp::now();
+        let ago = |hours: u64| {
+            now.checked_sub(Duration::from_secs(hours * 60 * 60))
+                .unwrap()
+        };
+        let visits = [
+            (
+                "https://www.example.com/1",
+                "Example one",
+                VisitTransition::Link,
+                1,
+            ),
+            (
+                "https://www.example.com/1",
+                "Example one",
+                VisitTransition::Link,
+                2,
+            ),
+            (
+                "https://www.example.com/2",
+                "Example two",
+                VisitTransition::Typed,
+                30,
+            ),
+            (
+                "https://www.example.com/3",
+                "Another page",
+                VisitTransition::Link,
+                24 * 10,
+            ),
+            (
+                "https://www.mozilla.org/",
+                "Mozilla",
+                VisitTransition::Link,
+                3,
+            ),
+            (
+                "https://www.mozilla.org/",
+                "Mozilla",
+                VisitTransition::Download,
+                4,
+            ),
+            (
+                "https://www.mozilla.org/",
+                "Mozilla",
+                VisitTransition::Link,
+                24 * 20,
+            ),
+        ];
+        for &(url, title, visit_type, hours_ago) in &visits {
+            apply_observation(
+                &conn,
+                VisitObservation::new(Url::parse(url)?)
+                    .with_title(title.to_string())
+                    .with_visit_type(visit_type)
+                    .with_at(ago(hours_ago)),
+            )?;
+        }
+        let summarize = |results: &HistorySearchResults| -> Vec<(String, i64, Option<i32>)> {
+            results
+                .results
+                .iter()
+                .map(|r| (r.url.clone(), r.visit_count, r.bucket))
+                .collect()
+        };
+
+        // One row per page, newest first.
+        let results = search_history(
+            &conn,
+            &HistorySearchParams {
+                group_by_date: true,
+                ..HistorySearchParams::default()
+            },
+        )?;
+        assert_eq!(
+            summarize(&results),
+            vec![
+                ("https://www.example.com/1".to_string(), 2, Some(0)),
+                ("https://www.mozilla.org/".to_string(), 3, Some(0)),
+                ("https://www.example.com/2".to_string(), 1, Some(1)),
+                ("https://www.example.com/3".to_string(), 1, Some(2)),
+            ]
+        );
+        assert_eq!(results.results[0].title, Some("Example one".to_string()));
+        assert_eq!(results.results[0].last_visit_date, ago(1).0 as i64);
+        assert_eq!(results.next_cursor, None);
+
+        // Text search matches every word against the title or URL.
+        let results = search_history(
+            &conn,
+            &HistorySearchParams {
+                query: "example page".into(),
+                ..HistorySearchParams::default()
+            },
+        )?;
+        assert_eq!(
+            summarize(&results),
+            vec![("https://www.example.com/3".to_string(), 1, None)]
+        );
+
+        // Excluded visit types and the date range affect the visit counts and
+        // dates.
+        let results = search_history(
+            &conn,
+            &HistorySearchParams {
+                query: "mozilla".into(),
+                start: ago(24),
+                exclude_types: VisitTransitionSet::single(VisitTransition::Link),
+                ..HistorySearchParams::default()
+            },
+        )?;
+        assert_eq!(
+            summarize(&results),
+            vec![("https://www.mozilla.org/".to_string(), 1, None)]
+        );
+        assert_eq!(results.results[0].last_visit_date, ago(4).0 as i64);
+
+        // Paging with a cursor.
+        let mut params = HistorySearchParams {
+            limit: 3,
+            ..HistorySearchParams::default()
+        };
+        let first = search_history(&conn, &params)?;
+        assert_eq!(first.results.len(), 3);
+        assert!(first.next_cursor.is_some());
+
+        // A new visit to a page we've already seen doesn't shift the next
+        // page.
+        apply_observation(
+            &conn,
+            VisitObservation::new(Url::parse("https://www.example.com/2")?)
+                .with_visit_type(VisitTransition::Link)
+                .with_at(now),
+        )?;
+        params.cursor = first.next_cursor;
+        let second = search_history(&conn, &params)?;
+        assert_eq!(
+            summarize(&second),
+            vec![("https://www.example.com/3".to_string(), 1, None)]
+        );
+        assert_eq!(second.next_cursor, None);
+
+        params.cursor = Some("bogus".into());
+        match search_history(&conn, &params).unwrap_err().kind() {
+            crate::error::ErrorKind::InvalidPlaceInfo(InvalidPlaceInfo::InvalidCursor) => {}
+            e => panic!("Unexpected error: {:?}", e),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_note_page_metadata() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = PlacesDb::open_in_memory(ConnectionType::ReadWrite)?;
+        let url = Url::parse("https://www.example.com/article")?;
+        let image = Url::parse("https://www.example.com/preview.png")?;
+        apply_observation(
+            &conn,
+            VisitObservation::new(url.clone())
+                .with_title("Article".to_string())
+                .with_visit_type(VisitTransition::Link),
+        )?;
+        let counter = fetch_page_info(&conn, &url)?
+            .expect("should exist")
+            .page
+            .sync_change_counter;
+
+        note_page_metadata(
+            &conn,
+            &url,
+            &PageMetadata {
+                description: Some("x".repeat(crate::storage::DESCRIPTION_LENGTH_MAX + 10)),
+                preview_image_url: Some(image.clone()),
+                ..PageMetadata::default()
+            },
+        )?;
+        let infos = get_visit_infos(
+            &conn,
+            Timestamp(0),
+            Timestamp::now(),
+            VisitTransitionSet::empty(),
+        )?
+        .infos;
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].title, Some("Article".to_string()));
+        assert_eq!(
+            infos[0].description,
+            Some("x".repeat(crate::storage::DESCRIPTION_LENGTH_MAX))
+        );
+        assert_eq!(infos[0].preview_image_url, Some(image.to_string()));
+        // Descriptions and images aren't synced, so the page doesn't need to
+        // be uploaded.
+        assert_eq!(
+            fetch_page_info(&conn, &url)?
+                .expect("should exist")
+                .page
+                .sync_change_counter,
+            counter
+        );
+
+        // Changing the title does need a sync, and leaves the other fields
+        // alone.
+        note_page_metadata(
+            &conn,
+            &url,
+            &PageMetadata {
+                title: Some("New title".into()),
+                ..PageMetadata::default()
+            },
+        )?;
+        let page = fetch_page_info(&conn, &url)?.expect("should exist").page;
+        assert_eq!(page.title, "New title");
+        assert_eq!(page.sync_change_counter, counter + 1);
+        assert_eq!(page.visit_count_local, 1);
+        let infos = get_visit_infos(
+            &conn,
+            Timestamp(0),
+            Timestamp::now(),
+            VisitTransitionSet::empty(),
+        )?
+        .infos;
+        assert_eq!(infos[0].preview_image_url, Some(image.to_string()));
+
+        // Metadata for an unknown page adds the page without visits.
+        let unvisited = Url::parse("https://www.example.com/unvisited")?;
+        note_page_metadata(
+            &conn,
+            &unvisited,
+            &PageMetadata {
+                title: Some("Unvisited".into()),
+                ..PageMetadata::default()
+            },
+        )?;
+        let page = fetch_page_info(&conn, &unvisited)?
+            .expect("should exist")
+            .page;
+        assert_eq!(page.title, "Unvisited");
+        assert_eq!(page.visit_count_local, 0);
+        Ok(())
+    }
 }
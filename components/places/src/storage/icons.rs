@@ -0,0 +1,516 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Favicon storage. Like Desktop, each icon URL may have several payloads of
+//! different widths, and pages are linked to their icons by URL. Icons for
+//! `/favicon.ico` are "root" icons, which are also used for other pages on
+//! the same host (or a parent domain) that don't have an icon of their own.
+
+use super::{delete_pending_temp_tables, RowId};
+use crate::db::PlacesDb;
+use crate::error::{InvalidPlaceInfo, Result};
+use crate::types::Timestamp;
+use rusqlite::Row;
+use sql_support::ConnExt;
+use std::time::Duration;
+use url::Url;
+
+/// How long icons are considered fresh, if the caller doesn't say.
+pub const DEFAULT_ICON_EXPIRATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How long we keep icons after they expire, if they aren't refreshed.
+/// Expired icons are still returned by lookups until then, since a stale
+/// icon is better than none.
+const EXPIRED_ICON_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// An icon payload, as returned by `get_icon_for_page`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Icon {
+    pub url: Url,
+    /// The width of the icon in pixels, or 0 if it's unknown or the icon is
+    /// scalable.
+    pub width: u32,
+    pub data: Vec<u8>,
+    /// When the icon should be fetched again.
+    pub expires: Timestamp,
+    /// Whether this is a root (`/favicon.ico`) icon.
+    pub is_root: bool,
+}
+
+impl Icon {
+    /// Returns `true` if the icon should be fetched again.
+    pub fn is_expired(&self) -> bool {
+        self.expires <= Timestamp::now()
+    }
+
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(Self {
+            url: Url::parse(&row.get::<_, String>("icon_url")?)?,
+            width: row.get("width")?,
+            data: row.get("data")?,
+            expires: row.get("expire_ms")?,
+            is_root: row.get("root")?,
+        })
+    }
+}
+
+/// Returns the URL without its scheme or a leading "www." in the host, so
+/// that, for example, `http://example.com/favicon.ico` and
+/// `https://www.example.com/favicon.ico` are considered the same icon when
+/// looking for root icons.
+fn fixup_url(url: &Url) -> String {
+    let spec = &url[url::Position::BeforeHost..];
+    spec.trim_start_matches("www.").to_owned()
+}
+
+fn is_root_icon(url: &Url) -> bool {
+    url.has_host() && url.path() == "/favicon.ico" && url.query().is_none()
+}
+
+/// Returns the fixed-up root icon URLs to try for a page without its own
+/// icon: first its own host, then each parent domain.
+fn root_icon_candidates(page_url: &Url) -> Vec<String> {
+    let host = match page_url.host_str() {
+        Some(host) => host.trim_start_matches("www."),
+        None => return Vec::new(),
+    };
+    let port = match page_url.port() {
+        Some(port) => format!(":{}", port),
+        None => String::new(),
+    };
+    let mut candidates = vec![format!("{}{}/favicon.ico", host, port)];
+    // IP addresses don't have parent domains.
+    if page_url.domain().is_some() {
+        let mut domain = host;
+        while let Some(index) = domain.find('.') {
+            domain = &domain[index + 1..];
+            if !domain.contains('.') {
+                break;
+            }
+            candidates.push(format!("{}{}/favicon.ico", domain, port));
+        }
+    }
+    candidates
+}
+
+/// Sets an icon for the specified page. Setting an icon with a different URL
+/// replaces the page's previous icons, while setting the same icon URL with a
+/// different width adds another size.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `page_url` - The URL of the page. This must already be in the database.
+///
+/// * `icon_url` - The URL of the icon.
+///
+/// * `width` - The width of the icon in pixels, or 0 if it's unknown or the
+///   icon is scalable.
+///
+/// * `data` - The icon data.
+///
+/// * `expires` - When the icon should be fetched again. Defaults to
+///   `DEFAULT_ICON_EXPIRATION` from now.
+///
+/// # Returns
+///
+/// There is no success return value.
+pub fn set_page_icon(
+    db: &PlacesDb,
+    page_url: &Url,
+    icon_url: &Url,
+    width: u32,
+    data: &[u8],
+    expires: Option<Timestamp>,
+) -> Result<()> {
+    let expires = expires
+        .unwrap_or_else(|| Timestamp::from(std::time::SystemTime::now() + DEFAULT_ICON_EXPIRATION));
+    let tx = db.begin_transaction()?;
+
+    let page_exists = db
+        .try_query_row(
+            "SELECT 1 FROM moz_places
+             WHERE url_hash = hash(:url) AND url = :url",
+            &[(":url", &page_url.as_str())],
+            |row| row.get::<_, i64>(0),
+            true,
+        )?
+        .is_some();
+    if !page_exists {
+        return Err(InvalidPlaceInfo::NoSuchUrl.into());
+    }
+
+    db.execute_named_cached(
+        "INSERT INTO moz_pages_w_icons(page_url, page_url_hash)
+         SELECT :url, hash(:url)
+         WHERE NOT EXISTS(SELECT 1 FROM moz_pages_w_icons
+                          WHERE page_url_hash = hash(:url) AND page_url = :url)",
+        &[(":url", &page_url.as_str())],
+    )?;
+    let page_id = db.query_row_and_then_named(
+        "SELECT id FROM moz_pages_w_icons
+         WHERE page_url_hash = hash(:url) AND page_url = :url",
+        &[(":url", &page_url.as_str())],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )?;
+
+    db.execute_named_cached(
+        "INSERT INTO moz_icons(icon_url, fixed_icon_url_hash, width, root,
+                               expire_ms, data)
+         VALUES(:icon_url, hash(:fixed_icon_url), :width, :root, :expires, :data)
+         ON CONFLICT(icon_url, width) DO UPDATE SET
+           expire_ms = excluded.expire_ms,
+           data = excluded.data",
+        &[
+            (":icon_url", &icon_url.as_str()),
+            (":fixed_icon_url", &fixup_url(icon_url)),
+            (":width", &width),
+            (":root", &is_root_icon(icon_url)),
+            (":expires", &expires),
+            (":data", &data),
+        ],
+    )?;
+
+    // Unlink icons with other URLs, removing any that no other page uses,
+    // then link all the sizes of this one.
+    db.execute_named_cached(
+        "DELETE FROM moz_icons
+         WHERE icon_url <> :icon_url AND
+               id IN (SELECT icon_id FROM moz_icons_to_pages
+                      WHERE page_id = :page_id) AND
+               NOT EXISTS(SELECT 1 FROM moz_icons_to_pages
+                          WHERE icon_id = moz_icons.id AND
+                                page_id <> :page_id)",
+        &[(":page_id", &page_id), (":icon_url", &icon_url.as_str())],
+    )?;
+    db.execute_named_cached(
+        "DELETE FROM moz_icons_to_pages
+         WHERE page_id = :page_id AND
+               icon_id IN (SELECT id FROM moz_icons
+                           WHERE icon_url <> :icon_url)",
+        &[(":page_id", &page_id), (":icon_url", &icon_url.as_str())],
+    )?;
+    db.execute_named_cached(
+        "INSERT OR IGNORE INTO moz_icons_to_pages(page_id, icon_id)
+         SELECT :page_id, id FROM moz_icons
+         WHERE icon_url = :icon_url",
+        &[(":page_id", &page_id), (":icon_url", &icon_url.as_str())],
+    )?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Prefers the smallest icon that's at least as wide as requested, and
+// otherwise the largest one.
+const BEST_SIZE_ORDER: &str = "ORDER BY i.width < :width,
+                                        CASE WHEN i.width >= :width
+                                             THEN i.width
+                                             ELSE -i.width
+                                        END
+                               LIMIT 1";
+
+/// Finds the best icon for the specified page. If the page doesn't have its
+/// own icon, we fall back to the root icon for its host, and then for each
+/// of its parent domains.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `page_url` - The URL of the page.
+///
+/// * `width` - The desired width in pixels. We return the smallest icon at
+///   least as wide as this, or the largest icon if they're all smaller. Use
+///   0 for the smallest icon.
+///
+/// # Returns
+///
+/// * The icon, or `None` if we don't have one for the page.
+pub fn get_icon_for_page(db: &PlacesDb, page_url: &Url, width: u32) -> Result<Option<Icon>> {
+    let icon = db.try_query_row(
+        &format!(
+            "SELECT i.icon_url, i.width, i.data, i.expire_ms, i.root
+             FROM moz_icons i
+             JOIN moz_icons_to_pages ip ON ip.icon_id = i.id
+             JOIN moz_pages_w_icons p ON p.id = ip.page_id
+             WHERE p.page_url_hash = hash(:url) AND p.page_url = :url
+             {order}",
+            order = BEST_SIZE_ORDER,
+        ),
+        &[(":url", &page_url.as_str()), (":width", &width)],
+        Icon::from_row,
+        true,
+    )?;
+    if icon.is_some() {
+        return Ok(icon);
+    }
+    for fixed_icon_url in root_icon_candidates(page_url) {
+        let icon = db.try_query_row(
+            &format!(
+                "SELECT i.icon_url, i.width, i.data, i.expire_ms, i.root
+                 FROM moz_icons i
+                 WHERE i.fixed_icon_url_hash = hash(:fixed_icon_url) AND i.root
+                 {order}",
+                order = BEST_SIZE_ORDER,
+            ),
+            &[(":fixed_icon_url", &fixed_icon_url), (":width", &width)],
+            Icon::from_row,
+            true,
+        )?;
+        if icon.is_some() {
+            return Ok(icon);
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the widths we have for the specified icon URL, smallest first.
+pub fn get_icon_widths(db: &PlacesDb, icon_url: &Url) -> Result<Vec<u32>> {
+    Ok(db.query_rows_and_then_named_cached(
+        "SELECT width FROM moz_icons
+         WHERE icon_url = :icon_url
+         ORDER BY width",
+        &[(":icon_url", &icon_url.as_str())],
+        |row| row.get::<_, u32>(0),
+    )?)
+}
+
+/// Removes the icons for the specified page. The icons themselves are
+/// removed if no other page uses them.
+pub fn remove_page_icons(db: &PlacesDb, page_url: &Url) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    // `moz_updateiconsdelete_afterdelete_trigger` removes the page's icon
+    // links and unused icons, just like when the page itself is removed.
+    db.execute_named_cached(
+        "INSERT OR IGNORE INTO moz_updateiconsdelete_temp(url_hash, url)
+         VALUES(hash(:url), :url)",
+        &[(":url", &page_url.as_str())],
+    )?;
+    delete_pending_temp_tables(db)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Removes icons that expired more than `EXPIRED_ICON_RETENTION` ago, along
+/// with any icons for pages that are no longer in the database. This runs as
+/// part of `run_maintenance`.
+pub fn expire_icons(db: &PlacesDb) -> Result<()> {
+    let cutoff = Timestamp::now()
+        .checked_sub(EXPIRED_ICON_RETENTION)
+        .unwrap_or_default();
+    db.execute_named_cached(
+        "DELETE FROM moz_icons WHERE expire_ms < :cutoff",
+        &[(":cutoff", &cutoff)],
+    )?;
+    delete_orphaned_icons(db)
+}
+
+/// Removes icon links for pages that have been removed from moz_places, and
+/// icons that are no longer linked to any page. Removing pages already cleans
+/// up their icons via `moz_updateiconsdelete_temp`, so this full sweep only
+/// runs during maintenance, to catch anything left over.
+fn delete_orphaned_icons(db: &PlacesDb) -> Result<()> {
+    db.execute_all(&[
+        "DELETE FROM moz_pages_w_icons
+         WHERE NOT EXISTS(SELECT 1 FROM moz_places h
+                          WHERE h.url_hash = moz_pages_w_icons.page_url_hash AND
+                                h.url = moz_pages_w_icons.page_url)",
+        "DELETE FROM moz_icons
+         WHERE NOT EXISTS(SELECT 1 FROM moz_icons_to_pages
+                          WHERE icon_id = moz_icons.id)",
+    ])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::history::{
+        delete_place_by_guid, delete_visits_between, url_to_guid, wipe_local,
+    };
+    use crate::storage::run_maintenance;
+    use crate::tests::visit_url;
+
+    fn count_icons(db: &PlacesDb) -> u32 {
+        db.query_one::<u32>("SELECT COUNT(*) FROM moz_icons")
+            .expect("should count")
+    }
+
+    fn widths(icon: Option<Icon>) -> Option<u32> {
+        icon.map(|i| i.width)
+    }
+
+    #[test]
+    fn test_fixup() -> Result<()> {
+        assert_eq!(
+            fixup_url(&Url::parse("https://www.example.com:8080/favicon.ico?a")?),
+            "example.com:8080/favicon.ico?a"
+        );
+        assert!(is_root_icon(&Url::parse("http://example.com/favicon.ico")?));
+        assert!(!is_root_icon(&Url::parse(
+            "http://example.com/a/favicon.ico"
+        )?));
+        assert_eq!(
+            root_icon_candidates(&Url::parse("https://www.a.b.example.com/page")?),
+            vec![
+                "a.b.example.com/favicon.ico",
+                "b.example.com/favicon.ico",
+                "example.com/favicon.ico",
+            ]
+        );
+        assert_eq!(
+            root_icon_candidates(&Url::parse("http://127.0.0.1:8000/")?),
+            vec!["127.0.0.1:8000/favicon.ico"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_size() -> Result<()> {
+        let conn = new_mem_connection();
+        let page = Url::parse("https://www.example.com/page")?;
+        let icon = Url::parse("https://www.example.com/icon.png")?;
+
+        // The page needs to exist.
+        assert!(set_page_icon(&conn, &page, &icon, 16, b"16", None).is_err());
+        visit_url(&conn, &page);
+
+        assert_eq!(get_icon_for_page(&conn, &page, 16)?, None);
+        set_page_icon(&conn, &page, &icon, 16, b"16", None)?;
+        set_page_icon(&conn, &page, &icon, 32, b"32", None)?;
+        set_page_icon(&conn, &page, &icon, 64, b"64", None)?;
+        assert_eq!(get_icon_widths(&conn, &icon)?, vec![16, 32, 64]);
+
+        assert_eq!(widths(get_icon_for_page(&conn, &page, 0)?), Some(16));
+        assert_eq!(widths(get_icon_for_page(&conn, &page, 16)?), Some(16));
+        assert_eq!(widths(get_icon_for_page(&conn, &page, 20)?), Some(32));
+        assert_eq!(widths(get_icon_for_page(&conn, &page, 128)?), Some(64));
+
+        let found = get_icon_for_page(&conn, &page, 32)?.expect("should have an icon");
+        assert_eq!(found.url, icon);
+        assert_eq!(found.data, b"32".to_vec());
+        assert!(!found.is_root);
+        assert!(!found.is_expired());
+
+        // Updating a size replaces its data.
+        set_page_icon(&conn, &page, &icon, 32, b"new", None)?;
+        let found = get_icon_for_page(&conn, &page, 32)?.expect("should have an icon");
+        assert_eq!(found.data, b"new".to_vec());
+
+        // Setting a different icon replaces the old one, which is removed
+        // since nothing else uses it.
+        let new_icon = Url::parse("https://www.example.com/new.png")?;
+        set_page_icon(&conn, &page, &new_icon, 16, b"new16", None)?;
+        let found = get_icon_for_page(&conn, &page, 64)?.expect("should have an icon");
+        assert_eq!(found.url, new_icon);
+        assert_eq!(count_icons(&conn), 1);
+
+        remove_page_icons(&conn, &page)?;
+        assert_eq!(get_icon_for_page(&conn, &page, 16)?, None);
+        assert_eq!(count_icons(&conn), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_fallback() -> Result<()> {
+        let conn = new_mem_connection();
+        let root_page = Url::parse("https://example.com/")?;
+        let other_page = Url::parse("http://www.example.com/other")?;
+        let sub_page = Url::parse("https://sub.example.com/")?;
+        let unrelated = Url::parse("https://example.org/")?;
+        let page_with_icon = Url::parse("https://example.com/own")?;
+        for url in &[
+            &root_page,
+            &other_page,
+            &sub_page,
+            &unrelated,
+            &page_with_icon,
+        ] {
+            visit_url(&conn, url);
+        }
+
+        let root_icon = Url::parse("https://example.com/favicon.ico")?;
+        set_page_icon(&conn, &root_page, &root_icon, 16, b"root", None)?;
+        let own_icon = Url::parse("https://example.com/own.png")?;
+        set_page_icon(&conn, &page_with_icon, &own_icon, 16, b"own", None)?;
+
+        let found = get_icon_for_page(&conn, &other_page, 16)?.expect("should fall back");
+        assert_eq!(found.url, root_icon);
+        assert!(found.is_root);
+        let found = get_icon_for_page(&conn, &sub_page, 16)?.expect("should fall back");
+        assert_eq!(found.url, root_icon);
+        let found = get_icon_for_page(&conn, &page_with_icon, 16)?.expect("should have one");
+        assert_eq!(found.url, own_icon);
+        assert_eq!(get_icon_for_page(&conn, &unrelated, 16)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_deletion() -> Result<()> {
+        let conn = new_mem_connection();
+        let page1 = Url::parse("https://example.com/1")?;
+        let page2 = Url::parse("https://example.com/2")?;
+        let page3 = Url::parse("https://example.com/3")?;
+        let shared = Url::parse("https://example.com/shared.png")?;
+        let icon3 = Url::parse("https://example.com/3.png")?;
+        for url in &[&page1, &page2, &page3] {
+            visit_url(&conn, url);
+        }
+        set_page_icon(&conn, &page1, &shared, 16, b"shared", None)?;
+        set_page_icon(&conn, &page2, &shared, 16, b"shared", None)?;
+        set_page_icon(&conn, &page3, &icon3, 16, b"3", None)?;
+        assert_eq!(count_icons(&conn), 2);
+
+        // The shared icon stays while another page uses it.
+        let guid = url_to_guid(&conn, &page1)?.expect("should exist");
+        delete_place_by_guid(&conn, &guid)?;
+        assert_eq!(get_icon_for_page(&conn, &page1, 16)?, None);
+        assert!(get_icon_for_page(&conn, &page2, 16)?.is_some());
+        assert_eq!(count_icons(&conn), 2);
+
+        delete_visits_between(&conn, Timestamp(0), Timestamp::now())?;
+        assert_eq!(count_icons(&conn), 0);
+
+        visit_url(&conn, &page3);
+        set_page_icon(&conn, &page3, &icon3, 16, b"3", None)?;
+        wipe_local(&conn)?;
+        assert_eq!(count_icons(&conn), 0);
+        assert_eq!(
+            conn.query_one::<u32>("SELECT COUNT(*) FROM moz_pages_w_icons")?,
+            0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiry() -> Result<()> {
+        let conn = new_mem_connection();
+        let page = Url::parse("https://example.com/")?;
+        let icon = Url::parse("https://example.com/icon.png")?;
+        visit_url(&conn, &page);
+
+        // Expired icons are still returned until they're cleaned up.
+        let expired = Timestamp::now()
+            .checked_sub(Duration::from_secs(60))
+            .expect("should be valid");
+        set_page_icon(&conn, &page, &icon, 16, b"16", Some(expired))?;
+        let found = get_icon_for_page(&conn, &page, 16)?.expect("should have an icon");
+        assert!(found.is_expired());
+        run_maintenance(&conn)?;
+        assert!(get_icon_for_page(&conn, &page, 16)?.is_some());
+
+        let long_expired = Timestamp::now()
+            .checked_sub(EXPIRED_ICON_RETENTION + Duration::from_secs(60))
+            .expect("should be valid");
+        set_page_icon(&conn, &page, &icon, 16, b"16", Some(long_expired))?;
+        run_maintenance(&conn)?;
+        assert_eq!(get_icon_for_page(&conn, &page, 16)?, None);
+        assert_eq!(count_icons(&conn), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::RowId;
+use crate::db::PlacesDb;
+use crate::error::{InvalidPlaceInfo, Result};
+use sql_support::ConnExt;
+use url::Url;
+
+/// Checks the validity of the specified keyword.
+///
+/// # Returns
+///
+/// * The normalized keyword, with leading and trailing whitespace removed and
+///   converted to lowercase, or an error if the keyword is empty or contains
+///   whitespace.
+pub fn validate_keyword(keyword: &str) -> Result<String> {
+    let k = keyword.trim();
+    if k.is_empty() || k.find(char::is_whitespace).is_some() {
+        return Err(InvalidPlaceInfo::InvalidKeyword.into());
+    }
+    Ok(k.to_lowercase())
+}
+
+/// Sets the keyword for the specified URL, replacing any existing keyword
+/// for the URL. If the keyword is already used by a different URL, it's
+/// moved to this one.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `url` - The URL for the keyword. This must be bookmarked.
+///
+/// * `keyword` - The keyword to set for the URL.
+///
+/// # Returns
+///
+/// There is no success return value.
+pub fn set_keyword(db: &PlacesDb, url: &Url, keyword: &str) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    set_keyword_in_tx(db, url, keyword)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Assumes a transaction is already set up by the caller.
+pub(crate) fn set_keyword_in_tx(db: &PlacesDb, url: &Url, keyword: &str) -> Result<()> {
+    let keyword = validate_keyword(keyword)?;
+
+    // Keywords only make sense for bookmarked URLs, and are removed along
+    // with the last bookmark for the URL.
+    let place_id = match db.try_query_row(
+        "SELECT h.id FROM moz_places h
+         WHERE h.url_hash = hash(:url) AND h.url = :url AND
+               EXISTS(SELECT 1 FROM moz_bookmarks b
+                      WHERE b.fk = h.id)",
+        &[(":url", &url.as_str())],
+        |row| row.get::<_, RowId>(0),
+        true,
+    )? {
+        Some(place_id) => place_id,
+        None => return Err(InvalidPlaceInfo::NoSuchUrl.into()),
+    };
+
+    // We don't use `INSERT OR REPLACE` here, because that doesn't fire the
+    // delete triggers which maintain the foreign count.
+    db.execute_named_cached(
+        "DELETE FROM moz_keywords
+         WHERE (keyword = :keyword AND place_id <> :place_id) OR
+               (place_id = :place_id AND keyword <> :keyword)",
+        &[(":keyword", &keyword), (":place_id", &place_id)],
+    )?;
+    db.execute_named_cached(
+        "INSERT OR IGNORE INTO moz_keywords(keyword, place_id)
+         VALUES(:keyword, :place_id)",
+        &[(":keyword", &keyword), (":place_id", &place_id)],
+    )?;
+    Ok(())
+}
+
+/// Retrieves the keyword for the specified URL.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `url` - The URL to query.
+///
+/// # Returns
+///
+/// * The keyword for the URL, or `None` if the URL doesn't have one.
+pub fn get_keyword_for_url(db: &PlacesDb, url: &Url) -> Result<Option<String>> {
+    Ok(db.try_query_row(
+        "SELECT k.keyword
+         FROM moz_keywords k
+         JOIN moz_places h ON h.id = k.place_id
+         WHERE h.url_hash = hash(:url) AND h.url = :url",
+        &[(":url", &url.as_str())],
+        |row| row.get::<_, String>(0),
+        true,
+    )?)
+}
+
+/// Looks up the URL for the specified keyword.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `keyword` - The keyword to look up. Keywords are case-insensitive.
+///
+/// # Returns
+///
+/// * The URL for the keyword, or `None` if the keyword isn't used.
+pub fn get_url_for_keyword(db: &PlacesDb, keyword: &str) -> Result<Option<Url>> {
+    let keyword = validate_keyword(keyword)?;
+    let href = db.try_query_row(
+        "SELECT h.url
+         FROM moz_keywords k
+         JOIN moz_places h ON h.id = k.place_id
+         WHERE k.keyword = :keyword",
+        &[(":keyword", &keyword)],
+        |row| row.get::<_, String>(0),
+        true,
+    )?;
+    match href {
+        Some(href) => Ok(Some(Url::parse(&href)?)),
+        None => Ok(None),
+    }
+}
+
+/// Removes the specified keyword.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `keyword` - The keyword to remove.
+///
+/// # Returns
+///
+/// There is no success return value - the operation is ignored if the
+/// keyword isn't used.
+pub fn remove_keyword(db: &PlacesDb, keyword: &str) -> Result<()> {
+    let keyword = validate_keyword(keyword)?;
+    db.execute_named_cached(
+        "DELETE FROM moz_keywords
+         WHERE keyword = :keyword",
+        &[(":keyword", &keyword)],
+    )?;
+    Ok(())
+}
+
+/// Removes the keyword from the specified URL.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `url` - The URL for which the keyword should be removed.
+///
+/// # Returns
+///
+/// There is no success return value.
+pub fn remove_keyword_for_url(db: &PlacesDb, url: &Url) -> Result<()> {
+    db.execute_named_cached(
+        "DELETE FROM moz_keywords
+         WHERE place_id = (SELECT id FROM moz_places
+                           WHERE url_hash = hash(:url)
+                           AND url = :url)",
+        &[(":url", &url.as_str())],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::bookmarks::{delete_bookmark, get_raw_bookmark};
+    use crate::tests::bookmark_url;
+
+    fn get_foreign_count(db: &PlacesDb, url: &Url) -> i32 {
+        let count: Result<Option<i32>> = db.try_query_row(
+            "SELECT foreign_count
+             FROM moz_places
+             WHERE url = :url",
+            &[(":url", &url.as_str())],
+            |row| Ok(row.get::<_, i32>(0)?),
+            false,
+        );
+        count.expect("should work").expect("should get a value")
+    }
+
+    #[test]
+    fn test_validate_keyword() {
+        assert_eq!(validate_keyword("foo").expect("should work"), "foo");
+        assert_eq!(validate_keyword(" FoO ").expect("should work"), "foo");
+        assert!(validate_keyword("").is_err());
+        assert!(validate_keyword("   ").is_err());
+        assert!(validate_keyword("foo bar").is_err());
+    }
+
+    #[test]
+    fn test_keywords() -> Result<()> {
+        let conn = new_mem_connection();
+        let url1 = Url::parse("http://example.com/search?q=%s")?;
+        let url2 = Url::parse("http://example2.com/")?;
+
+        // Can't set a keyword for a URL that isn't bookmarked.
+        assert!(set_keyword(&conn, &url1, "ex").is_err());
+
+        let guid1 = bookmark_url(&conn, &url1);
+        let guid2 = bookmark_url(&conn, &url2);
+        assert_eq!(get_foreign_count(&conn, &url1), 1);
+
+        conn.execute_batch("UPDATE moz_bookmarks SET syncChangeCounter = 0")?;
+
+        set_keyword(&conn, &url1, "Ex")?;
+        assert_eq!(get_keyword_for_url(&conn, &url1)?, Some("ex".to_string()));
+        assert_eq!(get_url_for_keyword(&conn, "EX")?, Some(url1.clone()));
+        assert_eq!(get_foreign_count(&conn, &url1), 2);
+        let rb = get_raw_bookmark(&conn, &guid1)?.expect("should exist");
+        assert_eq!(rb.sync_change_counter, 1);
+
+        // Setting a new keyword for the URL replaces the old one.
+        set_keyword(&conn, &url1, "ex2")?;
+        assert_eq!(get_keyword_for_url(&conn, &url1)?, Some("ex2".to_string()));
+        assert_eq!(get_url_for_keyword(&conn, "ex")?, None);
+        assert_eq!(get_foreign_count(&conn, &url1), 2);
+
+        // Setting the same keyword for another URL moves it.
+        set_keyword(&conn, &url2, "ex2")?;
+        assert_eq!(get_keyword_for_url(&conn, &url1)?, None);
+        assert_eq!(get_url_for_keyword(&conn, "ex2")?, Some(url2.clone()));
+        assert_eq!(get_foreign_count(&conn, &url1), 1);
+        assert_eq!(get_foreign_count(&conn, &url2), 2);
+
+        remove_keyword(&conn, "ex2")?;
+        assert_eq!(get_keyword_for_url(&conn, &url2)?, None);
+        assert_eq!(get_foreign_count(&conn, &url2), 1);
+
+        set_keyword(&conn, &url1, "ex")?;
+        remove_keyword_for_url(&conn, &url1)?;
+        assert_eq!(get_url_for_keyword(&conn, "ex")?, None);
+
+        // Removing the last bookmark for a URL removes its keyword.
+        set_keyword(&conn, &url2, "ex")?;
+        let guid3 = bookmark_url(&conn, &url2);
+        delete_bookmark(&conn, &guid2)?;
+        assert_eq!(get_url_for_keyword(&conn, "ex")?, Some(url2.clone()));
+        delete_bookmark(&conn, &guid3)?;
+        assert_eq!(get_url_for_keyword(&conn, "ex")?, None);
+
+        Ok(())
+    }
+}
@@ -5,9 +5,14 @@
 // A "storage" module - this module is intended to be the layer between the
 // API and the database.
 
+pub mod annotations;
 pub mod bookmarks;
+pub mod expiration;
 pub mod history;
+pub mod icons;
+pub mod keywords;
 pub mod tags;
+pub mod top_sites;
 
 use crate::db::PlacesDb;
 use crate::error::{ErrorKind, InvalidPlaceInfo, Result};
@@ -25,10 +30,12 @@ use url::Url;
 pub const URL_LENGTH_MAX: usize = 65536;
 pub const TITLE_LENGTH_MAX: usize = 4096;
 pub const TAG_LENGTH_MAX: usize = 100;
-// pub const DESCRIPTION_LENGTH_MAX: usize = 256;
+pub const DESCRIPTION_LENGTH_MAX: usize = 256;
 
 // Typesafe way to manage RowIds. Does it make sense? A better way?
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Deserialize, Serialize, Default)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize, Default,
+)]
 pub struct RowId(pub i64);
 
 impl From<RowId> for i64 {
@@ -184,6 +191,8 @@ impl HistoryVisitInfo {
         Ok(Self {
             url: row.get("url")?,
             title: row.get("title")?,
+            description: row.get("description")?,
+            preview_image_url: row.get("preview_image_url")?,
             timestamp: visit_date.0 as i64,
             visit_type: visit_type as i32,
         })
@@ -191,6 +200,8 @@ impl HistoryVisitInfo {
 }
 
 pub fn run_maintenance(conn: &PlacesDb) -> Result<()> {
+    annotations::expire_annotations(conn)?;
+    icons::expire_icons(conn)?;
     conn.execute_all(&[
         "VACUUM",
         "PRAGMA optimize",
@@ -226,7 +237,8 @@ pub(crate) fn delete_pending_temp_tables(conn: &PlacesDb) -> Result<()> {
     conn.execute_batch(
         "DELETE FROM moz_updateoriginsupdate_temp;
          DELETE FROM moz_updateoriginsdelete_temp;
-         DELETE FROM moz_updateoriginsinsert_temp;",
+         DELETE FROM moz_updateoriginsinsert_temp;
+         DELETE FROM moz_updateiconsdelete_temp;",
     )?;
     Ok(())
 }
@@ -5,6 +5,8 @@
 use super::{fetch_page_info, TAG_LENGTH_MAX};
 use crate::db::PlacesDb;
 use crate::error::{InvalidPlaceInfo, Result};
+use crate::types::Timestamp;
+use serde_derive::*;
 use sql_support::ConnExt;
 use url::Url;
 
@@ -69,8 +71,15 @@ pub fn validate_tag(tag: &str) -> ValidatedTag<'_> {
 ///
 /// There is no success return value.
 pub fn tag_url(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
-    let tag = validate_tag(&tag).ensure_valid()?;
     let tx = db.begin_transaction()?;
+    tag_url_in_tx(db, url, tag)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Assumes a transaction is already set up by the caller.
+pub(crate) fn tag_url_in_tx(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
+    let tag = validate_tag(&tag).ensure_valid()?;
 
     // This function will not create a new place.
     // Fetch the place id, so we (a) avoid creating a new tag when we aren't
@@ -91,7 +100,6 @@ pub fn tag_url(db: &PlacesDb, url: &Url, tag: &str) -> Result<()> {
          VALUES((SELECT id FROM moz_tags WHERE tag = :tag), :place_id)",
         &[(":tag", &tag), (":place_id", &place_id)],
     )?;
-    tx.commit()?;
     Ok(())
 }
 
@@ -228,11 +236,124 @@ pub fn get_tags_for_url(db: &PlacesDb, url: &Url) -> Result<Vec<String>> {
     Ok(tags)
 }
 
+/// A tag, with the number of URLs that have it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagInfo {
+    pub tag: String,
+    pub url_count: u32,
+    pub last_modified: Timestamp,
+}
+
+/// Retrieves all tags that are used by at least one URL.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// # Returns
+///
+/// * A Vec<TagInfo> with all tags, ordered by the number of URLs that have
+///   them (most to least), then by name.
+pub fn get_all_tags(db: &PlacesDb) -> Result<Vec<TagInfo>> {
+    Ok(db.query_rows_and_then_named_cached(
+        "SELECT t.tag, COUNT(*) AS url_count, t.lastModified
+         FROM moz_tags t
+         JOIN moz_tags_relation r ON r.tag_id = t.id
+         GROUP BY t.id
+         ORDER BY url_count DESC, t.tag",
+        &[],
+        |row| -> rusqlite::Result<_> {
+            Ok(TagInfo {
+                tag: row.get("tag")?,
+                url_count: row.get("url_count")?,
+                last_modified: row.get("lastModified")?,
+            })
+        },
+    )?)
+}
+
+/// Renames a tag on all URLs that have it. If a tag with the new name already
+/// exists, the two tags are merged.
+///
+/// # Arguments
+///
+/// * `conn` - A database connection on which to operate.
+///
+/// * `old_tag` - The tag to rename.
+///
+/// * `new_tag` - The new name for the tag.
+///
+/// # Returns
+///
+/// There is no success return value - the operation is ignored if the old
+/// tag doesn't exist.
+pub fn rename_tag(db: &PlacesDb, old_tag: &str, new_tag: &str) -> Result<()> {
+    let old_tag = validate_tag(&old_tag).ensure_valid()?;
+    let new_tag = validate_tag(&new_tag).ensure_valid()?;
+    if old_tag == new_tag {
+        return Ok(());
+    }
+    let tx = db.begin_transaction()?;
+    let new_exists = db
+        .try_query_row(
+            "SELECT 1 FROM moz_tags WHERE tag = :tag",
+            &[(":tag", &new_tag)],
+            |row| row.get::<_, i64>(0),
+            true,
+        )?
+        .is_some();
+    let renamed = if new_exists {
+        // Move the old tag's URLs over to the new tag, then remove the old
+        // tag, which also removes its remaining relations.
+        db.execute_named_cached(
+            "INSERT OR IGNORE INTO moz_tags_relation(tag_id, place_id)
+             SELECT (SELECT id FROM moz_tags WHERE tag = :new_tag), r.place_id
+             FROM moz_tags_relation r
+             JOIN moz_tags t ON t.id = r.tag_id
+             WHERE t.tag = :old_tag",
+            &[(":old_tag", &old_tag), (":new_tag", &new_tag)],
+        )?;
+        let removed = db.execute_named_cached(
+            "DELETE FROM moz_tags WHERE tag = :old_tag",
+            &[(":old_tag", &old_tag)],
+        )?;
+        if removed > 0 {
+            db.execute_named_cached(
+                "UPDATE moz_tags SET lastModified = now() WHERE tag = :new_tag",
+                &[(":new_tag", &new_tag)],
+            )?;
+        }
+        removed > 0
+    } else {
+        db.execute_named_cached(
+            "UPDATE moz_tags SET tag = :new_tag, lastModified = now()
+             WHERE tag = :old_tag",
+            &[(":old_tag", &old_tag), (":new_tag", &new_tag)],
+        )? > 0
+    };
+    if renamed {
+        // Tags are synced as part of each bookmark, so bookmarks for the
+        // tagged URLs need to be uploaded again. Only changes to
+        // `moz_tags_relation` do this automatically.
+        db.execute_named_cached(
+            "UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+             WHERE fk IN (SELECT place_id FROM moz_tags_relation
+                          WHERE tag_id = (SELECT id FROM moz_tags
+                                          WHERE tag = :new_tag))",
+            &[(":new_tag", &new_tag)],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::api::places_api::test::new_mem_connection;
     use crate::storage::new_page_info;
+    use crate::tests::bookmark_url;
+    use rusqlite::NO_PARAMS;
 
     fn check_tags_for_url(db: &PlacesDb, url: &Url, mut expected: Vec<String>) {
         let mut tags = get_tags_for_url(&db, &url).expect("should work");
@@ -359,4 +480,74 @@ mod tests {
             .expect("should work")
             .expect("should exist");
     }
+
+    #[test]
+    fn test_all_tags_and_rename() -> Result<()> {
+        let conn = new_mem_connection();
+        let url1 = Url::parse("http://example.com")?;
+        let url2 = Url::parse("http://example2.com")?;
+        let url3 = Url::parse("http://example3.com")?;
+        for url in &[&url1, &url2, &url3] {
+            new_page_info(&conn, url, None)?;
+        }
+        tag_url(&conn, &url1, "common")?;
+        tag_url(&conn, &url2, "common")?;
+        tag_url(&conn, &url3, "common")?;
+        tag_url(&conn, &url1, "one")?;
+        tag_url(&conn, &url2, "two")?;
+        tag_url(&conn, &url3, "unused")?;
+        untag_url(&conn, &url3, "unused")?;
+
+        let summarize = |tags: Vec<TagInfo>| -> Vec<(String, u32)> {
+            tags.into_iter().map(|t| (t.tag, t.url_count)).collect()
+        };
+        assert_eq!(
+            summarize(get_all_tags(&conn)?),
+            vec![
+                ("common".to_string(), 3),
+                ("one".to_string(), 1),
+                ("two".to_string(), 1),
+            ]
+        );
+
+        // Renaming to a new name keeps the URLs.
+        rename_tag(&conn, "one", " uno ")?;
+        check_urls_with_tag(&conn, "uno", vec![url1.clone()]);
+        check_urls_with_tag(&conn, "one", vec![]);
+        assert_eq!(get_foreign_count(&conn, &url1), 2);
+
+        // Renaming to an existing name merges the tags.
+        rename_tag(&conn, "two", "uno")?;
+        check_urls_with_tag(&conn, "uno", vec![url1.clone(), url2.clone()]);
+        check_tags_for_url(&conn, &url2, vec!["common".into(), "uno".into()]);
+        assert_eq!(get_foreign_count(&conn, &url2), 2);
+        rename_tag(&conn, "common", "uno")?;
+        check_tags_for_url(&conn, &url1, vec!["uno".into()]);
+        assert_eq!(get_foreign_count(&conn, &url1), 1);
+        assert_eq!(
+            summarize(get_all_tags(&conn)?),
+            vec![("uno".to_string(), 3)]
+        );
+
+        // Renaming uploads the bookmarks for the tagged URLs again.
+        let url4 = Url::parse("http://example4.com")?;
+        for url in &[&url1, &url4] {
+            bookmark_url(&conn, url);
+        }
+        conn.execute("UPDATE moz_bookmarks SET syncChangeCounter = 0", NO_PARAMS)?;
+        rename_tag(&conn, "uno", "eins")?;
+        let counters = conn.query_rows_and_then_named(
+            "SELECT h.url, b.syncChangeCounter FROM moz_bookmarks b
+             JOIN moz_places h ON h.id = b.fk
+             ORDER BY h.url",
+            &[],
+            |row| -> rusqlite::Result<_> { Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)) },
+        )?;
+        assert_eq!(counters, vec![(url1.to_string(), 1), (url4.to_string(), 0)]);
+
+        // Unknown tags are ignored, and invalid names are rejected.
+        rename_tag(&conn, "missing", "eins")?;
+        assert!(rename_tag(&conn, "eins", "two words").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,433 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! "Top sites": the most frecent pages or origins, with sites the user has
+//! pinned at fixed positions, and without the ones they've blocked.
+
+use crate::db::PlacesDb;
+use crate::error::Result;
+use rusqlite::Row;
+use serde_derive::*;
+use sql_support::ConnExt;
+use url::Url;
+
+/// How `top_frecent_sites` groups pages into sites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TopSitesGrouping {
+    /// Each page is a separate site.
+    Pages = 0,
+    /// Only the most frecent page for each origin is returned.
+    OnePagePerOrigin = 1,
+    /// Origins are returned instead of pages, ranked by the frecency of all
+    /// their pages.
+    Origins = 2,
+}
+
+impl TopSitesGrouping {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        Some(match v {
+            0 => TopSitesGrouping::Pages,
+            1 => TopSitesGrouping::OnePagePerOrigin,
+            2 => TopSitesGrouping::Origins,
+            _ => return None,
+        })
+    }
+}
+
+/// Options for `top_frecent_sites`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopFrecentSitesOptions {
+    /// The maximum number of sites to return, including pinned sites.
+    pub limit: u32,
+    pub grouping: TopSitesGrouping,
+    pub exclude_hidden: bool,
+    /// Excludes pages whose latest visit redirected to another page.
+    pub exclude_redirect_sources: bool,
+    /// Excludes pages whose latest visit was an error page.
+    pub exclude_error_pages: bool,
+}
+
+impl Default for TopFrecentSitesOptions {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            grouping: TopSitesGrouping::Pages,
+            exclude_hidden: true,
+            exclude_redirect_sources: true,
+            exclude_error_pages: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TopFrecentSiteInfo {
+    /// The URL of the page, or the root URL of the origin when grouping by
+    /// origin.
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub frecency: i64,
+    /// Whether the user pinned this site.
+    pub pinned: bool,
+}
+
+impl TopFrecentSiteInfo {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(Self {
+            url: Url::parse(&row.get::<_, String>("url")?)?,
+            title: row.get("title")?,
+            frecency: row.get("frecency")?,
+            pinned: row.get("pinned")?,
+        })
+    }
+}
+
+/// Returns the SQL conditions that pages (aliased as `h`) must meet to be
+/// considered top sites.
+fn page_conditions(options: &TopFrecentSitesOptions) -> String {
+    let mut conditions = vec![
+        "h.frecency > 0",
+        // Unvisited bookmarks have a frecency too, but aren't top sites.
+        "(h.last_visit_date_local + h.last_visit_date_remote) > 0",
+    ];
+    if options.exclude_hidden {
+        conditions.push("NOT h.hidden");
+    }
+    if options.exclude_redirect_sources {
+        conditions.push("NOT h.is_redirect_source");
+    }
+    if options.exclude_error_pages {
+        conditions.push("NOT h.is_error_page");
+    }
+    conditions.join(" AND ")
+}
+
+/// Returns the most frecent sites, with pinned sites at their positions, and
+/// without blocked sites.
+pub fn top_frecent_sites(
+    db: &PlacesDb,
+    options: &TopFrecentSitesOptions,
+) -> Result<Vec<TopFrecentSiteInfo>> {
+    let limit = options.limit as usize;
+    let pinned = db.query_rows_and_then_named_cached(
+        "SELECT p.url, IFNULL(p.title, h.title) AS title,
+                IFNULL(h.frecency, 0) AS frecency, 1 AS pinned, p.position
+         FROM moz_topsites_pinned p
+         LEFT JOIN moz_places h ON h.url_hash = hash(p.url) AND h.url = p.url
+         WHERE p.position < :limit
+         ORDER BY p.position",
+        &[(":limit", &options.limit)],
+        |row| -> Result<_> {
+            Ok((
+                row.get::<_, u32>("position")? as usize,
+                TopFrecentSiteInfo::from_row(row)?,
+            ))
+        },
+    )?;
+
+    let conditions = page_conditions(options);
+    let sql = match options.grouping {
+        TopSitesGrouping::Pages => format!(
+            "SELECT h.url, h.title, h.frecency, 0 AS pinned
+             FROM moz_places h
+             WHERE {conditions}
+               AND h.url NOT IN (SELECT url FROM moz_topsites_blocked)
+               AND h.url NOT IN (SELECT url FROM moz_topsites_pinned)
+             ORDER BY h.frecency DESC, h.id DESC
+             LIMIT :limit",
+            conditions = conditions
+        ),
+        // SQLite takes the bare columns from the row with the maximum value
+        // when a query has a single `MAX` aggregate, so this returns the most
+        // frecent page for each origin.
+        TopSitesGrouping::OnePagePerOrigin => format!(
+            "SELECT h.url, h.title, MAX(h.frecency) AS frecency, 0 AS pinned
+             FROM moz_places h
+             WHERE {conditions}
+               AND h.url NOT IN (SELECT url FROM moz_topsites_blocked)
+               AND h.url NOT IN (SELECT url FROM moz_topsites_pinned)
+             GROUP BY h.origin_id
+             ORDER BY frecency DESC, h.origin_id DESC
+             LIMIT :limit",
+            conditions = conditions
+        ),
+        TopSitesGrouping::Origins => format!(
+            "SELECT o.prefix || o.host || '/' AS url, NULL AS title,
+                    o.frecency, 0 AS pinned
+             FROM moz_origins o
+             WHERE o.frecency > 0
+               AND EXISTS(SELECT 1 FROM moz_places h
+                          WHERE h.origin_id = o.id AND {conditions})
+               AND (o.prefix || o.host || '/') NOT IN
+                   (SELECT url FROM moz_topsites_blocked
+                    UNION ALL
+                    SELECT url FROM moz_topsites_pinned)
+             ORDER BY o.frecency DESC, o.id DESC
+             LIMIT :limit",
+            conditions = conditions
+        ),
+    };
+    let frecent = db.query_rows_and_then_named_cached(
+        &sql,
+        // We might need to fill every position if there are no pins.
+        &[(":limit", &options.limit)],
+        TopFrecentSiteInfo::from_row,
+    )?;
+
+    // Fill each position with a pin if there is one, and otherwise the next
+    // most frecent site. If we run out of those, the remaining pins move up.
+    let mut pinned = pinned.into_iter().peekable();
+    let mut frecent = frecent.into_iter();
+    let mut sites = Vec::with_capacity(limit);
+    while sites.len() < limit {
+        let position = sites.len();
+        let pinned_here = pinned.peek().map_or(false, |(p, _)| *p == position);
+        let site = if pinned_here {
+            pinned.next().map(|(_, s)| s)
+        } else {
+            frecent.next().or_else(|| pinned.next().map(|(_, s)| s))
+        };
+        match site {
+            Some(site) => sites.push(site),
+            None => break,
+        }
+    }
+    Ok(sites)
+}
+
+/// Pins a site at `position` in the top sites, replacing any site already
+/// pinned there. Pinning a blocked site unblocks it.
+pub fn pin_top_site(db: &PlacesDb, url: &Url, title: Option<&str>, position: u32) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    db.execute_named_cached(
+        "DELETE FROM moz_topsites_pinned WHERE url = :url OR position = :position",
+        &[(":url", &url.as_str()), (":position", &position)],
+    )?;
+    db.execute_named_cached(
+        "INSERT INTO moz_topsites_pinned(url, title, position)
+         VALUES(:url, :title, :position)",
+        &[
+            (":url", &url.as_str()),
+            (":title", &title),
+            (":position", &position),
+        ],
+    )?;
+    db.execute_named_cached(
+        "DELETE FROM moz_topsites_blocked WHERE url = :url",
+        &[(":url", &url.as_str())],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Unpins a site. Returns `true` if it was pinned.
+pub fn unpin_top_site(db: &PlacesDb, url: &Url) -> Result<bool> {
+    let changes = db.execute_named_cached(
+        "DELETE FROM moz_topsites_pinned WHERE url = :url",
+        &[(":url", &url.as_str())],
+    )?;
+    Ok(changes > 0)
+}
+
+/// Blocks a site from appearing in the top sites. Blocking a pinned site
+/// unpins it.
+pub fn block_top_site(db: &PlacesDb, url: &Url) -> Result<()> {
+    let tx = db.begin_transaction()?;
+    db.execute_named_cached(
+        "INSERT OR IGNORE INTO moz_topsites_blocked(url) VALUES(:url)",
+        &[(":url", &url.as_str())],
+    )?;
+    db.execute_named_cached(
+        "DELETE FROM moz_topsites_pinned WHERE url = :url",
+        &[(":url", &url.as_str())],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Unblocks a site. Returns `true` if it was blocked.
+pub fn unblock_top_site(db: &PlacesDb, url: &Url) -> Result<bool> {
+    let changes = db.execute_named_cached(
+        "DELETE FROM moz_topsites_blocked WHERE url = :url",
+        &[(":url", &url.as_str())],
+    )?;
+    Ok(changes > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::apply_observation;
+    use crate::tests::visit_url;
+    use crate::types::VisitTransition;
+
+    fn visit(db: &PlacesDb, url: &str, times: usize) -> Url {
+        let url = Url::parse(url).unwrap();
+        for _ in 0..times {
+            visit_url(db, &url);
+        }
+        url
+    }
+
+    fn urls(sites: Vec<TopFrecentSiteInfo>) -> Vec<String> {
+        sites.into_iter().map(|s| s.url.into_string()).collect()
+    }
+
+    #[test]
+    fn test_top_frecent_sites() -> Result<()> {
+        let _ = env_logger::try_init();
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/", 5);
+        visit(&conn, "https://example.com/page", 3);
+        visit(&conn, "https://mozilla.org/", 4);
+        visit(&conn, "https://rust-lang.org/", 1);
+        apply_observation(
+            &conn,
+            VisitObservation::new(Url::parse("https://error.example.net/")?)
+                .with_visit_type(VisitTransition::Link)
+                .with_is_error(true),
+        )?;
+        let redirect = Url::parse("https://redirect.example.net/")?;
+        for _ in 0..10 {
+            apply_observation(
+                &conn,
+                VisitObservation::new(redirect.clone())
+                    .with_visit_type(VisitTransition::Link)
+                    .with_is_redirect_source(true),
+            )?;
+        }
+
+        let options = TopFrecentSitesOptions::default();
+        assert_eq!(
+            urls(top_frecent_sites(&conn, &options)?),
+            vec![
+                "https://example.com/",
+                "https://mozilla.org/",
+                "https://example.com/page",
+                "https://rust-lang.org/",
+            ]
+        );
+
+        // Redirect sources are included if we ask for them. Pages that have
+        // only been visited as redirect sources are also hidden.
+        let sites = top_frecent_sites(
+            &conn,
+            &TopFrecentSitesOptions {
+                exclude_hidden: false,
+                exclude_redirect_sources: false,
+                ..TopFrecentSitesOptions::default()
+            },
+        )?;
+        assert_eq!(sites.len(), 5);
+        assert!(urls(sites).contains(&redirect.into_string()));
+
+        let deduped = TopFrecentSitesOptions {
+            grouping: TopSitesGrouping::OnePagePerOrigin,
+            ..TopFrecentSitesOptions::default()
+        };
+        assert_eq!(
+            urls(top_frecent_sites(&conn, &deduped)?),
+            vec![
+                "https://example.com/",
+                "https://mozilla.org/",
+                "https://rust-lang.org/",
+            ]
+        );
+
+        // Pinning a page only excludes that page, so its origin can still
+        // show up with another page.
+        let example = Url::parse("https://example.com/")?;
+        pin_top_site(&conn, &example, None, 0)?;
+        assert_eq!(
+            urls(top_frecent_sites(&conn, &deduped)?),
+            vec![
+                "https://example.com/",
+                "https://mozilla.org/",
+                "https://example.com/page",
+                "https://rust-lang.org/",
+            ]
+        );
+        unpin_top_site(&conn, &example)?;
+
+        let origins = TopFrecentSitesOptions {
+            grouping: TopSitesGrouping::Origins,
+            ..TopFrecentSitesOptions::default()
+        };
+        let sites = top_frecent_sites(&conn, &origins)?;
+        assert_eq!(sites[0].url.as_str(), "https://example.com/");
+        assert_eq!(sites[0].title, None);
+        assert_eq!(sites.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pins_and_blocks() -> Result<()> {
+        let conn = new_mem_connection();
+        let example = visit(&conn, "https://example.com/", 5);
+        let mozilla = visit(&conn, "https://mozilla.org/", 4);
+        visit(&conn, "https://rust-lang.org/", 3);
+        let never_visited = Url::parse("https://never.example.org/")?;
+
+        pin_top_site(&conn, &never_visited, Some("Pinned"), 1)?;
+        pin_top_site(&conn, &mozilla, None, 0)?;
+        // Pins past the limit aren't returned.
+        pin_top_site(&conn, &Url::parse("https://far.example.org/")?, None, 10)?;
+        let sites = top_frecent_sites(
+            &conn,
+            &TopFrecentSitesOptions {
+                limit: 3,
+                ..TopFrecentSitesOptions::default()
+            },
+        )?;
+        assert_eq!(
+            sites
+                .iter()
+                .map(|s| (
+                    s.url.as_str(),
+                    s.title.as_ref().map(String::as_str),
+                    s.pinned
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://mozilla.org/", None, true),
+                ("https://never.example.org/", Some("Pinned"), true),
+                ("https://example.com/", None, false),
+            ]
+        );
+        assert!(sites[0].frecency > 0);
+        assert_eq!(sites[1].frecency, 0);
+
+        // Pinning at a taken position replaces the old pin, and blocking a
+        // pinned site unpins it.
+        pin_top_site(&conn, &example, None, 1)?;
+        block_top_site(&conn, &mozilla)?;
+        assert_eq!(
+            urls(top_frecent_sites(
+                &conn,
+                &TopFrecentSitesOptions::default()
+            )?),
+            vec!["https://rust-lang.org/", "https://example.com/"]
+        );
+
+        assert!(unblock_top_site(&conn, &mozilla)?);
+        assert!(!unblock_top_site(&conn, &mozilla)?);
+        assert!(unpin_top_site(&conn, &example)?);
+        assert_eq!(
+            urls(top_frecent_sites(
+                &conn,
+                &TopFrecentSitesOptions::default()
+            )?),
+            vec![
+                "https://example.com/",
+                "https://mozilla.org/",
+                "https://rust-lang.org/",
+            ]
+        );
+        Ok(())
+    }
+}
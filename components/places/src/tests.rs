@@ -7,11 +7,17 @@ use serde_json::Value;
 
 use crate::{
     db::PlacesDb,
-    storage::bookmarks::{fetch_tree, insert_tree, BookmarkTreeNode},
-    types::SyncGuid,
+    observation::VisitObservation,
+    storage::bookmarks::{
+        fetch_tree, get_raw_bookmark, insert_bookmark, insert_tree, BookmarkPosition,
+        BookmarkRootGuid, BookmarkTreeNode, InsertableBookmark, InsertableItem,
+    },
+    storage::history::apply_observation,
+    types::{SyncGuid, Timestamp, VisitTransition},
 };
 
 use pretty_assertions::assert_eq;
+use url::Url;
 
 pub fn insert_json_tree(conn: &PlacesDb, jtree: Value) {
     let tree: BookmarkTreeNode = serde_json::from_value(jtree).expect("should be valid");
@@ -22,6 +28,48 @@ pub fn insert_json_tree(conn: &PlacesDb, jtree: Value) {
     insert_tree(conn, &folder_node).expect("should insert");
 }
 
+/// Records a link visit to `url`.
+pub fn visit_url(conn: &PlacesDb, url: &Url) {
+    visit_url_at(conn, url, Timestamp::now());
+}
+
+/// Records a link visit to `url` at the given time.
+pub fn visit_url_at(conn: &PlacesDb, url: &Url, at: Timestamp) {
+    apply_observation(
+        conn,
+        VisitObservation::new(url.clone())
+            .with_visit_type(VisitTransition::Link)
+            .with_at(at),
+    )
+    .expect("should apply");
+}
+
+/// Bookmarks `url` at the end of the unfiled root, and returns the new
+/// bookmark's GUID.
+pub fn bookmark_url(conn: &PlacesDb, url: &Url) -> SyncGuid {
+    insert_bookmark(
+        conn,
+        &InsertableItem::Bookmark(InsertableBookmark {
+            parent_guid: BookmarkRootGuid::Unfiled.into(),
+            position: BookmarkPosition::Append,
+            date_added: None,
+            last_modified: None,
+            guid: None,
+            url: url.clone(),
+            title: None,
+        }),
+    )
+    .expect("should insert bookmark")
+}
+
+/// Returns the sync change counter of the bookmark with the given GUID.
+pub fn change_counter(conn: &PlacesDb, guid: &str) -> u32 {
+    get_raw_bookmark(conn, &guid.into())
+        .expect("should work")
+        .expect("should exist")
+        .sync_change_counter
+}
+
 pub fn assert_json_tree(conn: &PlacesDb, folder: &SyncGuid, expected: Value) {
     let fetched = fetch_tree(conn, folder)
         .expect("error fetching tree")
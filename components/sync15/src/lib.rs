@@ -31,7 +31,7 @@ pub use crate::coll_state::{CollState, CollSyncIds, StoreSyncAssociation};
 pub use crate::error::{Error, ErrorKind, Result};
 pub use crate::key_bundle::KeyBundle;
 pub use crate::migrate_state::extract_v1_state;
-pub use crate::request::CollectionRequest;
+pub use crate::request::{CollectionRequest, RequestOrder};
 pub use crate::state::{GlobalState, SetupStateMachine};
 pub use crate::status::{ServiceStatus, SyncResult};
 pub use crate::sync::{synchronize, Store};